@@ -1,9 +1,13 @@
+use std::path::PathBuf;
+
+use data_transporter::auth::Scope;
+use data_transporter::sbom::SbomFormat;
 use structopt::StructOpt;
 
 #[derive(StructOpt, Debug, Default, Clone)]
 pub struct CratesProCli {
     #[structopt(subcommand)]
-    pub(crate) _command: Option<Command>,
+    pub(crate) command: Option<Command>,
 
     #[structopt(short, long)]
     pub(crate) _mega_base: Option<String>,
@@ -16,4 +20,72 @@ pub struct CratesProCli {
 pub enum Command {
     #[default]
     Mega,
+    /// Write the SBOM of a crate version to a file
+    Sbom(SbomCommand),
+    /// List the crate versions transitively affected by an advisory as CSV
+    BlastRadius(BlastRadiusCommand),
+    /// Issue an API key for the public API and print it once
+    IssueApiKey(IssueApiKeyCommand),
+    /// Score every search sort order against a labelled query set
+    SearchEval(SearchEvalCommand),
+    /// Recompute embeddings for crates whose name or description changed
+    RefreshEmbeddings,
+    /// Drop the API caches of crates written to the import files; run after `lgraph_import`
+    PublishCrateChanges,
+}
+
+#[derive(StructOpt, Debug, Clone)]
+pub struct SbomCommand {
+    /// First half of the namespace, e.g. the GitHub owner
+    pub(crate) nsfront: String,
+    /// Second half of the namespace, e.g. the repository name
+    pub(crate) nsbehind: String,
+    pub(crate) name: String,
+    pub(crate) version: String,
+    /// `cyclonedx` (CycloneDX 1.5) or `spdx` (SPDX 2.3)
+    #[structopt(short, long, default_value = "cyclonedx")]
+    pub(crate) format: SbomFormat,
+    #[structopt(short, long, parse(from_os_str))]
+    pub(crate) output: PathBuf,
+}
+
+#[derive(StructOpt, Debug, Clone)]
+pub struct BlastRadiusCommand {
+    /// RustSec ID or alias, e.g. `RUSTSEC-2020-0071` or `CVE-2020-26235`
+    pub(crate) advisory: String,
+    #[structopt(long)]
+    pub(crate) max_depth: Option<usize>,
+    #[structopt(long)]
+    pub(crate) max_nodes: Option<usize>,
+    /// Write to this file instead of stdout
+    #[structopt(short, long, parse(from_os_str))]
+    pub(crate) output: Option<PathBuf>,
+}
+
+#[derive(StructOpt, Debug, Clone)]
+pub struct IssueApiKeyCommand {
+    /// Email of the user the key acts as
+    pub(crate) email: String,
+    /// `read`, `upload` or `admin`; repeat for several scopes
+    #[structopt(short, long = "scope", required = true, number_of_values = 1)]
+    pub(crate) scopes: Vec<Scope>,
+    /// Note about who uses the key
+    #[structopt(short, long)]
+    pub(crate) name: Option<String>,
+    /// Requests per minute, overrides `RATE_LIMIT_KEY_PER_MINUTE`
+    #[structopt(long)]
+    pub(crate) rate_limit: Option<u32>,
+}
+
+#[derive(StructOpt, Debug, Clone)]
+pub struct SearchEvalCommand {
+    /// JSON Lines file, one `{"query": ..., "relevant": {"name": grade}}` per line
+    #[structopt(parse(from_os_str))]
+    pub(crate) queries: PathBuf,
+    /// Cut-off for nDCG and recall
+    #[structopt(short, long, default_value = "10")]
+    pub(crate) k: usize,
+    /// Also write the per-query metrics as JSON
+    #[structopt(short, long, parse(from_os_str))]
+    pub(crate) output: Option<PathBuf>,
 }
@@ -4,20 +4,34 @@
 //! other processes.
 
 //use analysis::analyse_once;
+use data_transporter::cache::{CacheEvent, CachePublisher};
+use data_transporter::db::DBHandler;
 #[allow(unused_imports)]
 use data_transporter::{run_api_server, Transporter};
-use repo_import::ImportDriver;
+use repo_import::{ImportDriver, ImportedMessage};
 
 use crate::cli::CratesProCli;
 use futures_util::future::FutureExt;
 //use std::process::Command;
 //use std::process::Command;
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
 #[allow(unused_imports)]
 use std::{env, fs, sync::Arc, time::Duration};
 use tokio::signal::unix::{signal, SignalKind};
 use tokio::sync::Mutex;
 
+/// 已写入导入文件、等待导入 TuGraph 的 crate，每行一个。导入完成后运行
+/// `crates_pro publish-crate-changes` 通知 API 服务删除它们的缓存
+const PENDING_CHANGES_FILE: &str = "changed_crates.txt";
+/// 正在发送的待通知列表的后缀，发送失败时留在目录中，下次一并发送
+const PUBLISHING_SUFFIX: &str = ".publishing";
+
 pub struct CoreController {
     pub cli: CratesProCli,
 
@@ -66,6 +80,12 @@ impl CoreController {
 
                 let mut import_driver = ImportDriver::new(dont_clone).await;
                 let mut count = 0;
+                // 用户上传的导入结果记入对应的任务
+                let job_db = DBHandler::connect()
+                    .await
+                    .map_err(|e| tracing::error!("upload job tracking disabled: {}", e))
+                    .ok();
+                let mut changed_crates = BTreeSet::new();
                 let is_importing = Arc::new(AtomicBool::new(false));
                 let is_importing_clone = Arc::clone(&is_importing);
                 let mut term_signal = signal(SignalKind::terminate()).unwrap();
@@ -82,6 +102,12 @@ impl CoreController {
                     is_importing_clone.store(true, Ordering::SeqCst);
                     let result = import_driver.import_from_mq_for_a_message().await;
                     is_importing_clone.store(false, Ordering::SeqCst);
+                    if let Ok(imported) = &result {
+                        changed_crates.extend(imported.versions.iter().map(|v| v.name.clone()));
+                        if let (Some(db), Some(job)) = (&job_db, &imported.upload_job) {
+                            record_upload_job(db, job, imported).await;
+                        }
+                    }
 
                     if !received_term && term_signal.recv().now_or_never().is_some() {
                         tracing::info!(
@@ -91,6 +117,8 @@ impl CoreController {
                     }
 
                     if received_term {
+                        // 退出前写出导入文件，避免丢失尚未记录的变化
+                        flush_import_files(&mut import_driver, &mut changed_crates).await;
                         tracing::info!("Import task saving final checkpoint...");
                         if let Err(e) = import_driver.save_checkpoint().await {
                             tracing::error!("Failed to save final checkpoint: {}", e);
@@ -102,7 +130,7 @@ impl CoreController {
 
                     count += 1;
                     if count == 1000 {
-                        import_driver.context.write_tugraph_import_files().await;
+                        flush_import_files(&mut import_driver, &mut changed_crates).await;
                         count = 0;
                     }
                     drop(state);
@@ -187,3 +215,103 @@ impl CoreController {
         }
     }
 }
+
+async fn record_upload_job(db: &DBHandler, job: &str, imported: &ImportedMessage) {
+    let versions: Vec<(String, String)> = imported
+        .versions
+        .iter()
+        .map(|v| (v.name.clone(), v.version.clone()))
+        .collect();
+    if let Err(e) =
+        data_transporter::jobs::record_import(db, job, &versions, imported.error.as_deref()).await
+    {
+        tracing::error!("failed to record import of upload job {}: {}", job, e);
+    }
+}
+
+fn import_files_dir() -> Result<PathBuf, env::VarError> {
+    Ok(PathBuf::from(env::var("TUGRAPH_IMPORT_FILES_PG")?))
+}
+
+/// 写出导入文件，并把这期间变化的 crate 追加到待通知列表。此时数据还没有导入
+/// TuGraph，立即删除缓存只会让缓存重新填入旧数据
+async fn flush_import_files(
+    import_driver: &mut ImportDriver,
+    changed_crates: &mut BTreeSet<String>,
+) {
+    import_driver.context.write_tugraph_import_files().await;
+    match record_pending_changes(changed_crates) {
+        Ok(()) => changed_crates.clear(),
+        Err(e) => tracing::error!(
+            "failed to record changed crates, retrying with the next write: {}",
+            e
+        ),
+    }
+}
+
+fn record_pending_changes(names: &BTreeSet<String>) -> Result<(), Box<dyn Error>> {
+    if names.is_empty() {
+        return Ok(());
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(import_files_dir()?.join(PENDING_CHANGES_FILE))?;
+    for name in names {
+        writeln!(file, "{}", name)?;
+    }
+    file.sync_all()?;
+    Ok(())
+}
+
+/// 导入 TuGraph 之后调用：为待通知列表中的 crate 发布 `CrateChanged`，返回通知的 crate 数。
+/// 列表先改名再发送，发送期间导入任务写入的变化留给下一次
+pub(crate) async fn publish_pending_changes() -> Result<usize, Box<dyn Error>> {
+    let dir = import_files_dir()?;
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+    let pending = dir.join(PENDING_CHANGES_FILE);
+    let claimed = dir.join(format!(
+        "{}.{}{}",
+        PENDING_CHANGES_FILE, timestamp, PUBLISHING_SUFFIX
+    ));
+    match fs::rename(&pending, &claimed) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    // 包括之前发送失败留下的列表
+    let mut files = vec![];
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        let is_claimed = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with(PENDING_CHANGES_FILE) && n.ends_with(PUBLISHING_SUFFIX));
+        if is_claimed {
+            files.push(path);
+        }
+    }
+    let mut names = BTreeSet::new();
+    for file in &files {
+        names.extend(
+            fs::read_to_string(file)?
+                .lines()
+                .map(str::trim)
+                .filter(|n| !n.is_empty())
+                .map(str::to_string),
+        );
+    }
+    if !names.is_empty() {
+        let publisher = CachePublisher::from_env()?;
+        for name in &names {
+            publisher
+                .publish(&CacheEvent::CrateChanged { name: name.clone() })
+                .await?;
+        }
+    }
+    for file in &files {
+        fs::remove_file(file)?;
+    }
+    Ok(names.len())
+}
@@ -1,8 +1,14 @@
 mod cli;
 mod core_controller;
 
-use cli::CratesProCli;
+use cli::{
+    BlastRadiusCommand, Command, CratesProCli, IssueApiKeyCommand, SbomCommand, SearchEvalCommand,
+};
 use core_controller::CoreController;
+use data_transporter::auth::NewApiKey;
+use data_transporter::blast_radius::BlastRadiusQuery;
+use data_transporter::pool::{ConnectionPools, PoolConfig};
+use search::crates_search::{SearchConfig, SearchModule, SearchSortCriteria};
 use std::fs::File;
 use std::time::{SystemTime, UNIX_EPOCH};
 use structopt::StructOpt;
@@ -31,6 +37,157 @@ async fn main() {
     tracing::info!("Starting with log file: {}", log_path);
 
     let cli = CratesProCli::from_args();
-    let core_controller = CoreController::new(cli).await;
-    core_controller.run().await;
+    let result = match &cli.command {
+        Some(Command::Sbom(command)) => write_sbom(command)
+            .await
+            .map_err(|e| format!("failed to write sbom: {}", e)),
+        Some(Command::BlastRadius(command)) => write_blast_radius(command)
+            .await
+            .map_err(|e| format!("failed to query blast radius: {}", e)),
+        Some(Command::IssueApiKey(command)) => issue_api_key(command)
+            .await
+            .map_err(|e| format!("failed to issue api key: {}", e)),
+        Some(Command::RefreshEmbeddings) => refresh_embeddings()
+            .await
+            .map_err(|e| format!("failed to refresh embeddings: {}", e)),
+        Some(Command::PublishCrateChanges) => core_controller::publish_pending_changes()
+            .await
+            .map(|count| println!("published changes of {} crates", count))
+            .map_err(|e| format!("failed to publish crate changes: {}", e)),
+        Some(Command::SearchEval(command)) => evaluate_search(command)
+            .await
+            .map_err(|e| format!("failed to evaluate search: {}", e)),
+        _ => {
+            let core_controller = CoreController::new(cli).await;
+            core_controller.run().await;
+            return;
+        }
+    };
+    if let Err(message) = result {
+        tracing::error!("{}", message);
+        eprintln!("{}", message);
+        std::process::exit(1);
+    }
+}
+
+/// 生成与 `/sbom` 接口相同的文档并写入文件
+async fn write_sbom(command: &SbomCommand) -> Result<(), Box<dyn std::error::Error>> {
+    let pools = ConnectionPools::new(&PoolConfig::from_env()).await?;
+    let sbom = data_transporter::build_sbom(
+        &pools,
+        command.nsfront.clone(),
+        command.nsbehind.clone(),
+        command.name.clone(),
+        command.version.clone(),
+    )
+    .await?;
+    let document = serde_json::to_string_pretty(&sbom.render(command.format))?;
+    std::fs::write(&command.output, document)?;
+    println!(
+        "wrote {} components to {}",
+        sbom.components.len() + 1,
+        command.output.display()
+    );
+    if sbom.truncated {
+        eprintln!("warning: the dependency tree was truncated, the SBOM is incomplete");
+    }
+    Ok(())
+}
+
+/// 以 CSV 输出与 `/blast-radius` 接口相同的结果，未指定文件时写到标准输出
+async fn write_blast_radius(
+    command: &BlastRadiusCommand,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let pools = ConnectionPools::new(&PoolConfig::from_env()).await?;
+    let radius = data_transporter::build_blast_radius(
+        &pools,
+        command.advisory.clone(),
+        BlastRadiusQuery {
+            max_depth: command.max_depth,
+            max_nodes: command.max_nodes,
+        },
+    )
+    .await?;
+    let csv = radius.to_csv()?;
+    match &command.output {
+        Some(output) => {
+            std::fs::write(output, csv)?;
+            println!(
+                "wrote {} affected versions to {}",
+                radius.total,
+                output.display()
+            );
+        }
+        None => print!("{}", csv),
+    }
+    if radius.truncated {
+        eprintln!("result truncated, raise --max-depth or --max-nodes to see more");
+    }
+    Ok(())
+}
+
+/// 签发 API Key 并打印明文，明文之后无法再查到
+async fn issue_api_key(command: &IssueApiKeyCommand) -> Result<(), Box<dyn std::error::Error>> {
+    let pools = ConnectionPools::new(&PoolConfig::from_env()).await?;
+    let dbhandler = pools.pg().await?;
+    let issued = data_transporter::auth::issue_key(
+        &dbhandler,
+        &NewApiKey {
+            email: command.email.clone(),
+            name: command.name.clone(),
+            scopes: command.scopes.clone(),
+            rate_limit: command.rate_limit,
+        },
+    )
+    .await?;
+    eprintln!(
+        "issued key {} for {} with scopes {:?}, store it now, it cannot be shown again",
+        issued.id, issued.email, issued.scopes
+    );
+    println!("{}", issued.key);
+    Ok(())
+}
+
+/// 用标注集评估每种排序方式，`Comprehensive` 的权重取自 `SEARCH_WEIGHT_*` 环境变量
+async fn evaluate_search(command: &SearchEvalCommand) -> Result<(), Box<dyn std::error::Error>> {
+    let queries = search::evaluation::load_queries(&command.queries)?;
+    let pools = ConnectionPools::new(&PoolConfig::from_env()).await?;
+    let dbhandler = pools.pg().await?;
+    let module = SearchModule::new(&dbhandler.client, &SearchConfig::from_env()).await;
+    let mut reports = vec![];
+    println!(
+        "{:<14} {:>8} {:>8} {:>10}",
+        "sort",
+        format!("nDCG@{}", command.k),
+        "MRR",
+        format!("Recall@{}", command.k)
+    );
+    for sort_by in [
+        SearchSortCriteria::Relavance,
+        SearchSortCriteria::Downloads,
+        SearchSortCriteria::Comprehensive,
+    ] {
+        let report = search::evaluation::evaluate(&module, &queries, sort_by, command.k).await?;
+        println!(
+            "{:<14} {:>8.4} {:>8.4} {:>10.4}",
+            serde_json::to_value(sort_by)?.as_str().unwrap_or_default(),
+            report.mean.ndcg,
+            report.mean.reciprocal_rank,
+            report.mean.recall
+        );
+        reports.push(report);
+    }
+    if let Some(output) = &command.output {
+        std::fs::write(output, serde_json::to_string_pretty(&reports)?)?;
+        eprintln!("wrote per-query metrics to {}", output.display());
+    }
+    Ok(())
+}
+
+/// 准备 `embedding` 列并用 `EMBEDDING_PROVIDER` 指定的提供者增量刷新向量
+async fn refresh_embeddings() -> Result<(), Box<dyn std::error::Error>> {
+    let pools = ConnectionPools::new(&PoolConfig::from_env()).await?;
+    let dbhandler = pools.pg().await?;
+    let prepare = search::search_prepare::SearchPrepare::new(&dbhandler.client).await;
+    prepare.prepare_embedding().await
 }
@@ -0,0 +1,293 @@
+//! 按 crate 名索引的内存漏洞表
+//!
+//! 启动时从 `rustsecs` / `rustsec_info` 全量加载一次，之后定时刷新，
+//! 或在收到 `rustsec_changed` 通道的 `NOTIFY` 时立即刷新。刷新后漏洞有变化的
+//! crate 会通过 [`CacheEvent::AdvisoriesChanged`] 通知 API 服务删除相关缓存。
+//! 查询某个 `name@version` 的漏洞只需一次哈希查找加范围判断。
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    env,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use futures_util::StreamExt;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use tokio_postgres::{AsyncMessage, Client, Error, NoTls};
+
+use crate::{
+    advisory::AdvisoryMatcher,
+    cache::{CacheEvent, CachePublisher},
+    db::new_rustsec_from_row,
+    handler::NewRustsec,
+};
+
+/// 漏洞数据变更时导入程序发送 `NOTIFY` 的通道名
+pub const ADVISORY_CHANNEL: &str = "rustsec_changed";
+
+/// 默认刷新间隔（秒），可通过 `ADVISORY_INDEX_REFRESH_SECS` 覆盖
+const DEFAULT_REFRESH_SECS: u64 = 600;
+
+/// 监听连接断开后重连的初始等待时间和上限
+const RECONNECT_BACKOFF_MIN: Duration = Duration::from_secs(1);
+const RECONNECT_BACKOFF_MAX: Duration = Duration::from_secs(60);
+
+static ADVISORY_INDEX: RwLock<Option<Arc<AdvisoryIndex>>> = RwLock::new(None);
+
+struct IndexedAdvisory {
+    info: NewRustsec,
+    matcher: AdvisoryMatcher,
+}
+
+/// 一条漏洞记录影响的 crate
+#[derive(Debug, Clone)]
+pub struct AdvisoryTarget {
+    pub crate_name: String,
+    pub info: NewRustsec,
+    pub matcher: AdvisoryMatcher,
+}
+
+#[derive(Default)]
+pub struct AdvisoryIndex {
+    by_crate: HashMap<String, Vec<IndexedAdvisory>>,
+}
+
+impl AdvisoryIndex {
+    /// 从数据库全量读取漏洞信息并构建索引
+    pub async fn load(client: &Client) -> Result<Self, Error> {
+        let rows = client
+            .query(
+                "SELECT r.cratename, i.* FROM rustsecs r JOIN rustsec_info i ON r.id = i.id;",
+                &[],
+            )
+            .await?;
+        let mut by_crate: HashMap<String, Vec<IndexedAdvisory>> = HashMap::new();
+        for row in rows {
+            let cratename: String = row.get("cratename");
+            let info = new_rustsec_from_row(&row);
+            let matcher = AdvisoryMatcher::new(&info.patched, &info.unaffected);
+            by_crate
+                .entry(cratename)
+                .or_default()
+                .push(IndexedAdvisory { info, matcher });
+        }
+        tracing::info!("advisory index loaded, {} crates", by_crate.len());
+        Ok(Self { by_crate })
+    }
+
+    /// 与 `old` 相比漏洞有新增、修改或删除的 crate，按名称排序
+    pub fn changed_crates(&self, old: &AdvisoryIndex) -> Vec<String> {
+        let infos = |index: &AdvisoryIndex, name: &str| -> HashSet<NewRustsec> {
+            index
+                .by_crate
+                .get(name)
+                .map(|advisories| advisories.iter().map(|a| a.info.clone()).collect())
+                .unwrap_or_default()
+        };
+        let names: BTreeSet<&String> = self.by_crate.keys().chain(old.by_crate.keys()).collect();
+        names
+            .into_iter()
+            .filter(|name| infos(self, name) != infos(old, name))
+            .cloned()
+            .collect()
+    }
+
+    /// 影响 `name@version` 的漏洞，`version` 为空时返回该 crate 的全部漏洞
+    pub fn advisories_of(&self, name: &str, version: Option<&str>) -> Vec<AdvisoryTarget> {
+        self.by_crate
+            .get(name)
+            .map(|advisories| {
+                advisories
+                    .iter()
+                    .filter(|a| version.is_none_or(|v| a.matcher.is_affected(v)))
+                    .map(|a| AdvisoryTarget {
+                        crate_name: name.to_string(),
+                        info: a.info.clone(),
+                        matcher: a.matcher.clone(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// 按 RustSec ID 或别名（如 CVE、GHSA 编号）查找漏洞，不区分大小写
+    pub fn find(&self, id_or_alias: &str) -> Vec<AdvisoryTarget> {
+        let wanted = id_or_alias.trim();
+        let mut targets = vec![];
+        for (crate_name, advisories) in &self.by_crate {
+            for a in advisories {
+                let hit = a.info.id.eq_ignore_ascii_case(wanted)
+                    || a.info
+                        .aliases
+                        .split(';')
+                        .any(|alias| alias.trim().eq_ignore_ascii_case(wanted));
+                if hit {
+                    targets.push(AdvisoryTarget {
+                        crate_name: crate_name.clone(),
+                        info: a.info.clone(),
+                        matcher: a.matcher.clone(),
+                    });
+                }
+            }
+        }
+        targets.sort_by(|a, b| a.crate_name.cmp(&b.crate_name));
+        targets
+    }
+}
+
+/// 获取当前索引，尚未加载时用 `client` 加载一次
+pub async fn advisory_index(client: &Client) -> Result<Arc<AdvisoryIndex>, Error> {
+    if let Some(index) = ADVISORY_INDEX.read().unwrap().clone() {
+        return Ok(index);
+    }
+    refresh_advisory_index(client).await
+}
+
+/// 重新加载索引并替换当前索引
+pub async fn refresh_advisory_index(client: &Client) -> Result<Arc<AdvisoryIndex>, Error> {
+    let index = Arc::new(AdvisoryIndex::load(client).await?);
+    *ADVISORY_INDEX.write().unwrap() = Some(index.clone());
+    Ok(index)
+}
+
+/// 建立监听 `ADVISORY_CHANNEL` 的连接，连接断开后返回的通知通道随之关闭
+async fn listen(db_connection_config: &str) -> Result<(Client, UnboundedReceiver<String>), Error> {
+    let (client, mut connection) = tokio_postgres::connect(db_connection_config, NoTls).await?;
+    let (tx, rx) = unbounded_channel();
+    tokio::spawn(async move {
+        let mut messages = futures_util::stream::poll_fn(move |cx| connection.poll_message(cx));
+        while let Some(message) = messages.next().await {
+            match message {
+                Ok(AsyncMessage::Notification(n)) => {
+                    let _ = tx.send(n.payload().to_string());
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::warn!("advisory listener connection error: {}", e);
+                    break;
+                }
+            }
+        }
+    });
+    client
+        .batch_execute(&format!("LISTEN {};", ADVISORY_CHANNEL))
+        .await?;
+    Ok((client, rx))
+}
+
+/// 重连等待时间翻倍，不超过 [`RECONNECT_BACKOFF_MAX`]
+fn next_backoff(backoff: Duration) -> Duration {
+    (backoff * 2).min(RECONNECT_BACKOFF_MAX)
+}
+
+/// 在后台定时刷新索引，并监听 `ADVISORY_CHANNEL` 通知以便立即刷新。
+/// 监听连接断开后按指数退避重连，重连后先全量重新加载一次，以补上断线期间漏掉的通知。
+pub fn spawn_advisory_index_refresher(db_connection_config: String) {
+    tokio::spawn(async move {
+        let refresh_secs = env::var("ADVISORY_INDEX_REFRESH_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|secs| *secs > 0)
+            .unwrap_or(DEFAULT_REFRESH_SECS);
+        // 漏洞变化后通知 API 服务删除受影响 crate 的缓存
+        let cache_publisher = CachePublisher::from_env()
+            .map_err(|e| tracing::error!("cache events disabled: {}", e))
+            .ok();
+        let mut backoff = RECONNECT_BACKOFF_MIN;
+        loop {
+            let (client, mut rx) = match listen(&db_connection_config).await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    tracing::error!(
+                        "advisory index refresher failed to connect, retrying in {:?}: {}",
+                        backoff,
+                        e
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = next_backoff(backoff);
+                    continue;
+                }
+            };
+            // 第一次 tick 立即触发，即连接建立后先全量重新加载
+            let mut ticker = tokio::time::interval(Duration::from_secs(refresh_secs));
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    payload = rx.recv() => match payload {
+                        Some(payload) => tracing::info!("advisory change notified: {}", payload),
+                        None => break,
+                    },
+                }
+                let old = ADVISORY_INDEX.read().unwrap().clone();
+                match refresh_advisory_index(&client).await {
+                    Ok(index) => {
+                        backoff = RECONNECT_BACKOFF_MIN;
+                        if let (Some(publisher), Some(old)) = (&cache_publisher, old) {
+                            let crates = index.changed_crates(&old);
+                            if !crates.is_empty() {
+                                let event = CacheEvent::AdvisoriesChanged { crates };
+                                if let Err(e) = publisher.publish(&event).await {
+                                    tracing::warn!("failed to publish {:?}: {}", event, e);
+                                }
+                            }
+                        }
+                    }
+                    Err(e) => tracing::error!("failed to refresh advisory index: {}", e),
+                }
+            }
+            tracing::warn!(
+                "advisory listener disconnected, reconnecting in {:?}",
+                backoff
+            );
+            tokio::time::sleep(backoff).await;
+            backoff = next_backoff(backoff);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(entries: &[(&str, &str, &str)]) -> AdvisoryIndex {
+        let mut by_crate: HashMap<String, Vec<IndexedAdvisory>> = HashMap::new();
+        for (name, id, patched) in entries {
+            by_crate
+                .entry(name.to_string())
+                .or_default()
+                .push(IndexedAdvisory {
+                    info: NewRustsec {
+                        id: id.to_string(),
+                        patched: patched.to_string(),
+                        ..Default::default()
+                    },
+                    matcher: AdvisoryMatcher::new(patched, ""),
+                });
+        }
+        AdvisoryIndex { by_crate }
+    }
+
+    #[test]
+    fn test_changed_crates() {
+        let old = index(&[
+            ("time", "RUSTSEC-2020-0071", ">= 0.2.23"),
+            ("chrono", "RUSTSEC-2020-0159", ">= 0.4.20"),
+            ("ring", "RUSTSEC-2025-0009", ">= 0.17.12"),
+        ]);
+        let new = index(&[
+            ("time", "RUSTSEC-2020-0071", ">= 0.2.23"),
+            ("chrono", "RUSTSEC-2020-0159", ">= 0.4.21"),
+            ("openssl", "RUSTSEC-2023-0044", ">= 0.10.55"),
+        ]);
+        assert_eq!(new.changed_crates(&old), ["chrono", "openssl", "ring"]);
+        assert!(new.changed_crates(&new).is_empty());
+    }
+
+    #[test]
+    fn test_next_backoff() {
+        assert_eq!(next_backoff(RECONNECT_BACKOFF_MIN), Duration::from_secs(2));
+        assert_eq!(next_backoff(Duration::from_secs(45)), RECONNECT_BACKOFF_MAX);
+        assert_eq!(next_backoff(RECONNECT_BACKOFF_MAX), RECONNECT_BACKOFF_MAX);
+    }
+}
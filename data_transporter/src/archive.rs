@@ -0,0 +1,542 @@
+//! 上传项目的解压
+//!
+//! 支持 zip、`.crate` 和 `.tar.gz`，格式按文件头识别。条目路径必须是普通的相对路径，
+//! 绝对路径、含 `..` 的路径、符号链接、硬链接和设备文件都会让整个归档被拒绝。
+//! 条目数和解压出的字节数受 [`ArchiveLimits`] 限制，字节数按实际解压的数据计算，
+//! 不信任归档头中记录的大小。解压后要求项目中有 `Cargo.toml`。解压失败时删除已解压的目录和上传的归档。
+
+use std::env;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek};
+use std::path::{Component, Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use zip::ZipArchive;
+
+use crate::error::ApiError;
+
+/// 可以导入的归档扩展名，`.crate` 即 crates.io 发布的 `.tar.gz`
+const EXTENSIONS: [(&str, ArchiveFormat); 4] = [
+    (".zip", ArchiveFormat::Zip),
+    (".crate", ArchiveFormat::TarGz),
+    (".tar.gz", ArchiveFormat::TarGz),
+    (".tgz", ArchiveFormat::TarGz),
+];
+
+/// tar 中每个条目的头部、长文件名和 pax 扩展所允许的额外字节数
+const TAR_OVERHEAD_PER_ENTRY: u64 = 8 * 1024;
+
+/// 解压限制
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArchiveLimits {
+    /// 上传的归档文件最大字节数
+    pub max_archive_bytes: u64,
+    /// 解压出的文件总字节数上限
+    pub max_extracted_bytes: u64,
+    /// 条目数上限，目录也计入
+    pub max_entries: usize,
+}
+
+impl Default for ArchiveLimits {
+    fn default() -> Self {
+        ArchiveLimits {
+            max_archive_bytes: 256 * 1024 * 1024,
+            max_extracted_bytes: 1024 * 1024 * 1024,
+            max_entries: 20_000,
+        }
+    }
+}
+
+impl ArchiveLimits {
+    /// 从 `UPLOAD_MAX_ARCHIVE_BYTES`、`UPLOAD_MAX_EXTRACTED_BYTES`、`UPLOAD_MAX_ENTRIES`
+    /// 读取配置，未设置的项使用默认值
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(key: &str) -> Option<T> {
+            env::var(key).ok().and_then(|v| v.parse().ok())
+        }
+        let default = ArchiveLimits::default();
+        ArchiveLimits {
+            max_archive_bytes: var("UPLOAD_MAX_ARCHIVE_BYTES").unwrap_or(default.max_archive_bytes),
+            max_extracted_bytes: var("UPLOAD_MAX_EXTRACTED_BYTES")
+                .unwrap_or(default.max_extracted_bytes),
+            max_entries: var("UPLOAD_MAX_ENTRIES").unwrap_or(default.max_entries),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    /// 按文件名判断格式
+    pub fn from_name(name: &str) -> Option<Self> {
+        split_extension(name).map(|(_, format)| format)
+    }
+
+    /// 按文件头判断格式
+    pub fn detect(header: &[u8]) -> Option<Self> {
+        if header.starts_with(b"PK\x03\x04") || header.starts_with(b"PK\x05\x06") {
+            Some(ArchiveFormat::Zip)
+        } else if header.starts_with(&[0x1f, 0x8b]) {
+            Some(ArchiveFormat::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+/// 去掉归档扩展名后的文件名，作为导入时的 crate 名
+pub fn crate_name(file_name: &str) -> Option<&str> {
+    split_extension(file_name)
+        .map(|(stem, _)| stem)
+        .filter(|stem| !stem.is_empty())
+}
+
+fn split_extension(name: &str) -> Option<(&str, ArchiveFormat)> {
+    EXTENSIONS.iter().find_map(|(ext, format)| {
+        let stem_len = name.len().checked_sub(ext.len())?;
+        let suffix = name.get(stem_len..)?;
+        suffix
+            .eq_ignore_ascii_case(ext)
+            .then(|| (&name[..stem_len], *format))
+    })
+}
+
+fn rejected(reason: impl fmt::Display) -> ApiError {
+    ApiError::BadRequest(reason.to_string())
+}
+
+/// 把 `archive` 解压到 `dest`，返回最外层 `Cargo.toml` 所在的目录，失败时删除 `dest` 和 `archive`
+pub fn extract(archive: &Path, dest: &Path, limits: &ArchiveLimits) -> Result<PathBuf, ApiError> {
+    let result = extract_into(archive, dest, limits);
+    if result.is_err() {
+        let _ = fs::remove_dir_all(dest);
+        let _ = fs::remove_file(archive);
+    }
+    result
+}
+
+fn extract_into(archive: &Path, dest: &Path, limits: &ArchiveLimits) -> Result<PathBuf, ApiError> {
+    let mut file = File::open(archive)?;
+    if file.metadata()?.len() > limits.max_archive_bytes {
+        return Err(rejected(format!(
+            "archive is larger than {} bytes",
+            limits.max_archive_bytes
+        )));
+    }
+    let mut header = Vec::new();
+    (&mut file).take(4).read_to_end(&mut header)?;
+    file.rewind()?;
+    let format = ArchiveFormat::detect(&header)
+        .ok_or_else(|| rejected("unsupported archive format, expected .zip, .crate or .tar.gz"))?;
+
+    fs::create_dir_all(dest)?;
+    let mut extractor = Extractor {
+        dest,
+        limits,
+        entries: 0,
+        bytes: 0,
+    };
+    match format {
+        ArchiveFormat::Zip => extractor.zip(file)?,
+        ArchiveFormat::TarGz => extractor.tar_gz(file)?,
+    }
+    project_root(dest)
+}
+
+struct Extractor<'a> {
+    dest: &'a Path,
+    limits: &'a ArchiveLimits,
+    entries: usize,
+    bytes: u64,
+}
+
+impl Extractor<'_> {
+    fn zip(&mut self, file: File) -> Result<(), ApiError> {
+        let mut archive =
+            ZipArchive::new(file).map_err(|e| rejected(format!("invalid zip archive: {}", e)))?;
+        for i in 0..archive.len() {
+            self.count_entry()?;
+            let mut entry = archive
+                .by_index(i)
+                .map_err(|e| rejected(format!("invalid zip entry: {}", e)))?;
+            let name = entry.name().to_string();
+            if entry.is_symlink() {
+                return Err(rejected(format!("symlink in archive: {}", name)));
+            }
+            if entry.is_dir() {
+                self.dir(&name)?;
+            } else {
+                self.file(&name, &mut entry)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn tar_gz(&mut self, file: File) -> Result<(), ApiError> {
+        // 长文件名和 pax 扩展会被 tar 整个读入内存，因此限制解压后的整个数据流
+        let budget = self.limits.max_extracted_bytes
+            + (self.limits.max_entries as u64 + 1) * TAR_OVERHEAD_PER_ENTRY;
+        let mut archive = tar::Archive::new(Limited {
+            inner: GzDecoder::new(file),
+            remaining: budget,
+            limit: self.limits.max_extracted_bytes,
+        });
+        let entries = archive.entries().map_err(read_error)?;
+        for entry in entries {
+            let mut entry = entry.map_err(read_error)?;
+            let kind = entry.header().entry_type();
+            if kind.is_pax_global_extensions() {
+                continue;
+            }
+            self.count_entry()?;
+            let name = String::from_utf8_lossy(&entry.path_bytes()).into_owned();
+            if kind.is_symlink() || kind.is_hard_link() {
+                return Err(rejected(format!("link in archive: {}", name)));
+            } else if kind.is_dir() {
+                self.dir(&name)?;
+            } else if kind.is_file() {
+                self.file(&name, &mut entry)?;
+            } else {
+                return Err(rejected(format!("unsupported entry in archive: {}", name)));
+            }
+        }
+        Ok(())
+    }
+
+    fn count_entry(&mut self) -> Result<(), ApiError> {
+        self.entries += 1;
+        if self.entries > self.limits.max_entries {
+            return Err(rejected(format!(
+                "archive has more than {} entries",
+                self.limits.max_entries
+            )));
+        }
+        Ok(())
+    }
+
+    fn dir(&mut self, name: &str) -> Result<(), ApiError> {
+        fs::create_dir_all(self.dest.join(safe_path(name)?))?;
+        Ok(())
+    }
+
+    fn file(&mut self, name: &str, reader: &mut dyn Read) -> Result<(), ApiError> {
+        let path = self.dest.join(safe_path(name)?);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let remaining = self.limits.max_extracted_bytes - self.bytes;
+        let mut data = reader.take(remaining + 1);
+        let mut out = File::create(&path)?;
+        let mut buffer = [0u8; 64 * 1024];
+        loop {
+            let n = data.read(&mut buffer).map_err(read_error)?;
+            if n == 0 {
+                break;
+            }
+            self.bytes += n as u64;
+            if self.bytes > self.limits.max_extracted_bytes {
+                return Err(rejected(format!(
+                    "archive expands to more than {} bytes",
+                    self.limits.max_extracted_bytes
+                )));
+            }
+            io::Write::write_all(&mut out, &buffer[..n])?;
+        }
+        Ok(())
+    }
+}
+
+/// 读取归档数据时的错误都归因于归档本身
+fn read_error(e: io::Error) -> ApiError {
+    match e
+        .get_ref()
+        .and_then(|inner| inner.downcast_ref::<LimitExceeded>())
+    {
+        Some(limit) => rejected(limit),
+        None => rejected(format!("corrupted archive: {}", e)),
+    }
+}
+
+/// 校验条目路径，只接受不含 `..` 的相对路径
+pub fn safe_path(name: &str) -> Result<PathBuf, ApiError> {
+    let unsafe_path = || rejected(format!("unsafe path in archive: {}", name));
+    if name.contains('\0') {
+        return Err(unsafe_path());
+    }
+    // Windows 上生成的 zip 可能使用反斜杠作为分隔符
+    let normalized = name.replace('\\', "/");
+    let mut path = PathBuf::new();
+    for component in Path::new(&normalized).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                return Err(unsafe_path())
+            }
+        }
+    }
+    if path.as_os_str().is_empty() {
+        return Err(unsafe_path());
+    }
+    Ok(path)
+}
+
+/// 最外层的 `Cargo.toml` 所在目录，同一层有多个时取路径最小的
+fn project_root(dest: &Path) -> Result<PathBuf, ApiError> {
+    let mut level = vec![dest.to_path_buf()];
+    while !level.is_empty() {
+        let mut roots = vec![];
+        let mut next = vec![];
+        for dir in level {
+            for entry in fs::read_dir(&dir)? {
+                let entry = entry?;
+                let file_type = entry.file_type()?;
+                if file_type.is_dir() {
+                    next.push(entry.path());
+                } else if file_type.is_file() && entry.file_name() == "Cargo.toml" {
+                    roots.push(dir.clone());
+                }
+            }
+        }
+        if let Some(root) = roots.into_iter().min() {
+            return Ok(root);
+        }
+        level = next;
+    }
+    Err(rejected("no Cargo.toml in the archive"))
+}
+
+#[derive(Debug)]
+struct LimitExceeded(u64);
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "archive expands to more than {} bytes", self.0)
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+/// 读出超过 `remaining` 字节后报错的读取器，错误中给出配置的上限 `limit`
+struct Limited<R> {
+    inner: R,
+    remaining: u64,
+    limit: u64,
+}
+
+impl<R: Read> Read for Limited<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.remaining = self
+            .remaining
+            .checked_sub(n as u64)
+            .ok_or_else(|| io::Error::other(LimitExceeded(self.limit)))?;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    const MANIFEST: &[u8] = b"[package]\nname = \"demo\"\nversion = \"0.1.0\"\n";
+
+    fn zip_fixture(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in entries {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    /// tar 的 Builder 会拒绝不安全的路径，这里直接写入头部
+    fn tar_gz_fixture(entries: &[(&str, tar::EntryType, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for (name, kind, data) in entries {
+            let mut header = tar::Header::new_old();
+            header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_entry_type(*kind);
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append(&header, *data).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn extract_bytes(
+        data: &[u8],
+        limits: &ArchiveLimits,
+    ) -> (tempfile::TempDir, Result<PathBuf, ApiError>) {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("upload");
+        fs::write(&archive, data).unwrap();
+        let result = extract(&archive, &dir.path().join("out"), limits);
+        (dir, result)
+    }
+
+    fn rejection(data: &[u8], limits: &ArchiveLimits) -> String {
+        match extract_bytes(data, limits).1 {
+            Err(ApiError::BadRequest(reason)) => reason,
+            other => panic!("expected rejection, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_formats() {
+        assert_eq!(
+            ArchiveFormat::from_name("demo.ZIP"),
+            Some(ArchiveFormat::Zip)
+        );
+        assert_eq!(
+            ArchiveFormat::from_name("demo-0.1.0.crate"),
+            Some(ArchiveFormat::TarGz)
+        );
+        assert_eq!(ArchiveFormat::from_name("demo.tar"), None);
+        assert_eq!(crate_name("demo-0.1.0.tar.gz"), Some("demo-0.1.0"));
+        assert_eq!(crate_name(".zip"), None);
+        assert_eq!(
+            ArchiveFormat::detect(b"PK\x03\x04rest"),
+            Some(ArchiveFormat::Zip)
+        );
+        assert_eq!(
+            ArchiveFormat::detect(&[0x1f, 0x8b, 8]),
+            Some(ArchiveFormat::TarGz)
+        );
+        assert_eq!(ArchiveFormat::detect(b"Rar!"), None);
+
+        assert_eq!(safe_path("./src/lib.rs").unwrap(), Path::new("src/lib.rs"));
+        assert_eq!(safe_path("src\\main.rs").unwrap(), Path::new("src/main.rs"));
+        for name in [
+            "../evil",
+            "src/../../evil",
+            "/etc/passwd",
+            "..\\evil",
+            ".",
+            "a\0b",
+        ] {
+            assert!(safe_path(name).is_err(), "{} accepted", name);
+        }
+    }
+
+    #[test]
+    fn test_extract_valid() {
+        let limits = ArchiveLimits::default();
+        let zip = zip_fixture(&[
+            ("demo/Cargo.toml", MANIFEST),
+            ("demo/src/lib.rs", b"pub fn f() {}\n"),
+            ("demo/examples/nested/Cargo.toml", MANIFEST),
+        ]);
+        let (dir, root) = extract_bytes(&zip, &limits);
+        let root = root.unwrap();
+        assert_eq!(root, dir.path().join("out/demo"));
+        assert!(root.join("src/lib.rs").is_file());
+
+        let krate = tar_gz_fixture(&[
+            ("demo-0.1.0/", tar::EntryType::Directory, b""),
+            ("demo-0.1.0/Cargo.toml", tar::EntryType::Regular, MANIFEST),
+        ]);
+        let (dir, root) = extract_bytes(&krate, &limits);
+        assert_eq!(root.unwrap(), dir.path().join("out/demo-0.1.0"));
+    }
+
+    #[test]
+    fn test_extract_malicious() {
+        let limits = ArchiveLimits::default();
+        let (dir, result) = extract_bytes(
+            &zip_fixture(&[("Cargo.toml", MANIFEST), ("../evil.rs", b"boom")]),
+            &limits,
+        );
+        assert!(
+            matches!(result, Err(ApiError::BadRequest(reason)) if reason.contains("../evil.rs"))
+        );
+        assert!(!dir.path().join("evil.rs").exists());
+        // 部分解压的内容和上传的归档都被删除
+        assert!(!dir.path().join("out").exists());
+        assert!(!dir.path().join("upload").exists());
+
+        assert!(
+            rejection(&zip_fixture(&[("/tmp/evil", b"boom")]), &limits).starts_with("unsafe path")
+        );
+        assert!(rejection(
+            &tar_gz_fixture(&[("../../evil", tar::EntryType::Regular, b"boom")]),
+            &limits
+        )
+        .starts_with("unsafe path"));
+        assert!(rejection(
+            &tar_gz_fixture(&[("Cargo.toml", tar::EntryType::Symlink, b"")]),
+            &limits
+        )
+        .starts_with("link in archive"));
+        assert!(rejection(
+            &tar_gz_fixture(&[("dev", tar::EntryType::Char, b"")]),
+            &limits
+        )
+        .starts_with("unsupported entry"));
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .add_symlink("Cargo.toml", "/etc/passwd", SimpleFileOptions::default())
+            .unwrap();
+        let symlink = writer.finish().unwrap().into_inner();
+        assert!(rejection(&symlink, &limits).starts_with("symlink in archive"));
+
+        assert_eq!(
+            rejection(&zip_fixture(&[("README.md", b"no manifest")]), &limits),
+            "no Cargo.toml in the archive"
+        );
+        assert!(rejection(b"just some text", &limits).starts_with("unsupported archive format"));
+    }
+
+    #[test]
+    fn test_extract_limits() {
+        let limits = ArchiveLimits {
+            max_archive_bytes: 64 * 1024,
+            max_extracted_bytes: 1024 * 1024,
+            max_entries: 3,
+        };
+        // 高压缩比的 "炸弹"：压缩后只有几 KB
+        let zeros = vec![0u8; 2 * 1024 * 1024];
+        assert_eq!(
+            rejection(&zip_fixture(&[("Cargo.toml", &zeros)]), &limits),
+            "archive expands to more than 1048576 bytes"
+        );
+        assert_eq!(
+            rejection(
+                &tar_gz_fixture(&[("Cargo.toml", tar::EntryType::Regular, &zeros)]),
+                &limits
+            ),
+            "archive expands to more than 1048576 bytes"
+        );
+
+        let many: Vec<(String, &[u8])> = (0..4).map(|i| (format!("f{}", i), &b""[..])).collect();
+        let many: Vec<(&str, &[u8])> = many.iter().map(|(n, d)| (n.as_str(), *d)).collect();
+        assert_eq!(
+            rejection(&zip_fixture(&many), &limits),
+            "archive has more than 3 entries"
+        );
+
+        let limits = ArchiveLimits {
+            max_archive_bytes: 16,
+            ..limits
+        };
+        assert_eq!(
+            rejection(&zip_fixture(&[("Cargo.toml", MANIFEST)]), &limits),
+            "archive is larger than 16 bytes"
+        );
+    }
+}
@@ -0,0 +1,366 @@
+//! Cargo.lock 审计
+//!
+//! 解析上传的 `Cargo.lock`，或与 `upload_crate` 相同的 zip 项目中最外层的
+//! `Cargo.lock`，对每个锁定的包给出已知漏洞、yank 状态、许可证和评估分数，
+//! 并标出 Crates Pro 不认识的包。只有来自 crates.io 的包会被查询，
+//! 路径依赖、git 依赖和其他 registry 的包只列出，不做判断。
+
+use std::collections::{BTreeSet, HashMap};
+use std::io::{Cursor, Read};
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use zip::ZipArchive;
+
+use crate::advisory_index::AdvisoryTarget;
+use crate::error::ApiError;
+
+/// 上传的 Cargo.lock 最大字节数，zip 中的 Cargo.lock 同样受此限制
+pub const MAX_LOCKFILE_BYTES: usize = 16 * 1024 * 1024;
+
+/// 上传的 zip 项目最大字节数
+pub const MAX_ARCHIVE_BYTES: usize = 256 * 1024 * 1024;
+
+const CRATES_IO_SOURCES: [&str; 2] = [
+    "registry+https://github.com/rust-lang/crates.io-index",
+    "sparse+https://index.crates.io/",
+];
+
+/// 仅用于文档，描述 multipart 请求体
+#[derive(ToSchema)]
+#[allow(dead_code)]
+pub struct AuditUpload {
+    /// `Cargo.lock` 文件或包含它的 zip 项目
+    #[schema(value_type = String, format = Binary)]
+    file: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+struct Lockfile {
+    #[serde(default)]
+    package: Vec<LockedPackage>,
+}
+
+/// Cargo.lock 中的一个 `[[package]]`
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: String,
+    pub source: Option<String>,
+}
+
+impl LockedPackage {
+    pub fn source_kind(&self) -> PackageSource {
+        match self.source.as_deref() {
+            None => PackageSource::Path,
+            Some(source) if CRATES_IO_SOURCES.contains(&source) => PackageSource::CratesIo,
+            Some(source) if source.starts_with("git+") => PackageSource::Git,
+            Some(_) => PackageSource::Registry,
+        }
+    }
+}
+
+/// 包的来源
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PackageSource {
+    #[serde(rename = "crates.io")]
+    CratesIo,
+    /// crates.io 以外的 registry
+    Registry,
+    Git,
+    /// 工作区成员或路径依赖
+    Path,
+}
+
+/// 解析 Cargo.lock，按名称和版本排序并去重
+pub fn parse_lockfile(content: &str) -> Result<Vec<LockedPackage>, ApiError> {
+    let lockfile: Lockfile = toml::from_str(content)
+        .map_err(|e| ApiError::BadRequest(format!("invalid Cargo.lock: {}", e.message())))?;
+    let packages: BTreeSet<LockedPackage> = lockfile.package.into_iter().collect();
+    if packages.is_empty() {
+        return Err(ApiError::BadRequest(
+            "Cargo.lock contains no packages".to_string(),
+        ));
+    }
+    Ok(packages.into_iter().collect())
+}
+
+/// 取出 zip 项目中目录层级最浅的 Cargo.lock，即工作区根目录的那一个
+pub fn lockfile_from_zip(data: &[u8]) -> Result<String, ApiError> {
+    let mut archive = ZipArchive::new(Cursor::new(data))
+        .map_err(|e| ApiError::BadRequest(format!("invalid zip archive: {}", e)))?;
+    let index = (0..archive.len())
+        .filter_map(|i| {
+            let path = archive.by_index_raw(i).ok()?.enclosed_name()?;
+            (path.file_name()? == "Cargo.lock").then(|| (path.components().count(), i))
+        })
+        .min()
+        .map(|(_, i)| i)
+        .ok_or_else(|| ApiError::BadRequest("no Cargo.lock in the archive".to_string()))?;
+    let file = archive
+        .by_index(index)
+        .map_err(|e| ApiError::BadRequest(format!("invalid zip entry: {}", e)))?;
+    // 不信任 zip 头中记录的大小，按实际解压出的字节数限制
+    let mut content = String::new();
+    file.take(MAX_LOCKFILE_BYTES as u64 + 1)
+        .read_to_string(&mut content)
+        .map_err(|e| ApiError::BadRequest(format!("unreadable Cargo.lock: {}", e)))?;
+    if content.len() > MAX_LOCKFILE_BYTES {
+        return Err(ApiError::BadRequest(format!(
+            "Cargo.lock is larger than {} bytes",
+            MAX_LOCKFILE_BYTES
+        )));
+    }
+    Ok(content)
+}
+
+/// 审计时从数据库查到的信息
+#[derive(Debug, Default)]
+pub struct AuditFacts {
+    /// Crates Pro 已收录的 crate 及其评估分数
+    pub programs: HashMap<String, Option<f64>>,
+    pub licenses: HashMap<String, String>,
+    /// crates.io 上 `(name, version)` 是否已被 yank
+    pub yanked: HashMap<(String, String), bool>,
+    pub advisories: HashMap<(String, String), Vec<AdvisoryTarget>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct AuditReport {
+    pub summary: AuditSummary,
+    pub packages: Vec<AuditPackage>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, ToSchema)]
+pub struct AuditSummary {
+    pub packages: usize,
+    /// 有已知漏洞的包
+    pub vulnerable: usize,
+    /// 不同漏洞的数量
+    pub advisories: usize,
+    pub yanked: usize,
+    /// Crates Pro 未收录的 crates.io 包
+    pub unknown: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct AuditPackage {
+    pub name: String,
+    pub version: String,
+    pub source: PackageSource,
+    /// Crates Pro 是否收录了该 crate，非 crates.io 的包为空
+    pub known: Option<bool>,
+    /// 为空表示 crates.io 数据中没有该版本
+    pub yanked: Option<bool>,
+    pub license: Option<String>,
+    /// `metadata` 表中的综合评估分数
+    pub evaluated_score: Option<f64>,
+    pub advisories: Vec<AuditAdvisory>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct AuditAdvisory {
+    pub id: String,
+    pub title: String,
+    pub url: String,
+    pub aliases: Vec<String>,
+    /// 修复版本范围
+    pub patched: Vec<String>,
+}
+
+impl From<&AdvisoryTarget> for AuditAdvisory {
+    fn from(target: &AdvisoryTarget) -> Self {
+        let split = |value: &str, sep: char| {
+            value
+                .split(sep)
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+        AuditAdvisory {
+            id: target.info.id.clone(),
+            title: target.info.subtitle.clone(),
+            url: target.info.url.clone(),
+            aliases: split(&target.info.aliases, ';'),
+            patched: split(&target.info.patched, '|'),
+        }
+    }
+}
+
+/// 需要查询数据库的包，即来自 crates.io 的包
+pub fn crates_io_packages(packages: &[LockedPackage]) -> Vec<&LockedPackage> {
+    packages
+        .iter()
+        .filter(|p| p.source_kind() == PackageSource::CratesIo)
+        .collect()
+}
+
+pub fn build_report(packages: &[LockedPackage], facts: &AuditFacts) -> AuditReport {
+    let mut summary = AuditSummary {
+        packages: packages.len(),
+        ..Default::default()
+    };
+    let mut advisory_ids = BTreeSet::new();
+    let mut result = vec![];
+    for package in packages {
+        let source = package.source_kind();
+        let mut entry = AuditPackage {
+            name: package.name.clone(),
+            version: package.version.clone(),
+            source,
+            known: None,
+            yanked: None,
+            license: None,
+            evaluated_score: None,
+            advisories: vec![],
+        };
+        if source == PackageSource::CratesIo {
+            let key = (package.name.clone(), package.version.clone());
+            entry.known = Some(facts.programs.contains_key(&package.name));
+            entry.yanked = facts.yanked.get(&key).copied();
+            entry.license = facts.licenses.get(&package.name).cloned();
+            entry.evaluated_score = facts.programs.get(&package.name).copied().flatten();
+            for target in facts.advisories.get(&key).into_iter().flatten() {
+                if entry.advisories.iter().all(|a| a.id != target.info.id) {
+                    entry.advisories.push(target.into());
+                }
+            }
+        }
+
+        summary.vulnerable += usize::from(!entry.advisories.is_empty());
+        summary.yanked += usize::from(entry.yanked == Some(true));
+        summary.unknown += usize::from(entry.known == Some(false));
+        advisory_ids.extend(entry.advisories.iter().map(|a| a.id.clone()));
+        result.push(entry);
+    }
+    summary.advisories = advisory_ids.len();
+    AuditReport {
+        summary,
+        packages: result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+    use crate::advisory::AdvisoryMatcher;
+    use crate::handler::NewRustsec;
+
+    const LOCKFILE: &str = r#"
+version = 4
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = ["time", "smallvec"]
+
+[[package]]
+name = "smallvec"
+version = "1.6.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "fe0f37c9e8f3c5a4a66ad655a93c74daac4ad00c441533bf5c6e7990bb42604e"
+
+[[package]]
+name = "time"
+version = "0.1.44"
+source = "sparse+https://index.crates.io/"
+
+[[package]]
+name = "private"
+version = "1.0.0"
+source = "git+https://example.com/private.git#0123456789abcdef"
+"#;
+
+    #[test]
+    fn test_parse_lockfile() {
+        let packages = parse_lockfile(LOCKFILE).unwrap();
+        let sources: Vec<(&str, PackageSource)> = packages
+            .iter()
+            .map(|p| (p.name.as_str(), p.source_kind()))
+            .collect();
+        assert_eq!(
+            sources,
+            [
+                ("app", PackageSource::Path),
+                ("private", PackageSource::Git),
+                ("smallvec", PackageSource::CratesIo),
+                ("time", PackageSource::CratesIo),
+            ]
+        );
+        assert!(matches!(
+            parse_lockfile("[[package]]\nname = 1"),
+            Err(ApiError::BadRequest(_))
+        ));
+        assert!(parse_lockfile("version = 4").is_err());
+
+        // zip 中取根目录的 Cargo.lock
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default();
+        for (path, content) in [
+            ("project/vendor/dep/Cargo.lock", "version = 4"),
+            ("project/Cargo.lock", LOCKFILE),
+            ("project/src/main.rs", "fn main() {}"),
+        ] {
+            zip.start_file(path, options).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        let data = zip.finish().unwrap().into_inner();
+        assert_eq!(lockfile_from_zip(&data).unwrap(), LOCKFILE);
+        assert!(lockfile_from_zip(b"not a zip").is_err());
+    }
+
+    #[test]
+    fn test_build_report() {
+        let packages = parse_lockfile(LOCKFILE).unwrap();
+        let info = NewRustsec {
+            id: "RUSTSEC-2021-0003".to_string(),
+            subtitle: "Buffer overflow in SmallVec::insert_many".to_string(),
+            aliases: "CVE-2021-25900".to_string(),
+            patched: ">= 0.6.14, < 1.0.0|>= 1.6.1".to_string(),
+            url: "https://rustsec.org/advisories/RUSTSEC-2021-0003.html".to_string(),
+            ..Default::default()
+        };
+        let target = AdvisoryTarget {
+            crate_name: "smallvec".to_string(),
+            matcher: AdvisoryMatcher::new(&info.patched, ""),
+            info,
+        };
+        let facts = AuditFacts {
+            programs: HashMap::from([("smallvec".to_string(), Some(82.5))]),
+            licenses: HashMap::from([("smallvec".to_string(), "MIT OR Apache-2.0".to_string())]),
+            yanked: HashMap::from([(("smallvec".to_string(), "1.6.0".to_string()), true)]),
+            advisories: HashMap::from([(
+                ("smallvec".to_string(), "1.6.0".to_string()),
+                vec![target.clone(), target],
+            )]),
+        };
+        let report = build_report(&packages, &facts);
+        assert_eq!(
+            report.summary,
+            AuditSummary {
+                packages: 4,
+                vulnerable: 1,
+                advisories: 1,
+                yanked: 1,
+                unknown: 1,
+            }
+        );
+        let smallvec = &report.packages[2];
+        assert_eq!(smallvec.known, Some(true));
+        assert_eq!(smallvec.evaluated_score, Some(82.5));
+        assert_eq!(smallvec.advisories.len(), 1);
+        assert_eq!(
+            smallvec.advisories[0].patched,
+            [">= 0.6.14, < 1.0.0", ">= 1.6.1"]
+        );
+        let time = &report.packages[3];
+        assert_eq!((time.known, time.yanked), (Some(false), None));
+        // 路径和 git 依赖不查询
+        assert_eq!(report.packages[0].known, None);
+        assert_eq!(report.packages[1].license, None);
+    }
+}
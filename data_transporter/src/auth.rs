@@ -0,0 +1,577 @@
+//! API Key 认证与限流
+//!
+//! Key 的格式为 `cpk_<id>_<secret>`，通过 `X-API-Key` 或 `Authorization: Bearer`
+//! 请求头传入。数据库只保存 Key 的 SHA-256，明文只在签发时返回一次。
+//!
+//! [`authenticate`] 中间件校验 Key 并按 [`access`] 的规则检查权限：上传接口和上传任务
+//! 需要 `upload`，用户资料和 Cargo.lock 审计需要 `read`，Key 管理需要 `admin`（`admin`
+//! 包含其他所有权限），其余接口允许匿名访问，也接受任意权限的有效 Key。权限按请求将匹配的
+//! 路由模式判断，而不是原始路径，避免 `/api/%6Beys` 之类的编码路径绕过检查。每个请求在校验 Key 之前先按 IP 计数，
+//! 通过认证后再按 Key 计数，均在 Redis 中按分钟限流，Redis 不可用时不限流。通过认证的
+//! 请求可以用 [`Principal`] 提取调用者身份。
+
+use std::env;
+use std::fmt;
+use std::future::{ready, Ready};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderName, HeaderValue};
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::{web, FromRequest, HttpMessage, HttpRequest};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, SecurityScheme};
+use utoipa::{Modify, ToSchema};
+
+use crate::db::DBHandler;
+use crate::error::ApiError;
+use crate::pool::ConnectionPools;
+
+const KEY_PREFIX: &str = "cpk_";
+const KEY_ID_LEN: usize = 12;
+const KEY_SECRET_LEN: usize = 32;
+
+/// 限流的时间窗口（秒）
+const RATE_LIMIT_WINDOW_SECS: u64 = 60;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    Read,
+    Upload,
+    Admin,
+}
+
+impl Scope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::Upload => "upload",
+            Scope::Admin => "admin",
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [Scope::Read, Scope::Upload, Scope::Admin]
+            .into_iter()
+            .find(|scope| scope.as_str() == s)
+            .ok_or_else(|| format!("unknown scope {}, expected read, upload or admin", s))
+    }
+}
+
+/// 通过认证的调用者
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    pub key_id: String,
+    pub email: String,
+    pub scopes: Vec<Scope>,
+    /// 覆盖默认的每分钟请求数
+    pub rate_limit: Option<u32>,
+}
+
+impl Principal {
+    pub fn allows(&self, scope: Scope) -> bool {
+        self.scopes.contains(&Scope::Admin) || self.scopes.contains(&scope)
+    }
+}
+
+impl FromRequest for Principal {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(
+            req.extensions()
+                .get::<Principal>()
+                .cloned()
+                .ok_or_else(|| ApiError::Unauthorized("API key required".to_string())),
+        )
+    }
+}
+
+/// 签发 Key 的请求
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct NewApiKey {
+    /// Key 所代表的用户
+    pub email: String,
+    /// 备注，如使用 Key 的服务
+    pub name: Option<String>,
+    pub scopes: Vec<Scope>,
+    /// 每分钟请求数，默认使用 `RATE_LIMIT_KEY_PER_MINUTE`
+    pub rate_limit: Option<u32>,
+}
+
+/// 新签发的 Key，明文只返回这一次
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct IssuedApiKey {
+    pub id: String,
+    pub key: String,
+    pub email: String,
+    pub scopes: Vec<Scope>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct ApiKeyInfo {
+    pub id: String,
+    pub email: String,
+    pub name: Option<String>,
+    pub scopes: Vec<Scope>,
+    pub rate_limit: Option<u32>,
+    #[schema(value_type = String, format = DateTime)]
+    pub created_at: DateTime<Utc>,
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub last_used_at: Option<DateTime<Utc>>,
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct ApiKeyQuery {
+    /// 只列出该用户的 Key
+    pub email: Option<String>,
+}
+
+/// 在 OpenAPI 文档中声明 `X-API-Key` 认证方式
+pub struct ApiKeySecurity;
+
+impl Modify for ApiKeySecurity {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "api_key",
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-API-Key"))),
+            );
+        }
+    }
+}
+
+/// 生成新的 Key，返回 `(id, key)`
+pub fn generate_key() -> (String, String) {
+    let id = uuid::Uuid::new_v4().simple().to_string()[..KEY_ID_LEN].to_string();
+    let secret = uuid::Uuid::new_v4().simple().to_string();
+    let key = format!("{}{}_{}", KEY_PREFIX, id, secret);
+    (id, key)
+}
+
+/// 格式正确时返回 Key 的 id
+pub fn parse_key(key: &str) -> Option<&str> {
+    let (id, secret) = key.strip_prefix(KEY_PREFIX)?.split_once('_')?;
+    let is_hex = |s: &str, len: usize| s.len() == len && s.bytes().all(|b| b.is_ascii_hexdigit());
+    (is_hex(id, KEY_ID_LEN) && is_hex(secret, KEY_SECRET_LEN)).then_some(id)
+}
+
+pub fn hash_key(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))
+}
+
+/// 签发 Key 并保存其摘要
+pub async fn issue_key(db: &DBHandler, new: &NewApiKey) -> Result<IssuedApiKey, ApiError> {
+    if new.email.trim().is_empty() {
+        return Err(ApiError::BadRequest("email must not be empty".to_string()));
+    }
+    if new.scopes.is_empty() {
+        return Err(ApiError::BadRequest(
+            "at least one scope is required".to_string(),
+        ));
+    }
+    let (id, key) = generate_key();
+    db.create_api_key(&id, &hash_key(&key), new).await?;
+    Ok(IssuedApiKey {
+        id,
+        key,
+        email: new.email.clone(),
+        scopes: new.scopes.clone(),
+    })
+}
+
+/// 接口的访问要求
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// 不认证也不限流
+    Exempt,
+    /// 允许匿名访问，带 Key 时 Key 须有效
+    Public,
+    /// 需要带有该权限的 Key
+    Requires(Scope),
+}
+
+/// 按路由模式（见 [`route_pattern`]）判断接口的访问要求
+pub fn access(method: &Method, pattern: &str) -> Access {
+    match pattern {
+        "/api/health" => Access::Exempt,
+        _ if pattern.starts_with("/swagger-ui") || pattern.starts_with("/api-docs") => {
+            Access::Exempt
+        }
+        "/api/keys" | "/api/keys/{id}" => Access::Requires(Scope::Admin),
+        "/api/submit" | "/api/submitCrate" if method == Method::POST => {
+            Access::Requires(Scope::Upload)
+        }
+        "/api/jobs/{id}" => Access::Requires(Scope::Upload),
+        "/api/profile" | "/api/submitUserinfo" | "/api/audit/lockfile" => {
+            Access::Requires(Scope::Read)
+        }
+        _ => Access::Public,
+    }
+}
+
+/// 请求将匹配的路由模式，如 `/api/keys/{id}`，没有匹配的路由时为路径本身。
+/// 中间件在路由之前运行，`req.path()` 是未解码的原始路径，这里与路由器一样使用解码后的路径
+fn route_pattern(req: &ServiceRequest) -> String {
+    let path = req.match_info().as_str();
+    req.resource_map()
+        .match_pattern(path)
+        .unwrap_or_else(|| path.to_string())
+}
+
+pub fn authorize(access: Access, principal: Option<&Principal>) -> Result<(), ApiError> {
+    let required = match access {
+        Access::Exempt | Access::Public => return Ok(()),
+        Access::Requires(scope) => scope,
+    };
+    match principal {
+        None => Err(ApiError::Unauthorized("API key required".to_string())),
+        Some(principal) if !principal.allows(required) => Err(ApiError::Forbidden(format!(
+            "API key lacks the {} scope",
+            required
+        ))),
+        Some(_) => Ok(()),
+    }
+}
+
+/// 每分钟请求数限制
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimits {
+    pub anonymous_per_minute: u32,
+    pub key_per_minute: u32,
+    /// 同一 IP 上带 Key 的请求在校验 Key 之前的上限，限制用无效 Key 刷接口
+    pub keyed_ip_per_minute: u32,
+    /// 是否按 `Forwarded` / `X-Forwarded-For` 识别匿名请求的 IP，仅在反向代理后开启
+    pub trust_proxy: bool,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        RateLimits {
+            anonymous_per_minute: 60,
+            key_per_minute: 600,
+            keyed_ip_per_minute: 6000,
+            trust_proxy: false,
+        }
+    }
+}
+
+impl RateLimits {
+    /// 从 `RATE_LIMIT_ANONYMOUS_PER_MINUTE`、`RATE_LIMIT_KEY_PER_MINUTE`、
+    /// `RATE_LIMIT_KEYED_IP_PER_MINUTE`、`RATE_LIMIT_TRUST_PROXY` 读取配置，未设置的项使用默认值
+    pub fn from_env() -> Self {
+        fn var<T: FromStr>(key: &str) -> Option<T> {
+            env::var(key).ok().and_then(|v| v.parse().ok())
+        }
+        let default = RateLimits::default();
+        RateLimits {
+            anonymous_per_minute: var("RATE_LIMIT_ANONYMOUS_PER_MINUTE")
+                .unwrap_or(default.anonymous_per_minute),
+            key_per_minute: var("RATE_LIMIT_KEY_PER_MINUTE").unwrap_or(default.key_per_minute),
+            keyed_ip_per_minute: var("RATE_LIMIT_KEYED_IP_PER_MINUTE")
+                .unwrap_or(default.keyed_ip_per_minute),
+            trust_proxy: env::var("RATE_LIMIT_TRUST_PROXY").is_ok_and(|v| v == "1"),
+        }
+    }
+
+    /// 校验 Key 之前按 IP 计数的 bucket 和每分钟上限，带 Key 和匿名的请求分开计数
+    fn ip_bucket(&self, ip: &str, keyed: bool) -> (String, u32) {
+        if keyed {
+            (format!("key-ip:{}", ip), self.keyed_ip_per_minute)
+        } else {
+            (format!("ip:{}", ip), self.anonymous_per_minute)
+        }
+    }
+
+    /// 通过认证后按 Key 计数的 bucket 和每分钟上限
+    fn key_bucket(&self, principal: &Principal) -> (String, u32) {
+        (
+            format!("key:{}", principal.key_id),
+            principal.rate_limit.unwrap_or(self.key_per_minute),
+        )
+    }
+}
+
+/// 请求头中的 Key
+fn credential(req: &ServiceRequest) -> Option<String> {
+    let headers = req.headers();
+    if let Some(key) = headers.get("x-api-key") {
+        return key.to_str().ok().map(|key| key.trim().to_string());
+    }
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then(|| token.trim().to_string())
+}
+
+async fn verify(pools: &ConnectionPools, key: &str) -> Result<Principal, ApiError> {
+    let invalid = || ApiError::Unauthorized("invalid API key".to_string());
+    let id = parse_key(key).ok_or_else(invalid)?;
+    pools
+        .pg()
+        .await?
+        .authenticate_api_key(id, &hash_key(key))
+        .await?
+        .ok_or_else(invalid)
+}
+
+/// 计数并在超过 `limit` 时返回 429，返回已用次数和上限；Redis 不可用时返回 `None`
+async fn enforce_limit(
+    pools: &ConnectionPools,
+    bucket: &str,
+    limit: u32,
+) -> Result<Option<(u64, u32)>, ApiError> {
+    match count_request(pools, bucket).await {
+        Some((used, retry_after)) if used > u64::from(limit) => {
+            Err(ApiError::RateLimited { limit, retry_after })
+        }
+        counted => Ok(counted.map(|(used, _)| (used, limit))),
+    }
+}
+
+/// 在窗口内计数，返回已用次数和距窗口结束的秒数；Redis 不可用时返回 `None`
+async fn count_request(pools: &ConnectionPools, bucket: &str) -> Option<(u64, u64)> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let window = now / RATE_LIMIT_WINDOW_SECS;
+    let counted = match pools.redis().await {
+        Ok(mut redis) => redis
+            .count_request(bucket, window, RATE_LIMIT_WINDOW_SECS)
+            .await
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    match counted {
+        Ok(used) => Some((used, RATE_LIMIT_WINDOW_SECS - now % RATE_LIMIT_WINDOW_SECS)),
+        Err(e) => {
+            tracing::warn!("rate limiting skipped, redis unavailable: {}", e);
+            None
+        }
+    }
+}
+
+/// 认证与限流中间件
+pub async fn authenticate(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let access = access(req.method(), &route_pattern(&req));
+    if access == Access::Exempt {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    }
+    let key = credential(&req);
+    // 不带 Key 访问需要权限的接口，不必计数和查询数据库
+    if key.is_none() {
+        authorize(access, None)?;
+    }
+    let pools = req
+        .app_data::<web::Data<ConnectionPools>>()
+        .cloned()
+        .ok_or_else(|| ApiError::Backend("connection pools are not configured".to_string()))?;
+    let limits = req
+        .app_data::<web::Data<RateLimits>>()
+        .map(|limits| *limits.get_ref())
+        .unwrap_or_default();
+
+    let ip = {
+        let info = req.connection_info();
+        let ip = if limits.trust_proxy {
+            info.realip_remote_addr()
+        } else {
+            info.peer_addr()
+        };
+        ip.unwrap_or("unknown").to_string()
+    };
+    // 校验 Key 之前先按 IP 计数，无效的 Key 同样受限，也不会每次都查询数据库
+    let (bucket, limit) = limits.ip_bucket(&ip, key.is_some());
+    let mut counted = enforce_limit(&pools, &bucket, limit).await?;
+
+    let principal = match key {
+        Some(key) => Some(verify(&pools, &key).await?),
+        None => None,
+    };
+    authorize(access, principal.as_ref())?;
+
+    if let Some(principal) = principal {
+        let (bucket, limit) = limits.key_bucket(&principal);
+        counted = enforce_limit(&pools, &bucket, limit).await?;
+        req.extensions_mut().insert(principal);
+    }
+    let mut res = next.call(req).await?;
+    if let Some((used, limit)) = counted {
+        let remaining = u64::from(limit).saturating_sub(used);
+        let headers = res.headers_mut();
+        headers.insert(
+            HeaderName::from_static("x-ratelimit-limit"),
+            HeaderValue::from(limit),
+        );
+        headers.insert(
+            HeaderName::from_static("x-ratelimit-remaining"),
+            HeaderValue::from(remaining),
+        );
+    }
+    Ok(res.map_into_boxed_body())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn principal(scopes: &[Scope]) -> Principal {
+        Principal {
+            key_id: "0123456789ab".to_string(),
+            email: "dev@example.com".to_string(),
+            scopes: scopes.to_vec(),
+            rate_limit: None,
+        }
+    }
+
+    #[test]
+    fn test_keys() {
+        let (id, key) = generate_key();
+        assert_eq!(parse_key(&key), Some(id.as_str()));
+        assert_eq!(hash_key(&key), hash_key(&key));
+        assert_eq!(hash_key(&key).len(), 64);
+        assert_ne!(hash_key(&key), hash_key(&generate_key().1));
+
+        let secret = "0123456789abcdef0123456789abcdef";
+        assert_eq!(
+            parse_key(&format!("cpk_0123456789ab_{}", secret)),
+            Some("0123456789ab")
+        );
+        assert_eq!(parse_key(&format!("cpk_0123_{}", secret)), None);
+        assert_eq!(parse_key(&format!("xyz_0123456789ab_{}", secret)), None);
+        assert_eq!(parse_key("cpk_0123456789ab_not-hex"), None);
+
+        assert_eq!("upload".parse::<Scope>(), Ok(Scope::Upload));
+        assert!("write".parse::<Scope>().is_err());
+    }
+
+    #[test]
+    fn test_access() {
+        assert_eq!(access(&Method::GET, "/api/health"), Access::Exempt);
+        assert_eq!(access(&Method::GET, "/swagger-ui/{_:.*}"), Access::Exempt);
+        assert_eq!(access(&Method::GET, "/api/crates"), Access::Public);
+        assert_eq!(access(&Method::POST, "/api/search"), Access::Public);
+        assert_eq!(
+            access(&Method::POST, "/api/submitCrate"),
+            Access::Requires(Scope::Upload)
+        );
+        assert_eq!(
+            access(&Method::POST, "/api/profile"),
+            Access::Requires(Scope::Read)
+        );
+        assert_eq!(
+            access(&Method::DELETE, "/api/keys/{id}"),
+            Access::Requires(Scope::Admin)
+        );
+        assert_eq!(access(&Method::GET, "/api/keysmith"), Access::Public);
+        assert_eq!(
+            access(&Method::POST, "/api/audit/lockfile"),
+            Access::Requires(Scope::Read)
+        );
+        assert_eq!(
+            access(&Method::GET, "/api/jobs/{id}"),
+            Access::Requires(Scope::Upload)
+        );
+    }
+
+    /// 编码后的路径与路由器一样按解码后的路径匹配，不能绕过权限检查
+    #[actix_web::test]
+    async fn test_encoded_path() {
+        use actix_web::{middleware, test, App, HttpResponse};
+
+        let app = test::init_service(
+            App::new()
+                .wrap(middleware::from_fn(authenticate))
+                .route("/api/keys", web::post().to(HttpResponse::Ok))
+                .route("/api/keys/{id}", web::delete().to(HttpResponse::Ok))
+                .route("/api/audit/lockfile", web::post().to(HttpResponse::Ok)),
+        )
+        .await;
+        for (method, path) in [
+            (Method::POST, "/api/keys"),
+            (Method::POST, "/api/%6Beys"),
+            (Method::POST, "/api/%6b%65%79%73"),
+            (Method::DELETE, "/api/%6Beys/0123456789ab"),
+            (Method::POST, "/api/audit/%6Cockfile"),
+        ] {
+            let req = test::TestRequest::default()
+                .method(method)
+                .uri(path)
+                .to_request();
+            let status = match test::try_call_service(&app, req).await {
+                Ok(res) => res.status(),
+                Err(e) => e.as_response_error().status_code(),
+            };
+            assert_eq!(status, 401, "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_authorize() {
+        let reader = principal(&[Scope::Read]);
+        let uploader = principal(&[Scope::Upload]);
+        let admin = principal(&[Scope::Admin]);
+        let upload = Access::Requires(Scope::Upload);
+
+        assert!(authorize(Access::Public, None).is_ok());
+        assert!(authorize(Access::Public, Some(&reader)).is_ok());
+        // 任意权限的有效 Key 都可以访问公开接口
+        assert!(authorize(Access::Public, Some(&uploader)).is_ok());
+        assert!(matches!(
+            authorize(upload, None),
+            Err(ApiError::Unauthorized(_))
+        ));
+        assert!(matches!(
+            authorize(upload, Some(&reader)),
+            Err(ApiError::Forbidden(message)) if message == "API key lacks the upload scope"
+        ));
+        assert!(authorize(upload, Some(&uploader)).is_ok());
+        assert!(authorize(upload, Some(&admin)).is_ok());
+        assert!(authorize(Access::Requires(Scope::Admin), Some(&admin)).is_ok());
+        assert!(authorize(Access::Exempt, None).is_ok());
+    }
+
+    #[test]
+    fn test_rate_limit_bucket() {
+        let limits = RateLimits::default();
+        assert_eq!(
+            limits.ip_bucket("10.0.0.1", false),
+            ("ip:10.0.0.1".to_string(), 60)
+        );
+        assert_eq!(
+            limits.ip_bucket("10.0.0.1", true),
+            ("key-ip:10.0.0.1".to_string(), 6000)
+        );
+        let mut key = principal(&[Scope::Read]);
+        assert_eq!(
+            limits.key_bucket(&key),
+            ("key:0123456789ab".to_string(), 600)
+        );
+        key.rate_limit = Some(5);
+        assert_eq!(limits.key_bucket(&key).1, 5);
+    }
+}
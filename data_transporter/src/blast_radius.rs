@@ -0,0 +1,445 @@
+//! 漏洞影响面分析
+//!
+//! 给定一条 RustSec 漏洞，从漏洞 crate 受影响的版本出发，沿 `depends_on` 边
+//! 反向逐层遍历，找出所有传递依赖它的 library / application 版本，并记录每个
+//! 版本到漏洞版本的最短依赖路径。dev 依赖既不会传递，也不会随产物发布，
+//! 因此由 [`shipped`] 过滤掉，不参与遍历。
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::future::Future;
+
+use model::tugraph_model::DependencyKind;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::advisory::AdvisoryMatcher;
+use crate::dep_graph::{GraphEdge, GraphLimits};
+use crate::error::ApiError;
+
+pub const DEFAULT_MAX_DEPTH: usize = 20;
+pub const DEFAULT_MAX_NODES: usize = 5000;
+pub const MAX_DEPTH_LIMIT: usize = 50;
+pub const MAX_NODES_LIMIT: usize = 50000;
+
+/// 影响面查询的参数
+#[derive(Deserialize, Debug, Default, Clone, ToSchema)]
+pub struct BlastRadiusQuery {
+    /// 最大反向遍历深度，默认 20，最大 50
+    pub max_depth: Option<usize>,
+    /// 最多返回的受影响版本数，默认 5000，最大 50000
+    pub max_nodes: Option<usize>,
+}
+
+impl TryFrom<BlastRadiusQuery> for GraphLimits {
+    type Error = ApiError;
+
+    fn try_from(q: BlastRadiusQuery) -> Result<Self, ApiError> {
+        let max_depth = q.max_depth.unwrap_or(DEFAULT_MAX_DEPTH);
+        let max_nodes = q.max_nodes.unwrap_or(DEFAULT_MAX_NODES);
+        if max_depth == 0 || max_depth > MAX_DEPTH_LIMIT {
+            return Err(ApiError::BadRequest(format!(
+                "max_depth must be between 1 and {}",
+                MAX_DEPTH_LIMIT
+            )));
+        }
+        if max_nodes == 0 || max_nodes > MAX_NODES_LIMIT {
+            return Err(ApiError::BadRequest(format!(
+                "max_nodes must be between 1 and {}",
+                MAX_NODES_LIMIT
+            )));
+        }
+        Ok(GraphLimits {
+            max_depth,
+            max_nodes,
+        })
+    }
+}
+
+/// 版本所属程序的类型
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ProgramKind {
+    Library,
+    Application,
+    /// 图中找不到所属程序
+    #[default]
+    Unknown,
+}
+
+impl ProgramKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ProgramKind::Library => "library",
+            ProgramKind::Application => "application",
+            ProgramKind::Unknown => "unknown",
+        }
+    }
+}
+
+/// 一个受影响的版本
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct AffectedVersion {
+    pub name: String,
+    pub version: String,
+    pub kind: ProgramKind,
+    /// 到漏洞版本的最短依赖路径，从自身开始，以漏洞 crate 的受影响版本结束
+    pub path: Vec<String>,
+    /// 路径上的依赖层数
+    pub depth: usize,
+    /// 路径最后一跳（直接依赖漏洞 crate 的边）声明的版本要求
+    pub version_req: Option<String>,
+    /// 满足该版本要求且已修复的最高版本，为空表示只更新 Cargo.lock 无法修复
+    pub patched_upgrade: Option<String>,
+}
+
+/// 同一命名空间下的受影响版本
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct NamespaceGroup {
+    /// 图中找不到所属程序时为空
+    pub namespace: String,
+    pub versions: Vec<AffectedVersion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct BlastRadius {
+    pub advisory_id: String,
+    pub aliases: Vec<String>,
+    /// 漏洞所在的 crate
+    pub crates: Vec<String>,
+    /// 图中受影响的漏洞 crate 版本，`name/version`
+    pub vulnerable_versions: Vec<String>,
+    /// 受影响的 library / application 版本总数
+    pub total: usize,
+    pub max_depth: usize,
+    pub max_nodes: usize,
+    /// 是否因深度或数量限制而有受影响版本未被收录
+    pub truncated: bool,
+    pub namespaces: Vec<NamespaceGroup>,
+}
+
+/// 反向遍历的结果
+#[derive(Debug, Default, Clone)]
+pub struct DependentTrace {
+    /// 按发现顺序排列的受影响版本及其深度，不含起点
+    pub order: Vec<(String, usize)>,
+    /// 每个版本朝漏洞方向的下一条边
+    next_hop: HashMap<String, GraphEdge>,
+    pub truncated: bool,
+}
+
+impl DependentTrace {
+    /// `id` 到漏洞版本的最短路径以及最后一跳的边
+    pub fn path(&self, id: &str) -> (Vec<String>, Option<&GraphEdge>) {
+        let mut path = vec![id.to_string()];
+        let mut last = None;
+        let mut cur = id;
+        while let Some(edge) = self.next_hop.get(cur) {
+            path.push(edge.to.clone());
+            last = Some(edge);
+            cur = &edge.to;
+        }
+        (path, last)
+    }
+}
+
+/// 从 `sources` 出发按层反向遍历。`fetch_level` 接收一层的节点，返回指向这些
+/// 节点的 `depends_on` 边。同一层内按边排序，保证最短路径的选择稳定。
+pub async fn trace_dependents<F, Fut, E>(
+    sources: &[String],
+    limits: GraphLimits,
+    mut fetch_level: F,
+) -> Result<DependentTrace, E>
+where
+    F: FnMut(Vec<String>) -> Fut,
+    Fut: Future<Output = Result<Vec<GraphEdge>, E>>,
+{
+    let mut trace = DependentTrace::default();
+    let mut seen: BTreeSet<String> = sources.iter().cloned().collect();
+    let mut frontier: Vec<String> = seen.iter().cloned().collect();
+
+    for depth in 1..=limits.max_depth + 1 {
+        if frontier.is_empty() {
+            break;
+        }
+        let level: BTreeSet<GraphEdge> = fetch_level(frontier).await?.into_iter().collect();
+        let mut next = vec![];
+        for edge in level {
+            if seen.contains(&edge.from) {
+                continue;
+            }
+            if depth > limits.max_depth || trace.order.len() >= limits.max_nodes {
+                trace.truncated = true;
+                continue;
+            }
+            seen.insert(edge.from.clone());
+            trace.order.push((edge.from.clone(), depth));
+            next.push(edge.from.clone());
+            trace.next_hop.insert(edge.from.clone(), edge);
+        }
+        frontier = next;
+    }
+    Ok(trace)
+}
+
+/// 去掉 dev 依赖边
+pub fn shipped(edges: Vec<GraphEdge>) -> Vec<GraphEdge> {
+    edges
+        .into_iter()
+        .filter(|e| e.kind != DependencyKind::Dev)
+        .collect()
+}
+
+/// 只保留普通依赖边，与依赖树、依赖方列表的口径一致
+pub fn normal_only(edges: Vec<GraphEdge>) -> Vec<GraphEdge> {
+    edges
+        .into_iter()
+        .filter(|e| e.kind == DependencyKind::Normal)
+        .collect()
+}
+
+/// 满足 `req` 且不受漏洞影响的最高版本
+pub fn patched_upgrade(
+    req: &str,
+    versions: &[String],
+    matcher: &AdvisoryMatcher,
+) -> Option<String> {
+    let req = VersionReq::parse(req).ok()?;
+    versions
+        .iter()
+        .filter_map(|v| Version::parse(v).ok())
+        .filter(|v| req.matches(v) && !matcher.is_affected_version(v))
+        .max()
+        .map(|v| v.to_string())
+}
+
+/// 漏洞 crate 的信息，用于判断修复版本
+pub struct VulnerableCrate<'a> {
+    pub matcher: &'a AdvisoryMatcher,
+    /// 图中该 crate 的全部版本
+    pub versions: &'a [String],
+}
+
+/// 把遍历结果按命名空间分组
+pub fn group_by_namespace(
+    trace: &DependentTrace,
+    programs: &HashMap<String, (String, ProgramKind)>,
+    crates: &HashMap<String, VulnerableCrate>,
+) -> Vec<NamespaceGroup> {
+    let mut groups: BTreeMap<String, Vec<AffectedVersion>> = BTreeMap::new();
+    for (id, depth) in &trace.order {
+        let Some((name, version)) = id.split_once('/') else {
+            continue;
+        };
+        let (path, last) = trace.path(id);
+        let version_req = last.and_then(|e| e.version_req.clone());
+        let patched = match (last, &version_req) {
+            (Some(edge), Some(req)) => edge
+                .to
+                .split_once('/')
+                .and_then(|(crate_name, _)| crates.get(crate_name))
+                .and_then(|c| patched_upgrade(req, c.versions, c.matcher)),
+            _ => None,
+        };
+        let (namespace, kind) = programs.get(id).cloned().unwrap_or_default();
+        groups.entry(namespace).or_default().push(AffectedVersion {
+            name: name.to_string(),
+            version: version.to_string(),
+            kind,
+            path,
+            depth: *depth,
+            version_req,
+            patched_upgrade: patched,
+        });
+    }
+    groups
+        .into_iter()
+        .map(|(namespace, mut versions)| {
+            versions.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+            NamespaceGroup {
+                namespace,
+                versions,
+            }
+        })
+        .collect()
+}
+
+impl BlastRadius {
+    /// 每个受影响版本一行的 CSV
+    pub fn to_csv(&self) -> Result<String, csv::Error> {
+        let mut writer = csv::Writer::from_writer(vec![]);
+        writer.write_record([
+            "advisory_id",
+            "namespace",
+            "name",
+            "version",
+            "kind",
+            "depth",
+            "version_req",
+            "patched_upgrade",
+            "path",
+        ])?;
+        for group in &self.namespaces {
+            for v in &group.versions {
+                writer.write_record([
+                    self.advisory_id.as_str(),
+                    group.namespace.as_str(),
+                    v.name.as_str(),
+                    v.version.as_str(),
+                    v.kind.as_str(),
+                    v.depth.to_string().as_str(),
+                    v.version_req.as_deref().unwrap_or(""),
+                    v.patched_upgrade.as_deref().unwrap_or(""),
+                    v.path.join(" -> ").as_str(),
+                ])?;
+            }
+        }
+        let bytes = writer
+            .into_inner()
+            .map_err(|e| csv::Error::from(e.into_error()))?;
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge(from: &str, to: &str, req: &str, kind: DependencyKind) -> GraphEdge {
+        GraphEdge {
+            from: from.to_string(),
+            to: to.to_string(),
+            kind,
+            version_req: Some(req.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_blast_radius() {
+        // time 0.1.43 受影响，0.1.45 已修复
+        let edges = vec![
+            edge(
+                "chrono/0.4.19",
+                "time/0.1.43",
+                "^0.1.43",
+                DependencyKind::Normal,
+            ),
+            edge(
+                "old/1.0.0",
+                "time/0.1.43",
+                "=0.1.43",
+                DependencyKind::Normal,
+            ),
+            edge("app/1.0.0", "chrono/0.4.19", "0.4", DependencyKind::Normal),
+            edge("app/1.0.0", "time/0.1.43", "0.1", DependencyKind::Normal),
+            edge("tool/0.1.0", "app/1.0.0", "1", DependencyKind::Normal),
+            edge("bench/0.1.0", "chrono/0.4.19", "0.4", DependencyKind::Dev),
+        ];
+        let limits = GraphLimits {
+            max_depth: 10,
+            max_nodes: 100,
+        };
+        let sources = vec!["time/0.1.43".to_string()];
+        let fetch = |level: Vec<String>| {
+            let found: Vec<GraphEdge> = edges
+                .iter()
+                .filter(|e| level.contains(&e.to))
+                .cloned()
+                .collect();
+            async move { Ok::<_, ()>(shipped(found)) }
+        };
+        let trace = trace_dependents(&sources, limits, fetch).await.unwrap();
+        assert_eq!(
+            trace.order,
+            [
+                ("app/1.0.0".to_string(), 1),
+                ("chrono/0.4.19".to_string(), 1),
+                ("old/1.0.0".to_string(), 1),
+                ("tool/0.1.0".to_string(), 2),
+            ]
+        );
+        assert!(!trace.truncated);
+        // app 直接依赖 time，最短路径不经过 chrono
+        assert_eq!(
+            trace.path("tool/0.1.0").0,
+            ["tool/0.1.0", "app/1.0.0", "time/0.1.43"]
+        );
+
+        let matcher = AdvisoryMatcher::new(">= 0.1.45", "");
+        let versions = vec![
+            "0.1.43".to_string(),
+            "0.1.45".to_string(),
+            "0.2.0".to_string(),
+        ];
+        let crates = HashMap::from([(
+            "time".to_string(),
+            VulnerableCrate {
+                matcher: &matcher,
+                versions: &versions,
+            },
+        )]);
+        let programs = HashMap::from([
+            (
+                "app/1.0.0".to_string(),
+                ("acme/app".to_string(), ProgramKind::Application),
+            ),
+            (
+                "chrono/0.4.19".to_string(),
+                ("chronotope/chrono".to_string(), ProgramKind::Library),
+            ),
+        ]);
+        let groups = group_by_namespace(&trace, &programs, &crates);
+        let namespaces: Vec<&str> = groups.iter().map(|g| g.namespace.as_str()).collect();
+        assert_eq!(namespaces, ["", "acme/app", "chronotope/chrono"]);
+        let chrono = &groups[2].versions[0];
+        assert_eq!(chrono.kind, ProgramKind::Library);
+        assert_eq!(chrono.patched_upgrade.as_deref(), Some("0.1.45"));
+        let old = groups[0].versions.iter().find(|v| v.name == "old").unwrap();
+        assert_eq!(old.version_req.as_deref(), Some("=0.1.43"));
+        assert_eq!(old.patched_upgrade, None);
+        let tool = groups[0]
+            .versions
+            .iter()
+            .find(|v| v.name == "tool")
+            .unwrap();
+        assert_eq!(tool.depth, 2);
+        assert_eq!(tool.kind, ProgramKind::Unknown);
+        assert_eq!(tool.patched_upgrade.as_deref(), Some("0.1.45"));
+
+        let limited = trace_dependents(
+            &sources,
+            GraphLimits {
+                max_depth: 1,
+                max_nodes: 100,
+            },
+            fetch,
+        )
+        .await
+        .unwrap();
+        assert_eq!(limited.order.len(), 3);
+        assert!(limited.truncated);
+
+        let radius = BlastRadius {
+            advisory_id: "RUSTSEC-2020-0071".to_string(),
+            aliases: vec![],
+            crates: vec!["time".to_string()],
+            vulnerable_versions: sources.clone(),
+            total: trace.order.len(),
+            max_depth: 10,
+            max_nodes: 100,
+            truncated: false,
+            namespaces: groups,
+        };
+        let csv = radius.to_csv().unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("advisory_id,namespace,name,version,kind,depth,version_req,patched_upgrade,path")
+        );
+        assert!(csv.contains(
+            "RUSTSEC-2020-0071,acme/app,app,1.0.0,application,1,0.1,0.1.45,app/1.0.0 -> time/0.1.43"
+        ));
+        assert_eq!(csv.lines().count(), 5);
+    }
+}
@@ -0,0 +1,356 @@
+//! Redis 响应缓存
+//!
+//! 每种缓存内容对应一个 [`CacheKind`]，决定 key 前缀和过期时间。写入缓存时
+//! 同时把 key 记入 `cache_index:{name}` 集合，失效时按 crate 名称删除，
+//! 不需要扫描整个库。
+//!
+//! 导入程序和漏洞索引在数据变化后向 [`CACHE_EVENT_CHANNEL`] 发布
+//! [`CacheEvent`]，API 服务订阅该通道，删除变化的 crate、它的直接依赖
+//! （被依赖列表变了）以及全部传递被依赖者的缓存页面。Redis 的发布订阅
+//! 不保证送达，错过的事件由过期时间兜底。
+
+use std::collections::BTreeSet;
+use std::env;
+use std::error::Error;
+use std::time::Duration;
+
+use redis::aio::MultiplexedConnection;
+use redis::AsyncCommands;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::blast_radius::normal_only;
+use crate::data_reader::{DataReader, DataReaderTrait};
+use crate::dep_graph::GraphLimits;
+use crate::error::ApiError;
+use crate::pool::ConnectionPools;
+use crate::redis_store::redis_url_from_env;
+
+/// 缓存失效事件的发布订阅通道
+pub const CACHE_EVENT_CHANNEL: &str = "cratespro:cache_events";
+
+/// 旧版本写入的依赖列表和被依赖列表缓存的 key 前缀，新版本不再读取，
+/// API 服务启动时删除
+pub const LEGACY_PREFIXES: [&str; 2] = ["dependency", "dependent"];
+
+/// 查找传递被依赖者的范围，超出时清空全部缓存
+const INVALIDATION_LIMITS: GraphLimits = GraphLimits {
+    max_depth: 20,
+    max_nodes: 20000,
+};
+
+/// 缓存内容的种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheKind {
+    /// 版本详情页
+    CratesInfo,
+    /// 完整的依赖列表
+    DependencyList,
+    /// 依赖树
+    DependencyGraph,
+    /// 完整的被依赖列表
+    DependentList,
+    /// 全部版本列表
+    VersionPage,
+}
+
+impl CacheKind {
+    pub const ALL: [CacheKind; 5] = [
+        CacheKind::CratesInfo,
+        CacheKind::DependencyList,
+        CacheKind::DependencyGraph,
+        CacheKind::DependentList,
+        CacheKind::VersionPage,
+    ];
+
+    pub fn prefix(self) -> &'static str {
+        match self {
+            CacheKind::CratesInfo => "crates_info",
+            CacheKind::DependencyList => "dependency_list",
+            CacheKind::DependencyGraph => "dependencygraph",
+            CacheKind::DependentList => "dependent_list",
+            CacheKind::VersionPage => "versionpage",
+        }
+    }
+
+    /// 覆盖过期时间（秒）的环境变量
+    fn ttl_env(self) -> &'static str {
+        match self {
+            CacheKind::CratesInfo => "CACHE_TTL_CRATES_INFO_SECS",
+            CacheKind::DependencyList => "CACHE_TTL_DEPENDENCY_LIST_SECS",
+            CacheKind::DependencyGraph => "CACHE_TTL_DEPENDENCY_GRAPH_SECS",
+            CacheKind::DependentList => "CACHE_TTL_DEPENDENT_LIST_SECS",
+            CacheKind::VersionPage => "CACHE_TTL_VERSION_PAGE_SECS",
+        }
+    }
+
+    /// 被依赖者和新版本随导入不断增加，默认过期得更快
+    fn default_ttl(self) -> Duration {
+        const HOUR: u64 = 60 * 60;
+        Duration::from_secs(match self {
+            CacheKind::CratesInfo | CacheKind::DependencyList | CacheKind::DependencyGraph => {
+                24 * HOUR
+            }
+            CacheKind::DependentList => 6 * HOUR,
+            CacheKind::VersionPage => HOUR,
+        })
+    }
+
+    pub fn ttl(self) -> Duration {
+        parse_ttl(env::var(self.ttl_env()).ok().as_deref()).unwrap_or_else(|| self.default_ttl())
+    }
+}
+
+fn parse_ttl(value: Option<&str>) -> Option<Duration> {
+    value
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs)
+}
+
+/// 可以放入响应缓存的类型
+pub trait Cached: Serialize + DeserializeOwned {
+    const KIND: CacheKind;
+}
+
+/// 缓存条目所属的 crate，版本列表等不区分版本的内容 `version` 为空
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheKey {
+    pub namespace: String,
+    pub name: String,
+    pub version: Option<String>,
+}
+
+impl CacheKey {
+    pub fn version(namespace: &str, name: &str, version: &str) -> Self {
+        CacheKey {
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+            version: Some(version.to_string()),
+        }
+    }
+
+    pub fn program(namespace: &str, name: &str) -> Self {
+        CacheKey {
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+            version: None,
+        }
+    }
+
+    pub fn redis_key(&self, kind: CacheKind) -> String {
+        match &self.version {
+            Some(version) => format!(
+                "{}:{}:{}:{}",
+                kind.prefix(),
+                self.namespace,
+                self.name,
+                version
+            ),
+            None => format!("{}:{}:{}", kind.prefix(), self.namespace, self.name),
+        }
+    }
+}
+
+/// 记录某个 crate 全部缓存 key 的集合
+pub fn index_key(name: &str) -> String {
+    format!("cache_index:{}", name)
+}
+
+/// 缓存失效事件
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum CacheEvent {
+    /// 导入了 crate 的新版本
+    CrateChanged { name: String },
+    /// 这些 crate 的漏洞有新增、修改或删除
+    AdvisoriesChanged { crates: Vec<String> },
+}
+
+impl CacheEvent {
+    pub fn crates(&self) -> Vec<&str> {
+        match self {
+            CacheEvent::CrateChanged { name } => vec![name.as_str()],
+            CacheEvent::AdvisoriesChanged { crates } => crates.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+/// 向 [`CACHE_EVENT_CHANNEL`] 发布事件。连接在第一次发布时建立并一直复用，
+/// 发布失败后丢弃，下一次发布时重连
+pub struct CachePublisher {
+    client: redis::Client,
+    connection: Mutex<Option<MultiplexedConnection>>,
+}
+
+impl CachePublisher {
+    pub fn from_env() -> Result<Self, redis::RedisError> {
+        Ok(CachePublisher {
+            client: redis::Client::open(redis_url_from_env())?,
+            connection: Mutex::new(None),
+        })
+    }
+
+    pub async fn publish(&self, event: &CacheEvent) -> Result<(), Box<dyn Error>> {
+        let payload = serde_json::to_string(event)?;
+        let mut connection = self.connection.lock().await;
+        if connection.is_none() {
+            *connection = Some(self.client.get_multiplexed_async_connection().await?);
+        }
+        let result = connection
+            .as_mut()
+            .expect("connected above")
+            .publish::<_, _, ()>(CACHE_EVENT_CHANNEL, payload)
+            .await;
+        if result.is_err() {
+            *connection = None;
+        }
+        Ok(result?)
+    }
+}
+
+/// 处理一个事件，返回删除的缓存条数
+pub async fn invalidate(pools: &ConnectionPools, event: &CacheEvent) -> Result<usize, ApiError> {
+    let reader = DataReader::with_pools(
+        pools.tugraph.clone(),
+        pools.pg.clone(),
+        pools.pg_cratesio.clone(),
+    );
+    let mut names: BTreeSet<String> = event.crates().into_iter().map(str::to_string).collect();
+    let mut versions = reader
+        .get_versions_of_crates(&names.iter().cloned().collect::<Vec<_>>())
+        .await?;
+    let mut sources = vec![];
+    for name in &names {
+        for version in versions.remove(name).unwrap_or_default() {
+            sources.push(format!("{}/{}", name, version));
+        }
+    }
+    if let CacheEvent::CrateChanged { .. } = event {
+        // 新版本成为其依赖的被依赖者
+        for edge in normal_only(reader.get_direct_dependency_edges(sources.clone()).await?) {
+            names.extend(crate_name(&edge.to));
+        }
+    }
+    let trace =
+        crate::blast_radius::trace_dependents(&sources, INVALIDATION_LIMITS, |level| async {
+            Ok::<_, ApiError>(normal_only(reader.get_direct_dependent_edges(level).await?))
+        })
+        .await?;
+
+    let mut redis = pools.redis().await?;
+    if trace.truncated {
+        tracing::warn!(
+            "{:?} affects more than {} crate versions, dropping the whole response cache",
+            event,
+            INVALIDATION_LIMITS.max_nodes
+        );
+        return redis
+            .invalidate_all()
+            .await
+            .map_err(|e| ApiError::unavailable("redis", e));
+    }
+    names.extend(trace.order.iter().filter_map(|(id, _)| crate_name(id)));
+    redis
+        .invalidate_crates(&names)
+        .await
+        .map_err(|e| ApiError::unavailable("redis", e))
+}
+
+async fn drop_legacy_cache(pools: &ConnectionPools) -> Result<usize, ApiError> {
+    pools
+        .redis()
+        .await?
+        .invalidate_legacy()
+        .await
+        .map_err(|e| ApiError::unavailable("redis", e))
+}
+
+fn crate_name(id: &str) -> Option<String> {
+    id.split_once('/').map(|(name, _)| name.to_string())
+}
+
+/// 订阅 [`CACHE_EVENT_CHANNEL`] 并处理收到的事件，断线后重连
+pub fn spawn_cache_invalidator(pools: ConnectionPools) {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<CacheEvent>();
+    // redis 的同步订阅会阻塞，放在单独的线程中
+    tokio::task::spawn_blocking(move || loop {
+        if let Err(e) = subscribe(&tx) {
+            tracing::error!("cache event subscription failed: {}", e);
+        }
+        if tx.is_closed() {
+            return;
+        }
+        std::thread::sleep(Duration::from_secs(5));
+    });
+    tokio::spawn(async move {
+        match drop_legacy_cache(&pools).await {
+            Ok(count) => tracing::info!("dropped {} legacy cached responses", count),
+            Err(e) => tracing::warn!("failed to drop legacy cached responses: {}", e),
+        }
+        while let Some(event) = rx.recv().await {
+            match invalidate(&pools, &event).await {
+                Ok(count) => tracing::info!("{:?} dropped {} cached responses", event, count),
+                Err(e) => tracing::error!("failed to handle {:?}: {}", event, e),
+            }
+        }
+    });
+}
+
+fn subscribe(tx: &tokio::sync::mpsc::UnboundedSender<CacheEvent>) -> Result<(), Box<dyn Error>> {
+    let client = redis::Client::open(redis_url_from_env())?;
+    let mut connection = client.get_connection()?;
+    let mut pubsub = connection.as_pubsub();
+    pubsub.subscribe(CACHE_EVENT_CHANNEL)?;
+    loop {
+        let payload: String = pubsub.get_message()?.get_payload()?;
+        match serde_json::from_str(&payload) {
+            Ok(event) => {
+                if tx.send(event).is_err() {
+                    return Ok(());
+                }
+            }
+            Err(e) => tracing::warn!("ignoring malformed cache event {}: {}", payload, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_keys_and_events() {
+        let key = CacheKey::version("tokio-rs/tokio", "tokio", "1.0.0");
+        assert_eq!(
+            key.redis_key(CacheKind::CratesInfo),
+            "crates_info:tokio-rs/tokio:tokio:1.0.0"
+        );
+        assert_eq!(
+            CacheKey::program("tokio-rs/tokio", "tokio").redis_key(CacheKind::VersionPage),
+            "versionpage:tokio-rs/tokio:tokio"
+        );
+
+        assert_eq!(parse_ttl(Some(" 120 ")), Some(Duration::from_secs(120)));
+        assert_eq!(parse_ttl(Some("0")), None);
+        assert_eq!(parse_ttl(Some("soon")), None);
+        assert!(CacheKind::VersionPage.default_ttl() < CacheKind::CratesInfo.default_ttl());
+
+        let event = CacheEvent::AdvisoriesChanged {
+            crates: vec!["time".to_string(), "chrono".to_string()],
+        };
+        let json = serde_json::to_string(&event).unwrap();
+        assert_eq!(
+            json,
+            r#"{"event":"advisories_changed","crates":["time","chrono"]}"#
+        );
+        assert_eq!(serde_json::from_str::<CacheEvent>(&json).unwrap(), event);
+        assert_eq!(
+            serde_json::from_str::<CacheEvent>(r#"{"event":"crate_changed","name":"serde"}"#)
+                .unwrap()
+                .crates(),
+            ["serde"]
+        );
+    }
+}
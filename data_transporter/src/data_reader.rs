@@ -5,24 +5,95 @@ use semver::Version;
 use serde_json::Value;
 use std::{
     cmp::Ordering,
-    collections::{HashSet, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     error::Error,
 };
-use tokio_postgres::NoTls;
 use tudriver::tugraph_client::TuGraphClient;
 
 use crate::{
-    db::{db_connection_config_from_env, db_cratesio_connection_config_from_env, DBHandler},
+    blast_radius::ProgramKind,
+    db::{db_connection_config_from_env, db_cratesio_connection_config_from_env},
+    dep_graph::GraphEdge,
+    dep_list::{DepStats, Relation},
     handler::{
         Crateinfo, DependencyCount, DependencyCrateInfo, DependencyInfo, DependentCount,
         DependentData, DependentInfo, Deptree, Versionpage,
     },
+    pool::{pg_pool, PgPool, PoolConfig},
     NameVersion,
 };
+/// 批量查询直接依赖时每条 Cypher 语句包含的节点数上限
+const EDGE_QUERY_BATCH_SIZE: usize = 200;
+
+/// 传递依赖（或依赖方）遍历访问的节点数上限，超过后停止遍历并标记结果被截断
+const MAX_TRANSITIVE_NODES: usize = 100_000;
+
+/// 传递依赖（或依赖方）的遍历结果
+#[derive(Debug, Default, Clone)]
+pub struct TransitiveNodes {
+    /// 访问到的全部 `name/version`
+    pub nodes: HashSet<String>,
+    /// 是否因超过 `MAX_TRANSITIVE_NODES` 提前停止，此时 `nodes` 只是下界
+    pub truncated: bool,
+}
+
+/// 只保留普通依赖边 `r` 的条件。dev/build 依赖不会随依赖方一起发布，依赖树、依赖方、
+/// 漏洞统计等不区分依赖类型的遍历都只走普通依赖；旧数据的边上没有依赖类型，按普通依赖处理
+const NORMAL_DEPENDS_ON: &str =
+    "(r.dependency_type IS NULL OR r.dependency_type IN ['', 'normal'])";
+
+/// 转义为 Cypher 的单引号字符串字面量
+fn cypher_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// 转义并拼接为 Cypher 的列表字面量内容
+fn cypher_list(items: &[String]) -> String {
+    items
+        .iter()
+        .map(|item| cypher_quote(item))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// 按节点标签解析 `library_version` 或 `application_version` 节点
+fn parse_version(label: &str, node: Value) -> Result<Option<UVersion>, serde_json::Error> {
+    Ok(match label {
+        "library_version" => Some(UVersion::LibraryVersion(serde_json::from_value(node)?)),
+        "application_version" => Some(UVersion::ApplicationVersion(serde_json::from_value(node)?)),
+        _ => None,
+    })
+}
+
+/// 把全部依赖（或被依赖）节点按是否在 `direct` 中分为直接和间接两类
+fn relation_entries(
+    direct: &HashSet<String>,
+    all_nodes: HashSet<String>,
+) -> Vec<(String, Relation)> {
+    let mut entries: Vec<(String, Relation)> = all_nodes
+        .into_iter()
+        .chain(direct.iter().cloned())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .map(|nv| {
+            let relation = if direct.contains(&nv) {
+                Relation::Direct
+            } else {
+                Relation::Indirect
+            };
+            (nv, relation)
+        })
+        .collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    entries
+}
+
 pub trait DataReaderTrait {
-    async fn get_all_programs_id(&self) -> Vec<String>;
-    async fn get_program(&self, program_id: &str) -> Result<Program, Box<dyn Error>>;
-    async fn get_type(&self, program_id: &str) -> Result<(UProgram, bool), Box<dyn Error>>;
+    async fn get_all_programs_id(&self) -> Result<Vec<String>, Box<dyn Error>>;
+    /// 程序不存在时返回 `None`
+    async fn get_program(&self, program_id: &str) -> Result<Option<Program>, Box<dyn Error>>;
+    /// 程序不存在或没有类型节点时返回 `None`
+    async fn get_type(&self, program_id: &str) -> Result<Option<(UProgram, bool)>, Box<dyn Error>>;
     async fn get_versions(
         &self,
         program_id: &str,
@@ -32,6 +103,69 @@ pub trait DataReaderTrait {
         &self,
         name_and_version: &str,
     ) -> Result<Vec<crate::NameVersion>, Box<dyn Error>>;
+    /// 批量查询一组 `name/version` 的直接依赖边及其属性，包括 dev/build 依赖
+    async fn get_direct_dependency_edges(
+        &self,
+        name_and_versions: Vec<String>,
+    ) -> Result<Vec<GraphEdge>, Box<dyn Error>>;
+    /// 批量查询指向一组 `name/version` 的 `depends_on` 边，包括 dev/build 依赖
+    async fn get_direct_dependent_edges(
+        &self,
+        name_and_versions: Vec<String>,
+    ) -> Result<Vec<GraphEdge>, Box<dyn Error>>;
+    /// 与 `get_direct_dependent_edges` 相同，但最多返回 `limit` 条边，
+    /// 优先返回 `name_and_versions` 中靠前的版本的依赖方
+    async fn sample_direct_dependent_edges(
+        &self,
+        name_and_versions: Vec<String>,
+        limit: usize,
+    ) -> Result<Vec<GraphEdge>, Box<dyn Error>>;
+    /// 一组 crate 在图中的全部版本号，按版本节点的 `name` 属性精确匹配，没有版本的 crate 不在结果中
+    async fn get_versions_of_crates(
+        &self,
+        names: &[String],
+    ) -> Result<HashMap<String, Vec<String>>, Box<dyn Error>>;
+    /// 批量查询一组 `name/version` 所属程序的命名空间和类型，找不到的不在结果中
+    async fn get_program_of_versions(
+        &self,
+        name_and_versions: Vec<String>,
+    ) -> Result<HashMap<String, (String, ProgramKind)>, Box<dyn Error>>;
+    /// 批量查询一组 `name/version` 各自的直接被依赖数
+    async fn count_direct_dependents(
+        &self,
+        name_and_versions: Vec<String>,
+    ) -> Result<HashMap<String, usize>, Box<dyn Error>>;
+    /// 批量查询一组 `name/version` 的许可证、下载量、直接被依赖数和漏洞数
+    async fn get_dep_list_stats(
+        &self,
+        name_and_versions: Vec<String>,
+    ) -> Result<HashMap<String, DepStats>, Box<dyn Error>>;
+    /// 批量查询 `root` 的 Cargo.lock 中一组 `name/version` 实际锁定的依赖边
+    async fn get_locked_dependency_edges(
+        &self,
+        root: &str,
+        name_and_versions: Vec<String>,
+    ) -> Result<Vec<GraphEdge>, Box<dyn Error>>;
+    /// 按名称精确批量查询程序，同名程序可能位于不同命名空间
+    async fn get_programs_by_names(
+        &self,
+        names: Vec<String>,
+    ) -> Result<HashMap<String, Vec<Program>>, Box<dyn Error>>;
+    /// 批量查询程序对应的 library 或 application 节点
+    async fn get_program_types(
+        &self,
+        program_ids: Vec<String>,
+    ) -> Result<HashMap<String, UProgram>, Box<dyn Error>>;
+    /// 批量查询程序的全部版本节点，不查询依赖
+    async fn get_program_versions(
+        &self,
+        program_ids: Vec<String>,
+    ) -> Result<HashMap<String, Vec<UVersion>>, Box<dyn Error>>;
+    /// 批量查询 `name/version` 对应的 library_version 或 application_version 节点
+    async fn get_version_nodes(
+        &self,
+        name_and_versions: Vec<String>,
+    ) -> Result<HashMap<String, UVersion>, Box<dyn Error>>;
     async fn new_get_direct_dependency_nodes(
         &self,
         namespace: &str,
@@ -79,21 +213,23 @@ pub trait DataReaderTrait {
         &self,
         nameversion: NameVersion,
     ) -> Result<HashSet<String>, Box<dyn Error>>;
+    /// 按层批量遍历全部普通依赖，节点数超过上限时返回截断的结果
     async fn new_get_all_dependencies(
         &self,
         namespace: String,
         nameversion: String,
-    ) -> Result<HashSet<String>, Box<dyn Error>>;
+    ) -> Result<TransitiveNodes, Box<dyn Error>>;
     /*#[allow(dead_code)]
     async fn get_all_dependents(
         &self,
         nameversion: NameVersion,
     ) -> Result<HashSet<String>, Box<dyn Error>>;*/
+    /// 按层批量遍历全部普通依赖方，节点数超过上限时返回截断的结果
     async fn new_get_all_dependents(
         &self,
         namespace: String,
         nameversion: String,
-    ) -> Result<HashSet<String>, Box<dyn Error>>;
+    ) -> Result<TransitiveNodes, Box<dyn Error>>;
     async fn get_github_url(
         &self,
         namespace: String,
@@ -117,7 +253,7 @@ pub trait DataReaderTrait {
         nversion: String,
         nsfront: String,
         nsbehind: String,
-    ) -> Result<Crateinfo, Box<dyn Error>>;
+    ) -> Result<Option<Crateinfo>, Box<dyn Error>>;
     async fn get_dependency_from_tg(
         &self,
         name: String,
@@ -136,6 +272,8 @@ pub trait DataReaderTrait {
 #[derive(Clone)]
 pub struct DataReader {
     pub client: TuGraphClient,
+    pg: PgPool,
+    pg_cratesio: PgPool,
 }
 impl DataReader {
     pub async fn new(
@@ -145,7 +283,160 @@ impl DataReader {
         db: &str,
     ) -> Result<Self, Box<dyn Error>> {
         let client = TuGraphClient::new(uri, user, password, db).await?;
-        Ok(DataReader { client })
+        let config = PoolConfig::from_env();
+        Ok(DataReader {
+            client,
+            pg: pg_pool(db_connection_config_from_env(), &config),
+            pg_cratesio: pg_pool(db_cratesio_connection_config_from_env(), &config),
+        })
+    }
+
+    /// 使用 API 服务共享的 TuGraph 客户端和连接池
+    pub fn with_pools(client: TuGraphClient, pg: PgPool, pg_cratesio: PgPool) -> Self {
+        DataReader {
+            client,
+            pg,
+            pg_cratesio,
+        }
+    }
+
+    /// 批量查询一组节点的直接普通依赖，`dependents` 为真时查询直接普通依赖方
+    async fn query_normal_neighbours(
+        &self,
+        name_and_versions: &[String],
+        dependents: bool,
+    ) -> Result<HashSet<String>, Box<dyn Error>> {
+        let (side, other) = if dependents { ("m", "n") } else { ("n", "m") };
+        let mut nodes = HashSet::new();
+        for chunk in name_and_versions.chunks(EDGE_QUERY_BATCH_SIZE) {
+            let query = format!(
+                "
+                MATCH (n:version)-[r:depends_on]->(m:version)
+                WHERE {}.name_and_version IN [{}] AND {}
+                RETURN DISTINCT {}.name_and_version as name_and_version
+                ",
+                side,
+                cypher_list(chunk),
+                NORMAL_DEPENDS_ON,
+                other
+            );
+            for result in self.client.exec_query(&query).await? {
+                let result_json: Value = serde_json::from_str(&result)?;
+                if let Some(nv) = result_json["name_and_version"].as_str() {
+                    nodes.insert(nv.to_string());
+                }
+            }
+        }
+        Ok(nodes)
+    }
+
+    /// 从 `start` 出发按层遍历普通依赖（或依赖方），每层一次批量查询
+    async fn transitive_nodes(
+        &self,
+        start: Vec<String>,
+        dependents: bool,
+    ) -> Result<TransitiveNodes, Box<dyn Error>> {
+        let mut visited = HashSet::new();
+        let mut frontier: Vec<String> = start
+            .into_iter()
+            .filter(|nv| visited.insert(nv.clone()))
+            .collect();
+        while !frontier.is_empty() {
+            if visited.len() >= MAX_TRANSITIVE_NODES {
+                tracing::warn!(
+                    "transitive traversal stopped at {} nodes, result is truncated",
+                    visited.len()
+                );
+                return Ok(TransitiveNodes {
+                    nodes: visited,
+                    truncated: true,
+                });
+            }
+            frontier = self
+                .query_normal_neighbours(&frontier, dependents)
+                .await?
+                .into_iter()
+                .filter(|nv| visited.insert(nv.clone()))
+                .collect();
+        }
+        Ok(TransitiveNodes {
+            nodes: visited,
+            truncated: false,
+        })
+    }
+
+    /// 批量查询 `depends_on` 边，`side` 为 `n` 时按起点过滤（依赖），为 `m` 时按终点过滤（被依赖）。
+    /// 设置 `limit` 时按 `name_and_versions` 的顺序查询，达到上限后不再查询后面的批次
+    async fn query_depends_on_edges(
+        &self,
+        side: &str,
+        name_and_versions: &[String],
+        limit: Option<usize>,
+    ) -> Result<Vec<GraphEdge>, Box<dyn Error>> {
+        let mut edges = vec![];
+        // 分批查询，避免 IN 列表过长
+        for chunk in name_and_versions.chunks(EDGE_QUERY_BATCH_SIZE) {
+            let remaining = match limit {
+                Some(limit) if edges.len() >= limit => break,
+                Some(limit) => format!("LIMIT {}", limit - edges.len()),
+                None => String::new(),
+            };
+            let list = chunk
+                .iter()
+                .map(|nv| cypher_quote(nv))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let query = format!(
+                "
+                MATCH (n:version)-[r:depends_on]->(m:version)
+                WHERE {}.name_and_version IN [{}]
+                RETURN n.name_and_version as src, m.name_and_version as dst,
+                    r.dependency_type as dependency_type, r.optional as optional,
+                    r.default_features as default_features, r.features as features,
+                    r.target as target, r.enabled_by_default as enabled_by_default,
+                    r.source as source, r.version_req as version_req
+                {}
+                ",
+                side, list, remaining
+            );
+            let results = self.client.exec_query(&query).await?;
+            let unique_items: HashSet<String> = results.into_iter().collect();
+            for result in unique_items {
+                let result_json: Value = serde_json::from_str(&result)?;
+                if let (Some(src), Some(dst)) =
+                    (result_json["src"].as_str(), result_json["dst"].as_str())
+                {
+                    // 旧数据的边上没有属性，按普通的非可选依赖处理
+                    let text = |key: &str| {
+                        result_json[key]
+                            .as_str()
+                            .filter(|v| !v.is_empty())
+                            .map(str::to_string)
+                    };
+                    edges.push(GraphEdge {
+                        from: src.to_string(),
+                        to: dst.to_string(),
+                        kind: text("dependency_type")
+                            .and_then(|k| k.parse().ok())
+                            .unwrap_or_default(),
+                        optional: result_json["optional"].as_bool().unwrap_or(false),
+                        default_features: result_json["default_features"].as_bool().unwrap_or(true),
+                        features: text("features")
+                            .map(|f| f.split(',').map(str::to_string).collect())
+                            .unwrap_or_default(),
+                        target: text("target"),
+                        enabled_by_default: result_json["enabled_by_default"]
+                            .as_bool()
+                            .unwrap_or(true),
+                        source: text("source")
+                            .and_then(|s| s.parse().ok())
+                            .unwrap_or_default(),
+                        version_req: text("version_req"),
+                    });
+                }
+            }
+        }
+        Ok(edges)
     }
 }
 impl DataReaderTrait for DataReader {
@@ -158,65 +449,47 @@ impl DataReaderTrait for DataReader {
     ) -> Result<DependentInfo, Box<dyn Error>> {
         let namespace = nsfront.clone() + "/" + &nsbehind.clone();
         let nameversion = name.clone() + "/" + &version.clone();
-        let direct_nodes = self
+        let direct: HashSet<String> = self
             .new_get_direct_dependent_nodes(&namespace, &nameversion)
-            .await
-            .unwrap();
-        let getdirect_count = direct_nodes.len();
+            .await?
+            .into_iter()
+            .map(|node| node.name + "/" + &node.version)
+            .collect();
         let all_dependent_nodes = self
             .new_get_all_dependents(namespace.clone(), nameversion.clone())
-            .await
-            .unwrap();
-        let mut indirect_dependent = vec![];
-        for node in all_dependent_nodes {
-            let mut dr = false;
-            for node2 in direct_nodes.clone() {
-                let nv = node2.name.clone() + "/" + &node2.version.clone();
-                if node == nv {
-                    dr = true;
-                    break;
-                }
-            }
-            if !dr {
-                indirect_dependent.push(node);
-            }
-        }
-        let indirect_dependent_count = indirect_dependent.len();
+            .await?;
+        let entries = relation_entries(&direct, all_dependent_nodes.nodes);
+        let mut stats = self
+            .get_dep_list_stats(entries.iter().map(|(id, _)| id.clone()).collect())
+            .await?;
         let mut deps = vec![];
-        let mut count1 = 0;
-        for item in direct_nodes {
-            let dep = DependentData {
-                crate_name: item.clone().name,
-                version: item.clone().version,
-                relation: "Direct".to_string(),
-            };
-            deps.push(dep);
-            count1 += 1;
-            if count1 == 50 {
-                break;
-            }
-        }
-        let mut count2 = 0;
-        for item in indirect_dependent {
-            let parts: Vec<&str> = item.split('/').collect();
-            let dep = DependentData {
-                crate_name: parts[0].to_string(),
-                version: parts[1].to_string(),
-                relation: "Indirect".to_string(),
+        for (id, relation) in entries {
+            let Some((crate_name, version)) = id.split_once('/') else {
+                continue;
             };
-            count2 += 1;
-            if count2 == 50 {
-                break;
-            }
-            deps.push(dep);
+            let stat = stats.remove(&id).unwrap_or_default();
+            deps.push(DependentData {
+                crate_name: crate_name.to_string(),
+                version: version.to_string(),
+                relation: relation.label().to_string(),
+                license: stat.license,
+                downloads: stat.downloads,
+                dependents: stat.dependents,
+                advisories: stat.advisories,
+            });
         }
 
-        let res_deps = DependentInfo {
-            direct_count: getdirect_count,
-            indirect_count: indirect_dependent_count,
+        let direct_count = deps.iter().filter(|d| d.relation == "Direct").count();
+        Ok(DependentInfo {
+            direct_count,
+            indirect_count: deps.len() - direct_count,
+            truncated: all_dependent_nodes.truncated,
+            total: deps.len(),
+            page: 1,
+            per_page: deps.len(),
+            next_cursor: None,
             data: deps,
-        };
-        Ok(res_deps)
+        })
     }
     async fn get_dependency_from_tg(
         &self,
@@ -228,66 +501,49 @@ impl DataReaderTrait for DataReader {
         let namespace = nsfront.clone() + "/" + &nsbehind.clone();
         let nameversion = name.clone() + "/" + &version.clone();
         tracing::info!("{} {}", namespace.clone(), nameversion.clone());
-        let direct_nodes = self
+        let direct: HashSet<String> = self
             .new_get_direct_dependency_nodes(&namespace, &nameversion)
-            .await
-            .unwrap();
-        let getdirect_count = direct_nodes.len();
+            .await?
+            .into_iter()
+            .map(|node| node.name + "/" + &node.version)
+            .collect();
         let all_dependency_nodes = self
             .new_get_all_dependencies(namespace.clone(), nameversion.clone())
-            .await
-            .unwrap();
-        let mut indirect_dependency = vec![];
-        for node in all_dependency_nodes {
-            let mut dr = false;
-            for node2 in direct_nodes.clone() {
-                let nv = node2.name.clone() + "/" + &node2.version.clone();
-                if node == nv {
-                    dr = true;
-                    break;
-                }
-            }
-            if !dr {
-                indirect_dependency.push(node);
-            }
-        }
-        let indirect_dependency_count = indirect_dependency.len();
+            .await?;
+        let entries = relation_entries(&direct, all_dependency_nodes.nodes);
+        let mut stats = self
+            .get_dep_list_stats(entries.iter().map(|(id, _)| id.clone()).collect())
+            .await?;
         let mut deps = vec![];
-        for item in direct_nodes {
-            let dep_count = self.count_dependencies(item.clone()).await.unwrap();
-            let dep = DependencyCrateInfo {
-                crate_name: item.clone().name,
-                version: item.clone().version,
-                relation: "Direct".to_string(),
-                license: "".to_string(),
-                dependencies: dep_count,
-            };
-            deps.push(dep);
-        }
-        for item in indirect_dependency {
-            let parts: Vec<&str> = item.split('/').collect();
-            let newitem = NameVersion {
-                name: parts[0].to_string(),
-                version: parts[1].to_string(),
-            };
-            let dep_count = self.count_dependencies(newitem.clone()).await.unwrap();
-
-            let dep = DependencyCrateInfo {
-                crate_name: parts[0].to_string(),
-                version: parts[1].to_string(),
-                relation: "Indirect".to_string(),
-                license: "".to_string(),
-                dependencies: dep_count,
+        for (id, relation) in entries {
+            let Some((crate_name, version)) = id.split_once('/') else {
+                continue;
             };
-            deps.push(dep);
+            let stat = stats.remove(&id).unwrap_or_default();
+            // 传递依赖数开销较大，只在分页后为返回的项计算
+            deps.push(DependencyCrateInfo {
+                crate_name: crate_name.to_string(),
+                version: version.to_string(),
+                relation: relation.label().to_string(),
+                license: stat.license,
+                dependencies: 0,
+                downloads: stat.downloads,
+                dependents: stat.dependents,
+                advisories: stat.advisories,
+            });
         }
 
-        let res_deps = DependencyInfo {
-            direct_count: getdirect_count,
-            indirect_count: indirect_dependency_count,
+        let direct_count = deps.iter().filter(|d| d.relation == "Direct").count();
+        Ok(DependencyInfo {
+            direct_count,
+            indirect_count: deps.len() - direct_count,
+            truncated: all_dependency_nodes.truncated,
+            total: deps.len(),
+            page: 1,
+            per_page: deps.len(),
+            next_cursor: None,
             data: deps,
-        };
-        Ok(res_deps)
+        })
     }
     async fn get_crates_front_info_from_tg(
         &self,
@@ -295,27 +551,28 @@ impl DataReaderTrait for DataReader {
         nversion: String,
         nsfront: String,
         nsbehind: String,
-    ) -> Result<Crateinfo, Box<dyn Error>> {
+    ) -> Result<Option<Crateinfo>, Box<dyn Error>> {
         let namespace = nsfront.clone() + "/" + &nsbehind.clone();
         let name_and_version = nname.clone() + "/" + &nversion.clone();
+        let lib_versions = self
+            .new_get_lib_version(namespace.clone(), nname.clone())
+            .await?;
+        if !lib_versions.contains(&nversion) {
+            return Ok(None);
+        }
         let mut githuburl = self
             .get_github_url(namespace.clone(), nname.clone())
-            .await
-            .unwrap();
+            .await?;
         if githuburl == *"null" || githuburl == *"None" {
             githuburl = "".to_string();
         }
-        let mut docurl = self
-            .get_doc_url(namespace.clone(), nname.clone())
-            .await
-            .unwrap();
+        let mut docurl = self.get_doc_url(namespace.clone(), nname.clone()).await?;
         if docurl == *"null" || docurl == *"None" {
             docurl = "".to_string();
         }
         let direct_dependency_nodes = self
             .new_get_direct_dependency_nodes(&namespace, &name_and_version)
-            .await
-            .unwrap();
+            .await?;
         let direct_dependency_count = direct_dependency_nodes.len();
         tracing::info!(
             "finish get_direct_dependency_nodes:{}",
@@ -323,10 +580,9 @@ impl DataReaderTrait for DataReader {
         ); //ok
         let all_dependency_nodes = self
             .new_get_all_dependencies(namespace.clone(), name_and_version.clone())
-            .await
-            .unwrap();
+            .await?;
         let mut indirect_dependency = vec![];
-        for node in all_dependency_nodes.clone() {
+        for node in all_dependency_nodes.nodes.clone() {
             let mut dr = false;
             for node2 in direct_dependency_nodes.clone() {
                 let nv = node2.name.clone() + "/" + &node2.version.clone();
@@ -343,41 +599,19 @@ impl DataReaderTrait for DataReader {
         //get dependent count
         let direct_dependent_nodes = self
             .new_get_direct_dependent_nodes(&namespace, &name_and_version)
-            .await
-            .unwrap();
+            .await?;
         let direct_dependent_count = direct_dependent_nodes.len();
         tracing::info!(
             "finish get_direct_dependent_nodes:{}",
             direct_dependent_count
         );
-        let db_connection_config = db_connection_config_from_env();
-        #[allow(unused_variables)]
-        let (client, connection) = tokio_postgres::connect(&db_connection_config, NoTls)
-            .await
-            .unwrap();
-        tokio::spawn(async move {
-            if let Err(e) = connection.await {
-                eprintln!("connection error: {}", e);
-            }
-        });
+        let dbhandler = self.pg.get().await?;
         tracing::info!("finish connect pg");
-        let dbhandler = DBHandler { client };
-        let getcves = dbhandler
-            .get_direct_rustsec(&nname, &nversion)
-            .await
-            .unwrap();
+        let getcves = dbhandler.get_direct_rustsec(&nname, &nversion).await?;
         let get_dependency_cves = dbhandler
-            .get_dependency_rustsec(all_dependency_nodes.clone())
-            .await
-            .unwrap();
-        let getlicense = dbhandler
-            .get_license_by_name(&namespace, &nname)
-            .await
-            .unwrap();
-        let lib_versions = self
-            .new_get_lib_version(namespace.clone(), nname.clone())
-            .await
-            .unwrap();
+            .get_dependency_rustsec(all_dependency_nodes.nodes.clone())
+            .await?;
+        let getlicense = dbhandler.get_license_by_name(&namespace, &nname).await?;
         let mut getversions = vec![];
         for version in lib_versions {
             getversions.push(version);
@@ -396,6 +630,7 @@ impl DataReaderTrait for DataReader {
         let dcy_count = DependencyCount {
             direct: direct_dependency_count,
             indirect: indirect_dependency_count,
+            truncated: all_dependency_nodes.truncated,
         };
         let dt_count = DependentCount {
             direct: direct_dependent_count,
@@ -408,12 +643,12 @@ impl DataReaderTrait for DataReader {
             dependents: dt_count,
             cves: getcves,
             versions: getversions,
-            license: getlicense[0].clone(),
+            license: getlicense.first().cloned().unwrap_or_default(),
             github_url: githuburl,
             doc_url: docurl,
             dep_cves: get_dependency_cves,
         };
-        Ok(res)
+        Ok(Some(res))
     }
     #[allow(unused_assignments)]
     async fn get_version_page_from_tg(
@@ -425,8 +660,7 @@ impl DataReaderTrait for DataReader {
         let namespace = nsfront.clone() + "/" + &nsbehind;
         let all_versions = self
             .new_get_lib_version(namespace.clone(), nname.clone())
-            .await
-            .unwrap();
+            .await?;
         tracing::info!("finish get all versions");
         let mut getversions = vec![];
         for version in all_versions {
@@ -449,37 +683,22 @@ impl DataReaderTrait for DataReader {
             //let mut dts_count = 0;
             /*let all_dts = self
                 .new_get_all_dependents(namespace.clone(), name_and_version.clone())
-                .await
-                .unwrap();
+                .await?;
             dts_count = all_dts.len();
             if all_dts.len() == 0 {
                 let direct_dts = self
                     .new_get_direct_dependent_nodes(&namespace, &name_and_version)
-                    .await
-                    .unwrap();
+                    .await?;
                 dts_count = direct_dts.len();
             }*/
             let all_dts = self
                 .new_get_direct_dependent_nodes(&namespace, &name_and_version)
-                .await
-                .unwrap();
+                .await?;
             tracing::info!("finish get all dependents");
-            let _db_cratesio_connection_config = db_cratesio_connection_config_from_env();
-            #[allow(unused_variables)]
-            let (client2, connection2) =
-                tokio_postgres::connect(&_db_cratesio_connection_config, NoTls)
-                    .await
-                    .unwrap();
-            tokio::spawn(async move {
-                if let Err(e) = connection2.await {
-                    eprintln!("connection error: {}", e);
-                }
-            });
-            let dbhandler2 = DBHandler { client: client2 };
+            let dbhandler2 = self.pg_cratesio.get().await?;
             let res = dbhandler2
                 .get_dump_from_cratesio_pg(nname.clone(), version.clone())
-                .await
-                .unwrap();
+                .await?;
             tracing::info!("finish get dump from pg");
             if !res.is_empty() {
                 let parts: Vec<&str> = res.split("/").collect();
@@ -502,33 +721,26 @@ impl DataReaderTrait for DataReader {
         visited: &mut HashSet<String>,
     ) -> Result<(), Box<dyn Error>> {
         let name_and_version = &rootnode.name_and_version;
-        let res = self
-            .get_direct_dependency_nodes(name_and_version)
-            .await
-            .unwrap();
+        let res = self.get_direct_dependency_nodes(name_and_version).await?;
         tracing::info!("direct dep count:{}", res.len());
-        let db_connection_config = db_connection_config_from_env();
-        #[allow(unused_variables)]
-        let (client, connection) = tokio_postgres::connect(&db_connection_config, NoTls)
-            .await
-            .unwrap();
-        tokio::spawn(async move {
-            if let Err(e) = connection.await {
-                eprintln!("connection error: {}", e);
+        // 先查完本层的漏洞数并归还连接，避免递归时层层占用连接池
+        let mut children = Vec::new();
+        {
+            let dbhandler = self.pg.get().await?;
+            for node in res {
+                let name = node.name.clone();
+                let version = node.version.clone();
+                let dep_nv = name.clone() + "/" + &version;
+                let rustcve = dbhandler.get_direct_rustsec(&name, &version).await?;
+                children.push(Deptree {
+                    name_and_version: dep_nv,
+                    cve_count: rustcve.len(),
+                    direct_dependency: Vec::new(),
+                });
             }
-        });
-        let dbhandler = DBHandler { client };
-        for node in res {
-            let name = node.name.clone();
-            let version = node.version.clone();
-            let dep_nv = name.clone() + "/" + &version;
-            let rustcve = dbhandler.get_direct_rustsec(&name, &version).await.unwrap();
-            let mut dn = Deptree {
-                name_and_version: dep_nv.clone(),
-                cve_count: rustcve.len(),
-                direct_dependency: Vec::new(),
-            };
-            if visited.insert(dep_nv.clone()) {
+        }
+        for mut dn in children {
+            if visited.insert(dn.name_and_version.clone()) {
                 Box::pin(self.build_graph(&mut dn, visited)).await?;
                 rootnode.direct_dependency.push(dn);
             }
@@ -557,7 +769,7 @@ RETURN n.github_url
         let unique_items: HashSet<String> = res.clone().into_iter().collect();
         let mut nodes = vec![];
         for res in unique_items {
-            let parsed: Value = serde_json::from_str(&res).unwrap();
+            let parsed: Value = serde_json::from_str(&res)?;
             if let Some(url) = parsed.get("n.github_url").and_then(|v| v.as_str()) {
                 nodes.push(url.to_string());
             }
@@ -581,7 +793,7 @@ RETURN n.doc_url
         let unique_items: HashSet<String> = res.clone().into_iter().collect();
         let mut nodes = vec![];
         for res in unique_items {
-            let parsed: Value = serde_json::from_str(&res).unwrap();
+            let parsed: Value = serde_json::from_str(&res)?;
             if let Some(url) = parsed.get("n.doc_url").and_then(|v| v.as_str()) {
                 nodes.push(url.to_string());
             }
@@ -600,7 +812,7 @@ RETURN n.doc_url
 
         while let Some(current) = queue.pop_front() {
             if visited.insert(current.clone()) {
-                for dep in self.get_direct_dependency_nodes(&current).await.unwrap() {
+                for dep in self.get_direct_dependency_nodes(&current).await? {
                     let tmp = dep.name.clone() + "/" + &dep.version.clone();
                     queue.push_back(tmp);
                 }
@@ -614,31 +826,14 @@ RETURN n.doc_url
         &self,
         namespace: String,
         nameversion: String,
-    ) -> Result<HashSet<String>, Box<dyn Error>> {
-        let mut queue = VecDeque::new();
-        let mut visited = HashSet::new();
-        for node in self
+    ) -> Result<TransitiveNodes, Box<dyn Error>> {
+        let direct = self
             .new_get_direct_dependency_nodes(&namespace, &nameversion)
-            .await
-            .unwrap()
-        {
-            let nameandversion = node.clone().name + "/" + &node.clone().version;
-            queue.push_back(nameandversion.clone());
-        }
-        let mut count = 0;
-        while let Some(current) = queue.pop_front() {
-            count += 1;
-            if count == 500 {
-                break;
-            }
-            if visited.insert(current.clone()) {
-                for dep in self.get_direct_dependency_nodes(&current).await.unwrap() {
-                    let tmp = dep.name.clone() + "/" + &dep.version.clone();
-                    queue.push_back(tmp);
-                }
-            }
-        }
-        Ok(visited)
+            .await?
+            .into_iter()
+            .map(|node| node.name + "/" + &node.version)
+            .collect();
+        self.transitive_nodes(direct, false).await
     }
     /*async fn get_all_dependents(
         &self,
@@ -651,7 +846,7 @@ RETURN n.doc_url
 
         while let Some(current) = queue.pop_front() {
             if visited.insert(current.clone()) {
-                for dep in self.get_direct_dependent_nodes(&current).await.unwrap() {
+                for dep in self.get_direct_dependent_nodes(&current).await? {
                     let tmp = dep.name.clone() + "/" + &dep.version.clone();
                     queue.push_back(tmp);
                 }
@@ -665,52 +860,37 @@ RETURN n.doc_url
         &self,
         namespace: String,
         nameversion: String,
-    ) -> Result<HashSet<String>, Box<dyn Error>> {
-        let mut queue = VecDeque::new();
-        let mut visited = HashSet::new();
-        for node in self
+    ) -> Result<TransitiveNodes, Box<dyn Error>> {
+        let direct = self
             .new_get_direct_dependent_nodes(&namespace, &nameversion)
-            .await
-            .unwrap()
-        {
-            let nameandversion = node.clone().name + "/" + &node.clone().version;
-            queue.push_back(nameandversion.clone());
-        }
-        let len = queue.len();
-        if len < 500 {
-            while let Some(current) = queue.pop_front() {
-                if visited.insert(current.clone()) {
-                    for dep in self.get_direct_dependent_nodes(&current).await.unwrap() {
-                        let tmp = dep.name.clone() + "/" + &dep.version.clone();
-                        queue.push_back(tmp);
-                    }
-                }
-            }
-        }
-        Ok(visited)
+            .await?
+            .into_iter()
+            .map(|node| node.name + "/" + &node.version)
+            .collect();
+        self.transitive_nodes(direct, true).await
     }
-    async fn get_all_programs_id(&self) -> Vec<String> {
+    async fn get_all_programs_id(&self) -> Result<Vec<String>, Box<dyn Error>> {
         let query = "
             MATCH (p: program)
             RETURN p 
         ";
 
-        let results = self.client.exec_query(query).await.unwrap();
+        let results = self.client.exec_query(query).await?;
 
         let mut programs = vec![];
         for result in results {
-            let programs_json: Value = serde_json::from_str(&result).unwrap();
+            let programs_json: Value = serde_json::from_str(&result)?;
 
             let pro = programs_json["p"].clone();
 
-            let program: Program = serde_json::from_value(pro).unwrap();
+            let program: Program = serde_json::from_value(pro)?;
 
             programs.push(program.id);
         }
 
-        programs
+        Ok(programs)
     }
-    async fn get_program(&self, program_id: &str) -> Result<Program, Box<dyn Error>> {
+    async fn get_program(&self, program_id: &str) -> Result<Option<Program>, Box<dyn Error>> {
         let query = format!(
             "
             MATCH (p: program {{id: '{}'}})
@@ -719,12 +899,15 @@ RETURN n.doc_url
             program_id
         );
         let results = self.client.exec_query(&query).await?;
-        let programs_json: Value = serde_json::from_str(&results[0]).unwrap();
+        let Some(result) = results.first() else {
+            return Ok(None);
+        };
+        let programs_json: Value = serde_json::from_str(result)?;
         let pro = programs_json["p"].clone();
-        let program: Program = serde_json::from_value(pro).unwrap();
-        Ok(program)
+        let program: Program = serde_json::from_value(pro)?;
+        Ok(Some(program))
     }
-    async fn get_type(&self, program_id: &str) -> Result<(UProgram, bool), Box<dyn Error>> {
+    async fn get_type(&self, program_id: &str) -> Result<Option<(UProgram, bool)>, Box<dyn Error>> {
         let mut islib = false;
         let query = format!(
             "
@@ -737,21 +920,24 @@ RETURN n.doc_url
         let results = self.client.exec_query(&query).await?;
         let mut uprograms = vec![];
         for result in results {
-            let result_json: Value = serde_json::from_str(&result).unwrap();
+            let result_json: Value = serde_json::from_str(&result)?;
 
-            let label: String = serde_json::from_value(result_json["o_label"].clone()).unwrap();
+            let label: String = serde_json::from_value(result_json["o_label"].clone())?;
 
             let o = result_json["o"].clone();
             if label.eq(&"library".to_string()) {
                 islib = true;
-                let library: Library = serde_json::from_value(o).unwrap();
+                let library: Library = serde_json::from_value(o)?;
                 uprograms.push(UProgram::Library(library));
             } else if label.eq(&"application".to_string()) {
-                let application: Application = serde_json::from_value(o).unwrap();
+                let application: Application = serde_json::from_value(o)?;
                 uprograms.push(UProgram::Application(application));
             }
         }
-        Ok((uprograms[0].clone(), islib))
+        Ok(uprograms
+            .into_iter()
+            .next()
+            .map(|uprogram| (uprogram, islib)))
     }
     async fn get_versions(
         &self,
@@ -780,18 +966,18 @@ RETURN n.doc_url
 
         let mut versions: Vec<crate::VersionInfo> = vec![];
         for result in results {
-            let result_json: Value = serde_json::from_str(&result).unwrap();
+            let result_json: Value = serde_json::from_str(&result)?;
 
             let o = result_json["o"].clone();
 
             let (version_base, name_version) = if is_lib {
-                let library_version: LibraryVersion = serde_json::from_value(o).unwrap();
+                let library_version: LibraryVersion = serde_json::from_value(o)?;
                 (
                     UVersion::LibraryVersion(library_version.clone()),
                     library_version.name_and_version.clone(),
                 )
             } else {
-                let application_version: ApplicationVersion = serde_json::from_value(o).unwrap();
+                let application_version: ApplicationVersion = serde_json::from_value(o)?;
                 (
                     UVersion::ApplicationVersion(application_version.clone()),
                     application_version.name_and_version.clone(),
@@ -800,10 +986,7 @@ RETURN n.doc_url
             tracing::debug!("Read version for id {}: {:?}", program_id, version_base);
 
             // get dependencies
-            let dependencies = self
-                .get_direct_dependency_nodes(&name_version)
-                .await
-                .unwrap();
+            let dependencies = self.get_direct_dependency_nodes(&name_version).await?;
 
             versions.push(crate::VersionInfo {
                 version_base,
@@ -818,10 +1001,11 @@ RETURN n.doc_url
     ) -> Result<Vec<crate::NameVersion>, Box<dyn Error>> {
         let query = format!(
             "
-                MATCH (n:version {{name_and_version: '{}'}})-[:depends_on]->(m:version)
+                MATCH (n:version {{name_and_version: '{}'}})-[r:depends_on]->(m:version)
+                WHERE {}
                 RETURN m.name_and_version as name_and_version
                 ",
-            name_and_version
+            name_and_version, NORMAL_DEPENDS_ON
         );
 
         let results = self.client.exec_query(&query).await?;
@@ -829,9 +1013,9 @@ RETURN n.doc_url
         let mut nodes = vec![];
 
         for result in unique_items {
-            let result_json: Value = serde_json::from_str(&result).unwrap();
+            let result_json: Value = serde_json::from_str(&result)?;
             let name_version_str: String =
-                serde_json::from_value(result_json["name_and_version"].clone()).unwrap();
+                serde_json::from_value(result_json["name_and_version"].clone())?;
 
             if let Some(name_version) = crate::NameVersion::from_string(&name_version_str) {
                 nodes.push(name_version);
@@ -840,6 +1024,335 @@ RETURN n.doc_url
 
         Ok(nodes)
     }
+    async fn get_direct_dependency_edges(
+        &self,
+        name_and_versions: Vec<String>,
+    ) -> Result<Vec<GraphEdge>, Box<dyn Error>> {
+        self.query_depends_on_edges("n", &name_and_versions, None)
+            .await
+    }
+    async fn get_direct_dependent_edges(
+        &self,
+        name_and_versions: Vec<String>,
+    ) -> Result<Vec<GraphEdge>, Box<dyn Error>> {
+        self.query_depends_on_edges("m", &name_and_versions, None)
+            .await
+    }
+    async fn sample_direct_dependent_edges(
+        &self,
+        name_and_versions: Vec<String>,
+        limit: usize,
+    ) -> Result<Vec<GraphEdge>, Box<dyn Error>> {
+        self.query_depends_on_edges("m", &name_and_versions, Some(limit))
+            .await
+    }
+    async fn get_versions_of_crates(
+        &self,
+        names: &[String],
+    ) -> Result<HashMap<String, Vec<String>>, Box<dyn Error>> {
+        let mut versions: HashMap<String, Vec<String>> = HashMap::new();
+        for label in ["library_version", "application_version"] {
+            for chunk in names.chunks(EDGE_QUERY_BATCH_SIZE) {
+                let list = chunk
+                    .iter()
+                    .map(|name| cypher_quote(name))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let query = format!(
+                    "
+                    MATCH (v:{})
+                    WHERE v.name IN [{}]
+                    RETURN v.name as name, v.version as version
+                    ",
+                    label, list
+                );
+                for result in self.client.exec_query(&query).await? {
+                    let result_json: Value = serde_json::from_str(&result)?;
+                    if let (Some(name), Some(version)) = (
+                        result_json["name"].as_str(),
+                        result_json["version"].as_str(),
+                    ) {
+                        versions
+                            .entry(name.to_string())
+                            .or_default()
+                            .push(version.to_string());
+                    }
+                }
+            }
+        }
+        for list in versions.values_mut() {
+            list.sort();
+            list.dedup();
+        }
+        Ok(versions)
+    }
+    async fn get_program_of_versions(
+        &self,
+        name_and_versions: Vec<String>,
+    ) -> Result<HashMap<String, (String, ProgramKind)>, Box<dyn Error>> {
+        let mut programs = HashMap::new();
+        for (label, kind) in [
+            ("library", ProgramKind::Library),
+            ("application", ProgramKind::Application),
+        ] {
+            for chunk in name_and_versions.chunks(EDGE_QUERY_BATCH_SIZE) {
+                let list = chunk
+                    .iter()
+                    .map(|nv| cypher_quote(nv))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let query = format!(
+                    "
+                    MATCH (p:program)-[:has_type]->(l:{})-[:has_version]->(lv)-[:has_dep_version]->(v:version)
+                    WHERE v.name_and_version IN [{}]
+                    RETURN v.name_and_version as name_and_version, p.namespace as namespace
+                    ",
+                    label, list
+                );
+                for result in self.client.exec_query(&query).await? {
+                    let result_json: Value = serde_json::from_str(&result)?;
+                    if let (Some(nv), Some(namespace)) = (
+                        result_json["name_and_version"].as_str(),
+                        result_json["namespace"].as_str(),
+                    ) {
+                        programs
+                            .entry(nv.to_string())
+                            .or_insert_with(|| (namespace.to_string(), kind));
+                    }
+                }
+            }
+        }
+        Ok(programs)
+    }
+    async fn get_locked_dependency_edges(
+        &self,
+        root: &str,
+        name_and_versions: Vec<String>,
+    ) -> Result<Vec<GraphEdge>, Box<dyn Error>> {
+        let mut edges = vec![];
+        for chunk in name_and_versions.chunks(EDGE_QUERY_BATCH_SIZE) {
+            let list = chunk
+                .iter()
+                .map(|nv| cypher_quote(nv))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let query = format!(
+                "
+                MATCH (n:version)-[r:locked_depends_on]->(m:version)
+                WHERE r.root = {} AND n.name_and_version IN [{}]
+                RETURN n.name_and_version as src, m.name_and_version as dst, r.source as source
+                ",
+                cypher_quote(root),
+                list
+            );
+            let results = self.client.exec_query(&query).await?;
+            let unique_items: HashSet<String> = results.into_iter().collect();
+            for result in unique_items {
+                let result_json: Value = serde_json::from_str(&result)?;
+                if let (Some(src), Some(dst)) =
+                    (result_json["src"].as_str(), result_json["dst"].as_str())
+                {
+                    edges.push(GraphEdge {
+                        from: src.to_string(),
+                        to: dst.to_string(),
+                        default_features: true,
+                        enabled_by_default: true,
+                        source: result_json["source"]
+                            .as_str()
+                            .and_then(|s| s.parse().ok())
+                            .unwrap_or_default(),
+                        ..Default::default()
+                    });
+                }
+            }
+        }
+        Ok(edges)
+    }
+    async fn get_programs_by_names(
+        &self,
+        names: Vec<String>,
+    ) -> Result<HashMap<String, Vec<Program>>, Box<dyn Error>> {
+        let mut programs: HashMap<String, Vec<Program>> = HashMap::new();
+        for chunk in names.chunks(EDGE_QUERY_BATCH_SIZE) {
+            let query = format!(
+                "
+                MATCH (p:program)
+                WHERE p.name IN [{}]
+                RETURN p
+                ",
+                cypher_list(chunk)
+            );
+            for result in self.client.exec_query(&query).await? {
+                let result_json: Value = serde_json::from_str(&result)?;
+                let program: Program = serde_json::from_value(result_json["p"].clone())?;
+                programs
+                    .entry(program.name.clone())
+                    .or_default()
+                    .push(program);
+            }
+        }
+        for list in programs.values_mut() {
+            list.sort_by(|a, b| a.namespace.cmp(&b.namespace));
+            list.dedup_by(|a, b| a.id == b.id);
+        }
+        Ok(programs)
+    }
+    async fn get_program_types(
+        &self,
+        program_ids: Vec<String>,
+    ) -> Result<HashMap<String, UProgram>, Box<dyn Error>> {
+        let mut types = HashMap::new();
+        for chunk in program_ids.chunks(EDGE_QUERY_BATCH_SIZE) {
+            let query = format!(
+                "
+                MATCH (p:program)-[:has_type]->(o)
+                WHERE p.id IN [{}]
+                RETURN p.id as id, o, label(o) as o_label
+                ",
+                cypher_list(chunk)
+            );
+            for result in self.client.exec_query(&query).await? {
+                let result_json: Value = serde_json::from_str(&result)?;
+                let (Some(id), Some(label)) =
+                    (result_json["id"].as_str(), result_json["o_label"].as_str())
+                else {
+                    continue;
+                };
+                let o = result_json["o"].clone();
+                let uprogram = match label {
+                    "library" => UProgram::Library(serde_json::from_value(o)?),
+                    "application" => UProgram::Application(serde_json::from_value(o)?),
+                    _ => continue,
+                };
+                types.entry(id.to_string()).or_insert(uprogram);
+            }
+        }
+        Ok(types)
+    }
+    async fn get_program_versions(
+        &self,
+        program_ids: Vec<String>,
+    ) -> Result<HashMap<String, Vec<UVersion>>, Box<dyn Error>> {
+        let mut versions: HashMap<String, Vec<UVersion>> = HashMap::new();
+        for chunk in program_ids.chunks(EDGE_QUERY_BATCH_SIZE) {
+            let query = format!(
+                "
+                MATCH (p:program)-[:has_type]->(t)-[:has_version]->(o)
+                WHERE p.id IN [{}]
+                RETURN p.id as id, o, label(o) as o_label
+                ",
+                cypher_list(chunk)
+            );
+            let results = self.client.exec_query(&query).await?;
+            let unique_items: HashSet<String> = results.into_iter().collect();
+            for result in unique_items {
+                let result_json: Value = serde_json::from_str(&result)?;
+                let (Some(id), Some(label)) =
+                    (result_json["id"].as_str(), result_json["o_label"].as_str())
+                else {
+                    continue;
+                };
+                if let Some(version) = parse_version(label, result_json["o"].clone())? {
+                    versions.entry(id.to_string()).or_default().push(version);
+                }
+            }
+        }
+        Ok(versions)
+    }
+    async fn get_version_nodes(
+        &self,
+        name_and_versions: Vec<String>,
+    ) -> Result<HashMap<String, UVersion>, Box<dyn Error>> {
+        let mut nodes = HashMap::new();
+        for chunk in name_and_versions.chunks(EDGE_QUERY_BATCH_SIZE) {
+            let query = format!(
+                "
+                MATCH (o)-[:has_dep_version]->(v:version)
+                WHERE v.name_and_version IN [{}]
+                RETURN v.name_and_version as name_and_version, o, label(o) as o_label
+                ",
+                cypher_list(chunk)
+            );
+            for result in self.client.exec_query(&query).await? {
+                let result_json: Value = serde_json::from_str(&result)?;
+                let (Some(nv), Some(label)) = (
+                    result_json["name_and_version"].as_str(),
+                    result_json["o_label"].as_str(),
+                ) else {
+                    continue;
+                };
+                if let Some(version) = parse_version(label, result_json["o"].clone())? {
+                    nodes.entry(nv.to_string()).or_insert(version);
+                }
+            }
+        }
+        Ok(nodes)
+    }
+    async fn count_direct_dependents(
+        &self,
+        name_and_versions: Vec<String>,
+    ) -> Result<HashMap<String, usize>, Box<dyn Error>> {
+        let mut counts = HashMap::new();
+        for chunk in name_and_versions.chunks(EDGE_QUERY_BATCH_SIZE) {
+            let list = chunk
+                .iter()
+                .map(|nv| cypher_quote(nv))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let query = format!(
+                "
+                MATCH (n:version)<-[r:depends_on]-(m:version)
+                WHERE n.name_and_version IN [{}] AND {}
+                RETURN n.name_and_version as name_and_version, count(DISTINCT m) as dependents
+                ",
+                list, NORMAL_DEPENDS_ON
+            );
+            for result in self.client.exec_query(&query).await? {
+                let result_json: Value = serde_json::from_str(&result)?;
+                if let (Some(nv), Some(count)) = (
+                    result_json["name_and_version"].as_str(),
+                    result_json["dependents"].as_u64(),
+                ) {
+                    counts.insert(nv.to_string(), count as usize);
+                }
+            }
+        }
+        Ok(counts)
+    }
+    async fn get_dep_list_stats(
+        &self,
+        name_and_versions: Vec<String>,
+    ) -> Result<HashMap<String, DepStats>, Box<dyn Error>> {
+        let mut names: Vec<String> = name_and_versions
+            .iter()
+            .filter_map(|nv| nv.split_once('/').map(|(name, _)| name.to_string()))
+            .collect();
+        names.sort();
+        names.dedup();
+        let dbhandler = self.pg.get().await?;
+        let licenses = dbhandler.get_licenses_by_names(&names).await?;
+        let downloads = dbhandler.get_downloads_by_names(&names).await?;
+        let dependents = self
+            .count_direct_dependents(name_and_versions.clone())
+            .await?;
+        let advisories = dbhandler
+            .get_rustsec_by_versions(&name_and_versions)
+            .await?;
+        let mut stats = HashMap::new();
+        for nv in name_and_versions {
+            let Some((name, _)) = nv.split_once('/') else {
+                continue;
+            };
+            let stat = DepStats {
+                license: licenses.get(name).cloned().unwrap_or_default(),
+                downloads: downloads.get(name).copied().unwrap_or_default(),
+                dependents: dependents.get(&nv).copied().unwrap_or_default(),
+                advisories: advisories.get(&nv).map_or(0, Vec::len),
+            };
+            stats.insert(nv, stat);
+        }
+        Ok(stats)
+    }
     async fn new_get_direct_dependency_nodes(
         &self,
         namespace: &str,
@@ -848,11 +1361,13 @@ RETURN n.doc_url
         tracing::info!("enter get_direct_dependency_nodes");
         let query1 = format!(
             "
-                MATCH (p:program {{namespace: '{}'}})-[:has_type]->(l)-[:has_version]->(lv {{name_and_version: '{}'}})-[:has_dep_version]->(vs:version)-[:depends_on]->(m:version)
+                MATCH (p:program {{namespace: '{}'}})-[:has_type]->(l)-[:has_version]->(lv {{name_and_version: '{}'}})-[:has_dep_version]->(vs:version)-[r:depends_on]->(m:version)
+WHERE {}
 RETURN m.name_and_version as name_and_version
                 ",
             namespace,
             nameversion,
+            NORMAL_DEPENDS_ON,
         );
         let results1 = self.client.exec_query(&query1).await?;
         tracing::info!("finish get_direct_dep");
@@ -864,9 +1379,9 @@ RETURN m.name_and_version as name_and_version
         let mut nodes = vec![];
 
         for result in unique_items {
-            let result_json: Value = serde_json::from_str(&result).unwrap();
+            let result_json: Value = serde_json::from_str(&result)?;
             let name_version_str: String =
-                serde_json::from_value(result_json["name_and_version"].clone()).unwrap();
+                serde_json::from_value(result_json["name_and_version"].clone())?;
 
             if let Some(name_version) = crate::NameVersion::from_string(&name_version_str) {
                 nodes.push(name_version);
@@ -880,15 +1395,10 @@ RETURN m.name_and_version as name_and_version
         nameversion: NameVersion,
     ) -> Result<Vec<crate::NameVersion>, Box<dyn Error>> {
         let name_and_version = nameversion.name + "/" + &nameversion.version;
-        let mut nodes = self
-            .get_direct_dependency_nodes(&name_and_version)
-            .await
-            .unwrap();
+        let mut nodes = self.get_direct_dependency_nodes(&name_and_version).await?;
         for node in nodes.clone() {
             tracing::info!("{} {}", node.clone().name, node.clone().version);
-            let new_nodes = Box::pin(self.get_indirect_dependency_nodes(node))
-                .await
-                .unwrap();
+            let new_nodes = Box::pin(self.get_indirect_dependency_nodes(node)).await?;
             for new_node in new_nodes {
                 tracing::info!("{} {}", new_node.clone().name, new_node.clone().version);
                 nodes.push(new_node);
@@ -911,15 +1421,15 @@ RETURN m.name_and_version as name_and_version
         let results = self.client.exec_query(&query).await?;
         let mut programs = vec![];
         for result in results {
-            let programs_json: Value = serde_json::from_str(&result).unwrap();
+            let programs_json: Value = serde_json::from_str(&result)?;
             let pro = programs_json["p"].clone();
-            let program: Program = serde_json::from_value(pro).unwrap();
+            let program: Program = serde_json::from_value(pro)?;
             programs.push(program);
         }
         Ok(programs)
     }
     async fn count_dependencies(&self, nameversion: NameVersion) -> Result<usize, Box<dyn Error>> {
-        let all_nodes = self.get_all_dependencies(nameversion).await.unwrap();
+        let all_nodes = self.get_all_dependencies(nameversion).await?;
         Ok(all_nodes.len())
     }
     async fn get_direct_dependent_nodes(
@@ -928,10 +1438,11 @@ RETURN m.name_and_version as name_and_version
     ) -> Result<Vec<crate::NameVersion>, Box<dyn Error>> {
         let query = format!(
             "
-                MATCH (n:version {{name_and_version: '{}'}})<-[:depends_on]-(m:version)
+                MATCH (n:version {{name_and_version: '{}'}})<-[r:depends_on]-(m:version)
+                WHERE {}
                 RETURN m.name_and_version as name_and_version
                 ",
-            name_and_version
+            name_and_version, NORMAL_DEPENDS_ON
         );
 
         let results = self.client.exec_query(&query).await?;
@@ -939,9 +1450,9 @@ RETURN m.name_and_version as name_and_version
         let mut nodes = vec![];
 
         for result in unique_items {
-            let result_json: Value = serde_json::from_str(&result).unwrap();
+            let result_json: Value = serde_json::from_str(&result)?;
             let name_version_str: String =
-                serde_json::from_value(result_json["name_and_version"].clone()).unwrap();
+                serde_json::from_value(result_json["name_and_version"].clone())?;
 
             if let Some(name_version) = crate::NameVersion::from_string(&name_version_str) {
                 nodes.push(name_version);
@@ -957,11 +1468,13 @@ RETURN m.name_and_version as name_and_version
     ) -> Result<Vec<crate::NameVersion>, Box<dyn Error>> {
         let query1 = format!(
             "
-                MATCH (p:program {{namespace: '{}'}})-[:has_type]->(l)-[:has_version]->(lv {{name_and_version:'{}'}})-[:has_dep_version]->(vs:version)<-[:depends_on]-(m:version)
+                MATCH (p:program {{namespace: '{}'}})-[:has_type]->(l)-[:has_version]->(lv {{name_and_version:'{}'}})-[:has_dep_version]->(vs:version)<-[r:depends_on]-(m:version)
+WHERE {}
 RETURN m.name_and_version as name_and_version
                 ",
             namespace,
-            nameversion
+            nameversion,
+            NORMAL_DEPENDS_ON
         );
         let results1 = self.client.exec_query(&query1).await?;
         let mut res = vec![];
@@ -973,9 +1486,9 @@ RETURN m.name_and_version as name_and_version
         let mut nodes = vec![];
 
         for result in unique_items {
-            let result_json: Value = serde_json::from_str(&result).unwrap();
+            let result_json: Value = serde_json::from_str(&result)?;
             let name_version_str: String =
-                serde_json::from_value(result_json["name_and_version"].clone()).unwrap();
+                serde_json::from_value(result_json["name_and_version"].clone())?;
 
             if let Some(name_version) = crate::NameVersion::from_string(&name_version_str) {
                 nodes.push(name_version);
@@ -989,14 +1502,9 @@ RETURN m.name_and_version as name_and_version
         nameversion: NameVersion,
     ) -> Result<Vec<crate::NameVersion>, Box<dyn Error>> {
         let name_and_version = nameversion.name + "/" + &nameversion.version;
-        let mut nodes = self
-            .get_direct_dependent_nodes(&name_and_version)
-            .await
-            .unwrap();
+        let mut nodes = self.get_direct_dependent_nodes(&name_and_version).await?;
         for node in nodes.clone() {
-            let new_nodes = Box::pin(self.get_indirect_dependent_nodes(node))
-                .await
-                .unwrap();
+            let new_nodes = Box::pin(self.get_indirect_dependent_nodes(node)).await?;
             for new_node in new_nodes {
                 nodes.push(new_node);
             }
@@ -1010,12 +1518,12 @@ RETURN m.name_and_version as name_and_version
             name
         );
 
-        let results = self.client.exec_query(&query).await.unwrap();
+        let results = self.client.exec_query(&query).await?;
 
         let mut realres = vec![];
 
         for res in results {
-            let parsed: Value = serde_json::from_str(&res).unwrap();
+            let parsed: Value = serde_json::from_str(&res)?;
             if let Some(version) = parsed.get("n.version").and_then(|v| v.as_str()) {
                 realres.push(version.to_string());
             }
@@ -1036,13 +1544,13 @@ RETURN lv.version",
             name,
         );
 
-        let results = self.client.exec_query(&query).await.unwrap();
+        let results = self.client.exec_query(&query).await?;
         let unique_items: HashSet<String> = results.clone().into_iter().collect();
 
         let mut realres = vec![];
 
         for res in unique_items {
-            let parsed: Value = serde_json::from_str(&res).unwrap();
+            let parsed: Value = serde_json::from_str(&res)?;
             if let Some(version) = parsed.get("lv.version").and_then(|v| v.as_str()) {
                 realres.push(version.to_string());
             }
@@ -1056,10 +1564,10 @@ RETURN lv.version",
             MATCH (n:application_version {{name: '{}'}}) RETURN n.version LIMIT 100",
             name
         );
-        let results = self.client.exec_query(&query).await.unwrap();
+        let results = self.client.exec_query(&query).await?;
         let mut realres = vec![];
         for res in results {
-            let parsed: Value = serde_json::from_str(&res).unwrap();
+            let parsed: Value = serde_json::from_str(&res)?;
             if let Some(version) = parsed.get("n.version").and_then(|v| v.as_str()) {
                 realres.push(version.to_string());
             }
@@ -1079,13 +1587,13 @@ RETURN av.version",
             name,
         );
 
-        let results = self.client.exec_query(&query).await.unwrap();
+        let results = self.client.exec_query(&query).await?;
         let unique_items: HashSet<String> = results.clone().into_iter().collect();
 
         let mut realres = vec![];
 
         for res in unique_items {
-            let parsed: Value = serde_json::from_str(&res).unwrap();
+            let parsed: Value = serde_json::from_str(&res)?;
             if let Some(version) = parsed.get("av.version").and_then(|v| v.as_str()) {
                 realres.push(version.to_string());
             }
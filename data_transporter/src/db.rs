@@ -1,17 +1,22 @@
-use std::{cmp::Ordering, collections::HashSet, env};
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+};
 
 use crate::{
+    advisory_index::{advisory_index, AdvisoryTarget},
+    auth::{ApiKeyInfo, NewApiKey, Principal, Scope},
     handler::{
         Crateinfo, DependencyCount, DependencyCrateInfo, DependencyInfo, DependentCount,
-        DependentData, DependentInfo, NewRustsec, RustSec, Versionpage,
+        DependentData, DependentInfo, NewRustsec, Versionpage,
     },
+    jobs::{JobEvent, JobState, JobVersion, ReportState, UploadJob},
     UploadedCrate, Userinfo,
 };
 use chrono::NaiveDateTime;
 use model::tugraph_model::{Program, UProgram};
-use semver::Version;
 use serde::{Deserialize, Serialize};
-use tokio_postgres::{Error, NoTls};
+use tokio_postgres::{Error, NoTls, Row};
 use utoipa::ToSchema;
 pub struct DBHandler {
     pub client: tokio_postgres::Client,
@@ -51,6 +56,31 @@ pub fn db_cratesio_connection_config_from_env() -> String {
     )
 }
 
+/// 将 `rustsec_info` 的一行转换为 `NewRustsec`
+pub(crate) fn new_rustsec_from_row(row: &Row) -> NewRustsec {
+    let id: String = row.get("id");
+    let url = "https://rustsec.org/advisories/".to_string() + &id + ".html";
+    NewRustsec {
+        id,
+        subtitle: row.get("subtitle"),
+        reported: row.get("reported"),
+        issued: row.get("issued"),
+        package: row.get("package"),
+        ttype: row.get("type"),
+        keywords: row.get("keywords"),
+        aliases: row.get("aliases"),
+        reference: row.get("reference"),
+        patched: row.get("patched"),
+        unaffected: row.get("unaffected"),
+        description: row.get("description"),
+        url,
+    }
+}
+
+fn advisory_infos(targets: Vec<AdvisoryTarget>) -> Vec<NewRustsec> {
+    targets.into_iter().map(|target| target.info).collect()
+}
+
 impl DBHandler {
     pub async fn connect() -> Result<Self, Error> {
         let db_connection_config = db_connection_config_from_env();
@@ -114,7 +144,7 @@ impl DBHandler {
             )
             .await
     }
-    
+
     /// 从PostgreSQL数据库中查询并获取所有CVE记录的列表
     pub async fn create_tables(&self) -> Result<(), Error> {
         let create_programs_table = "
@@ -176,7 +206,7 @@ impl DBHandler {
             }
         }
     }
-    
+
     /// 将程序数据插入到PostgreSQL数据库中
     pub async fn insert_program_data(
         &self,
@@ -216,8 +246,7 @@ impl DBHandler {
             .map_err(|e| {
                 eprintln!("Error inserting program: {:?}", e);
                 e
-            })
-            .unwrap();
+            })?;
 
         tracing::info!("finish to insert program.");
 
@@ -244,8 +273,7 @@ impl DBHandler {
                                 &"LibraryVersion",
                             ],
                         )
-                        .await
-                        .unwrap();
+                        .await?;
                 }
                 UVersion::ApplicationVersion(app_ver) => {
                     self.client
@@ -265,8 +293,7 @@ impl DBHandler {
                                 &"ApplicationVersion",
                             ],
                         )
-                        .await
-                        .unwrap();
+                        .await?;
                 }
             }
 
@@ -320,406 +347,65 @@ impl DBHandler {
         Ok(res)
     }
 
-    /// 检查给定的版本号是否落在指定的版本范围区间内
-    pub async fn process_closed_interval_of_match_version(
+    /// 查询并返回指定 crate 在指定版本上未修复的所有 RustSec 漏洞详情。
+    pub async fn get_direct_rustsec(
         &self,
-        oneline_patched: String,
-        version: String,
-    ) -> Result<bool, Error> {
-        let mut matched = false;
-        let mut two_versions = vec![];
-        let newparts: Vec<&str> = oneline_patched.split(',').collect();
-        for part in newparts {
-            let one_version = part.to_string();
-            let res_one_version = one_version.trim();
-            two_versions.push(res_one_version.to_string());
-        }
-        let mut left = "".to_string();
-        let mut right = "".to_string();
-        if two_versions.len() == 2 {
-            if two_versions[0].clone().starts_with(">") || two_versions[0].clone().starts_with(">=")
-            {
-                left = two_versions[0].clone();
-                right = two_versions[1].clone();
-            } else if two_versions[0].clone().starts_with("<")
-                || two_versions[0].clone().starts_with("<=")
-            {
-                left = two_versions[1].clone();
-                right = two_versions[0].clone();
-            }
-        }
-        if (left.starts_with(">") && !left.starts_with(">="))
-            && (right.starts_with("<") && !right.starts_with("<="))
-        {
-            //> <
-            let mut versions = vec![];
-            let tmp_left = &left[1..];
-            let left_version = tmp_left.to_string();
-            let tmp_right = &right[1..];
-            let right_version = tmp_right.to_string();
-            versions.push(version.clone());
-            versions.push(left_version.clone());
-            versions.push(right_version.clone());
-            versions.sort_by(|a, b| {
-                let version_a = Version::parse(a);
-                let version_b = Version::parse(b);
-                match (version_a, version_b) {
-                    (Ok(v_a), Ok(v_b)) => v_b.cmp(&v_a),
-                    (Ok(_), Err(_)) => Ordering::Less,
-                    (Err(_), Ok(_)) => Ordering::Greater,
-                    (Err(_), Err(_)) => Ordering::Equal,
-                }
-            });
-            if version.clone() == versions[1].clone()
-                && (versions[0].clone() != version.clone()
-                    && versions[2].clone() != version.clone())
-            {
-                matched = true;
-            }
-        } else if (left.starts_with(">") && !left.starts_with(">=")) && right.starts_with("<=") {
-            //> <=
-            let mut versions = vec![];
-            let tmp_left = &left[1..];
-            let left_version = tmp_left.to_string();
-            let tmp_right = &right[2..];
-            let right_version = tmp_right.to_string();
-            versions.push(version.clone());
-            versions.push(left_version.clone());
-            versions.push(right_version.clone());
-            versions.sort_by(|a, b| {
-                let version_a = Version::parse(a);
-                let version_b = Version::parse(b);
-                match (version_a, version_b) {
-                    (Ok(v_a), Ok(v_b)) => v_b.cmp(&v_a),
-                    (Ok(_), Err(_)) => Ordering::Less,
-                    (Err(_), Ok(_)) => Ordering::Greater,
-                    (Err(_), Err(_)) => Ordering::Equal,
-                }
-            });
-            if version.clone() == versions[1].clone() && (versions[2].clone() != version.clone()) {
-                matched = true;
-            }
-        } else if left.starts_with(">=") && (right.starts_with("<") && !right.starts_with("<=")) {
-            //>= <
-            let mut versions = vec![];
-            let tmp_left = &left[2..];
-            let left_version = tmp_left.to_string();
-            let tmp_right = &right[1..];
-            let right_version = tmp_right.to_string();
-            versions.push(version.clone());
-            versions.push(left_version.clone());
-            versions.push(right_version.clone());
-            versions.sort_by(|a, b| {
-                let version_a = Version::parse(a);
-                let version_b = Version::parse(b);
-                match (version_a, version_b) {
-                    (Ok(v_a), Ok(v_b)) => v_b.cmp(&v_a),
-                    (Ok(_), Err(_)) => Ordering::Less,
-                    (Err(_), Ok(_)) => Ordering::Greater,
-                    (Err(_), Err(_)) => Ordering::Equal,
-                }
-            });
-            if version.clone() == versions[1].clone() && (versions[0].clone() != version.clone()) {
-                matched = true;
-            }
-        } else if left.starts_with(">=") && right.starts_with("<=") {
-            //>= <=
-            let mut versions = vec![];
-            let tmp_left = &left[2..];
-            let left_version = tmp_left.to_string();
-            let tmp_right = &right[2..];
-            let right_version = tmp_right.to_string();
-            versions.push(version.clone());
-            versions.push(left_version.clone());
-            versions.push(right_version.clone());
-            versions.sort_by(|a, b| {
-                let version_a = Version::parse(a);
-                let version_b = Version::parse(b);
-                match (version_a, version_b) {
-                    (Ok(v_a), Ok(v_b)) => v_b.cmp(&v_a),
-                    (Ok(_), Err(_)) => Ordering::Less,
-                    (Err(_), Ok(_)) => Ordering::Greater,
-                    (Err(_), Err(_)) => Ordering::Equal,
-                }
-            });
-            if version.clone() == versions[1].clone() {
-                matched = true;
-            }
-        }
-        Ok(matched)
+        cname: &str,
+        version: &str,
+    ) -> Result<Vec<NewRustsec>, Error> {
+        let index = advisory_index(&self.client).await?;
+        Ok(advisory_infos(index.advisories_of(cname, Some(version))))
     }
 
-    /// 检查一个版本是否满足单个约束条件
-    pub async fn process_open_interval_of_match_version(
-        &self,
-        oneline_patched: String,
-        version: String,
-    ) -> Result<bool, Error> {
-        let mut matched = false;
-        if oneline_patched.starts_with(">") && !oneline_patched.starts_with(">=") {
-            let mut versions = vec![];
-            let trimmed = &oneline_patched[1..];
-            let res = trimmed.to_string();
-            versions.push(version.clone());
-            versions.push(res.clone());
-            versions.sort_by(|a, b| {
-                let version_a = Version::parse(a);
-                let version_b = Version::parse(b);
-                match (version_a, version_b) {
-                    (Ok(v_a), Ok(v_b)) => v_b.cmp(&v_a),
-                    (Ok(_), Err(_)) => Ordering::Less,
-                    (Err(_), Ok(_)) => Ordering::Greater,
-                    (Err(_), Err(_)) => Ordering::Equal,
-                }
-            });
-            if versions[0].clone() == version.clone() && res.clone() != version.clone() {
-                matched = true;
-            }
-        } else if let Some(trimmed) = oneline_patched.strip_prefix(">=") {
-            let mut versions = vec![];
-            let res = trimmed.to_string();
-            versions.push(version.clone());
-            versions.push(res.clone());
-            versions.sort_by(|a, b| {
-                let version_a = Version::parse(a);
-                let version_b = Version::parse(b);
-                match (version_a, version_b) {
-                    (Ok(v_a), Ok(v_b)) => v_b.cmp(&v_a),
-                    (Ok(_), Err(_)) => Ordering::Less,
-                    (Err(_), Ok(_)) => Ordering::Greater,
-                    (Err(_), Err(_)) => Ordering::Equal,
-                }
-            });
-            if versions[0].clone() == version.clone() {
-                matched = true;
-            }
-        } else if oneline_patched.starts_with("<") && !oneline_patched.starts_with("<=") {
-            let mut versions = vec![];
-            let trimmed = &oneline_patched[1..];
-            let res = trimmed.to_string();
-            versions.push(version.clone());
-            versions.push(res.clone());
-            versions.sort_by(|a, b| {
-                let version_a = Version::parse(a);
-                let version_b = Version::parse(b);
-                match (version_a, version_b) {
-                    (Ok(v_a), Ok(v_b)) => v_b.cmp(&v_a),
-                    (Ok(_), Err(_)) => Ordering::Less,
-                    (Err(_), Ok(_)) => Ordering::Greater,
-                    (Err(_), Err(_)) => Ordering::Equal,
-                }
-            });
-            if versions[1].clone() == version.clone() && res.clone() != version.clone() {
-                matched = true;
-            }
-        } else if let Some(trimmed) = oneline_patched.strip_prefix("<=") {
-            let mut versions = vec![];
-            let res = trimmed.to_string();
-            versions.push(version.clone());
-            versions.push(res.clone());
-            versions.sort_by(|a, b| {
-                let version_a = Version::parse(a);
-                let version_b = Version::parse(b);
-                match (version_a, version_b) {
-                    (Ok(v_a), Ok(v_b)) => v_b.cmp(&v_a),
-                    (Ok(_), Err(_)) => Ordering::Less,
-                    (Err(_), Ok(_)) => Ordering::Greater,
-                    (Err(_), Err(_)) => Ordering::Equal,
-                }
-            });
-            if versions[1].clone() == version.clone() {
-                matched = true;
-            }
-        }
-        Ok(matched)
+    /// 按 RustSec ID 或 CVE 等别名查找漏洞及其影响的 crate。
+    pub async fn find_advisory(&self, id_or_alias: &str) -> Result<Vec<AdvisoryTarget>, Error> {
+        let index = advisory_index(&self.client).await?;
+        Ok(index.find(id_or_alias))
     }
 
-    /// 检查一个版本 version 是否匹配一个版本匹配表达式 patched 中的任意一个规则
-    pub async fn match_version(&self, patched: String, version: String) -> Result<bool, Error> {
-        let mut matched = false;
-        let mut part_petched = vec![];
-        let parts: Vec<&str> = patched.split('|').collect();
-        for part in parts {
-            part_petched.push(part);
-        }
-        for np in part_petched {
-            let oneline_patched = np.to_string();
-            if oneline_patched.clone().contains(",") {
-                //closed interval
-                matched = matched
-                    || self
-                        .process_closed_interval_of_match_version(
-                            oneline_patched.clone(),
-                            version.clone(),
-                        )
-                        .await
-                        .unwrap();
-            } else if oneline_patched.clone().contains("^") {
-                //specific version
-                if let Some(trimmed) = oneline_patched.strip_prefix("^") {
-                    let res = trimmed.to_string();
-                    if version == res {
-                        matched = true;
-                    }
-                }
-            } else {
-                //open interval
-                matched = matched
-                    || self
-                        .process_open_interval_of_match_version(
-                            oneline_patched.clone(),
-                            version.clone(),
-                        )
-                        .await?;
-            }
-        }
-        Ok(matched)
+    /// 批量查询影响每个 `(name, version)` 的漏洞，`version` 为空时返回该 crate 的全部漏洞。
+    pub async fn get_package_advisories(
+        &self,
+        packages: &[(String, Option<String>)],
+    ) -> Result<Vec<Vec<AdvisoryTarget>>, Error> {
+        let index = advisory_index(&self.client).await?;
+        Ok(packages
+            .iter()
+            .map(|(name, version)| index.advisories_of(name, version.as_deref()))
+            .collect())
     }
-    
-    /// 查询并返回指定 crate 在指定版本上未修复的所有 RustSec 漏洞详情。
-    pub async fn get_direct_rustsec(
+
+    /// 批量查询一组 `name/version` 各自未修复的 RustSec 漏洞，没有漏洞的不出现在结果中。
+    pub async fn get_rustsec_by_versions<'a>(
         &self,
-        cname: &str,
-        version: &str,
-    ) -> Result<Vec<NewRustsec>, Error> {
-        tracing::info!("enter get direct_rustsec");
-        let rows = self
-            .client
-            .query("SELECT * FROM rustsecs;", &[])
-            .await
-            .unwrap();
-        let mut get_direct_rust_sec = vec![];
-        for row in rows {
-            let t_aliases: String = row.get("aliases");
-            let parts: Vec<&str> = t_aliases.split(';').collect();
-            let mut real_aliases = vec![];
-            for part in parts {
-                real_aliases.push(part.to_string());
-            }
-            let rs = RustSec {
-                id: row.get("id"),
-                cratename: row.get("cratename"),
-                patched: row.get("patched"),
-                aliases: real_aliases.clone(),
-                small_desc: row.get("small_desc"),
-            };
-            get_direct_rust_sec.push(rs.clone());
-        }
-        let mut getres = vec![];
-        for rc in get_direct_rust_sec {
-            if rc.cratename.clone() == *cname {
-                let matched = self
-                    .match_version(rc.clone().patched, version.to_string())
-                    .await
-                    .unwrap();
-                if !matched {
-                    let rows2 = self
-                        .client
-                        .query("SELECT * FROM rustsec_info WHERE id=$1;", &[&rc.clone().id])
-                        .await
-                        .unwrap();
-                    for row in rows2 {
-                        let tmp_id: String = row.get("id");
-                        let rs_url =
-                            "https://rustsec.org/advisories/".to_string() + &tmp_id + ".html";
-                        let nrs = NewRustsec {
-                            id: row.get("id"),
-                            subtitle: row.get("subtitle"),
-                            reported: row.get("reported"),
-                            issued: row.get("issued"),
-                            package: row.get("package"),
-                            ttype: row.get("type"),
-                            keywords: row.get("keywords"),
-                            aliases: row.get("aliases"),
-                            reference: row.get("reference"),
-                            patched: row.get("patched"),
-                            unaffected: row.get("unaffected"),
-                            description: row.get("description"),
-                            url: rs_url.clone(),
-                        };
-                        getres.push(nrs.clone());
-                    }
+        name_and_versions: impl IntoIterator<Item = &'a String>,
+    ) -> Result<HashMap<String, Vec<NewRustsec>>, Error> {
+        let index = advisory_index(&self.client).await?;
+        let mut found = HashMap::new();
+        for nv in name_and_versions {
+            if let Some((cname, version)) = nv.split_once('/') {
+                let advisories = advisory_infos(index.advisories_of(cname, Some(version)));
+                if !advisories.is_empty() {
+                    found.insert(nv.clone(), advisories);
                 }
             }
         }
-        tracing::info!("finish get direct_rustsec");
-        Ok(getres)
+        Ok(found)
     }
+
+    /// 查询一组 `name/version` 依赖上未修复的 RustSec 漏洞，结果去重。
     pub async fn get_dependency_rustsec(
         &self,
         nameversion: HashSet<String>,
     ) -> Result<Vec<NewRustsec>, Error> {
-        let rows = self
-            .client
-            .query("SELECT * FROM rustsecs;", &[])
-            .await
-            .unwrap();
-        let mut get_all_rust_sec = vec![];
-        for row in rows {
-            let t_aliases: String = row.get("aliases");
-            let parts: Vec<&str> = t_aliases.split(';').collect();
-            let mut real_aliases = vec![];
-            for part in parts {
-                real_aliases.push(part.to_string());
-            }
-            let rs = RustSec {
-                id: row.get("id"),
-                cratename: row.get("cratename"),
-                patched: row.get("patched"),
-                aliases: real_aliases.clone(),
-                small_desc: row.get("small_desc"),
-            };
-            get_all_rust_sec.push(rs.clone());
-        }
-        let mut getres = vec![];
+        let index = advisory_index(&self.client).await?;
+        let mut getres = HashSet::new();
         for nv in nameversion {
-            let parts: Vec<&str> = nv.split('/').collect();
-            let cname = parts[0].to_string();
-            let version = parts[1].to_string();
-            for rc in get_all_rust_sec.clone() {
-                if rc.cratename.clone() == cname {
-                    let matched = self
-                        .match_version(rc.clone().patched, version.to_string())
-                        .await
-                        .unwrap();
-                    if !matched {
-                        let rows2 = self
-                            .client
-                            .query("SELECT * FROM rustsec_info WHERE id=$1;", &[&rc.clone().id])
-                            .await
-                            .unwrap();
-                        for row in rows2 {
-                            let tmp_id: String = row.get("id");
-                            let rs_url =
-                                "https://rustsec.org/advisories/".to_string() + &tmp_id + ".html";
-                            let nrs = NewRustsec {
-                                id: row.get("id"),
-                                subtitle: row.get("subtitle"),
-                                reported: row.get("reported"),
-                                issued: row.get("issued"),
-                                package: row.get("package"),
-                                ttype: row.get("type"),
-                                keywords: row.get("keywords"),
-                                aliases: row.get("aliases"),
-                                reference: row.get("reference"),
-                                patched: row.get("patched"),
-                                unaffected: row.get("unaffected"),
-                                description: row.get("description"),
-                                url: rs_url.clone(),
-                            };
-                            getres.push(nrs.clone());
-                        }
-                        //getres.push(rc.clone());
-                    }
-                }
+            if let Some((cname, version)) = nv.split_once('/') {
+                getres.extend(advisory_infos(index.advisories_of(cname, Some(version))));
             }
         }
-        let unique: Vec<NewRustsec> = getres
-            .into_iter()
-            .collect::<HashSet<_>>()
-            .into_iter()
-            .collect();
-        Ok(unique)
+        Ok(getres.into_iter().collect())
     }
     /*#[allow(dead_code)]
     pub async fn get_direct_cve_by_cratenameandversion(
@@ -727,7 +413,7 @@ impl DBHandler {
         cratename: &str,
         version: &str,
     ) -> Result<Vec<String>, Error> {
-        let rows = self.client.query("SELECT * FROM cves;", &[]).await.unwrap();
+        let rows = self.client.query("SELECT * FROM cves;", &[]).await?;
         let mut getallcves = vec![];
         for row in rows {
             let cveinfo = CveInfo {
@@ -771,7 +457,7 @@ impl DBHandler {
         &self,
         nameversion: HashSet<String>,
     ) -> Result<Vec<String>, Error> {
-        let rows = self.client.query("SELECT * FROM cves;", &[]).await.unwrap();
+        let rows = self.client.query("SELECT * FROM cves;", &[]).await?;
         let mut getallcves = vec![];
         for row in rows {
             let cveinfo = CveInfo {
@@ -832,8 +518,7 @@ impl DBHandler {
                 "SELECT license FROM license WHERE program_namespace = $1 and program_name = $2;",
                 &[&namespace.to_string(), &name.to_string()],
             )
-            .await
-            .unwrap();
+            .await?;
         let mut licenses = vec![];
         for row in rows {
             let new_license: String = row.get(0);
@@ -843,6 +528,128 @@ impl DBHandler {
         Ok(licenses)
     }
 
+    /// 按程序名称批量查询许可证，返回 `program_name -> license`，同名程序只取其一。
+    pub async fn get_licenses_by_names(
+        &self,
+        names: &[String],
+    ) -> Result<HashMap<String, String>, Error> {
+        let rows = self
+            .client
+            .query(
+                "SELECT program_name, license FROM license WHERE program_name = ANY($1);",
+                &[&names],
+            )
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get(0), row.get(1)))
+            .collect())
+    }
+
+    /// 按名称批量查询已收录的程序及其 `metadata` 评估分数，返回 `name -> evaluated_score`，
+    /// 没有评估结果的程序分数为空，同名程序取最大值。
+    pub async fn get_program_scores(
+        &self,
+        names: &[String],
+    ) -> Result<HashMap<String, Option<f64>>, Error> {
+        let rows = self
+            .client
+            .query(
+                "SELECT p.name, MAX(m.evaluated_score) FROM programs p LEFT JOIN metadata m ON m.id = p.id WHERE p.name = ANY($1) GROUP BY p.name;",
+                &[&names],
+            )
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get(0), row.get(1)))
+            .collect())
+    }
+
+    /// 在 crates.io 数据中批量查询 `(name, version)` 是否已被 yank，不存在的版本不返回。
+    pub async fn get_yanked_versions(
+        &self,
+        packages: &[(String, String)],
+    ) -> Result<HashMap<(String, String), bool>, Error> {
+        let (names, versions): (Vec<String>, Vec<String>) = packages.iter().cloned().unzip();
+        let rows = self
+            .client
+            .query(
+                "SELECT c.name, v.num, v.yanked FROM versions v JOIN crates c ON c.id = v.crate_id \
+                 JOIN unnest($1::text[], $2::text[]) AS q(name, num) ON q.name = c.name AND q.num = v.num;",
+                &[&names, &versions],
+            )
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| ((row.get(0), row.get(1)), row.get(2)))
+            .collect())
+    }
+
+    /// 按程序名称批量查询下载量，返回 `name -> downloads`，同名程序取最大值。
+    pub async fn get_downloads_by_names(
+        &self,
+        names: &[String],
+    ) -> Result<HashMap<String, i64>, Error> {
+        let rows = self
+            .client
+            .query(
+                "SELECT name, MAX(COALESCE(downloads, 0)) FROM programs WHERE name = ANY($1) GROUP BY name;",
+                &[&names],
+            )
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get(0), row.get(1)))
+            .collect())
+    }
+
+    /// 按名称批量查询程序的命名空间和最新版本，同名程序取下载量最大的一个。
+    pub async fn get_latest_programs_by_names(
+        &self,
+        names: &[String],
+    ) -> Result<HashMap<String, (String, String)>, Error> {
+        let rows = self
+            .client
+            .query(
+                "SELECT DISTINCT ON (name) name, namespace, max_version FROM programs \
+                 WHERE name = ANY($1) AND namespace IS NOT NULL AND max_version IS NOT NULL \
+                 ORDER BY name, downloads DESC NULLS LAST;",
+                &[&names],
+            )
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get(0), (row.get(1), row.get(2))))
+            .collect())
+    }
+
+    /// 查询与指定程序 `embedding` 余弦距离最近的其他程序，返回按相似度降序排列的
+    /// `(name, similarity)`，同名程序只保留最相似的一个。需要 pgvector 和 `embedding` 列。
+    pub async fn get_embedding_neighbours(
+        &self,
+        name: &str,
+        namespace: &str,
+        limit: i64,
+    ) -> Result<Vec<(String, f32)>, Error> {
+        let rows = self
+            .client
+            .query(
+                "SELECT n.name, MAX(n.similarity) AS similarity FROM ( \
+                     SELECT p.name, (1 - (p.embedding <=> t.embedding))::real AS similarity \
+                     FROM programs p, (SELECT embedding FROM programs \
+                         WHERE name = $1 AND namespace = $2 AND embedding IS NOT NULL LIMIT 1) t \
+                     WHERE p.embedding IS NOT NULL AND p.name <> $1 \
+                     ORDER BY p.embedding <=> t.embedding LIMIT $3 \
+                 ) n GROUP BY n.name ORDER BY similarity DESC;",
+                &[&name, &namespace, &limit],
+            )
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get(0), row.get(1)))
+            .collect())
+    }
+
     /// 从 PostgreSQL 数据库中查询并返回指定 crate 的详细信息。
     pub async fn query_crates_info_from_pg(
         &self,
@@ -856,8 +663,7 @@ impl DBHandler {
                 "SELECT * FROM crates_info WHERE id = $1;",
                 &[&id.to_string()],
             )
-            .await
-            .unwrap();
+            .await?;
 
         let mut cf = vec![];
         for row in rows {
@@ -933,6 +739,7 @@ impl DBHandler {
                 dependencies: DependencyCount {
                     direct: dcyct as usize,
                     indirect: indcyct as usize,
+                    truncated: false,
                 },
                 dependents: DependentCount {
                     direct: dtct as usize,
@@ -1029,7 +836,7 @@ impl DBHandler {
         let cs = every_cs.clone().join("||||||");
         Ok(cs)
     }
-    
+
     /// 该函数异步将指定 crate 的信息（描述、依赖、漏洞、版本、许可证等）插入 PostgreSQL 数据库表。
     pub async fn insert_crates_info_into_pg(
         &self,
@@ -1044,8 +851,8 @@ impl DBHandler {
         let dtct = crateinfo.dependents.direct as i32;
         let indtct = crateinfo.dependents.indirect as i32;
         let vs = crateinfo.versions.clone().join("/");
-        let cs = self.process_cves(crateinfo.clone().cves).await.unwrap();
-        let depcs = self.process_cves(crateinfo.clone().dep_cves).await.unwrap();
+        let cs = self.process_cves(crateinfo.clone().cves).await?;
+        let depcs = self.process_cves(crateinfo.clone().dep_cves).await?;
         self.client
             .execute(
                 "
@@ -1070,8 +877,7 @@ impl DBHandler {
                     &crateinfo.doc_url,
                 ],
             )
-            .await
-            .unwrap();
+            .await?;
         Ok(())
     }
 
@@ -1087,8 +893,7 @@ impl DBHandler {
         let rows = self
             .client
             .query("SELECT * FROM graph_info WHERE id = $1;", &[&id])
-            .await
-            .unwrap();
+            .await?;
         let mut res = vec![];
         for row in rows {
             let graph: String = row.get("graph");
@@ -1098,7 +903,6 @@ impl DBHandler {
     }
 
     ///该函数异步将指定的命名空间、包名、版本号组合成唯一 ID，并将对应的图结构字符串插入到数据库的 `graph_info` 表中。
-
     pub async fn insert_graph_into_pg(
         &self,
         nsfront: String,
@@ -1117,8 +921,7 @@ impl DBHandler {
                         ",
                 &[&id, &graph],
             )
-            .await
-            .unwrap();
+            .await?;
         Ok(())
     }
 
@@ -1133,8 +936,7 @@ impl DBHandler {
         let rows = self
             .client
             .query("SELECT * FROM version_info WHERE id = $1;", &[&id])
-            .await
-            .unwrap();
+            .await?;
         let mut res = vec![];
         for row in rows {
             let newversion: String = row.get("versions");
@@ -1174,8 +976,7 @@ impl DBHandler {
                         ",
                 &[&id, &versions],
             )
-            .await
-            .unwrap();
+            .await?;
         Ok(())
     }
     ///该函数异步根据给定的包名，从数据库中查找对应的包 ID，再根据该 ID 查询其所有版本信息，找到指定版本后返回该版本的更新时间和下载量组成的字符串。
@@ -1188,8 +989,7 @@ impl DBHandler {
         let rows1 = self
             .client
             .query("SELECT * FROM crates WHERE name=$1 LIMIT 1", &[&name])
-            .await
-            .unwrap();
+            .await?;
         tracing::info!("finish get id");
         let mut res = "".to_string();
         for row in rows1 {
@@ -1200,8 +1000,7 @@ impl DBHandler {
             let rows = self
                 .client
                 .query("SELECT * FROM versions WHERE crate_id=$1;", &[&crate_id])
-                .await
-                .unwrap();
+                .await?;
             tracing::info!("finish get num,up,dl");
             for row in rows {
                 let num: String = row.get("num");
@@ -1230,8 +1029,7 @@ impl DBHandler {
         let rows = self
             .client
             .query("SELECT * FROM dependency_cache WHERE id = $1;", &[&id])
-            .await
-            .unwrap();
+            .await?;
         let mut res = vec![];
         for row in rows {
             let all_dependency: String = row.get("dependency");
@@ -1244,13 +1042,14 @@ impl DBHandler {
                 let parts2: Vec<&str> = one_dep.split("/").collect();
                 if parts2.len() == 5 {
                     let dcs = parts2[4].to_string();
-                    let dcc = dcs.parse::<usize>().unwrap();
+                    let dcc = dcs.parse::<usize>()?;
                     let one_res = DependencyCrateInfo {
                         crate_name: parts2[0].to_string(),
                         version: parts2[1].to_string(),
                         relation: parts2[2].to_string(),
                         license: parts2[3].to_string(),
                         dependencies: dcc,
+                        ..Default::default()
                     };
                     deps.push(one_res);
                 }
@@ -1258,6 +1057,11 @@ impl DBHandler {
             let real_res = DependencyInfo {
                 direct_count: direct as usize,
                 indirect_count: indirect as usize,
+                truncated: false,
+                total: deps.len(),
+                page: 1,
+                per_page: deps.len(),
+                next_cursor: None,
                 data: deps,
             };
             res.push(real_res);
@@ -1279,8 +1083,7 @@ impl DBHandler {
         let rows = self
             .client
             .query("SELECT * FROM dependent_cache WHERE id = $1;", &[&id])
-            .await
-            .unwrap();
+            .await?;
         let mut res = vec![];
         for row in rows {
             let all_dependent: String = row.get("dependent");
@@ -1296,6 +1099,7 @@ impl DBHandler {
                         crate_name: parts2[0].to_string(),
                         version: parts2[1].to_string(),
                         relation: parts2[2].to_string(),
+                        ..Default::default()
                     };
                     deps.push(one_res);
                 }
@@ -1303,6 +1107,11 @@ impl DBHandler {
             let real_res = DependentInfo {
                 direct_count: direct as usize,
                 indirect_count: indirect as usize,
+                truncated: false,
+                total: deps.len(),
+                page: 1,
+                per_page: deps.len(),
+                next_cursor: None,
                 data: deps,
             };
             res.push(real_res);
@@ -1349,8 +1158,7 @@ impl DBHandler {
                     &real_dep,
                 ],
             )
-            .await
-            .unwrap();
+            .await?;
         Ok(())
     }
     ///该函数异步将命名空间、包名、版本号组合成唯一 ID，
@@ -1385,8 +1193,7 @@ impl DBHandler {
                     &real_dep,
                 ],
             )
-            .await
-            .unwrap();
+            .await?;
         Ok(())
     }
     /// 该函数异步将用户信息（包括邮箱、头像、姓名和过期时间）
@@ -1409,8 +1216,7 @@ impl DBHandler {
                     &info.expires,
                 ],
             )
-            .await
-            .unwrap();
+            .await?;
         Ok(())
     }
     ///该函数异步根据用户邮箱，从数据库查询该用户上传的所有包信息，
@@ -1421,21 +1227,37 @@ impl DBHandler {
     ) -> Result<Vec<UploadedCrate>, Box<dyn std::error::Error>> {
         let rows = self
             .client
-            .query("SELECT * FROM uploadedcrate WHERE email=$1", &[&email])
-            .await
-            .unwrap();
+            .query(
+                "SELECT c.filename, c.uploadtime, j.id AS job_id, j.state
+                 FROM uploadedcrate c
+                 LEFT JOIN LATERAL (
+                     SELECT id, state FROM upload_jobs j
+                     WHERE j.email = c.email AND j.source = c.filename
+                       AND j.upload_time = c.uploadtime
+                     ORDER BY j.created_at DESC
+                     LIMIT 1
+                 ) j ON true
+                 WHERE c.email=$1",
+                &[&email],
+            )
+            .await?;
         let mut res = vec![];
         for row in rows {
             let name: String = row.get("filename");
             let time: String = row.get("uploadtime");
-            let tmp_res = UploadedCrate { name, time };
+            let state: Option<&str> = row.get("state");
+            let tmp_res = UploadedCrate {
+                name,
+                time,
+                job_id: row.get("job_id"),
+                state: state.map(job_state),
+            };
             res.push(tmp_res);
         }
         Ok(res)
     }
     ///该函数异步根据用户邮箱，从数据库查询该用户上传的所有
     ///  GitHub URL 及上传时间，并返回列表。
-
     pub async fn query_uploaded_url_from_pg(
         &self,
         email: String,
@@ -1443,13 +1265,16 @@ impl DBHandler {
         let rows = self
             .client
             .query("SELECT * FROM uploadedurl WHERE email=$1", &[&email])
-            .await
-            .unwrap();
+            .await?;
         let mut res = vec![];
         for row in rows {
             let name: String = row.get("githuburl");
             let time: String = row.get("uploadtime");
-            let tmp_res = UploadedCrate { name, time };
+            let tmp_res = UploadedCrate {
+                name,
+                time,
+                ..Default::default()
+            };
             res.push(tmp_res);
         }
         Ok(res)
@@ -1469,8 +1294,7 @@ impl DBHandler {
                         DO UPDATE SET res=$2;",
                 &[&id, &result],
             )
-            .await
-            .unwrap();
+            .await?;
         Ok(())
     }
     ///该函数异步将镜像检查结果根据唯一 ID 插入或更新到
@@ -1488,11 +1312,10 @@ impl DBHandler {
                         DO UPDATE SET res=$2;",
                 &[&id, &result],
             )
-            .await
-            .unwrap();
+            .await?;
         Ok(())
     }
-    /// 该函数异步将镜像检查失败的唯一 
+    /// 该函数异步将镜像检查失败的唯一
     /// ID 插入到数据库的 `mirchecker_run_failed` 表中，
     /// 若已存在则不做任何操作。
     pub async fn insert_mirchecker_failed_into_pg(
@@ -1507,8 +1330,7 @@ impl DBHandler {
                         DO NOTHING;",
                 &[&id],
             )
-            .await
-            .unwrap();
+            .await?;
         Ok(())
     }
     ///该函数异步根据唯一 ID，从数据库中查询敏感泄露检测结果，
@@ -1521,8 +1343,7 @@ impl DBHandler {
         let rows = self
             .client
             .query("SELECT * FROM senseleak_res WHERE id=$1", &[&id])
-            .await
-            .unwrap();
+            .await?;
         let mut tmp_res = vec![];
         for row in rows {
             let s_res: String = row.get("res");
@@ -1544,8 +1365,7 @@ impl DBHandler {
         let rows = self
             .client
             .query("SELECT * FROM mirchecker_res WHERE id=$1", &[&id])
-            .await
-            .unwrap();
+            .await?;
         let mut tmp_res = vec![];
         for row in rows {
             let s_res: String = row.get("res");
@@ -1557,7 +1377,7 @@ impl DBHandler {
         }
         Ok(real_res)
     }
-    ///该函数异步根据唯一 ID 查询镜像检查失败记录表，返回布尔值表示该 
+    ///该函数异步根据唯一 ID 查询镜像检查失败记录表，返回布尔值表示该
     /// ID 是否未失败（存在记录返回 `false`，否则返回 `true`）。
     #[allow(clippy::len_zero)]
     pub async fn get_mirchecker_run_state_from_pg(
@@ -1567,8 +1387,7 @@ impl DBHandler {
         let rows = self
             .client
             .query("SELECT * FROM mirchecker_run_failed WHERE id=$1", &[&id])
-            .await
-            .unwrap();
+            .await?;
         let mut tmp_res = vec![];
         for row in rows {
             let s_res: String = row.get("id");
@@ -1580,4 +1399,257 @@ impl DBHandler {
         }
         Ok(real_res)
     }
+
+    /// 创建处于 queued 状态的上传任务并记录第一条事件。
+    pub async fn create_upload_job(
+        &self,
+        id: &str,
+        email: &str,
+        source: &str,
+        upload_time: &str,
+    ) -> Result<(), Error> {
+        self.client
+            .execute(
+                "WITH job AS (
+                    INSERT INTO upload_jobs (id, email, source, upload_time, state)
+                    VALUES ($1, $2, $3, $4, 'queued') RETURNING id
+                )
+                INSERT INTO upload_job_events (job_id, state) SELECT id, 'queued' FROM job;",
+                &[&id, &email, &source, &upload_time],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// 把任务转移到 `state` 并记录事件，进入 failed 时 `message` 同时作为错误原因。
+    /// 当前状态不能转移到 `state` 时不做修改，返回 `false`。
+    pub async fn advance_upload_job(
+        &self,
+        id: &str,
+        state: JobState,
+        message: Option<&str>,
+    ) -> Result<bool, Error> {
+        let from: Vec<&str> = state
+            .predecessors()
+            .into_iter()
+            .map(JobState::as_str)
+            .collect();
+        let moved = self
+            .client
+            .execute(
+                "WITH moved AS (
+                    UPDATE upload_jobs
+                    SET state = $2::TEXT, updated_at = NOW(),
+                        error = CASE WHEN $2::TEXT = 'failed' THEN $3 ELSE error END
+                    WHERE id = $1 AND state = ANY($4) RETURNING id
+                )
+                INSERT INTO upload_job_events (job_id, state, message)
+                SELECT id, $2::TEXT, $3 FROM moved;",
+                &[&id, &state.as_str(), &message, &from],
+            )
+            .await?;
+        if moved == 0 {
+            tracing::warn!("upload job {} cannot move to {}", id, state.as_str());
+        }
+        Ok(moved > 0)
+    }
+
+    /// 记录任务导入产生的 crate 版本。
+    pub async fn add_upload_job_versions(
+        &self,
+        id: &str,
+        namespace: &str,
+        versions: &[(String, String)],
+    ) -> Result<(), Error> {
+        let (names, versions): (Vec<String>, Vec<String>) = versions.iter().cloned().unzip();
+        self.client
+            .execute(
+                "INSERT INTO upload_job_versions (job_id, namespace, name, version)
+                SELECT $1, $2, name, version FROM unnest($3::TEXT[], $4::TEXT[]) AS v(name, version)
+                ON CONFLICT DO NOTHING;",
+                &[&id, &namespace, &names, &versions],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// 查询任务详情，包括全部事件、导入的版本及其分析报告状态。
+    pub async fn get_upload_job(&self, id: &str) -> Result<Option<UploadJob>, Error> {
+        let Some(row) = self
+            .client
+            .query_opt(
+                "SELECT id, email, source, upload_time, state, error, created_at, updated_at
+                FROM upload_jobs WHERE id = $1;",
+                &[&id],
+            )
+            .await?
+        else {
+            return Ok(None);
+        };
+        let events = self
+            .client
+            .query(
+                "SELECT state, message, created_at FROM upload_job_events
+                WHERE job_id = $1 ORDER BY id;",
+                &[&id],
+            )
+            .await?
+            .into_iter()
+            .map(|row| JobEvent {
+                state: job_state(row.get(0)),
+                message: row.get(1),
+                at: row.get(2),
+            })
+            .collect();
+        let versions = self
+            .client
+            .query(
+                "SELECT v.namespace, v.name, v.version,
+                    EXISTS (SELECT 1 FROM senseleak_res s WHERE s.id = v.namespace),
+                    EXISTS (SELECT 1 FROM mirchecker_res m
+                        WHERE m.id = v.namespace || '/' || v.name || '/' || v.version),
+                    EXISTS (SELECT 1 FROM mirchecker_run_failed f
+                        WHERE f.id = v.namespace || '/' || v.name || '/' || v.version)
+                FROM upload_job_versions v WHERE v.job_id = $1 ORDER BY v.name, v.version;",
+                &[&id],
+            )
+            .await?
+            .into_iter()
+            .map(|row| {
+                let report = |ready: bool, failed: bool| match (ready, failed) {
+                    (true, _) => ReportState::Ready,
+                    (false, true) => ReportState::Failed,
+                    (false, false) => ReportState::Pending,
+                };
+                JobVersion::new(
+                    row.get(0),
+                    row.get(1),
+                    row.get(2),
+                    report(row.get(3), false),
+                    report(row.get(4), row.get(5)),
+                )
+            })
+            .collect();
+        Ok(Some(UploadJob {
+            id: row.get(0),
+            email: row.get(1),
+            source: row.get(2),
+            upload_time: row.get(3),
+            state: job_state(row.get(4)),
+            error: row.get(5),
+            created_at: row.get(6),
+            updated_at: row.get(7),
+            events,
+            versions,
+        }))
+    }
+
+    /// 保存新签发的 API Key，只保存 Key 的摘要。
+    pub async fn create_api_key(
+        &self,
+        id: &str,
+        key_hash: &str,
+        new: &NewApiKey,
+    ) -> Result<(), Error> {
+        let scopes: Vec<&str> = new.scopes.iter().map(|scope| scope.as_str()).collect();
+        let rate_limit = new
+            .rate_limit
+            .map(|limit| limit.min(i32::MAX as u32) as i32);
+        self.client
+            .execute(
+                "INSERT INTO api_keys (id, key_hash, email, name, scopes, rate_limit)
+                 VALUES ($1, $2, $3, $4, $5, $6);",
+                &[&id, &key_hash, &new.email, &new.name, &scopes, &rate_limit],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// 校验未吊销的 Key，Key 不存在或摘要不符时返回 `None`。
+    /// 只在校验通过时记录使用时间，且每分钟至多写一次。
+    pub async fn authenticate_api_key(
+        &self,
+        id: &str,
+        key_hash: &str,
+    ) -> Result<Option<Principal>, Error> {
+        let row = self
+            .client
+            .query_opt(
+                "SELECT email, scopes, rate_limit,
+                        last_used_at IS NULL OR last_used_at < now() - interval '1 minute' AS stale
+                 FROM api_keys
+                 WHERE id = $1 AND key_hash = $2 AND revoked_at IS NULL;",
+                &[&id, &key_hash],
+            )
+            .await?;
+        if row.as_ref().is_some_and(|row| row.get::<_, bool>("stale")) {
+            self.client
+                .execute(
+                    "UPDATE api_keys SET last_used_at = now() WHERE id = $1;",
+                    &[&id],
+                )
+                .await?;
+        }
+        Ok(row.map(|row| Principal {
+            key_id: id.to_string(),
+            email: row.get("email"),
+            scopes: scopes(row.get("scopes")),
+            rate_limit: row
+                .get::<_, Option<i32>>("rate_limit")
+                .map(|limit| limit.max(0) as u32),
+        }))
+    }
+
+    /// 列出 API Key，`email` 为空时列出全部。
+    pub async fn list_api_keys(&self, email: Option<&str>) -> Result<Vec<ApiKeyInfo>, Error> {
+        let rows = self
+            .client
+            .query(
+                "SELECT id, email, name, scopes, rate_limit, created_at, last_used_at, revoked_at
+                 FROM api_keys WHERE $1::text IS NULL OR email = $1
+                 ORDER BY created_at;",
+                &[&email],
+            )
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| ApiKeyInfo {
+                id: row.get("id"),
+                email: row.get("email"),
+                name: row.get("name"),
+                scopes: scopes(row.get("scopes")),
+                rate_limit: row
+                    .get::<_, Option<i32>>("rate_limit")
+                    .map(|limit| limit.max(0) as u32),
+                created_at: row.get("created_at"),
+                last_used_at: row.get("last_used_at"),
+                revoked_at: row.get("revoked_at"),
+            })
+            .collect())
+    }
+
+    /// 吊销 Key，Key 不存在或已吊销时返回 `false`。
+    pub async fn revoke_api_key(&self, id: &str) -> Result<bool, Error> {
+        let revoked = self
+            .client
+            .execute(
+                "UPDATE api_keys SET revoked_at = now() WHERE id = $1 AND revoked_at IS NULL;",
+                &[&id],
+            )
+            .await?;
+        Ok(revoked > 0)
+    }
+}
+
+/// 数据库中的任务状态，无法识别的值视为 failed
+fn job_state(value: &str) -> JobState {
+    JobState::parse(value).unwrap_or(JobState::Failed)
+}
+
+/// 数据库中的 Key 权限，忽略无法识别的值
+fn scopes(values: Vec<String>) -> Vec<Scope> {
+    values
+        .iter()
+        .filter_map(|value| value.parse().ok())
+        .collect()
 }
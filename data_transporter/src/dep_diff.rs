@@ -0,0 +1,411 @@
+//! 同一 crate 两个版本之间的依赖树对比
+//!
+//! 依赖按 crate 名称对比：只在新版本树中出现的为新增，只在旧版本树中出现的为
+//! 移除，两边都有但版本集合不同的为版本变化。漏洞按 RustSec ID 对比，包括
+//! crate 自身的漏洞。许可证表按程序名称记录，因此许可证变化体现为依赖树中
+//! 新出现或不再出现的许可证。
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::dep_list::Relation;
+use crate::error::ApiError;
+use crate::handler::NewRustsec;
+
+/// 依赖对比的查询参数
+#[derive(Deserialize, Debug, Default, Clone, ToSchema)]
+pub struct DiffQuery {
+    /// 旧版本号
+    pub from: Option<String>,
+    /// 新版本号
+    pub to: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffParams {
+    pub from: String,
+    pub to: String,
+}
+
+impl TryFrom<DiffQuery> for DiffParams {
+    type Error = ApiError;
+
+    fn try_from(q: DiffQuery) -> Result<Self, ApiError> {
+        let required = |v: Option<String>, name: &str| {
+            v.map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+                .ok_or_else(|| ApiError::BadRequest(format!("`{}` version is required", name)))
+        };
+        Ok(DiffParams {
+            from: required(q.from, "from")?,
+            to: required(q.to, "to")?,
+        })
+    }
+}
+
+/// 一个版本的依赖树，节点均为 `name/version`
+#[derive(Debug, Default, Clone)]
+pub struct TreeSnapshot {
+    pub version: String,
+    pub direct: HashSet<String>,
+    /// 全部直接和间接依赖，不含自身
+    pub all: HashSet<String>,
+    /// 依赖的许可证，`name -> license`
+    pub licenses: HashMap<String, String>,
+    /// 依赖及自身的未修复漏洞，`name/version -> advisories`
+    pub advisories: HashMap<String, Vec<NewRustsec>>,
+    /// 依赖树过大、遍历被截断
+    pub truncated: bool,
+}
+
+impl TreeSnapshot {
+    /// `name -> versions`
+    fn versions_by_name(&self) -> BTreeMap<&str, BTreeSet<&str>> {
+        let mut map: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        for id in self.all.iter().chain(&self.direct) {
+            if let Some((name, version)) = id.split_once('/') {
+                map.entry(name).or_default().insert(version);
+            }
+        }
+        map
+    }
+
+    fn relation(&self, name: &str) -> Relation {
+        let is_direct = self
+            .direct
+            .iter()
+            .any(|id| id.split_once('/').is_some_and(|(n, _)| n == name));
+        if is_direct {
+            Relation::Direct
+        } else {
+            Relation::Indirect
+        }
+    }
+}
+
+/// 一个依赖的变化
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct DependencyChange {
+    pub name: String,
+    /// 新增和版本变化取新版本树中的关系，移除取旧版本树中的关系
+    pub relation: Relation,
+    /// 旧版本树中的版本，新增时为空
+    pub from_versions: Vec<String>,
+    /// 新版本树中的版本，移除时为空
+    pub to_versions: Vec<String>,
+}
+
+/// 新引入或已修复的漏洞
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct AdvisoryChange {
+    pub id: String,
+    pub title: String,
+    pub url: String,
+    /// 受影响的 `name/version`
+    pub crates: Vec<String>,
+}
+
+/// 新出现或不再出现的许可证
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct LicenseChange {
+    pub license: String,
+    /// 使用该许可证的依赖
+    pub crates: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct DependencyDiff {
+    pub name: String,
+    pub from: String,
+    pub to: String,
+    /// 可读的变化摘要
+    pub summary: String,
+    pub added: Vec<DependencyChange>,
+    pub removed: Vec<DependencyChange>,
+    pub changed: Vec<DependencyChange>,
+    pub advisories_introduced: Vec<AdvisoryChange>,
+    pub advisories_fixed: Vec<AdvisoryChange>,
+    pub licenses_added: Vec<LicenseChange>,
+    pub licenses_removed: Vec<LicenseChange>,
+    /// 任一版本的依赖树被截断，此时变化列表可能不完整
+    pub truncated: bool,
+}
+
+/// 对比同一 crate `from` 和 `to` 两个版本的依赖树
+pub fn diff_trees(name: &str, from: &TreeSnapshot, to: &TreeSnapshot) -> DependencyDiff {
+    let old = from.versions_by_name();
+    let new = to.versions_by_name();
+    let list = |set: Option<&BTreeSet<&str>>| -> Vec<String> {
+        set.map(|s| s.iter().map(|v| v.to_string()).collect())
+            .unwrap_or_default()
+    };
+
+    let (mut added, mut removed, mut changed) = (vec![], vec![], vec![]);
+    let names: BTreeSet<&str> = old.keys().chain(new.keys()).copied().collect();
+    for dep in names {
+        let (o, n) = (old.get(dep), new.get(dep));
+        let change = |relation| DependencyChange {
+            name: dep.to_string(),
+            relation,
+            from_versions: list(o),
+            to_versions: list(n),
+        };
+        match (o, n) {
+            (None, Some(_)) => added.push(change(to.relation(dep))),
+            (Some(_), None) => removed.push(change(from.relation(dep))),
+            (Some(o), Some(n)) if o != n => changed.push(change(to.relation(dep))),
+            _ => {}
+        }
+    }
+
+    let old_advisories = advisories_by_id(from);
+    let new_advisories = advisories_by_id(to);
+    let only_in = |a: &BTreeMap<String, AdvisoryChange>, b: &BTreeMap<String, AdvisoryChange>| {
+        a.iter()
+            .filter(|(id, _)| !b.contains_key(*id))
+            .map(|(_, c)| c.clone())
+            .collect::<Vec<_>>()
+    };
+    let advisories_introduced = only_in(&new_advisories, &old_advisories);
+    let advisories_fixed = only_in(&old_advisories, &new_advisories);
+
+    let old_licenses = licenses_in(from);
+    let new_licenses = licenses_in(to);
+    let only_in = |a: &BTreeMap<String, BTreeSet<String>>,
+                   b: &BTreeMap<String, BTreeSet<String>>| {
+        a.iter()
+            .filter(|(license, _)| !b.contains_key(*license))
+            .map(|(license, crates)| LicenseChange {
+                license: license.clone(),
+                crates: crates.iter().cloned().collect(),
+            })
+            .collect::<Vec<_>>()
+    };
+    let licenses_added = only_in(&new_licenses, &old_licenses);
+    let licenses_removed = only_in(&old_licenses, &new_licenses);
+
+    let mut diff = DependencyDiff {
+        name: name.to_string(),
+        from: from.version.clone(),
+        to: to.version.clone(),
+        summary: String::new(),
+        added,
+        removed,
+        changed,
+        advisories_introduced,
+        advisories_fixed,
+        licenses_added,
+        licenses_removed,
+        truncated: from.truncated || to.truncated,
+    };
+    diff.summary = summarize(&diff);
+    diff
+}
+
+fn advisories_by_id(tree: &TreeSnapshot) -> BTreeMap<String, AdvisoryChange> {
+    let mut map: BTreeMap<String, AdvisoryChange> = BTreeMap::new();
+    for (id, advisories) in &tree.advisories {
+        for a in advisories {
+            let change = map.entry(a.id.clone()).or_insert_with(|| AdvisoryChange {
+                id: a.id.clone(),
+                title: a.subtitle.clone(),
+                url: a.url.clone(),
+                crates: vec![],
+            });
+            change.crates.push(id.clone());
+        }
+    }
+    for change in map.values_mut() {
+        change.crates.sort();
+        change.crates.dedup();
+    }
+    map
+}
+
+/// `license -> crate names`
+fn licenses_in(tree: &TreeSnapshot) -> BTreeMap<String, BTreeSet<String>> {
+    let mut map: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    for (name, _) in tree.versions_by_name() {
+        if let Some(license) = tree.licenses.get(name).filter(|l| !l.is_empty()) {
+            map.entry(license.clone())
+                .or_default()
+                .insert(name.to_string());
+        }
+    }
+    map
+}
+
+fn count_direct(changes: &[DependencyChange]) -> usize {
+    changes
+        .iter()
+        .filter(|c| c.relation == Relation::Direct)
+        .count()
+}
+
+fn summarize(diff: &DependencyDiff) -> String {
+    let mut parts = vec![];
+    for (label, changes) in [
+        ("added", &diff.added),
+        ("removed", &diff.removed),
+        ("changed version", &diff.changed),
+    ] {
+        if !changes.is_empty() {
+            parts.push(format!(
+                "{} {} ({} direct)",
+                changes.len(),
+                label,
+                count_direct(changes)
+            ));
+        }
+    }
+    let ids = |changes: &[AdvisoryChange]| {
+        changes
+            .iter()
+            .map(|c| c.id.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    if !diff.advisories_introduced.is_empty() {
+        parts.push(format!(
+            "{} advisories introduced: {}",
+            diff.advisories_introduced.len(),
+            ids(&diff.advisories_introduced)
+        ));
+    }
+    if !diff.advisories_fixed.is_empty() {
+        parts.push(format!(
+            "{} advisories fixed: {}",
+            diff.advisories_fixed.len(),
+            ids(&diff.advisories_fixed)
+        ));
+    }
+    let licenses = |changes: &[LicenseChange]| {
+        changes
+            .iter()
+            .map(|c| c.license.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    if !diff.licenses_added.is_empty() {
+        parts.push(format!("new licenses: {}", licenses(&diff.licenses_added)));
+    }
+    if !diff.licenses_removed.is_empty() {
+        parts.push(format!(
+            "licenses no longer used: {}",
+            licenses(&diff.licenses_removed)
+        ));
+    }
+    let mut changes = if parts.is_empty() {
+        "no dependency changes".to_string()
+    } else {
+        parts.join("; ")
+    };
+    if diff.truncated {
+        changes.push_str(" (dependency tree truncated, the diff may be incomplete)");
+    }
+    format!("{} {} -> {}: {}", diff.name, diff.from, diff.to, changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(ids: &[&str]) -> HashSet<String> {
+        ids.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn advisory(id: &str) -> NewRustsec {
+        NewRustsec {
+            id: id.to_string(),
+            subtitle: format!("{} title", id),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_diff_trees() {
+        let licenses = HashMap::from([
+            ("serde".to_string(), "MIT OR Apache-2.0".to_string()),
+            ("time".to_string(), "MIT/Apache-2.0".to_string()),
+            ("ring".to_string(), "ISC".to_string()),
+            ("libc".to_string(), "MIT OR Apache-2.0".to_string()),
+        ]);
+        let from = TreeSnapshot {
+            version: "0.1.0".to_string(),
+            direct: set(&["serde/1.0.100", "time/0.1.43"]),
+            all: set(&["serde/1.0.100", "time/0.1.43", "libc/0.2.150"]),
+            licenses: licenses.clone(),
+            advisories: HashMap::from([
+                (
+                    "time/0.1.43".to_string(),
+                    vec![advisory("RUSTSEC-2020-0071")],
+                ),
+                ("app/0.1.0".to_string(), vec![advisory("RUSTSEC-2099-0001")]),
+            ]),
+            truncated: false,
+        };
+        let to = TreeSnapshot {
+            version: "0.2.0".to_string(),
+            direct: set(&["serde/1.0.200", "ring/0.16.20"]),
+            all: set(&["serde/1.0.200", "ring/0.16.20", "libc/0.2.150"]),
+            licenses,
+            advisories: HashMap::from([(
+                "ring/0.16.20".to_string(),
+                vec![advisory("RUSTSEC-2025-0009")],
+            )]),
+            truncated: false,
+        };
+        let diff = diff_trees("app", &from, &to);
+        let names = |c: &[DependencyChange]| c.iter().map(|c| c.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&diff.added), ["ring"]);
+        assert_eq!(names(&diff.removed), ["time"]);
+        assert_eq!(names(&diff.changed), ["serde"]);
+        assert_eq!(diff.changed[0].from_versions, ["1.0.100"]);
+        assert_eq!(diff.changed[0].to_versions, ["1.0.200"]);
+        assert_eq!(diff.removed[0].relation, Relation::Direct);
+
+        assert_eq!(diff.advisories_introduced.len(), 1);
+        assert_eq!(diff.advisories_introduced[0].crates, ["ring/0.16.20"]);
+        let fixed: Vec<&str> = diff
+            .advisories_fixed
+            .iter()
+            .map(|a| a.id.as_str())
+            .collect();
+        assert_eq!(fixed, ["RUSTSEC-2020-0071", "RUSTSEC-2099-0001"]);
+
+        assert_eq!(diff.licenses_added[0].license, "ISC");
+        assert_eq!(diff.licenses_removed[0].license, "MIT/Apache-2.0");
+        assert_eq!(diff.licenses_removed[0].crates, ["time"]);
+        assert_eq!(
+            diff.summary,
+            "app 0.1.0 -> 0.2.0: 1 added (1 direct); 1 removed (1 direct); \
+             1 changed version (1 direct); 1 advisories introduced: RUSTSEC-2025-0009; \
+             2 advisories fixed: RUSTSEC-2020-0071, RUSTSEC-2099-0001; new licenses: ISC; \
+             licenses no longer used: MIT/Apache-2.0"
+        );
+
+        let same = diff_trees("app", &from, &from);
+        assert!(same.added.is_empty() && same.changed.is_empty());
+        assert_eq!(same.summary, "app 0.1.0 -> 0.1.0: no dependency changes");
+
+        let truncated = TreeSnapshot {
+            truncated: true,
+            ..from.clone()
+        };
+        let partial = diff_trees("app", &from, &truncated);
+        assert!(partial.truncated);
+        assert_eq!(
+            partial.summary,
+            "app 0.1.0 -> 0.1.0: no dependency changes \
+             (dependency tree truncated, the diff may be incomplete)"
+        );
+
+        assert!(DiffParams::try_from(DiffQuery {
+            from: Some("0.1.0".to_string()),
+            to: None,
+        })
+        .is_err());
+    }
+}
@@ -0,0 +1,317 @@
+//! 按依赖边属性过滤
+//!
+//! `depends_on` 边上记录了依赖类型（normal/dev/build）、是否可选、是否由默认
+//! feature 启用以及 `[target.'cfg(..)'.dependencies]` 中的 target。这里把查询参数
+//! 解析为 [`DependencyFilter`]，并对 target 做简单的 `cfg(..)` 求值。
+
+use std::collections::HashSet;
+
+use model::tugraph_model::DependencyKind;
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use crate::{dep_graph::GraphEdge, error::ApiError};
+
+/// 依赖过滤的查询参数
+#[derive(Deserialize, Debug, Default, Clone, ToSchema)]
+pub struct DependencyFilterQuery {
+    /// 逗号分隔的依赖类型，可选 `normal`、`dev`、`build`，默认全部
+    pub kind: Option<String>,
+    /// `default` 只保留默认 feature 下启用的依赖，`all` 保留全部，默认 `all`
+    pub features: Option<String>,
+    /// 目标平台的 `target_os`，如 `linux`、`windows`、`macos`，默认不过滤
+    pub target_os: Option<String>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct DependencyFilter {
+    kinds: Option<HashSet<DependencyKind>>,
+    default_features_only: bool,
+    target_os: Option<String>,
+}
+
+impl TryFrom<DependencyFilterQuery> for DependencyFilter {
+    type Error = ApiError;
+
+    fn try_from(q: DependencyFilterQuery) -> Result<Self, ApiError> {
+        let kinds = q
+            .kind
+            .map(|kinds| {
+                kinds
+                    .split(',')
+                    .map(|k| k.trim().parse::<DependencyKind>())
+                    .collect::<Result<HashSet<_>, _>>()
+            })
+            .transpose()
+            .map_err(ApiError::BadRequest)?;
+        let default_features_only = match q.features.as_deref() {
+            None | Some("all") => false,
+            Some("default") => true,
+            Some(other) => {
+                return Err(ApiError::BadRequest(format!(
+                    "features must be `default` or `all`, got `{}`",
+                    other
+                )))
+            }
+        };
+        Ok(DependencyFilter {
+            kinds,
+            default_features_only,
+            target_os: q.target_os.map(|os| os.to_lowercase()),
+        })
+    }
+}
+
+impl DependencyFilter {
+    /// 判断一条依赖边是否保留。dev 依赖只对 `root` 自身生效，不会传递。
+    pub fn matches(&self, edge: &GraphEdge, root: &str) -> bool {
+        if edge.kind == DependencyKind::Dev && edge.from != root {
+            return false;
+        }
+        if let Some(kinds) = &self.kinds {
+            if !kinds.contains(&edge.kind) {
+                return false;
+            }
+        }
+        if self.default_features_only && !edge.enabled_by_default {
+            return false;
+        }
+        match (&self.target_os, &edge.target) {
+            (Some(os), Some(target)) => target_matches_os(target, os),
+            _ => true,
+        }
+    }
+}
+
+/// `target` 是否可能作用于 `target_os`。无法判断的谓词（如 `target_arch`）
+/// 视为可能成立，避免漏掉依赖。
+pub fn target_matches_os(target: &str, os: &str) -> bool {
+    let target = target.trim();
+    match target.strip_prefix("cfg(") {
+        Some(expr) => expr
+            .strip_suffix(')')
+            .and_then(|expr| CfgParser::new(expr).parse())
+            .map(|cfg| cfg.eval(os) != Some(false))
+            .unwrap_or(true),
+        None => {
+            // target triple，如 x86_64-unknown-linux-gnu
+            let os = if os == "macos" { "darwin" } else { os };
+            target.split('-').any(|part| part.starts_with(os))
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Cfg {
+    Name(String),
+    KeyValue(String, String),
+    All(Vec<Cfg>),
+    Any(Vec<Cfg>),
+    Not(Box<Cfg>),
+}
+
+impl Cfg {
+    /// 三值求值，`None` 表示与 `target_os` 无关、无法判断
+    fn eval(&self, os: &str) -> Option<bool> {
+        match self {
+            Cfg::Name(name) => match name.as_str() {
+                "unix" | "windows" => Some(target_family(os) == Some(name.as_str())),
+                _ => None,
+            },
+            Cfg::KeyValue(key, value) => match key.as_str() {
+                "target_os" => Some(value == os),
+                "target_family" => Some(target_family(os) == Some(value.as_str())),
+                _ => None,
+            },
+            Cfg::All(cfgs) => {
+                let results: Vec<_> = cfgs.iter().map(|c| c.eval(os)).collect();
+                if results.contains(&Some(false)) {
+                    Some(false)
+                } else if results.contains(&None) {
+                    None
+                } else {
+                    Some(true)
+                }
+            }
+            Cfg::Any(cfgs) => {
+                let results: Vec<_> = cfgs.iter().map(|c| c.eval(os)).collect();
+                if results.contains(&Some(true)) {
+                    Some(true)
+                } else if results.contains(&None) {
+                    None
+                } else {
+                    Some(false)
+                }
+            }
+            Cfg::Not(cfg) => cfg.eval(os).map(|b| !b),
+        }
+    }
+}
+
+fn target_family(os: &str) -> Option<&'static str> {
+    match os {
+        "windows" => Some("windows"),
+        "linux" | "macos" | "ios" | "android" | "freebsd" | "netbsd" | "openbsd" | "dragonfly"
+        | "solaris" | "illumos" | "haiku" => Some("unix"),
+        _ => None,
+    }
+}
+
+struct CfgParser<'a> {
+    rest: &'a str,
+}
+
+impl<'a> CfgParser<'a> {
+    fn new(expr: &'a str) -> Self {
+        CfgParser { rest: expr }
+    }
+
+    fn parse(mut self) -> Option<Cfg> {
+        let cfg = self.predicate()?;
+        self.skip_ws();
+        self.rest.is_empty().then_some(cfg)
+    }
+
+    fn skip_ws(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_ws();
+        match self.rest.strip_prefix(c) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn ident(&mut self) -> Option<String> {
+        self.skip_ws();
+        let end = self
+            .rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(self.rest.len());
+        if end == 0 {
+            return None;
+        }
+        let (ident, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        Some(ident.to_string())
+    }
+
+    fn string(&mut self) -> Option<String> {
+        self.skip_ws();
+        let rest = self.rest.strip_prefix('"')?;
+        let end = rest.find('"')?;
+        self.rest = &rest[end + 1..];
+        Some(rest[..end].to_string())
+    }
+
+    fn list(&mut self) -> Option<Vec<Cfg>> {
+        if !self.eat('(') {
+            return None;
+        }
+        let mut cfgs = vec![];
+        loop {
+            if self.eat(')') {
+                return Some(cfgs);
+            }
+            cfgs.push(self.predicate()?);
+            if !self.eat(',') {
+                return self.eat(')').then_some(cfgs);
+            }
+        }
+    }
+
+    fn predicate(&mut self) -> Option<Cfg> {
+        let ident = self.ident()?;
+        match ident.as_str() {
+            "all" => self.list().map(Cfg::All),
+            "any" => self.list().map(Cfg::Any),
+            "not" => {
+                let mut cfgs = self.list()?;
+                (cfgs.len() == 1).then(|| Cfg::Not(Box::new(cfgs.remove(0))))
+            }
+            _ if self.eat('=') => Some(Cfg::KeyValue(ident, self.string()?)),
+            _ => Some(Cfg::Name(ident)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_matches_os() {
+        assert!(target_matches_os("cfg(unix)", "linux"));
+        assert!(!target_matches_os("cfg(unix)", "windows"));
+        assert!(target_matches_os("cfg(not(windows))", "macos"));
+        assert!(target_matches_os(
+            "cfg(any(target_os = \"linux\", target_os = \"android\"))",
+            "linux"
+        ));
+        assert!(!target_matches_os(
+            "cfg(all(unix, not(target_os = \"linux\")))",
+            "linux"
+        ));
+        // 与操作系统无关的谓词无法判断，保留
+        assert!(target_matches_os("cfg(target_arch = \"wasm32\")", "linux"));
+        assert!(target_matches_os(
+            "cfg(all(windows, target_env = \"msvc\"))",
+            "windows"
+        ));
+        assert!(!target_matches_os(
+            "cfg(all(windows, target_env = \"msvc\"))",
+            "linux"
+        ));
+        // target triple
+        assert!(target_matches_os("x86_64-unknown-linux-gnu", "linux"));
+        assert!(target_matches_os("aarch64-apple-darwin", "macos"));
+        assert!(!target_matches_os("x86_64-pc-windows-msvc", "linux"));
+        // 无法解析时保留
+        assert!(target_matches_os("cfg(unix", "windows"));
+    }
+
+    #[test]
+    fn test_dependency_filter() {
+        let filter = DependencyFilter::try_from(DependencyFilterQuery {
+            kind: Some("normal".to_string()),
+            features: Some("default".to_string()),
+            target_os: Some("linux".to_string()),
+        })
+        .unwrap();
+        let edge = |from: &str, kind, enabled_by_default, target: Option<&str>| GraphEdge {
+            from: from.to_string(),
+            to: "dep/1.0.0".to_string(),
+            kind,
+            enabled_by_default,
+            target: target.map(str::to_string),
+            ..Default::default()
+        };
+        assert!(filter.matches(&edge("a/1", DependencyKind::Normal, true, None), "a/1"));
+        assert!(filter.matches(
+            &edge("b/1", DependencyKind::Normal, true, Some("cfg(unix)")),
+            "a/1"
+        ));
+        assert!(!filter.matches(
+            &edge("a/1", DependencyKind::Normal, true, Some("cfg(windows)")),
+            "a/1"
+        ));
+        assert!(!filter.matches(&edge("a/1", DependencyKind::Normal, false, None), "a/1"));
+        assert!(!filter.matches(&edge("a/1", DependencyKind::Build, true, None), "a/1"));
+
+        // dev 依赖只在根节点生效
+        let all = DependencyFilter::default();
+        assert!(all.matches(&edge("a/1", DependencyKind::Dev, true, None), "a/1"));
+        assert!(!all.matches(&edge("b/1", DependencyKind::Dev, true, None), "a/1"));
+
+        assert!(DependencyFilter::try_from(DependencyFilterQuery {
+            kind: Some("runtime".to_string()),
+            ..Default::default()
+        })
+        .is_err());
+    }
+}
@@ -0,0 +1,273 @@
+//! 有界的依赖图构建
+//!
+//! 按层做广度优先遍历，每一层的直接依赖通过一次批量查询取回。返回结果是
+//! 真实的 DAG：共享依赖只出现一次，已收录节点之间的边全部保留。遍历受
+//! `max_depth` 和 `max_nodes` 限制，被截断时 `truncated` 为 `true`。
+
+use std::collections::{BTreeSet, HashMap};
+use std::future::Future;
+
+use model::tugraph_model::{DependencyKind, DependencySource};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::error::ApiError;
+
+pub const DEFAULT_MAX_DEPTH: usize = 10;
+pub const DEFAULT_MAX_NODES: usize = 500;
+pub const MAX_DEPTH_LIMIT: usize = 50;
+pub const MAX_NODES_LIMIT: usize = 5000;
+
+/// 依赖图的来源
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Resolution {
+    /// 按 Cargo.toml 中声明的版本要求解析出的 `depends_on` 边
+    #[default]
+    Declared,
+    /// 应用版本的 Cargo.lock 中实际锁定的 `locked_depends_on` 边
+    Locked,
+}
+
+/// 依赖图接口的查询参数
+#[derive(Deserialize, Debug, Default, Clone, ToSchema)]
+pub struct GraphQuery {
+    /// 最大遍历深度，根节点深度为 0，默认 10，最大 50
+    pub max_depth: Option<usize>,
+    /// 最多返回的节点数（含根节点），默认 500，最大 5000
+    pub max_nodes: Option<usize>,
+    /// `declared`（默认）或 `locked`
+    pub resolution: Option<Resolution>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GraphLimits {
+    pub max_depth: usize,
+    pub max_nodes: usize,
+}
+
+impl TryFrom<GraphQuery> for GraphLimits {
+    type Error = ApiError;
+
+    fn try_from(q: GraphQuery) -> Result<Self, ApiError> {
+        let max_depth = q.max_depth.unwrap_or(DEFAULT_MAX_DEPTH);
+        let max_nodes = q.max_nodes.unwrap_or(DEFAULT_MAX_NODES);
+        if max_depth > MAX_DEPTH_LIMIT {
+            return Err(ApiError::BadRequest(format!(
+                "max_depth must be <= {}",
+                MAX_DEPTH_LIMIT
+            )));
+        }
+        if max_nodes == 0 || max_nodes > MAX_NODES_LIMIT {
+            return Err(ApiError::BadRequest(format!(
+                "max_nodes must be between 1 and {}",
+                MAX_NODES_LIMIT
+            )));
+        }
+        Ok(GraphLimits {
+            max_depth,
+            max_nodes,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct GraphNode {
+    /// `name/version`
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    /// 距根节点的最短层数
+    pub depth: usize,
+    pub cve_count: usize,
+}
+
+/// 一条 `depends_on` 边，同一对节点之间可能有多条属性不同的边
+#[derive(
+    Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, ToSchema,
+)]
+pub struct GraphEdge {
+    pub from: String,
+    pub to: String,
+    pub kind: DependencyKind,
+    pub optional: bool,
+    pub default_features: bool,
+    pub features: Vec<String>,
+    /// `cfg(..)` 表达式或 target triple，`None` 表示对所有平台生效
+    pub target: Option<String>,
+    /// 是否由依赖方的默认 feature 启用
+    pub enabled_by_default: bool,
+    /// registry、git 或 path
+    pub source: DependencySource,
+    /// 声明的版本要求，旧数据和 locked 图中为空
+    pub version_req: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct DependencyGraph {
+    pub root: String,
+    pub resolution: Resolution,
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+    pub max_depth: usize,
+    pub max_nodes: usize,
+    /// 是否因深度或节点数限制而有依赖未被收录
+    pub truncated: bool,
+}
+
+impl DependencyGraph {
+    fn new_node(id: &str, depth: usize) -> GraphNode {
+        let (name, version) = id.split_once('/').unwrap_or((id, ""));
+        GraphNode {
+            id: id.to_string(),
+            name: name.to_string(),
+            version: version.to_string(),
+            depth,
+            cve_count: 0,
+        }
+    }
+}
+
+/// 从 `root`（`name/version`）出发逐层构建依赖图。
+///
+/// `fetch_level` 接收一层的全部节点，返回这些节点出发的边，过滤在其中完成。
+/// 每个收录的节点恰好被查询一次；最深一层也会查询，以便保留指向已收录
+/// 节点的边并判断是否被截断。
+pub async fn build_dependency_graph<F, Fut, E>(
+    root: &str,
+    limits: GraphLimits,
+    mut fetch_level: F,
+) -> Result<DependencyGraph, E>
+where
+    F: FnMut(Vec<String>) -> Fut,
+    Fut: Future<Output = Result<Vec<GraphEdge>, E>>,
+{
+    let mut index: HashMap<String, usize> = HashMap::new();
+    let mut nodes = vec![DependencyGraph::new_node(root, 0)];
+    index.insert(root.to_string(), 0);
+    let mut edges = BTreeSet::new();
+    let mut truncated = false;
+    let mut frontier = vec![root.to_string()];
+
+    for depth in 0..=limits.max_depth {
+        if frontier.is_empty() {
+            break;
+        }
+        // 排序后再处理，保证节点数受限时结果稳定
+        let level: BTreeSet<GraphEdge> = fetch_level(frontier).await?.into_iter().collect();
+        let mut next = Vec::new();
+        for edge in level {
+            if !index.contains_key(&edge.to) {
+                if depth == limits.max_depth || nodes.len() >= limits.max_nodes {
+                    truncated = true;
+                    continue;
+                }
+                index.insert(edge.to.clone(), nodes.len());
+                nodes.push(DependencyGraph::new_node(&edge.to, depth + 1));
+                next.push(edge.to.clone());
+            }
+            edges.insert(edge);
+        }
+        frontier = next;
+    }
+
+    Ok(DependencyGraph {
+        root: root.to_string(),
+        resolution: Resolution::default(),
+        nodes,
+        edges: edges.into_iter().collect(),
+        max_depth: limits.max_depth,
+        max_nodes: limits.max_nodes,
+        truncated,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> HashMap<&'static str, Vec<&'static str>> {
+        // a -> b, c; b -> d; c -> d; d -> e
+        HashMap::from([
+            ("a/1", vec!["b/1", "c/1"]),
+            ("b/1", vec!["d/1"]),
+            ("c/1", vec!["d/1"]),
+            ("d/1", vec!["e/1"]),
+        ])
+    }
+
+    async fn build(limits: GraphLimits) -> (DependencyGraph, usize) {
+        let adj = fixture();
+        let mut calls = 0;
+        let graph = build_dependency_graph("a/1", limits, |level| {
+            calls += 1;
+            let edges: Vec<GraphEdge> = level
+                .iter()
+                .flat_map(|from| {
+                    adj.get(from.as_str())
+                        .into_iter()
+                        .flatten()
+                        .map(move |to| GraphEdge {
+                            from: from.clone(),
+                            to: to.to_string(),
+                            ..Default::default()
+                        })
+                })
+                .collect();
+            async move { Ok::<_, ()>(edges) }
+        })
+        .await
+        .unwrap();
+        (graph, calls)
+    }
+
+    fn ids(graph: &DependencyGraph) -> Vec<&str> {
+        graph.nodes.iter().map(|n| n.id.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_build_dependency_graph_limits() {
+        // 共享依赖 d 只出现一次，b->d 和 c->d 两条边都保留；每层一次查询
+        let (graph, calls) = build(GraphLimits {
+            max_depth: 10,
+            max_nodes: 100,
+        })
+        .await;
+        assert_eq!(ids(&graph), ["a/1", "b/1", "c/1", "d/1", "e/1"]);
+        assert_eq!(graph.edges.len(), 5);
+        assert_eq!(graph.nodes[3].depth, 2);
+        assert!(!graph.truncated);
+        assert_eq!(calls, 4);
+
+        let (graph, _) = build(GraphLimits {
+            max_depth: 2,
+            max_nodes: 100,
+        })
+        .await;
+        assert_eq!(ids(&graph), ["a/1", "b/1", "c/1", "d/1"]);
+        assert_eq!(graph.edges.len(), 4);
+        assert!(graph.truncated);
+
+        let (graph, _) = build(GraphLimits {
+            max_depth: 10,
+            max_nodes: 2,
+        })
+        .await;
+        assert_eq!(ids(&graph), ["a/1", "b/1"]);
+        assert_eq!(
+            graph.edges,
+            [GraphEdge {
+                from: "a/1".to_string(),
+                to: "b/1".to_string(),
+                ..Default::default()
+            }]
+        );
+        assert!(graph.truncated);
+
+        assert!(GraphLimits::try_from(GraphQuery {
+            max_nodes: Some(0),
+            ..Default::default()
+        })
+        .is_err());
+    }
+}
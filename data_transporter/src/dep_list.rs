@@ -0,0 +1,360 @@
+//! 依赖 / 被依赖列表的分页、排序与过滤
+//!
+//! `/dependencies` 和 `/dependents` 先取出完整列表（可缓存），再按查询参数过滤、
+//! 排序并截取一页。翻页既可以用 `page`，也可以用上一页返回的 `next_cursor`；
+//! 游标是上一页最后一项的 `name/version`，下一页从它之后开始。
+
+use std::cmp::Ordering;
+
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::error::ApiError;
+
+pub const DEFAULT_PER_PAGE: usize = 20;
+pub const MAX_PER_PAGE: usize = 100;
+
+/// 排序方式
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DepSort {
+    /// 按名称升序，同名按版本从高到低
+    #[default]
+    Name,
+    /// 按下载量降序
+    Downloads,
+    /// 按直接被依赖数降序
+    Dependents,
+}
+
+/// 直接或间接依赖关系
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Relation {
+    Direct,
+    Indirect,
+}
+
+impl Relation {
+    /// 列表项中 `relation` 字段使用的名称
+    pub fn label(self) -> &'static str {
+        match self {
+            Relation::Direct => "Direct",
+            Relation::Indirect => "Indirect",
+        }
+    }
+}
+
+/// 依赖列表接口的查询参数
+#[derive(Deserialize, Debug, Default, Clone, ToSchema)]
+pub struct DepListQuery {
+    /// 页码，从 1 开始，默认 1；给出 `cursor` 时忽略
+    pub page: Option<usize>,
+    /// 每页条数，默认 20，最大 100
+    pub per_page: Option<usize>,
+    /// `name`（默认）、`downloads` 或 `dependents`
+    pub sort: Option<DepSort>,
+    /// 只返回 `direct` 或 `indirect` 的项
+    pub relation: Option<Relation>,
+    /// `true` 只返回有 RustSec 漏洞的项，`false` 只返回没有漏洞的项
+    pub has_advisory: Option<bool>,
+    /// 许可证，匹配许可证表达式中的任一标识符，不区分大小写
+    pub license: Option<String>,
+    /// 上一页返回的 `next_cursor`
+    pub cursor: Option<String>,
+}
+
+/// 列表项中参与过滤和排序的字段
+#[derive(Debug, Clone, Copy)]
+pub struct DepListKey<'a> {
+    pub name: &'a str,
+    pub version: &'a str,
+    pub relation: &'a str,
+    pub license: &'a str,
+    pub downloads: i64,
+    pub dependents: usize,
+    pub advisories: usize,
+}
+
+pub trait DepListEntry {
+    fn key(&self) -> DepListKey<'_>;
+}
+
+/// 列表项的统计信息，由 `DataReader::get_dep_list_stats` 批量查询
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DepStats {
+    pub license: String,
+    pub downloads: i64,
+    pub dependents: usize,
+    pub advisories: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepPage<T> {
+    /// 过滤后的总条数
+    pub total: usize,
+    pub page: usize,
+    pub per_page: usize,
+    pub next_cursor: Option<String>,
+    pub data: Vec<T>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DepListParams {
+    page: usize,
+    per_page: usize,
+    sort: DepSort,
+    relation: Option<Relation>,
+    has_advisory: Option<bool>,
+    license: Option<String>,
+    cursor: Option<String>,
+}
+
+impl TryFrom<DepListQuery> for DepListParams {
+    type Error = ApiError;
+
+    fn try_from(q: DepListQuery) -> Result<Self, ApiError> {
+        let page = q.page.unwrap_or(1);
+        let per_page = q.per_page.unwrap_or(DEFAULT_PER_PAGE);
+        if page == 0 {
+            return Err(ApiError::BadRequest("page starts from 1".to_string()));
+        }
+        if per_page == 0 || per_page > MAX_PER_PAGE {
+            return Err(ApiError::BadRequest(format!(
+                "per_page must be between 1 and {}",
+                MAX_PER_PAGE
+            )));
+        }
+        Ok(DepListParams {
+            page,
+            per_page,
+            sort: q.sort.unwrap_or_default(),
+            relation: q.relation,
+            has_advisory: q.has_advisory,
+            license: q
+                .license
+                .map(|l| l.trim().to_lowercase())
+                .filter(|l| !l.is_empty()),
+            cursor: q.cursor,
+        })
+    }
+}
+
+impl DepListParams {
+    fn matches(&self, key: &DepListKey) -> bool {
+        if let Some(relation) = self.relation {
+            if !key.relation.eq_ignore_ascii_case(relation.label()) {
+                return false;
+            }
+        }
+        if let Some(has_advisory) = self.has_advisory {
+            if (key.advisories > 0) != has_advisory {
+                return false;
+            }
+        }
+        match &self.license {
+            Some(license) => license_matches(key.license, license),
+            None => true,
+        }
+    }
+
+    fn compare(&self, a: &DepListKey, b: &DepListKey) -> Ordering {
+        let by_name = a
+            .name
+            .cmp(b.name)
+            .then_with(|| compare_versions(b.version, a.version));
+        match self.sort {
+            DepSort::Name => by_name,
+            DepSort::Downloads => b.downloads.cmp(&a.downloads).then(by_name),
+            DepSort::Dependents => b.dependents.cmp(&a.dependents).then(by_name),
+        }
+    }
+
+    /// 过滤、排序并截取一页
+    pub fn apply<T: DepListEntry>(&self, items: Vec<T>) -> Result<DepPage<T>, ApiError> {
+        let mut items: Vec<T> = items
+            .into_iter()
+            .filter(|item| self.matches(&item.key()))
+            .collect();
+        items.sort_by(|a, b| self.compare(&a.key(), &b.key()));
+        let total = items.len();
+
+        let start = match &self.cursor {
+            Some(cursor) => {
+                items
+                    .iter()
+                    .position(|item| entry_id(&item.key()) == *cursor)
+                    .ok_or_else(|| {
+                        ApiError::BadRequest(format!("cursor `{}` is no longer valid", cursor))
+                    })?
+                    + 1
+            }
+            None => (self.page - 1).saturating_mul(self.per_page).min(total),
+        };
+        let end = (start + self.per_page).min(total);
+        let data: Vec<T> = items.into_iter().skip(start).take(end - start).collect();
+        let next_cursor = if end < total {
+            data.last().map(|item| entry_id(&item.key()))
+        } else {
+            None
+        };
+        Ok(DepPage {
+            total,
+            page: start / self.per_page + 1,
+            per_page: self.per_page,
+            next_cursor,
+            data,
+        })
+    }
+}
+
+fn entry_id(key: &DepListKey) -> String {
+    format!("{}/{}", key.name, key.version)
+}
+
+/// 无法解析的版本号认为更小
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    match (Version::parse(a), Version::parse(b)) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        (Ok(_), Err(_)) => Ordering::Greater,
+        (Err(_), Ok(_)) => Ordering::Less,
+        (Err(_), Err(_)) => a.cmp(b),
+    }
+}
+
+/// `license` 是否包含标识符 `wanted`（已转为小写），如 `mit` 匹配 `MIT/Apache-2.0`
+fn license_matches(license: &str, wanted: &str) -> bool {
+    let license = license.to_lowercase();
+    license == wanted
+        || license
+            .split(|c: char| c.is_whitespace() || "/(),".contains(c))
+            .any(|id| id == wanted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Item {
+        name: &'static str,
+        version: &'static str,
+        relation: &'static str,
+        license: &'static str,
+        downloads: i64,
+        advisories: usize,
+    }
+
+    impl DepListEntry for Item {
+        fn key(&self) -> DepListKey<'_> {
+            DepListKey {
+                name: self.name,
+                version: self.version,
+                relation: self.relation,
+                license: self.license,
+                downloads: self.downloads,
+                dependents: 0,
+                advisories: self.advisories,
+            }
+        }
+    }
+
+    fn items() -> Vec<Item> {
+        let item = |name, version, relation, license, downloads, advisories| Item {
+            name,
+            version,
+            relation,
+            license,
+            downloads,
+            advisories,
+        };
+        vec![
+            item("serde", "1.0.200", "Direct", "MIT OR Apache-2.0", 500, 0),
+            item("time", "0.1.43", "Indirect", "MIT/Apache-2.0", 300, 1),
+            item("libc", "0.2.150", "Indirect", "MIT OR Apache-2.0", 900, 0),
+            item("ring", "0.16.20", "Direct", "", 100, 1),
+            item("libc", "0.2.9", "Indirect", "MIT OR Apache-2.0", 10, 0),
+        ]
+    }
+
+    fn params(q: DepListQuery) -> DepListParams {
+        DepListParams::try_from(q).unwrap()
+    }
+
+    fn ids<T: DepListEntry>(page: &DepPage<T>) -> Vec<String> {
+        page.data.iter().map(|i| entry_id(&i.key())).collect()
+    }
+
+    #[test]
+    fn test_dep_list_pagination() {
+        let page = params(DepListQuery {
+            per_page: Some(2),
+            ..Default::default()
+        })
+        .apply(items())
+        .unwrap();
+        assert_eq!(page.total, 5);
+        assert_eq!(ids(&page), ["libc/0.2.150", "libc/0.2.9"]);
+        assert_eq!(page.next_cursor.as_deref(), Some("libc/0.2.9"));
+
+        // 游标和页码得到同一页
+        let by_cursor = params(DepListQuery {
+            per_page: Some(2),
+            cursor: page.next_cursor.clone(),
+            ..Default::default()
+        })
+        .apply(items())
+        .unwrap();
+        let by_page = params(DepListQuery {
+            per_page: Some(2),
+            page: Some(2),
+            ..Default::default()
+        })
+        .apply(items())
+        .unwrap();
+        assert_eq!(ids(&by_cursor), ["ring/0.16.20", "serde/1.0.200"]);
+        assert_eq!(ids(&by_cursor), ids(&by_page));
+        assert_eq!(by_cursor.page, 2);
+
+        let last = params(DepListQuery {
+            per_page: Some(2),
+            page: Some(3),
+            ..Default::default()
+        })
+        .apply(items())
+        .unwrap();
+        assert_eq!(ids(&last), ["time/0.1.43"]);
+        assert_eq!(last.next_cursor, None);
+
+        let sorted = params(DepListQuery {
+            sort: Some(DepSort::Downloads),
+            relation: Some(Relation::Indirect),
+            ..Default::default()
+        })
+        .apply(items())
+        .unwrap();
+        assert_eq!(ids(&sorted), ["libc/0.2.150", "time/0.1.43", "libc/0.2.9"]);
+
+        let filtered = params(DepListQuery {
+            has_advisory: Some(true),
+            license: Some("mit".to_string()),
+            ..Default::default()
+        })
+        .apply(items())
+        .unwrap();
+        assert_eq!(ids(&filtered), ["time/0.1.43"]);
+        assert_eq!(filtered.total, 1);
+
+        assert!(params(DepListQuery {
+            cursor: Some("gone/1.0.0".to_string()),
+            ..Default::default()
+        })
+        .apply(items())
+        .is_err());
+        assert!(DepListParams::try_from(DepListQuery {
+            per_page: Some(MAX_PER_PAGE + 1),
+            ..Default::default()
+        })
+        .is_err());
+    }
+}
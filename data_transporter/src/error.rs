@@ -0,0 +1,168 @@
+//! HTTP 接口统一的错误类型
+//!
+//! 后端（Postgres / Redis / TuGraph）失败映射为 5xx，资源不存在映射为 404，
+//! 请求参数不合法映射为 400，缺少或无效的 API Key 映射为 401/403，超出限流映射为 429，
+//! 响应体统一为 [`ApiErrorBody`]。
+
+use std::error::Error as StdError;
+
+use actix_web::{
+    http::{header, StatusCode},
+    HttpResponse, ResponseError,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use utoipa::ToSchema;
+
+#[derive(Debug, Error)]
+pub enum ApiError {
+    #[error("{0} not found")]
+    NotFound(String),
+    #[error("invalid request: {0}")]
+    BadRequest(String),
+    #[error("unauthorized: {0}")]
+    Unauthorized(String),
+    #[error("forbidden: {0}")]
+    Forbidden(String),
+    #[error("rate limit of {limit} requests per minute exceeded")]
+    RateLimited { limit: u32, retry_after: u64 },
+    #[error("{service} unavailable")]
+    Unavailable {
+        service: &'static str,
+        detail: String,
+    },
+    #[error("database error")]
+    Database(#[from] tokio_postgres::Error),
+    #[error("cache decode error")]
+    Serialization(#[from] serde_json::Error),
+    #[error("io error")]
+    Io(#[from] std::io::Error),
+    #[error("backend error")]
+    Backend(String),
+}
+
+/// 错误响应体
+#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+pub struct ApiErrorBody {
+    /// HTTP 状态码
+    pub code: u16,
+    /// 错误类别，如 `not_found`、`bad_request`、`backend`
+    pub error: String,
+    /// 错误描述
+    pub message: String,
+}
+
+impl ApiError {
+    /// 连接后端服务失败
+    pub fn unavailable(service: &'static str, detail: impl ToString) -> Self {
+        ApiError::Unavailable {
+            service,
+            detail: detail.to_string(),
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            ApiError::NotFound(_) => "not_found",
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::RateLimited { .. } => "rate_limited",
+            ApiError::Unavailable { .. } => "unavailable",
+            ApiError::Database(_) => "database",
+            ApiError::Serialization(_) => "serialization",
+            ApiError::Io(_) => "io",
+            ApiError::Backend(_) => "backend",
+        }
+    }
+}
+
+/// 数据层大多返回 `Box<dyn Error>`，这里统一视为后端错误
+impl From<Box<dyn StdError>> for ApiError {
+    fn from(e: Box<dyn StdError>) -> Self {
+        ApiError::Backend(e.to_string())
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Unavailable { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Database(_)
+            | ApiError::Serialization(_)
+            | ApiError::Io(_)
+            | ApiError::Backend(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        if status.is_server_error() {
+            // 5xx 只返回概要信息，细节写日志
+            match self {
+                ApiError::Database(e) => tracing::error!("database error: {:?}", e),
+                ApiError::Serialization(e) => tracing::error!("serialization error: {:?}", e),
+                ApiError::Io(e) => tracing::error!("io error: {:?}", e),
+                ApiError::Backend(e) => tracing::error!("backend error: {}", e),
+                ApiError::Unavailable { service, detail } => {
+                    tracing::error!("{} unavailable: {}", service, detail)
+                }
+                _ => {}
+            }
+        }
+        let mut response = HttpResponse::build(status);
+        if let ApiError::RateLimited { retry_after, .. } = self {
+            response.insert_header((header::RETRY_AFTER, retry_after.to_string()));
+        }
+        response.json(ApiErrorBody {
+            code: status.as_u16(),
+            error: self.kind().to_string(),
+            message: self.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn body_of(err: ApiError) -> (StatusCode, ApiErrorBody) {
+        let resp = err.error_response();
+        let status = resp.status();
+        let bytes = actix_web::body::to_bytes(resp.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_error_status_and_body() {
+        let (status, body) = body_of(ApiError::NotFound("crate serde".to_string())).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body.code, 404);
+        assert_eq!(body.error, "not_found");
+        assert_eq!(body.message, "crate serde not found");
+
+        let (status, body) = body_of(ApiError::BadRequest("per_page must be > 0".into())).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body.error, "bad_request");
+
+        let resp = ApiError::RateLimited {
+            limit: 60,
+            retry_after: 12,
+        }
+        .error_response();
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(resp.headers().get(header::RETRY_AFTER).unwrap(), "12");
+
+        let boxed: Box<dyn StdError> = "bolt connection reset".into();
+        let (status, body) = body_of(boxed.into()).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body.error, "backend");
+        // 5xx 不向客户端泄露后端细节
+        assert_eq!(body.message, "backend error");
+    }
+}
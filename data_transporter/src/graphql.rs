@@ -0,0 +1,1020 @@
+//! GraphQL 接口：一次请求查询程序、版本、依赖关系、漏洞、许可证和评估分数
+//!
+//! 字段都通过 [`GraphLoader`] 批量加载，同一层嵌套字段的 TuGraph / Postgres 查询会合并为一次，
+//! 避免 N+1 查询。`DataLoader` 每个请求新建一个，加载结果不会跨请求缓存。
+
+use std::{cmp::Ordering, collections::HashMap, str::FromStr, sync::Arc};
+
+use async_graphql::{
+    dataloader::{DataLoader, Loader},
+    Context, EmptyMutation, EmptySubscription, Enum, Interface, Object, Result, Schema,
+    SimpleObject,
+};
+use model::tugraph_model::{
+    self, Application, ApplicationVersion, Library, LibraryVersion, Program, UProgram, UVersion,
+};
+use semver::Version;
+
+use crate::{
+    advisory_index::AdvisoryTarget,
+    blast_radius,
+    data_reader::{DataReader, DataReaderTrait},
+    dep_graph::GraphEdge,
+    error::ApiError,
+    pool::ConnectionPools,
+};
+
+pub type CrateSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// 查询的嵌套深度和复杂度上限，防止一次请求展开整张依赖图
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GraphqlLimits {
+    pub max_depth: usize,
+    pub max_complexity: usize,
+}
+
+impl Default for GraphqlLimits {
+    fn default() -> Self {
+        GraphqlLimits {
+            max_depth: 12,
+            max_complexity: 2000,
+        }
+    }
+}
+
+impl GraphqlLimits {
+    /// 从 `GRAPHQL_MAX_DEPTH`、`GRAPHQL_MAX_COMPLEXITY` 读取，未设置或无法解析时使用默认值
+    pub fn from_env() -> Self {
+        fn var<T: FromStr>(key: &str) -> Option<T> {
+            std::env::var(key).ok().and_then(|v| v.parse().ok())
+        }
+        let default = GraphqlLimits::default();
+        GraphqlLimits {
+            max_depth: var("GRAPHQL_MAX_DEPTH").unwrap_or(default.max_depth),
+            max_complexity: var("GRAPHQL_MAX_COMPLEXITY").unwrap_or(default.max_complexity),
+        }
+    }
+}
+
+pub fn build_schema(limits: GraphqlLimits) -> CrateSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .limit_depth(limits.max_depth)
+        .limit_complexity(limits.max_complexity)
+        .finish()
+}
+
+/// 为一个请求创建批量加载器，需要放入请求的 data 中
+pub fn loader(pools: &ConnectionPools) -> DataLoader<GraphLoader> {
+    DataLoader::new(
+        GraphLoader {
+            reader: pools.data_reader(),
+            pools: pools.clone(),
+        },
+        tokio::spawn,
+    )
+}
+
+/// 批量加载图数据，每种 key 对应一条批量查询
+pub struct GraphLoader {
+    reader: DataReader,
+    pools: ConnectionPools,
+}
+
+/// 按名称查询程序
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ProgramsNamed(String);
+/// 按程序 id 查询 library / application 节点
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ProgramType(String);
+/// 按程序 id 查询全部版本
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ProgramVersions(String);
+/// 按 `name/version` 查询版本节点
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct VersionOf(String);
+/// 按 `name/version` 查询所属程序的命名空间
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ProgramOfVersion(String);
+/// 按 `name/version` 查询直接依赖边
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct DependenciesOf(String);
+/// 按 `name/version` 查询直接被依赖边
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct DependentsOf(String);
+/// 按 `name/version` 查询影响该版本的漏洞
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct AdvisoriesOf(String);
+/// 按 crate 名称查询许可证
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct LicenseOf(String);
+/// 按 crate 名称查询评估分数
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ScoreOf(String);
+
+/// 加载失败只向客户端返回概要信息，细节写日志
+fn backend_error(e: impl ToString) -> Arc<ApiError> {
+    let detail = e.to_string();
+    tracing::error!("graphql loader failed: {}", detail);
+    Arc::new(ApiError::Backend(detail))
+}
+
+/// 把边按 `key` 所在的一端分组，每组按另一端排序
+fn group_edges(
+    edges: Vec<GraphEdge>,
+    key: impl Fn(&GraphEdge) -> &String,
+) -> HashMap<String, Vec<GraphEdge>> {
+    let mut groups: HashMap<String, Vec<GraphEdge>> = HashMap::new();
+    for edge in edges {
+        groups.entry(key(&edge).clone()).or_default().push(edge);
+    }
+    for group in groups.values_mut() {
+        group.sort();
+        group.dedup();
+    }
+    groups
+}
+
+/// 按语义化版本从新到旧排序，无法解析的版本号排在最后
+fn sort_versions(versions: &mut [UVersion]) {
+    fn version_of(v: &UVersion) -> &str {
+        match v {
+            UVersion::LibraryVersion(lv) => &lv.version,
+            UVersion::ApplicationVersion(av) => &av.version,
+        }
+    }
+    versions.sort_by(
+        |a, b| match (Version::parse(version_of(a)), Version::parse(version_of(b))) {
+            (Ok(a), Ok(b)) => b.cmp(&a),
+            (Ok(_), Err(_)) => Ordering::Less,
+            (Err(_), Ok(_)) => Ordering::Greater,
+            (Err(_), Err(_)) => version_of(a).cmp(version_of(b)),
+        },
+    );
+}
+
+impl Loader<ProgramsNamed> for GraphLoader {
+    type Value = Vec<Program>;
+    type Error = Arc<ApiError>;
+
+    async fn load(
+        &self,
+        keys: &[ProgramsNamed],
+    ) -> Result<HashMap<ProgramsNamed, Self::Value>, Self::Error> {
+        let programs = self
+            .reader
+            .get_programs_by_names(keys.iter().map(|k| k.0.clone()).collect())
+            .await
+            .map_err(backend_error)?;
+        Ok(programs
+            .into_iter()
+            .map(|(name, list)| (ProgramsNamed(name), list))
+            .collect())
+    }
+}
+
+impl Loader<ProgramType> for GraphLoader {
+    type Value = UProgram;
+    type Error = Arc<ApiError>;
+
+    async fn load(
+        &self,
+        keys: &[ProgramType],
+    ) -> Result<HashMap<ProgramType, Self::Value>, Self::Error> {
+        let types = self
+            .reader
+            .get_program_types(keys.iter().map(|k| k.0.clone()).collect())
+            .await
+            .map_err(backend_error)?;
+        Ok(types
+            .into_iter()
+            .map(|(id, uprogram)| (ProgramType(id), uprogram))
+            .collect())
+    }
+}
+
+impl Loader<ProgramVersions> for GraphLoader {
+    type Value = Vec<UVersion>;
+    type Error = Arc<ApiError>;
+
+    async fn load(
+        &self,
+        keys: &[ProgramVersions],
+    ) -> Result<HashMap<ProgramVersions, Self::Value>, Self::Error> {
+        let versions = self
+            .reader
+            .get_program_versions(keys.iter().map(|k| k.0.clone()).collect())
+            .await
+            .map_err(backend_error)?;
+        Ok(versions
+            .into_iter()
+            .map(|(id, mut list)| {
+                sort_versions(&mut list);
+                (ProgramVersions(id), list)
+            })
+            .collect())
+    }
+}
+
+impl Loader<VersionOf> for GraphLoader {
+    type Value = UVersion;
+    type Error = Arc<ApiError>;
+
+    async fn load(
+        &self,
+        keys: &[VersionOf],
+    ) -> Result<HashMap<VersionOf, Self::Value>, Self::Error> {
+        let nodes = self
+            .reader
+            .get_version_nodes(keys.iter().map(|k| k.0.clone()).collect())
+            .await
+            .map_err(backend_error)?;
+        Ok(nodes
+            .into_iter()
+            .map(|(nv, version)| (VersionOf(nv), version))
+            .collect())
+    }
+}
+
+impl Loader<ProgramOfVersion> for GraphLoader {
+    type Value = (String, blast_radius::ProgramKind);
+    type Error = Arc<ApiError>;
+
+    async fn load(
+        &self,
+        keys: &[ProgramOfVersion],
+    ) -> Result<HashMap<ProgramOfVersion, Self::Value>, Self::Error> {
+        let programs = self
+            .reader
+            .get_program_of_versions(keys.iter().map(|k| k.0.clone()).collect())
+            .await
+            .map_err(backend_error)?;
+        Ok(programs
+            .into_iter()
+            .map(|(nv, program)| (ProgramOfVersion(nv), program))
+            .collect())
+    }
+}
+
+impl Loader<DependenciesOf> for GraphLoader {
+    type Value = Vec<GraphEdge>;
+    type Error = Arc<ApiError>;
+
+    async fn load(
+        &self,
+        keys: &[DependenciesOf],
+    ) -> Result<HashMap<DependenciesOf, Self::Value>, Self::Error> {
+        let edges = self
+            .reader
+            .get_direct_dependency_edges(keys.iter().map(|k| k.0.clone()).collect())
+            .await
+            .map_err(backend_error)?;
+        Ok(group_edges(edges, |e| &e.from)
+            .into_iter()
+            .map(|(nv, edges)| (DependenciesOf(nv), edges))
+            .collect())
+    }
+}
+
+impl Loader<DependentsOf> for GraphLoader {
+    type Value = Vec<GraphEdge>;
+    type Error = Arc<ApiError>;
+
+    async fn load(
+        &self,
+        keys: &[DependentsOf],
+    ) -> Result<HashMap<DependentsOf, Self::Value>, Self::Error> {
+        let edges = self
+            .reader
+            .get_direct_dependent_edges(keys.iter().map(|k| k.0.clone()).collect())
+            .await
+            .map_err(backend_error)?;
+        Ok(group_edges(edges, |e| &e.to)
+            .into_iter()
+            .map(|(nv, edges)| (DependentsOf(nv), edges))
+            .collect())
+    }
+}
+
+impl Loader<AdvisoriesOf> for GraphLoader {
+    type Value = Vec<AdvisoryTarget>;
+    type Error = Arc<ApiError>;
+
+    async fn load(
+        &self,
+        keys: &[AdvisoriesOf],
+    ) -> Result<HashMap<AdvisoriesOf, Self::Value>, Self::Error> {
+        let packages: Vec<(String, Option<String>)> = keys
+            .iter()
+            .filter_map(|k| k.0.split_once('/'))
+            .map(|(name, version)| (name.to_string(), Some(version.to_string())))
+            .collect();
+        let dbhandler = self.pools.pg().await?;
+        let advisories = dbhandler
+            .get_package_advisories(&packages)
+            .await
+            .map_err(backend_error)?;
+        Ok(packages
+            .into_iter()
+            .zip(advisories)
+            .map(|((name, version), targets)| {
+                let nv = format!("{}/{}", name, version.unwrap_or_default());
+                (AdvisoriesOf(nv), targets)
+            })
+            .collect())
+    }
+}
+
+impl Loader<LicenseOf> for GraphLoader {
+    type Value = String;
+    type Error = Arc<ApiError>;
+
+    async fn load(
+        &self,
+        keys: &[LicenseOf],
+    ) -> Result<HashMap<LicenseOf, Self::Value>, Self::Error> {
+        let dbhandler = self.pools.pg().await?;
+        let licenses = dbhandler
+            .get_licenses_by_names(&keys.iter().map(|k| k.0.clone()).collect::<Vec<_>>())
+            .await
+            .map_err(backend_error)?;
+        Ok(licenses
+            .into_iter()
+            .map(|(name, license)| (LicenseOf(name), license))
+            .collect())
+    }
+}
+
+impl Loader<ScoreOf> for GraphLoader {
+    type Value = Option<f64>;
+    type Error = Arc<ApiError>;
+
+    async fn load(&self, keys: &[ScoreOf]) -> Result<HashMap<ScoreOf, Self::Value>, Self::Error> {
+        let dbhandler = self.pools.pg().await?;
+        let scores = dbhandler
+            .get_program_scores(&keys.iter().map(|k| k.0.clone()).collect::<Vec<_>>())
+            .await
+            .map_err(backend_error)?;
+        Ok(scores
+            .into_iter()
+            .map(|(name, score)| (ScoreOf(name), score))
+            .collect())
+    }
+}
+
+fn graph_loader<'a>(ctx: &Context<'a>) -> &'a DataLoader<GraphLoader> {
+    ctx.data_unchecked::<DataLoader<GraphLoader>>()
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// 按名称查询程序，同名程序可能来自不同命名空间
+    async fn programs(&self, ctx: &Context<'_>, name: String) -> Result<Vec<ProgramNode>> {
+        let programs = graph_loader(ctx).load_one(ProgramsNamed(name)).await?;
+        Ok(programs
+            .unwrap_or_default()
+            .into_iter()
+            .map(ProgramNode)
+            .collect())
+    }
+
+    /// 按命名空间（如 `tokio-rs/tokio`）和名称查询程序
+    async fn program(
+        &self,
+        ctx: &Context<'_>,
+        namespace: String,
+        name: String,
+    ) -> Result<Option<ProgramNode>> {
+        program_in(ctx, &namespace, name).await
+    }
+
+    /// 按名称和版本号查询版本
+    async fn version(
+        &self,
+        ctx: &Context<'_>,
+        name: String,
+        version: String,
+    ) -> Result<Option<VersionNode>> {
+        let nv = format!("{}/{}", name, version);
+        Ok(graph_loader(ctx)
+            .load_one(VersionOf(nv))
+            .await?
+            .map(VersionNode::from))
+    }
+
+    /// 批量查询版本，参数为 `name/version`，不存在的版本被忽略
+    async fn versions(
+        &self,
+        ctx: &Context<'_>,
+        name_and_versions: Vec<String>,
+    ) -> Result<Vec<VersionNode>> {
+        let mut nodes = graph_loader(ctx)
+            .load_many(name_and_versions.iter().cloned().map(VersionOf))
+            .await?;
+        Ok(name_and_versions
+            .into_iter()
+            .filter_map(|nv| nodes.remove(&VersionOf(nv)))
+            .map(VersionNode::from)
+            .collect())
+    }
+}
+
+async fn program_in(
+    ctx: &Context<'_>,
+    namespace: &str,
+    name: String,
+) -> Result<Option<ProgramNode>> {
+    let programs = graph_loader(ctx).load_one(ProgramsNamed(name)).await?;
+    Ok(programs
+        .unwrap_or_default()
+        .into_iter()
+        .find(|p| p.namespace.as_deref() == Some(namespace))
+        .map(ProgramNode))
+}
+
+async fn license_of(ctx: &Context<'_>, name: &str) -> Result<Option<String>> {
+    Ok(graph_loader(ctx)
+        .load_one(LicenseOf(name.to_string()))
+        .await?)
+}
+
+async fn score_of(ctx: &Context<'_>, name: &str) -> Result<Option<f64>> {
+    Ok(graph_loader(ctx)
+        .load_one(ScoreOf(name.to_string()))
+        .await?
+        .flatten())
+}
+
+async fn program_of(ctx: &Context<'_>, name: &str, nv: &str) -> Result<Option<ProgramNode>> {
+    let Some((namespace, _)) = graph_loader(ctx)
+        .load_one(ProgramOfVersion(nv.to_string()))
+        .await?
+    else {
+        return Ok(None);
+    };
+    program_in(ctx, &namespace, name.to_string()).await
+}
+
+async fn dependencies_of(ctx: &Context<'_>, nv: &str) -> Result<Vec<DependencyEdge>> {
+    let edges = graph_loader(ctx)
+        .load_one(DependenciesOf(nv.to_string()))
+        .await?;
+    Ok(edges
+        .unwrap_or_default()
+        .into_iter()
+        .map(|edge| DependencyEdge {
+            other: edge.to.clone(),
+            edge,
+        })
+        .collect())
+}
+
+async fn dependents_of(ctx: &Context<'_>, nv: &str) -> Result<Vec<DependencyEdge>> {
+    let edges = graph_loader(ctx)
+        .load_one(DependentsOf(nv.to_string()))
+        .await?;
+    Ok(edges
+        .unwrap_or_default()
+        .into_iter()
+        .map(|edge| DependencyEdge {
+            other: edge.from.clone(),
+            edge,
+        })
+        .collect())
+}
+
+async fn advisories_of(ctx: &Context<'_>, nv: &str) -> Result<Vec<Advisory>> {
+    let targets = graph_loader(ctx)
+        .load_one(AdvisoriesOf(nv.to_string()))
+        .await?;
+    Ok(targets
+        .unwrap_or_default()
+        .iter()
+        .map(Advisory::from)
+        .collect())
+}
+
+/// 程序类型
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+#[graphql(name = "ProgramKind", remote = "blast_radius::ProgramKind")]
+pub enum GqlProgramKind {
+    Library,
+    Application,
+    /// 图中找不到所属程序
+    Unknown,
+}
+
+/// 依赖声明所在的 `Cargo.toml` 段
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+#[graphql(name = "DependencyKind", remote = "tugraph_model::DependencyKind")]
+pub enum GqlDependencyKind {
+    Normal,
+    Dev,
+    Build,
+}
+
+/// 依赖来源
+#[derive(Enum, Debug, Clone, Copy, PartialEq, Eq)]
+#[graphql(name = "DependencySource", remote = "tugraph_model::DependencySource")]
+pub enum GqlDependencySource {
+    Registry,
+    Git,
+    Path,
+}
+
+pub struct ProgramNode(Program);
+
+/// 程序，即一个 crate 仓库
+#[Object(name = "Program")]
+impl ProgramNode {
+    async fn id(&self) -> &String {
+        &self.0.id
+    }
+
+    async fn name(&self) -> &String {
+        &self.0.name
+    }
+
+    async fn description(&self) -> &Option<String> {
+        &self.0.description
+    }
+
+    /// 命名空间，如 `tokio-rs/tokio`
+    async fn namespace(&self) -> &Option<String> {
+        &self.0.namespace
+    }
+
+    async fn max_version(&self) -> &Option<String> {
+        &self.0.max_version
+    }
+
+    async fn github_url(&self) -> &Option<String> {
+        &self.0.github_url
+    }
+
+    async fn mega_url(&self) -> &Option<String> {
+        &self.0.mega_url
+    }
+
+    async fn doc_url(&self) -> &Option<String> {
+        &self.0.doc_url
+    }
+
+    async fn kind(&self, ctx: &Context<'_>) -> Result<GqlProgramKind> {
+        let uprogram = graph_loader(ctx)
+            .load_one(ProgramType(self.0.id.clone()))
+            .await?;
+        Ok(match uprogram {
+            Some(UProgram::Library(_)) => GqlProgramKind::Library,
+            Some(UProgram::Application(_)) => GqlProgramKind::Application,
+            None => GqlProgramKind::Unknown,
+        })
+    }
+
+    /// 程序为库时的 library 节点
+    async fn library(&self, ctx: &Context<'_>) -> Result<Option<LibraryNode>> {
+        let uprogram = graph_loader(ctx)
+            .load_one(ProgramType(self.0.id.clone()))
+            .await?;
+        Ok(match uprogram {
+            Some(UProgram::Library(library)) => Some(LibraryNode(library)),
+            _ => None,
+        })
+    }
+
+    /// 程序为应用时的 application 节点
+    async fn application(&self, ctx: &Context<'_>) -> Result<Option<ApplicationNode>> {
+        let uprogram = graph_loader(ctx)
+            .load_one(ProgramType(self.0.id.clone()))
+            .await?;
+        Ok(match uprogram {
+            Some(UProgram::Application(application)) => Some(ApplicationNode(application)),
+            _ => None,
+        })
+    }
+
+    /// 全部版本，从新到旧
+    async fn versions(&self, ctx: &Context<'_>) -> Result<Vec<VersionNode>> {
+        let versions = graph_loader(ctx)
+            .load_one(ProgramVersions(self.0.id.clone()))
+            .await?;
+        Ok(versions
+            .unwrap_or_default()
+            .into_iter()
+            .map(VersionNode::from)
+            .collect())
+    }
+
+    async fn license(&self, ctx: &Context<'_>) -> Result<Option<String>> {
+        license_of(ctx, &self.0.name).await
+    }
+
+    /// `metadata` 表中的综合评估分数
+    async fn score(&self, ctx: &Context<'_>) -> Result<Option<f64>> {
+        score_of(ctx, &self.0.name).await
+    }
+}
+
+pub struct LibraryNode(Library);
+
+#[Object(name = "Library")]
+impl LibraryNode {
+    async fn id(&self) -> &String {
+        &self.0.id
+    }
+
+    async fn name(&self) -> &String {
+        &self.0.name
+    }
+
+    async fn downloads(&self) -> i64 {
+        self.0.downloads
+    }
+
+    /// crates.io 页面地址
+    async fn cratesio(&self) -> &Option<String> {
+        &self.0.cratesio
+    }
+
+    /// 全部版本，从新到旧
+    async fn versions(&self, ctx: &Context<'_>) -> Result<Vec<LibraryVersionNode>> {
+        let versions = graph_loader(ctx)
+            .load_one(ProgramVersions(self.0.id.clone()))
+            .await?;
+        Ok(versions
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|v| match v {
+                UVersion::LibraryVersion(lv) => Some(LibraryVersionNode(lv)),
+                UVersion::ApplicationVersion(_) => None,
+            })
+            .collect())
+    }
+}
+
+pub struct ApplicationNode(Application);
+
+#[Object(name = "Application")]
+impl ApplicationNode {
+    async fn id(&self) -> &String {
+        &self.0.id
+    }
+
+    async fn name(&self) -> &String {
+        &self.0.name
+    }
+
+    /// 全部版本，从新到旧
+    async fn versions(&self, ctx: &Context<'_>) -> Result<Vec<ApplicationVersionNode>> {
+        let versions = graph_loader(ctx)
+            .load_one(ProgramVersions(self.0.id.clone()))
+            .await?;
+        Ok(versions
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|v| match v {
+                UVersion::ApplicationVersion(av) => Some(ApplicationVersionNode(av)),
+                UVersion::LibraryVersion(_) => None,
+            })
+            .collect())
+    }
+}
+
+/// 库或应用的一个版本
+#[allow(clippy::duplicated_attributes)]
+#[derive(Interface)]
+#[graphql(
+    name = "Version",
+    field(name = "name_and_version", ty = "&String"),
+    field(name = "name", ty = "&String"),
+    field(name = "version", ty = "&String"),
+    field(name = "program", ty = "Option<ProgramNode>"),
+    field(name = "dependencies", ty = "Vec<DependencyEdge>"),
+    field(name = "dependents", ty = "Vec<DependencyEdge>"),
+    field(name = "advisories", ty = "Vec<Advisory>"),
+    field(name = "license", ty = "Option<String>"),
+    field(name = "score", ty = "Option<f64>")
+)]
+pub enum VersionNode {
+    Library(LibraryVersionNode),
+    Application(ApplicationVersionNode),
+}
+
+impl From<UVersion> for VersionNode {
+    fn from(version: UVersion) -> Self {
+        match version {
+            UVersion::LibraryVersion(lv) => VersionNode::Library(LibraryVersionNode(lv)),
+            UVersion::ApplicationVersion(av) => {
+                VersionNode::Application(ApplicationVersionNode(av))
+            }
+        }
+    }
+}
+
+pub struct LibraryVersionNode(LibraryVersion);
+
+#[Object(name = "LibraryVersion")]
+impl LibraryVersionNode {
+    async fn id(&self) -> &String {
+        &self.0.id
+    }
+
+    /// `name/version`
+    async fn name_and_version(&self) -> &String {
+        &self.0.name_and_version
+    }
+
+    async fn name(&self) -> &String {
+        &self.0.name
+    }
+
+    async fn version(&self) -> &String {
+        &self.0.version
+    }
+
+    /// 文档地址
+    async fn documentation(&self) -> &String {
+        &self.0.documentation
+    }
+
+    /// 所属程序
+    async fn program(&self, ctx: &Context<'_>) -> Result<Option<ProgramNode>> {
+        program_of(ctx, &self.0.name, &self.0.name_and_version).await
+    }
+
+    /// 直接依赖
+    async fn dependencies(&self, ctx: &Context<'_>) -> Result<Vec<DependencyEdge>> {
+        dependencies_of(ctx, &self.0.name_and_version).await
+    }
+
+    /// 直接依赖该版本的版本
+    async fn dependents(&self, ctx: &Context<'_>) -> Result<Vec<DependencyEdge>> {
+        dependents_of(ctx, &self.0.name_and_version).await
+    }
+
+    /// 影响该版本的 RustSec 漏洞
+    async fn advisories(&self, ctx: &Context<'_>) -> Result<Vec<Advisory>> {
+        advisories_of(ctx, &self.0.name_and_version).await
+    }
+
+    async fn license(&self, ctx: &Context<'_>) -> Result<Option<String>> {
+        license_of(ctx, &self.0.name).await
+    }
+
+    /// 所属程序的综合评估分数
+    async fn score(&self, ctx: &Context<'_>) -> Result<Option<f64>> {
+        score_of(ctx, &self.0.name).await
+    }
+}
+
+pub struct ApplicationVersionNode(ApplicationVersion);
+
+#[Object(name = "ApplicationVersion")]
+impl ApplicationVersionNode {
+    async fn id(&self) -> &String {
+        &self.0.id
+    }
+
+    /// `name/version`
+    async fn name_and_version(&self) -> &String {
+        &self.0.name_and_version
+    }
+
+    async fn name(&self) -> &String {
+        &self.0.name
+    }
+
+    async fn version(&self) -> &String {
+        &self.0.version
+    }
+
+    /// 所属程序
+    async fn program(&self, ctx: &Context<'_>) -> Result<Option<ProgramNode>> {
+        program_of(ctx, &self.0.name, &self.0.name_and_version).await
+    }
+
+    /// 直接依赖
+    async fn dependencies(&self, ctx: &Context<'_>) -> Result<Vec<DependencyEdge>> {
+        dependencies_of(ctx, &self.0.name_and_version).await
+    }
+
+    /// 直接依赖该版本的版本
+    async fn dependents(&self, ctx: &Context<'_>) -> Result<Vec<DependencyEdge>> {
+        dependents_of(ctx, &self.0.name_and_version).await
+    }
+
+    /// 影响该版本的 RustSec 漏洞
+    async fn advisories(&self, ctx: &Context<'_>) -> Result<Vec<Advisory>> {
+        advisories_of(ctx, &self.0.name_and_version).await
+    }
+
+    async fn license(&self, ctx: &Context<'_>) -> Result<Option<String>> {
+        license_of(ctx, &self.0.name).await
+    }
+
+    /// 所属程序的综合评估分数
+    async fn score(&self, ctx: &Context<'_>) -> Result<Option<f64>> {
+        score_of(ctx, &self.0.name).await
+    }
+}
+
+/// 一条 `depends_on` 边，`other` 为边上相对于查询版本的另一端
+pub struct DependencyEdge {
+    other: String,
+    edge: GraphEdge,
+}
+
+/// 依赖关系，`nameAndVersion` 为关系另一端的版本
+#[Object(name = "Dependency")]
+impl DependencyEdge {
+    async fn name_and_version(&self) -> &String {
+        &self.other
+    }
+
+    async fn name(&self) -> &str {
+        self.other.split_once('/').map_or(&self.other, |(n, _)| n)
+    }
+
+    async fn version(&self) -> &str {
+        self.other.split_once('/').map_or("", |(_, v)| v)
+    }
+
+    async fn kind(&self) -> GqlDependencyKind {
+        self.edge.kind.into()
+    }
+
+    async fn optional(&self) -> bool {
+        self.edge.optional
+    }
+
+    async fn default_features(&self) -> bool {
+        self.edge.default_features
+    }
+
+    async fn features(&self) -> &Vec<String> {
+        &self.edge.features
+    }
+
+    /// `cfg(..)` 表达式或 target triple，为空表示对所有平台生效
+    async fn target(&self) -> &Option<String> {
+        &self.edge.target
+    }
+
+    /// 是否由依赖方的默认 feature 启用
+    async fn enabled_by_default(&self) -> bool {
+        self.edge.enabled_by_default
+    }
+
+    async fn source(&self) -> GqlDependencySource {
+        self.edge.source.into()
+    }
+
+    /// 声明的版本要求
+    async fn version_req(&self) -> &Option<String> {
+        &self.edge.version_req
+    }
+
+    /// 另一端的版本节点，图中没有对应的库或应用版本时为空
+    async fn node(&self, ctx: &Context<'_>) -> Result<Option<VersionNode>> {
+        Ok(graph_loader(ctx)
+            .load_one(VersionOf(self.other.clone()))
+            .await?
+            .map(VersionNode::from))
+    }
+}
+
+/// RustSec 漏洞
+#[derive(SimpleObject, Debug, Clone, PartialEq, Eq)]
+pub struct Advisory {
+    pub id: String,
+    pub title: String,
+    pub url: String,
+    pub aliases: Vec<String>,
+    /// 修复版本范围
+    pub patched: Vec<String>,
+    pub issued: String,
+}
+
+impl From<&AdvisoryTarget> for Advisory {
+    fn from(target: &AdvisoryTarget) -> Self {
+        let split = |value: &str, sep: char| {
+            value
+                .split(sep)
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+        Advisory {
+            id: target.info.id.clone(),
+            title: target.info.subtitle.clone(),
+            url: target.info.url.clone(),
+            aliases: split(&target.info.aliases, ';'),
+            patched: split(&target.info.patched, '|'),
+            issued: target.info.issued.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge(from: &str, to: &str) -> GraphEdge {
+        GraphEdge {
+            from: from.to_string(),
+            to: to.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn lib_version(version: &str) -> UVersion {
+        UVersion::LibraryVersion(LibraryVersion {
+            id: version.to_string(),
+            name_and_version: format!("demo/{}", version),
+            name: "demo".to_string(),
+            version: version.to_string(),
+            documentation: String::new(),
+        })
+    }
+
+    #[test]
+    fn test_schema() {
+        let sdl = build_schema(GraphqlLimits::default()).sdl();
+        for expected in [
+            "type Program",
+            "type Library",
+            "type Application",
+            "interface Version",
+            "type LibraryVersion implements Version",
+            "type ApplicationVersion implements Version",
+            "type Dependency",
+            "enum DependencyKind",
+            "nameAndVersion: String!",
+        ] {
+            assert!(
+                sdl.contains(expected),
+                "missing `{}` in:\n{}",
+                expected,
+                sdl
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_limits() {
+        let schema = build_schema(GraphqlLimits {
+            max_depth: 3,
+            max_complexity: 100,
+        });
+        // 超出深度的查询在执行前被拒绝，不会访问后端
+        let response = schema
+            .execute(
+                r#"{ version(name: "a", version: "1.0.0") {
+                    dependencies { node { dependencies { name } } }
+                } }"#,
+            )
+            .await;
+        assert_eq!(response.errors.len(), 1);
+        assert!(response.errors[0].message.contains("nested too deep"));
+
+        let response = schema.execute("{ programs(name: 1) { id } }").await;
+        assert_eq!(response.errors.len(), 1);
+    }
+
+    #[test]
+    fn test_group_edges() {
+        let edges = vec![
+            edge("a/1.0.0", "c/1.0.0"),
+            edge("a/1.0.0", "b/1.0.0"),
+            edge("a/1.0.0", "b/1.0.0"),
+            edge("b/1.0.0", "c/1.0.0"),
+        ];
+        let by_from = group_edges(edges.clone(), |e| &e.from);
+        let deps: Vec<&str> = by_from["a/1.0.0"].iter().map(|e| e.to.as_str()).collect();
+        assert_eq!(deps, ["b/1.0.0", "c/1.0.0"]);
+        let by_to = group_edges(edges, |e| &e.to);
+        assert_eq!(by_to["c/1.0.0"].len(), 2);
+        assert!(!by_to.contains_key("a/1.0.0"));
+    }
+
+    #[test]
+    fn test_sort_versions() {
+        let mut versions = vec![
+            lib_version("0.9.0"),
+            lib_version("weird"),
+            lib_version("0.10.0"),
+            lib_version("1.0.0-rc.1"),
+        ];
+        sort_versions(&mut versions);
+        let order: Vec<String> = versions
+            .into_iter()
+            .map(|v| match v {
+                UVersion::LibraryVersion(lv) => lv.version,
+                UVersion::ApplicationVersion(av) => av.version,
+            })
+            .collect();
+        assert_eq!(order, ["1.0.0-rc.1", "0.10.0", "0.9.0", "weird"]);
+    }
+}
@@ -1,18 +1,45 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 #[allow(unused_imports)]
 use std::env;
-use std::error::Error;
 //use std::error::Error;
 use std::time::Instant;
 
+use crate::archive::{self, ArchiveFormat, ArchiveLimits};
+use crate::audit::{
+    build_report, crates_io_packages, lockfile_from_zip, parse_lockfile, AuditFacts, AuditReport,
+    AuditUpload, MAX_ARCHIVE_BYTES, MAX_LOCKFILE_BYTES,
+};
+use crate::auth::{self, ApiKeyInfo, ApiKeyQuery, IssuedApiKey, NewApiKey, Principal};
+use crate::blast_radius::{
+    group_by_namespace, normal_only, shipped, trace_dependents, BlastRadius, BlastRadiusQuery,
+    VulnerableCrate,
+};
+use crate::cache::{CacheKey, CacheKind, Cached};
 use crate::data_reader::{DataReader, DataReaderTrait};
-use crate::db::{db_connection_config_from_env, DBHandler};
-use crate::redis_store::{get_redis_connection, RedisHandler};
-use crate::{get_tugraph_api_handler, NameVersion, Userinfo};
+use crate::dep_diff::{diff_trees, DependencyDiff, DiffParams, DiffQuery, TreeSnapshot};
+use crate::dep_filter::{DependencyFilter, DependencyFilterQuery};
+use crate::dep_graph::{
+    build_dependency_graph, DependencyGraph, GraphEdge, GraphLimits, GraphQuery, Resolution,
+};
+use crate::dep_list::{DepListEntry, DepListKey, DepListParams, DepListQuery, DepSort, Relation};
+use crate::error::{ApiError, ApiErrorBody};
+use crate::graphql::{self, CrateSchema};
+use crate::jobs::{self, JobState, UploadAccepted, UploadJob};
+use crate::osv::{
+    to_osv, OsvBatchResponse, OsvBatchResult, OsvQueryBatch, OsvVulnerability, PackageQuery,
+};
+use crate::pool::{ConnectionPools, HealthStatus};
+use crate::sbom::{Sbom, SbomComponent, SbomFormat, SbomQuery};
+use crate::similar::{
+    candidate_names, co_dependency, latest_version, rank_alternatives, sample_dependents,
+    sort_newest_first, CandidateInfo, DependentSample, SimilarCrates, SimilarParams, SimilarQuery,
+    EMBEDDING_NEIGHBOURS, MAX_DEPENDENT_EDGES, MAX_REPLACEMENT_CHECKS,
+};
+use crate::{NameVersion, Userinfo};
 use crate::{Query, VersionInfo};
 use actix_multipart::{Field, Multipart};
 use actix_web::http::header::ContentDisposition;
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{web, HttpResponse};
 use futures_util::StreamExt;
 //use model::repo_sync_model;
 //use model::repo_sync_model::CrateType;
@@ -21,24 +48,20 @@ use model::tugraph_model::{Program, UProgram};
 use sanitize_filename::sanitize;
 use search::crates_search::RecommendCrate;
 //use search::crates_search::RecommendCrate;
-use search::crates_search::SearchModule;
-use search::crates_search::SearchSortCriteria;
+use search::crates_search::{SearchConfig, SearchModule};
+use search::crates_search::{SearchResults, Suggestion};
+use search::facets::SearchFacets;
 use serde::Deserialize;
 use serde::Serialize;
-use std::io::Cursor;
-use std::io::Read;
 //use std::time::Instant;
 //use semver::Version;
-use tokio::io::AsyncReadExt;
 use tokio::io::AsyncWriteExt;
-use tokio_postgres::NoTls;
 use utoipa::ToSchema;
-use zip::ZipArchive;
 pub struct ApiHandler {
     reader: DataReader,
 }
 impl ApiHandler {
-    pub async fn new(reader: DataReader) -> Self {
+    pub fn new(reader: DataReader) -> Self {
         Self { reader }
     }
 }
@@ -52,7 +75,18 @@ pub struct QueryCratesInfo {
 #[derive(Serialize, Deserialize, Debug, Default, Clone, ToSchema)]
 pub struct QueryData {
     total_page: usize,
+    /// 去重后的结果总数
+    total: usize,
     items: Vec<QueryItem>,
+    /// 关键词匹配结果在各维度上的计数，每个维度不受自身过滤条件影响
+    facets: SearchFacets,
+    /// 全文检索没有结果，返回的是名称相似的 crate
+    fuzzy: bool,
+}
+#[derive(Deserialize, Debug)]
+pub struct SuggestQuery {
+    q: String,
+    limit: Option<i64>,
 }
 #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 pub struct QueryItem {
@@ -62,31 +96,87 @@ pub struct QueryItem {
     nsfront: String,
     nsbehind: String,
 }
-#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, ToSchema)]
 pub struct DependencyCrateInfo {
     pub crate_name: String,
     pub version: String,
     pub relation: String,
     pub license: String,
+    /// 传递依赖数，只为当前页的项计算
     pub dependencies: usize,
+    pub downloads: i64,
+    /// 直接被依赖数
+    pub dependents: usize,
+    /// RustSec 漏洞数
+    pub advisories: usize,
 }
 #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 pub struct DependencyInfo {
     pub direct_count: usize,
     pub indirect_count: usize,
+    /// 传递依赖过多、遍历被截断，此时计数和列表只是下界
+    #[serde(default)]
+    pub truncated: bool,
+    /// 过滤后的总条数
+    pub total: usize,
+    pub page: usize,
+    pub per_page: usize,
+    /// 下一页的游标，没有下一页时为空
+    pub next_cursor: Option<String>,
     pub data: Vec<DependencyCrateInfo>,
 }
 #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 pub struct DependentInfo {
     pub direct_count: usize,
     pub indirect_count: usize,
+    /// 传递依赖方过多、遍历被截断，此时计数和列表只是下界
+    #[serde(default)]
+    pub truncated: bool,
+    /// 过滤后的总条数
+    pub total: usize,
+    pub page: usize,
+    pub per_page: usize,
+    /// 下一页的游标，没有下一页时为空
+    pub next_cursor: Option<String>,
     pub data: Vec<DependentData>,
 }
-#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, ToSchema)]
 pub struct DependentData {
     pub crate_name: String,
     pub version: String,
     pub relation: String,
+    pub license: String,
+    pub downloads: i64,
+    /// 直接被依赖数
+    pub dependents: usize,
+    /// RustSec 漏洞数
+    pub advisories: usize,
+}
+impl DepListEntry for DependencyCrateInfo {
+    fn key(&self) -> DepListKey<'_> {
+        DepListKey {
+            name: &self.crate_name,
+            version: &self.version,
+            relation: &self.relation,
+            license: &self.license,
+            downloads: self.downloads,
+            dependents: self.dependents,
+            advisories: self.advisories,
+        }
+    }
+}
+impl DepListEntry for DependentData {
+    fn key(&self) -> DepListKey<'_> {
+        DepListKey {
+            name: &self.crate_name,
+            version: &self.version,
+            relation: &self.relation,
+            license: &self.license,
+            downloads: self.downloads,
+            dependents: self.dependents,
+            advisories: self.advisories,
+        }
+    }
 }
 #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 pub struct Crateinfo {
@@ -105,6 +195,9 @@ pub struct Crateinfo {
 pub struct DependencyCount {
     pub direct: usize,
     pub indirect: usize,
+    /// 传递依赖过多、遍历被截断，此时 `indirect` 只是下界
+    #[serde(default)]
+    pub truncated: bool,
 }
 #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 pub struct DependentCount {
@@ -119,7 +212,7 @@ pub struct RustSec {
     pub aliases: Vec<String>,
     pub small_desc: String,
 }
-#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Hash, ToSchema)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Default, Clone, Hash, ToSchema)]
 pub struct NewRustsec {
     pub id: String,
     pub subtitle: String,
@@ -149,6 +242,22 @@ pub struct Versionpage {
     pub downloads: String,
     pub dependents: usize,
 }
+
+impl Cached for Crateinfo {
+    const KIND: CacheKind = CacheKind::CratesInfo;
+}
+impl Cached for DependencyInfo {
+    const KIND: CacheKind = CacheKind::DependencyList;
+}
+impl Cached for Deptree {
+    const KIND: CacheKind = CacheKind::DependencyGraph;
+}
+impl Cached for DependentInfo {
+    const KIND: CacheKind = CacheKind::DependentList;
+}
+impl Cached for Vec<Versionpage> {
+    const KIND: CacheKind = CacheKind::VersionPage;
+}
 #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 pub struct SenseleakRes {
     pub exist: bool,
@@ -157,38 +266,45 @@ pub struct SenseleakRes {
 #[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
 pub struct MircheckerRes {
     pub run_state: bool,
-    pub exist:bool,
+    pub exist: bool,
     pub res: String,
 }
 
+/// 检查 Postgres、Redis、TuGraph 是否可用
+#[utoipa::path(
+    get,
+    path = "/api/health",
+    responses(
+        (status = 200, description = "所有后端可用", body = HealthStatus),
+        (status = 503, description = "存在不可用的后端", body = HealthStatus)
+    ),
+    tag = "health"
+)]
+pub async fn health_check(pools: &ConnectionPools) -> HttpResponse {
+    let status = pools.health().await;
+    if status.ok {
+        HttpResponse::Ok().json(status)
+    } else {
+        HttpResponse::ServiceUnavailable().json(status)
+    }
+}
+
 /// 获取cve信息
 #[utoipa::path(
     get,
     path = "/api/cvelist",
     responses(
         (status = 200, description = "成功获取crate信息", body = crate::db::Allcve),
-        (status = 404, description = "未找到crate信息")
+        (status = 500, description = "服务器内部错误", body = ApiErrorBody),
+        (status = 503, description = "后端服务不可用", body = ApiErrorBody)
     ),
     tag = "security"
 )]
-pub async fn get_cves() -> impl Responder {
-    let _handler = get_tugraph_api_handler().await;
-
-    let db_connection_config = db_connection_config_from_env();
-    #[allow(unused_variables)]
-    let (client, connection) = tokio_postgres::connect(&db_connection_config, NoTls)
-        .await
-        .unwrap();
-
-    tokio::spawn(async move {
-        if let Err(e) = connection.await {
-            eprintln!("connection error: {}", e);
-        }
-    });
-    let dbhd = DBHandler { client };
-    let cves = dbhd.get_all_cvelist().await.unwrap();
+pub async fn get_cves(pools: &ConnectionPools) -> Result<HttpResponse, ApiError> {
+    let dbhd = pools.pg().await?;
+    let cves = dbhd.get_all_cvelist().await?;
 
-    HttpResponse::Ok().json(cves)
+    Ok(HttpResponse::Ok().json(cves))
 }
 
 /// 获取所有crates
@@ -197,24 +313,27 @@ pub async fn get_cves() -> impl Responder {
     path = "/api/crates",
     responses(
         (status = 200, description = "成功获取所有crate的id", body = Vec<model::tugraph_model::Program>),
-        (status = 404, description = "未找到crate id")
+        (status = 500, description = "服务器内部错误", body = ApiErrorBody),
+        (status = 503, description = "后端服务不可用", body = ApiErrorBody)
     ),
     tag = "crates"
 )]
-pub async fn get_all_crates() -> impl Responder {
+pub async fn get_all_crates(pools: &ConnectionPools) -> Result<HttpResponse, ApiError> {
     tracing::info!("get all crates func run");
-    let handler = get_tugraph_api_handler().await;
-    let ids = handler.reader.get_all_programs_id().await;
+    let handler = pools.api_handler();
+    let ids = handler.reader.get_all_programs_id().await?;
 
     let mut programs = vec![];
     for id in &ids {
-        let program = handler.reader.get_program(id).await.unwrap();
-        programs.push(program);
+        // 列出 id 和读取程序之间可能被删除，跳过即可
+        if let Some(program) = handler.reader.get_program(id).await? {
+            programs.push(program);
+        }
     }
 
     tracing::info!("finish get all crates func");
 
-    HttpResponse::Ok().json(programs) // 返回 JSON 格式
+    Ok(HttpResponse::Ok().json(programs)) // 返回 JSON 格式
 }
 
 /// 获取crate详细信息,ok
@@ -226,32 +345,30 @@ pub async fn get_all_crates() -> impl Responder {
     ),
     responses(
         (status = 200, description = "成功获取crate详细信息", body= (Program, UProgram, Vec<VersionInfo>)),
-        (status = 404, description = "未找到crate"),
-        (status = 500, description = "服务器内部错误")
+        (status = 404, description = "未找到crate", body = ApiErrorBody),
+        (status = 500, description = "服务器内部错误", body = ApiErrorBody),
+        (status = 503, description = "后端服务不可用", body = ApiErrorBody)
     ),
     tag = "crates"
 )]
-pub async fn get_crate_details(crate_name: web::Path<String>) -> impl Responder {
-    let handler = get_tugraph_api_handler().await;
-    match handler.reader.get_program(&crate_name).await {
-        Ok(program) => {
-            match handler.reader.get_type(&crate_name).await {
-                Ok((uprogram, islib)) => {
-                    match handler.reader.get_versions(&crate_name, islib).await {
-                        Ok(versions) => {
-                            HttpResponse::Ok().json((program, uprogram, versions))
-                            // 返回 JSON 格式
-                        }
-                        Err(_) => {
-                            HttpResponse::InternalServerError().body("Failed to get versions.")
-                        }
-                    }
-                }
-                Err(_) => HttpResponse::InternalServerError().body("Failed to get type."),
-            }
-        }
-        Err(_) => HttpResponse::NotFound().body("Crate not found."),
-    }
+pub async fn get_crate_details(
+    pools: &ConnectionPools,
+    crate_name: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let handler = pools.api_handler();
+    let not_found = || ApiError::NotFound(format!("crate {}", crate_name));
+    let program = handler
+        .reader
+        .get_program(&crate_name)
+        .await?
+        .ok_or_else(not_found)?;
+    let (uprogram, islib) = handler
+        .reader
+        .get_type(&crate_name)
+        .await?
+        .ok_or_else(not_found)?;
+    let versions = handler.reader.get_versions(&crate_name, islib).await?;
+    Ok(HttpResponse::Ok().json((program, uprogram, versions)))
 }
 
 /// 获取直接依赖关系图,ok
@@ -260,23 +377,42 @@ pub async fn get_crate_details(crate_name: web::Path<String>) -> impl Responder
     path = "/api/graph/{cratename}/{version}/direct",
     params(
         ("cratename" = String, Path, description = "crate 名称"),
-        ("version" = String, Path, description = "版本号")
+        ("version" = String, Path, description = "版本号"),
+        ("kind" = Option<String>, Query, description = "逗号分隔的依赖类型：normal、dev、build，默认全部"),
+        ("features" = Option<String>, Query, description = "default 只保留默认 feature 下启用的依赖，all 保留全部"),
+        ("target_os" = Option<String>, Query, description = "只保留作用于该 target_os 的依赖，如 linux")
     ),
     responses(
         (status = 200, description = "成功获取依赖关系图", body = Vec<NameVersion>),
-        (status = 404, description = "未找到依赖关系图")
+        (status = 400, description = "参数不合法", body = ApiErrorBody),
+        (status = 500, description = "服务器内部错误", body = ApiErrorBody),
+        (status = 503, description = "后端服务不可用", body = ApiErrorBody)
     ),
     tag = "dependencies"
 )]
-pub async fn get_direct_dep_for_graph(nname: String, nversion: String) -> impl Responder {
-    let handler = get_tugraph_api_handler().await;
+pub async fn get_direct_dep_for_graph(
+    pools: &ConnectionPools,
+    nname: String,
+    nversion: String,
+    filter: DependencyFilterQuery,
+) -> Result<HttpResponse, ApiError> {
+    let filter = DependencyFilter::try_from(filter)?;
+    let handler = pools.api_handler();
     let name_and_version = nname + "/" + &nversion;
-    let res = handler
+    let edges = handler
         .reader
-        .get_direct_dependency_nodes(&name_and_version)
-        .await
-        .unwrap();
-    HttpResponse::Ok().json(res)
+        .get_direct_dependency_edges(vec![name_and_version.clone()])
+        .await?;
+    let deps: HashSet<String> = edges
+        .into_iter()
+        .filter(|e| filter.matches(e, &name_and_version))
+        .map(|e| e.to)
+        .collect();
+    let res: Vec<NameVersion> = deps
+        .iter()
+        .filter_map(|nv| NameVersion::from_string(nv))
+        .collect();
+    Ok(HttpResponse::Ok().json(res))
 }
 
 /*pub async fn get_max_version(versions: Vec<String>) -> Result<String, Box<dyn Error>> {
@@ -438,40 +574,48 @@ pub async fn get_direct_dep_for_graph(nname: String, nversion: String) -> impl R
     HttpResponse::Ok().json(res_deps)
 }*/
 
-/// 查询 crates
+/// 搜索接口每页条数上限
+const MAX_SEARCH_PER_PAGE: usize = 100;
+
+/// 查询 crates，支持按类型、许可证、漏洞、评估分数、下载量和更新时间过滤，并返回分面计数
 #[utoipa::path(
     post,
     path = "/api/search",
     request_body = Query,
     responses(
         (status = 200, description = "查询成功", body = QueryCratesInfo),
-        (status = 400, description = "无效的查询参数")
+        (status = 400, description = "无效的查询参数", body = ApiErrorBody),
+        (status = 500, description = "服务器内部错误", body = ApiErrorBody),
+        (status = 503, description = "后端服务不可用", body = ApiErrorBody)
     ),
     tag = "search"
 )]
-pub async fn query_crates(q: Query) -> impl Responder {
-    let _handler = get_tugraph_api_handler().await;
+pub async fn query_crates(
+    pools: &ConnectionPools,
+    search: &SearchConfig,
+    q: Query,
+) -> Result<HttpResponse, ApiError> {
     //add yj's search module
     let name = q.query;
     let page = q.pagination.page;
     let per_page = q.pagination.per_page;
     tracing::info!("name:{},page:{},per_page:{}", name, page, per_page);
-    let db_connection_config = db_connection_config_from_env();
-    let (client, connection) = tokio_postgres::connect(&db_connection_config, NoTls)
-        .await
-        .unwrap();
-    tokio::spawn(async move {
-        if let Err(e) = connection.await {
-            eprintln!("connection error: {}", e);
-        }
-    });
+    if page == 0 || per_page == 0 || per_page > MAX_SEARCH_PER_PAGE {
+        return Err(ApiError::BadRequest(format!(
+            "page must be greater than 0 and per_page must be between 1 and {}",
+            MAX_SEARCH_PER_PAGE
+        )));
+    }
+    q.filters.validate().map_err(ApiError::BadRequest)?;
+    let dbhandler = pools.pg().await?;
     let start_time2 = Instant::now();
     let question = name.clone();
-    let search_module = SearchModule::new(&client).await;
-    let res = search_module
-        .search_crate(&question, SearchSortCriteria::Relavance)
-        .await
-        .unwrap();
+    let search_module = SearchModule::new(&dbhandler.client, search).await;
+    let SearchResults {
+        crates: res,
+        facets,
+        fuzzy,
+    } = search_module.search(&question, &q.filters, q.sort).await?;
     tracing::trace!("search need time:{:?}", start_time2.elapsed());
     let mut seen = HashSet::new();
     let uniq_res: Vec<RecommendCrate> = res
@@ -484,18 +628,16 @@ pub async fn query_crates(q: Query) -> impl Responder {
         gettotal_page += 1;
     }
     let mut getitems = vec![];
-    for i in (page - 1) * 20..(page - 1) * 20 + 20 {
-        if i >= uniq_res.len() {
-            break;
-        }
+    let offset = (page - 1).saturating_mul(per_page);
+    for item in uniq_res.iter().skip(offset).take(per_page) {
         let mut mv = vec![];
-        let program_name = uniq_res[i].clone().name;
-        let getnamespace = uniq_res[i].clone().namespace;
-        let parts: Vec<&str> = getnamespace.split('/').collect();
-        let nsf = parts[0].to_string();
-        let nsb = parts[1].to_string();
+        let program_name = item.name.clone();
+        let getnamespace = item.namespace.clone();
+        let (nsf, nsb) = getnamespace
+            .split_once('/')
+            .unwrap_or((getnamespace.as_str(), ""));
 
-        mv.push(uniq_res[i].clone().max_version);
+        mv.push(item.max_version.clone());
 
         if mv[0].clone() == *"null" {
             mv[0] = "0.0.0".to_string();
@@ -504,8 +646,8 @@ pub async fn query_crates(q: Query) -> impl Responder {
             name: program_name.clone(),
             version: mv[0].clone(),
             date: "".to_string(),
-            nsfront: nsf,
-            nsbehind: nsb,
+            nsfront: nsf.to_string(),
+            nsbehind: nsb.to_string(),
         };
         getitems.push(query_item);
     }
@@ -514,22 +656,67 @@ pub async fn query_crates(q: Query) -> impl Responder {
         message: "成功".to_string(),
         data: QueryData {
             total_page: gettotal_page,
+            total: uniq_res.len(),
             items: getitems,
+            facets,
+            fuzzy,
         },
     };
 
-    HttpResponse::Ok().json(response)
+    Ok(HttpResponse::Ok().json(response))
+}
+/// 自动补全每次最多返回的名称数
+const MAX_SUGGEST_LIMIT: i64 = 50;
+
+/// 名称自动补全，前缀匹配的名称按下载量排在前面，不足时补充拼写相近的名称
+#[utoipa::path(
+    get,
+    path = "/api/search/suggest",
+    params(
+        ("q" = String, Query, description = "用户已输入的名称前缀"),
+        ("limit" = Option<i64>, Query, description = "返回的名称数，默认 10，最大 50")
+    ),
+    responses(
+        (status = 200, description = "候选名称", body = Vec<Suggestion>),
+        (status = 400, description = "limit 超出范围", body = ApiErrorBody),
+        (status = 500, description = "服务器内部错误", body = ApiErrorBody),
+        (status = 503, description = "后端服务不可用", body = ApiErrorBody)
+    ),
+    tag = "search"
+)]
+pub async fn suggest_crates(
+    pools: &ConnectionPools,
+    search: &SearchConfig,
+    q: SuggestQuery,
+) -> Result<HttpResponse, ApiError> {
+    let limit = q.limit.unwrap_or(10);
+    if !(1..=MAX_SUGGEST_LIMIT).contains(&limit) {
+        return Err(ApiError::BadRequest(format!(
+            "limit must be between 1 and {}",
+            MAX_SUGGEST_LIMIT
+        )));
+    }
+    let dbhandler = pools.pg().await?;
+    let search_module = SearchModule::new(&dbhandler.client, search).await;
+    let suggestions = search_module.suggest(&q.q, limit).await?;
+    Ok(HttpResponse::Ok().json(suggestions))
 }
 //post of upload
-#[allow(clippy::let_unit_value)]
-pub async fn upload_crate(mut payload: Multipart) -> impl Responder {
+/// 上传者以 API Key 对应的用户为准，表单中的 `user_email` 字段不再使用
+pub async fn upload_crate(
+    pools: &ConnectionPools,
+    principal: Principal,
+    mut payload: Multipart,
+) -> Result<HttpResponse, ApiError> {
     tracing::info!("enter upload crate");
     use futures_util::StreamExt as _;
     let mut upload_time: Option<String> = None;
-    let mut user_email: Option<String> = None;
+    let user_email = Some(principal.email);
     let mut github_link: Option<String> = None;
     let mut file_name: Option<String> = None;
-    while let Some(Ok(mut field)) = payload.next().await {
+    let job_id = uuid::Uuid::new_v4().to_string();
+    while let Some(field) = payload.next().await {
+        let mut field = field.map_err(|e| ApiError::BadRequest(e.to_string()))?;
         tracing::info!("enter while");
         if let Some(content_disposition) = field.content_disposition().cloned() {
             tracing::info!("enter first if");
@@ -538,25 +725,16 @@ pub async fn upload_crate(mut payload: Multipart) -> impl Responder {
                 match name {
                     "file" => {
                         tracing::info!("enter match file");
-                        file_name = process_file_of_upload_crate(&content_disposition, &mut field)
-                            .await
-                            .unwrap();
-                        // analyze
+                        file_name =
+                            process_file_of_upload_crate(&job_id, &content_disposition, &mut field)
+                                .await?;
                     }
                     "githubLink" => {
-                        github_link = process_githublink_of_upload_crate(&mut field)
-                            .await
-                            .unwrap();
+                        github_link = process_githublink_of_upload_crate(&mut field).await?;
                     }
                     "uploadTime" => {
                         tracing::info!("enter match uploadtime");
-                        upload_time = process_uploadtime_of_upload_crate(&mut field)
-                            .await
-                            .unwrap();
-                    }
-                    "user_email" => {
-                        tracing::info!("enter match user_email");
-                        user_email = process_useremail_of_upload_crate(&mut field).await.unwrap();
+                        upload_time = process_uploadtime_of_upload_crate(&mut field).await?;
                     }
                     _ => {
                         tracing::info!("enter match nothing");
@@ -565,30 +743,63 @@ pub async fn upload_crate(mut payload: Multipart) -> impl Responder {
             }
         }
     }
-    let _ = process_insertintopg_of_upload_crate(file_name, upload_time, github_link, user_email)
-        .await
-        .unwrap();
-    HttpResponse::Ok().json(())
+    let job_id = match &file_name {
+        Some(source) => {
+            start_upload_job(
+                pools,
+                &job_id,
+                source,
+                user_email.as_deref().unwrap_or_default(),
+                upload_time.as_deref().unwrap_or_default(),
+            )
+            .await?;
+            Some(job_id)
+        }
+        None => None,
+    };
+    process_insertintopg_of_upload_crate(pools, file_name, upload_time, github_link, user_email)
+        .await?;
+    Ok(HttpResponse::Accepted().json(UploadAccepted { job_id }))
+}
+/// 为上传的文件创建任务，zip 项目在后台解压并导入
+async fn start_upload_job(
+    pools: &ConnectionPools,
+    job_id: &str,
+    source: &str,
+    email: &str,
+    upload_time: &str,
+) -> Result<(), ApiError> {
+    let dbhandler = pools.pg().await?;
+    dbhandler
+        .create_upload_job(job_id, email, source, upload_time)
+        .await?;
+    let sanitized_filename = sanitize(source);
+    let crate_name = match archive::crate_name(&sanitized_filename) {
+        Some(stem) => stem.to_string(),
+        None => {
+            dbhandler
+                .advance_upload_job(
+                    job_id,
+                    JobState::Failed,
+                    Some("only .zip, .crate and .tar.gz archives can be imported"),
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+    jobs::spawn_upload_job(pools.clone(), job_id.to_string(), crate_name);
+    Ok(())
 }
 pub async fn process_insertintopg_of_upload_crate(
+    pools: &ConnectionPools,
     file_name: Option<String>,
     upload_time: Option<String>,
     github_link: Option<String>,
     user_email: Option<String>,
-) -> Result<(), Box<dyn Error>> {
+) -> Result<(), ApiError> {
     if let Some(filename) = file_name {
         tracing::info!("enter 1/2 if let");
-        let db_connection_config = db_connection_config_from_env();
-        #[allow(unused_variables)]
-        let (client, connection) = tokio_postgres::connect(&db_connection_config, NoTls)
-            .await
-            .unwrap();
-        tokio::spawn(async move {
-            if let Err(e) = connection.await {
-                eprintln!("connection error: {}", e);
-            }
-        });
-        let dbhandler = DBHandler { client };
+        let dbhandler = pools.pg().await?;
         if let Some(uploadtime) = upload_time.clone() {
             tracing::info!("enter upload time:{}", uploadtime.clone());
             if let Some(useremail) = user_email.clone() {
@@ -599,24 +810,13 @@ pub async fn process_insertintopg_of_upload_crate(
                         "INSERT INTO uploadedcrate(email,filename,uploadtime) VALUES ($1, $2,$3);",
                         &[&useremail.clone(), &filename.clone(), &uploadtime.clone()],
                     )
-                    .await
-                    .unwrap();
+                    .await?;
             }
         }
     };
     if let Some(githublink) = github_link {
         tracing::info!("enter 2/2 if let");
-        let db_connection_config = db_connection_config_from_env();
-        #[allow(unused_variables)]
-        let (client, connection) = tokio_postgres::connect(&db_connection_config, NoTls)
-            .await
-            .unwrap();
-        tokio::spawn(async move {
-            if let Err(e) = connection.await {
-                eprintln!("connection error: {}", e);
-            }
-        });
-        let dbhandler = DBHandler { client };
+        let dbhandler = pools.pg().await?;
         if let Some(uploadtime) = upload_time.clone() {
             if let Some(useremail) = user_email.clone() {
                 dbhandler
@@ -625,63 +825,41 @@ pub async fn process_insertintopg_of_upload_crate(
                         "INSERT INTO uploadedurl(email,githuburl,uploadtime) VALUES ($1, $2,$3);",
                         &[&useremail.clone(), &githublink.clone(), &uploadtime.clone()],
                     )
-                    .await
-                    .unwrap();
+                    .await?;
             }
         }
     }
     Ok(())
 }
-#[allow(unused_assignments)]
-pub async fn process_useremail_of_upload_crate(
-    field: &mut Field,
-) -> Result<Option<String>, Box<dyn Error>> {
-    let mut user_email: Option<String> = None;
-    let mut email_data = Vec::new();
-
+/// 读取 multipart 字段的全部内容
+async fn read_field(field: &mut Field) -> Result<Vec<u8>, ApiError> {
+    let mut data = Vec::new();
     while let Some(chunk) = field.next().await {
-        let data = chunk.unwrap();
-        email_data.extend_from_slice(&data);
+        let chunk = chunk.map_err(|e| ApiError::BadRequest(e.to_string()))?;
+        data.extend_from_slice(&chunk);
     }
-    user_email = Some(String::from_utf8(email_data).unwrap_or_default());
-    tracing::info!("user_email:{:?}", user_email);
-    Ok(user_email)
+    Ok(data)
 }
-#[allow(unused_assignments)]
 pub async fn process_uploadtime_of_upload_crate(
     field: &mut Field,
-) -> Result<Option<String>, Box<dyn Error>> {
-    let mut upload_time: Option<String> = None;
-    let mut time_data = Vec::new();
-
-    while let Some(chunk) = field.next().await {
-        let data = chunk.unwrap();
-        time_data.extend_from_slice(&data);
-    }
-    upload_time = Some(String::from_utf8(time_data).unwrap_or_default());
+) -> Result<Option<String>, ApiError> {
+    let time_data = read_field(field).await?;
+    let upload_time = Some(String::from_utf8(time_data).unwrap_or_default());
     tracing::info!("uploadtime:{:?}", upload_time);
     Ok(upload_time)
 }
-#[allow(unused_assignments)]
 pub async fn process_githublink_of_upload_crate(
     field: &mut Field,
-) -> Result<Option<String>, Box<dyn Error>> {
-    let mut github_link: Option<String> = None;
-    let mut url_data = Vec::new();
-    while let Some(chunk) = field.next().await {
-        let data = chunk.unwrap();
-        url_data.extend_from_slice(&data);
-    }
-    github_link = Some(String::from_utf8(url_data).unwrap_or_default());
-    Ok(github_link)
+) -> Result<Option<String>, ApiError> {
+    let url_data = read_field(field).await?;
+    Ok(Some(String::from_utf8(url_data).unwrap_or_default()))
 }
-#[allow(unused_assignments)]
+/// 保存上传的文件，归档保存为 `jobs::archive_path(job_id)`，返回原文件名
 pub async fn process_file_of_upload_crate(
+    job_id: &str,
     content_disposition: &ContentDisposition,
     field: &mut Field,
-) -> Result<Option<String>, Box<dyn Error>> {
-    tracing::info!("enter match file");
-    let mut file_name: Option<String> = None;
+) -> Result<Option<String>, ApiError> {
     let filename = if let Some(file_name) = content_disposition.get_filename() {
         file_name.to_string()
     } else {
@@ -689,182 +867,138 @@ pub async fn process_file_of_upload_crate(
     };
     tracing::info!("filename:{}", filename.clone());
     let sanitized_filename = sanitize(filename.clone());
-    file_name = Some(filename.clone());
-    tracing::info!("file_name:{:?}", file_name.clone());
-    if sanitized_filename.ends_with(".zip") {
-        tracing::info!("enter file zip");
-        let zip_filepath = format!("target/zip/upload/{}", sanitized_filename);
-        let _ = tokio::fs::create_dir_all("target/zip/upload/").await;
-        let mut f = tokio::fs::File::create(&zip_filepath).await.unwrap();
-        while let Some(chunk) = field.next().await {
-            let data = chunk.unwrap();
-            f.write_all(&data).await.unwrap();
-        }
-        let parts: Vec<&str> = sanitized_filename.split('.').collect();
-        let mut filename = "".to_string();
-        if parts.len() >= 2 {
-            filename = parts[0].to_string();
-            tracing::info!("filename without zip: {}", filename);
-        }
-        let mut zip_file = tokio::fs::File::open(&zip_filepath).await.unwrap();
-        let mut buffer = Vec::new();
-        zip_file.read_to_end(&mut buffer).await.unwrap();
-        let reader = Cursor::new(buffer.clone());
-        let mut archive = ZipArchive::new(reader).unwrap();
-        for i in 0..archive.len() {
-            let mut file = archive.by_index(i).unwrap();
-            let outpath = match file.enclosed_name() {
-                Some(path) => {
-                    format!("target/www/uploads/{}/{}", filename, path.display())
-                }
-                None => continue,
-            };
-
-            if file.name().ends_with('/') {
-                // This is a directory, create it
-                tokio::fs::create_dir_all(&outpath).await.unwrap();
-            } else {
-                // Ensure the parent directory exists
-                if let Some(parent) = std::path::Path::new(&outpath).parent() {
-                    if !parent.exists() {
-                        tokio::fs::create_dir_all(&parent).await.unwrap();
-                    }
-                }
-
-                // Write the file
-                let mut outfile = tokio::fs::File::create(&outpath).await.unwrap();
-                while let Ok(bytes_read) = file.read(&mut buffer) {
-                    if bytes_read == 0 {
-                        break;
-                    }
-                    outfile.write_all(&buffer[..bytes_read]).await.unwrap();
-                }
-            }
+    let is_archive = ArchiveFormat::from_name(&sanitized_filename).is_some();
+    let filepath = if is_archive {
+        let path = jobs::archive_path(job_id);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
         }
-        tracing::info!("finish match file");
-        //send message
+        path
     } else {
-        tracing::info!("enter else");
-        let filepath = format!("/home/rust/output/www/uploads/{}", sanitized_filename);
-        let mut f = tokio::fs::File::create(&filepath).await.unwrap();
+        std::path::PathBuf::from(format!(
+            "/home/rust/output/www/uploads/{}",
+            sanitized_filename
+        ))
+    };
+    let max_bytes = ArchiveLimits::from_env().max_archive_bytes;
+    let mut f = tokio::fs::File::create(&filepath).await?;
+    // 所有上传都受大小限制，写入失败或超限时删除已写入的部分
+    let result = write_upload(&mut f, field, max_bytes).await;
+    drop(f);
+    if let Err(e) = result {
+        let _ = tokio::fs::remove_file(&filepath).await;
+        return Err(e);
+    }
+    Ok(Some(filename))
+}
 
-        while let Some(chunk) = field.next().await {
-            let data = chunk.unwrap();
-            f.write_all(&data).await.unwrap();
+async fn write_upload(
+    f: &mut tokio::fs::File,
+    field: &mut Field,
+    max_bytes: u64,
+) -> Result<(), ApiError> {
+    let mut written = 0u64;
+    while let Some(chunk) = field.next().await {
+        let data = chunk.map_err(|e| ApiError::BadRequest(e.to_string()))?;
+        written += data.len() as u64;
+        if written > max_bytes {
+            return Err(ApiError::BadRequest(format!(
+                "upload is larger than {} bytes",
+                max_bytes
+            )));
         }
+        f.write_all(&data).await?;
     }
-    Ok(file_name)
+    f.flush().await?;
+    Ok(())
 }
 //post of log in
-pub async fn submituserinfo(info: Userinfo) -> impl Responder {
-    let db_connection_config = db_connection_config_from_env();
-    #[allow(unused_variables)]
-    let (client, connection) = tokio_postgres::connect(&db_connection_config, NoTls)
-        .await
-        .unwrap();
-    tokio::spawn(async move {
-        if let Err(e) = connection.await {
-            eprintln!("connection error: {}", e);
-        }
-    });
-    let dbhandler = DBHandler { client };
+pub async fn submituserinfo(
+    pools: &ConnectionPools,
+    principal: Principal,
+    mut info: Userinfo,
+) -> Result<HttpResponse, ApiError> {
+    // 以 API Key 对应的用户为准，不信任请求中的邮箱
+    info.user.email = principal.email;
+    let dbhandler = pools.pg().await?;
     tracing::info!("enter submituserinfo and set db client");
-    #[allow(clippy::let_unit_value)]
-    let _ = dbhandler
-        .insert_userinfo_into_pg(info.clone())
-        .await
-        .unwrap();
-    HttpResponse::Ok().json(())
+    dbhandler.insert_userinfo_into_pg(info.clone()).await?;
+    Ok(HttpResponse::Ok().json(()))
 }
-pub async fn query_upload_crate(email: String) -> impl Responder {
-    let db_connection_config = db_connection_config_from_env();
-    #[allow(unused_variables)]
-    let (client, connection) = tokio_postgres::connect(&db_connection_config, NoTls)
-        .await
-        .unwrap();
-    tokio::spawn(async move {
-        if let Err(e) = connection.await {
-            eprintln!("connection error: {}", e);
-        }
-    });
-    let dbhandler = DBHandler { client };
+pub async fn query_upload_crate(
+    pools: &ConnectionPools,
+    email: String,
+) -> Result<HttpResponse, ApiError> {
+    let dbhandler = pools.pg().await?;
     let mut real_res = vec![];
     let res = dbhandler
         .query_uploaded_crates_from_pg(email.clone())
-        .await
-        .unwrap();
+        .await?;
     for row in res {
         real_res.push(row);
     }
-    let res2 = dbhandler
-        .query_uploaded_url_from_pg(email.clone())
-        .await
-        .unwrap();
+    let res2 = dbhandler.query_uploaded_url_from_pg(email.clone()).await?;
     for row in res2 {
         real_res.push(row);
     }
-    HttpResponse::Ok().json(real_res)
+    Ok(HttpResponse::Ok().json(real_res))
 }
-pub async fn get_senseleak(nsfront: String, nsbehind: String) -> impl Responder {
-    let db_connection_config = db_connection_config_from_env();
-    #[allow(unused_variables)]
-    let (client, connection) = tokio_postgres::connect(&db_connection_config, NoTls)
-        .await
-        .unwrap();
-    tokio::spawn(async move {
-        if let Err(e) = connection.await {
-            eprintln!("connection error: {}", e);
-        }
-    });
-    let dbhandler = DBHandler { client };
+pub async fn get_senseleak(
+    pools: &ConnectionPools,
+    nsfront: String,
+    nsbehind: String,
+) -> Result<HttpResponse, ApiError> {
+    let dbhandler = pools.pg().await?;
     let id = nsfront.clone() + "/" + &nsbehind;
-    let res = dbhandler.get_senseleak_from_pg(id).await.unwrap();
+    let res = dbhandler.get_senseleak_from_pg(id).await?;
     let mut exist = true;
     if res.clone() == *"[]" {
         exist = false;
     }
     let return_val = SenseleakRes { exist, res };
-    HttpResponse::Ok().json(return_val)
+    Ok(HttpResponse::Ok().json(return_val))
 }
-pub async fn get_mirchecker(nsfront: String, nsbehind: String,name:String,version:String) -> impl Responder {
-    let db_connection_config = db_connection_config_from_env();
-    #[allow(unused_variables)]
-    let (client, connection) = tokio_postgres::connect(&db_connection_config, NoTls)
-        .await
-        .unwrap();
-    tokio::spawn(async move {
-        if let Err(e) = connection.await {
-            eprintln!("connection error: {}", e);
-        }
-    });
-    let dbhandler = DBHandler { client };
-    let id = nsfront.clone() + "/" + &nsbehind +"/" + &name + "/" +&version;
-    let run_state = dbhandler.get_mirchecker_run_state_from_pg(id.clone()).await.unwrap();
-    let res = dbhandler.get_mirchecker_from_pg(id.clone()).await.unwrap();
+pub async fn get_mirchecker(
+    pools: &ConnectionPools,
+    nsfront: String,
+    nsbehind: String,
+    name: String,
+    version: String,
+) -> Result<HttpResponse, ApiError> {
+    let dbhandler = pools.pg().await?;
+    let id = nsfront.clone() + "/" + &nsbehind + "/" + &name + "/" + &version;
+    let run_state = dbhandler
+        .get_mirchecker_run_state_from_pg(id.clone())
+        .await?;
+    let res = dbhandler.get_mirchecker_from_pg(id.clone()).await?;
     let mut exist = false;
-    if res.contains("warning: [MirChecker]"){
+    if res.contains("warning: [MirChecker]") {
         exist = true;
     }
-    let return_val = MircheckerRes { run_state,exist, res };
-    HttpResponse::Ok().json(return_val)
+    let return_val = MircheckerRes {
+        run_state,
+        exist,
+        res,
+    };
+    Ok(HttpResponse::Ok().json(return_val))
 }
 
 pub async fn new_get_crates_front_info_from_redis(
+    pools: &ConnectionPools,
     nname: String,
     nversion: String,
     nsfront: String,
     nsbehind: String,
-) -> impl Responder {
-    let handler = get_tugraph_api_handler().await;
+) -> Result<HttpResponse, ApiError> {
+    let handler = pools.api_handler();
     let namespace = nsfront.clone() + "/" + &nsbehind.clone();
 
-    let conn = get_redis_connection().await.unwrap();
-    let mut redisconn = RedisHandler { connection: conn };
-    let qid = format!("crates_info:{}:{}:{}", namespace, nname, nversion);
-    let qres = redisconn.query_from_redis(qid).await.unwrap();
-    println!("finish query crates from reids");
-    if qres.is_empty() {
-        println!("qres is empty");
+    let mut redisconn = pools.redis().await?;
+    let key = CacheKey::version(&namespace, &nname, &nversion);
+    let cached = redisconn.get_cached::<Crateinfo>(&key).await?;
+    if let Some(res) = cached {
+        Ok(HttpResponse::Ok().json(res))
+    } else {
         let res = handler
             .reader
             .get_crates_front_info_from_tg(
@@ -873,38 +1007,57 @@ pub async fn new_get_crates_front_info_from_redis(
                 nsfront.clone(),
                 nsbehind.clone(),
             )
-            .await
-            .unwrap();
-        println!("finish get crates_info from tugraph");
-        let val = serde_json::to_string(&res).unwrap();
-        redisconn
-            .insert_crates_info_into_redis(
-                namespace.clone(),
-                nname.clone(),
-                nversion.clone(),
-                val.clone(),
-            )
-            .await
-            .unwrap();
-        HttpResponse::Ok().json(res)
-    } else {
-        let res: Crateinfo = serde_json::from_str(&qres).unwrap();
-        HttpResponse::Ok().json(res.clone())
+            .await?
+            .ok_or_else(|| {
+                ApiError::NotFound(format!("{} {} in {}", nname, nversion, namespace))
+            })?;
+        redisconn.set_cached(&key, &res).await;
+        Ok(HttpResponse::Ok().json(res))
     }
 }
+/// 获取依赖列表，支持分页、排序和过滤
+#[utoipa::path(
+    get,
+    path = "/api/crates/{nsfront}/{nsbehind}/{cratename}/{version}/dependencies",
+    params(
+        ("nsfront" = String, Path, description = "命名空间前半部分"),
+        ("nsbehind" = String, Path, description = "命名空间后半部分"),
+        ("cratename" = String, Path, description = "crate 名称"),
+        ("version" = String, Path, description = "版本号"),
+        ("page" = Option<usize>, Query, description = "页码，从 1 开始，给出 cursor 时忽略"),
+        ("per_page" = Option<usize>, Query, description = "每页条数，默认 20，最大 100"),
+        ("sort" = Option<DepSort>, Query, description = "name（默认）、downloads 或 dependents"),
+        ("relation" = Option<Relation>, Query, description = "只返回 direct 或 indirect 依赖"),
+        ("has_advisory" = Option<bool>, Query, description = "是否有 RustSec 漏洞"),
+        ("license" = Option<String>, Query, description = "许可证标识符，如 MIT"),
+        ("cursor" = Option<String>, Query, description = "上一页返回的 next_cursor")
+    ),
+    responses(
+        (status = 200, description = "成功获取依赖列表", body = DependencyInfo),
+        (status = 400, description = "参数不合法", body = ApiErrorBody),
+        (status = 500, description = "服务器内部错误", body = ApiErrorBody),
+        (status = 503, description = "后端服务不可用", body = ApiErrorBody)
+    ),
+    tag = "dependencies"
+)]
 pub async fn dependency_redis_cache(
+    pools: &ConnectionPools,
     name: String,
     version: String,
     nsfront: String,
     nsbehind: String,
-) -> impl Responder {
-    let handler = get_tugraph_api_handler().await;
-    let conn = get_redis_connection().await.unwrap();
-    let mut redisconn = RedisHandler { connection: conn };
+    query: DepListQuery,
+) -> Result<HttpResponse, ApiError> {
+    let params = DepListParams::try_from(query)?;
+    let handler = pools.api_handler();
+    let mut redisconn = pools.redis().await?;
     let namespace = nsfront.clone() + "/" + &nsbehind.clone();
-    let qid = format!("dependency:{}:{}:{}", namespace, name, version);
-    let res = redisconn.query_from_redis(qid.clone()).await.unwrap();
-    if res.is_empty() {
+    // 缓存未分页的完整列表
+    let key = CacheKey::version(&namespace, &name, &version);
+    let cached = redisconn.get_cached::<DependencyInfo>(&key).await?;
+    let all_deps = if let Some(all_deps) = cached {
+        all_deps
+    } else {
         let res_deps = handler
             .reader
             .get_dependency_from_tg(
@@ -913,54 +1066,50 @@ pub async fn dependency_redis_cache(
                 nsfront.clone(),
                 nsbehind.clone(),
             )
-            .await
-            .unwrap();
-        let val = serde_json::to_string(&res_deps).unwrap();
-        redisconn
-            .insert_dependency_into_redis(
-                namespace.clone(),
-                name.clone(),
-                version.clone(),
-                val.clone(),
-            )
-            .await
-            .unwrap();
-        HttpResponse::Ok().json(res_deps.clone())
-    } else {
-        let res_deps: DependencyInfo = serde_json::from_str(&res).unwrap();
-        HttpResponse::Ok().json(res_deps.clone())
+            .await?;
+        redisconn.set_cached(&key, &res_deps).await;
+        res_deps
+    };
+    let page = params.apply(all_deps.data)?;
+    let mut data = page.data;
+    for dep in data.iter_mut() {
+        dep.dependencies = handler
+            .reader
+            .count_dependencies(NameVersion {
+                name: dep.crate_name.clone(),
+                version: dep.version.clone(),
+            })
+            .await?;
     }
+    Ok(HttpResponse::Ok().json(DependencyInfo {
+        direct_count: all_deps.direct_count,
+        indirect_count: all_deps.indirect_count,
+        truncated: all_deps.truncated,
+        total: page.total,
+        page: page.page,
+        per_page: page.per_page,
+        next_cursor: page.next_cursor,
+        data,
+    }))
 }
 pub async fn new_get_graph(
+    pools: &ConnectionPools,
     nsfront: String,
     nsbehind: String,
     nname: String,
     nversion: String,
-) -> impl Responder {
-    let handler = get_tugraph_api_handler().await;
-    let db_connection_config = db_connection_config_from_env();
-    #[allow(unused_variables)]
-    let (client, connection) = tokio_postgres::connect(&db_connection_config, NoTls)
-        .await
-        .unwrap();
-    tokio::spawn(async move {
-        if let Err(e) = connection.await {
-            eprintln!("connection error: {}", e);
-        }
-    });
-    let dbhandler = DBHandler { client };
-    let conn = get_redis_connection().await.unwrap();
-    let mut redisconn = RedisHandler { connection: conn };
+) -> Result<HttpResponse, ApiError> {
+    let handler = pools.api_handler();
+    let dbhandler = pools.pg().await?;
+    let mut redisconn = pools.redis().await?;
     let namespace = nsfront.clone() + "/" + &nsbehind.clone();
-    let qid = format!("dependencygraph:{}:{}:{}", namespace, nname, nversion);
-    let qres = redisconn.query_from_redis(qid.clone()).await.unwrap();
-    if qres.is_empty() {
-        tracing::info!("first time");
+    let key = CacheKey::version(&namespace, &nname, &nversion);
+    let cached = redisconn.get_cached::<Deptree>(&key).await?;
+    if let Some(res_tree) = cached {
+        Ok(HttpResponse::Ok().json(res_tree))
+    } else {
         let nav = nname.clone() + "/" + &nversion;
-        let rustcve = dbhandler
-            .get_direct_rustsec(&nname, &nversion)
-            .await
-            .unwrap();
+        let rustcve = dbhandler.get_direct_rustsec(&nname, &nversion).await?;
         let mut res = Deptree {
             name_and_version: nav.clone(),
             cve_count: rustcve.len(),
@@ -968,41 +1117,909 @@ pub async fn new_get_graph(
         };
         let mut visited = HashSet::new();
         visited.insert(nav.clone());
+        handler.reader.build_graph(&mut res, &mut visited).await?;
+        redisconn.set_cached(&key, &res).await;
+        Ok(HttpResponse::Ok().json(res))
+    }
+}
+/// 获取有界的依赖图，返回去重后的节点和全部依赖边
+#[utoipa::path(
+    get,
+    path = "/api/crates/{nsfront}/{nsbehind}/{cratename}/{version}/dependencies/graph",
+    params(
+        ("nsfront" = String, Path, description = "命名空间前半部分"),
+        ("nsbehind" = String, Path, description = "命名空间后半部分"),
+        ("cratename" = String, Path, description = "crate 名称"),
+        ("version" = String, Path, description = "版本号"),
+        ("max_depth" = Option<usize>, Query, description = "最大遍历深度，默认 10，最大 50"),
+        ("max_nodes" = Option<usize>, Query, description = "最多返回的节点数，默认 500，最大 5000"),
+        ("resolution" = Option<Resolution>, Query, description = "declared 为声明的依赖图（默认），locked 为应用版本 Cargo.lock 中实际锁定的依赖图，locked 图不支持 kind/features/target_os 过滤"),
+        ("kind" = Option<String>, Query, description = "逗号分隔的依赖类型：normal、dev、build，默认全部"),
+        ("features" = Option<String>, Query, description = "default 只保留默认 feature 下启用的依赖，all 保留全部"),
+        ("target_os" = Option<String>, Query, description = "只保留作用于该 target_os 的依赖，如 linux")
+    ),
+    responses(
+        (status = 200, description = "成功获取依赖图", body = DependencyGraph),
+        (status = 400, description = "参数不合法", body = ApiErrorBody),
+        (status = 500, description = "服务器内部错误", body = ApiErrorBody),
+        (status = 503, description = "后端服务不可用", body = ApiErrorBody)
+    ),
+    tag = "dependencies"
+)]
+pub async fn get_dependency_graph(
+    pools: &ConnectionPools,
+    nname: String,
+    nversion: String,
+    query: GraphQuery,
+    filter: DependencyFilterQuery,
+) -> Result<HttpResponse, ApiError> {
+    let resolution = query.resolution.unwrap_or_default();
+    let limits = GraphLimits::try_from(query)?;
+    let filter = DependencyFilter::try_from(filter)?;
+    let handler = pools.api_handler();
+    let root = nname + "/" + &nversion;
+    let mut graph = build_dependency_graph(&root, limits, |level| async {
+        let edges = match resolution {
+            Resolution::Declared => handler.reader.get_direct_dependency_edges(level).await?,
+            // Cargo.lock 已是实际构建的结果，没有 kind/target 等属性，不再过滤
+            Resolution::Locked => {
+                return Ok(handler
+                    .reader
+                    .get_locked_dependency_edges(&root, level)
+                    .await?)
+            }
+        };
+        Ok::<_, ApiError>(
+            edges
+                .into_iter()
+                .filter(|e| filter.matches(e, &root))
+                .collect(),
+        )
+    })
+    .await?;
+    graph.resolution = resolution;
+    let dbhandler = pools.pg().await?;
+    for node in graph.nodes.iter_mut() {
+        node.cve_count = dbhandler
+            .get_direct_rustsec(&node.name, &node.version)
+            .await?
+            .len();
+    }
+    Ok(HttpResponse::Ok().json(graph))
+}
+/// 收集一个版本的传递依赖、依赖边、许可证和 RustSec 漏洞，用于生成 SBOM
+pub async fn build_sbom(
+    pools: &ConnectionPools,
+    nsfront: String,
+    nsbehind: String,
+    nname: String,
+    nversion: String,
+) -> Result<Sbom, ApiError> {
+    let handler = pools.api_handler();
+    let namespace = nsfront + "/" + &nsbehind;
+    let root = nname.clone() + "/" + &nversion;
+    let all = handler
+        .reader
+        .new_get_all_dependencies(namespace.clone(), root.clone())
+        .await?;
+    if all.truncated {
+        tracing::warn!("sbom of {} in {} is truncated", root, namespace);
+    }
+    let mut ids: Vec<String> = all.nodes.into_iter().filter(|id| *id != root).collect();
+    ids.sort();
+
+    let mut level = ids.clone();
+    level.push(root.clone());
+    // dev/build 依赖不随发布的产物分发，不进入清单
+    let dependencies = normal_only(handler.reader.get_direct_dependency_edges(level).await?)
+        .into_iter()
+        .map(|e| (e.from, e.to))
+        .collect();
+
+    let dbhandler = pools.pg().await?;
+    let names: Vec<String> = ids
+        .iter()
+        .filter_map(|id| id.split_once('/').map(|(name, _)| name.to_string()))
+        .collect();
+    let licenses = dbhandler.get_licenses_by_names(&names).await?;
+    let mut components = vec![];
+    for id in &ids {
+        let Some((name, version)) = id.split_once('/') else {
+            continue;
+        };
+        components.push(SbomComponent {
+            name: name.to_string(),
+            version: version.to_string(),
+            license: licenses.get(name).cloned(),
+            advisories: dbhandler.get_direct_rustsec(name, version).await?,
+        });
+    }
+    // get_license_by_name 末尾总会追加 "None"
+    let root_license = dbhandler
+        .get_license_by_name(&namespace, &nname)
+        .await?
+        .into_iter()
+        .next()
+        .filter(|l| l != "None");
+    let root = SbomComponent {
+        advisories: dbhandler.get_direct_rustsec(&nname, &nversion).await?,
+        name: nname,
+        version: nversion,
+        license: root_license,
+    };
+    Ok(Sbom {
+        root,
+        components,
+        dependencies,
+        truncated: all.truncated,
+        created: chrono::Utc::now(),
+    })
+}
+/// 导出软件物料清单，包含全部传递依赖、许可证和已知漏洞
+#[utoipa::path(
+    get,
+    path = "/api/crates/{nsfront}/{nsbehind}/{cratename}/{version}/sbom",
+    params(
+        ("nsfront" = String, Path, description = "命名空间前半部分"),
+        ("nsbehind" = String, Path, description = "命名空间后半部分"),
+        ("cratename" = String, Path, description = "crate 名称"),
+        ("version" = String, Path, description = "版本号"),
+        ("format" = Option<SbomFormat>, Query, description = "cyclonedx 输出 CycloneDX 1.5 JSON（默认），spdx 输出 SPDX 2.3 JSON")
+    ),
+    responses(
+        (status = 200, description = "成功生成 SBOM", body = Object),
+        (status = 400, description = "参数不合法", body = ApiErrorBody),
+        (status = 500, description = "服务器内部错误", body = ApiErrorBody),
+        (status = 503, description = "后端服务不可用", body = ApiErrorBody)
+    ),
+    tag = "dependencies"
+)]
+pub async fn get_sbom(
+    pools: &ConnectionPools,
+    nsfront: String,
+    nsbehind: String,
+    nname: String,
+    nversion: String,
+    query: SbomQuery,
+) -> Result<HttpResponse, ApiError> {
+    let sbom = build_sbom(pools, nsfront, nsbehind, nname, nversion).await?;
+    Ok(HttpResponse::Ok().json(sbom.render(query.format.unwrap_or_default())))
+}
+/// 查询一条 RustSec 漏洞（ID 或 CVE 等别名）在图中的影响面
+pub async fn build_blast_radius(
+    pools: &ConnectionPools,
+    id: String,
+    query: BlastRadiusQuery,
+) -> Result<BlastRadius, ApiError> {
+    let limits = GraphLimits::try_from(query)?;
+    let targets = pools.pg().await?.find_advisory(&id).await?;
+    let Some(first) = targets.first() else {
+        return Err(ApiError::NotFound(format!("advisory {}", id)));
+    };
+    let handler = pools.api_handler();
+
+    // 同一 crate 只取第一条记录判断受影响版本和修复版本
+    let names: Vec<String> = targets.iter().map(|t| t.crate_name.clone()).collect();
+    let mut versions_of = handler.reader.get_versions_of_crates(&names).await?;
+    let mut crates: Vec<(String, Vec<String>, usize)> = vec![];
+    for (i, target) in targets.iter().enumerate() {
+        if crates.iter().any(|(name, _, _)| *name == target.crate_name) {
+            continue;
+        }
+        let versions = versions_of.remove(&target.crate_name).unwrap_or_default();
+        crates.push((target.crate_name.clone(), versions, i));
+    }
+    let mut sources = vec![];
+    for (name, versions, i) in &crates {
+        for version in versions {
+            if targets[*i].matcher.is_affected(version) {
+                sources.push(format!("{}/{}", name, version));
+            }
+        }
+    }
+
+    let trace = trace_dependents(&sources, limits, |level| async {
+        Ok::<_, ApiError>(shipped(
+            handler.reader.get_direct_dependent_edges(level).await?,
+        ))
+    })
+    .await?;
+    let programs = handler
+        .reader
+        .get_program_of_versions(trace.order.iter().map(|(id, _)| id.clone()).collect())
+        .await?;
+    let vulnerable = crates
+        .iter()
+        .map(|(name, versions, i)| {
+            (
+                name.clone(),
+                VulnerableCrate {
+                    matcher: &targets[*i].matcher,
+                    versions,
+                },
+            )
+        })
+        .collect();
+    Ok(BlastRadius {
+        advisory_id: first.info.id.clone(),
+        aliases: first
+            .info
+            .aliases
+            .split(';')
+            .map(str::trim)
+            .filter(|a| !a.is_empty())
+            .map(str::to_string)
+            .collect(),
+        crates: crates.iter().map(|(name, _, _)| name.clone()).collect(),
+        vulnerable_versions: sources,
+        total: trace.order.len(),
+        max_depth: limits.max_depth,
+        max_nodes: limits.max_nodes,
+        truncated: trace.truncated,
+        namespaces: group_by_namespace(&trace, &programs, &vulnerable),
+    })
+}
+/// 查询传递依赖某条漏洞受影响版本的全部 library / application 版本，按命名空间分组
+#[utoipa::path(
+    get,
+    path = "/api/advisories/{id}/blast-radius",
+    params(
+        ("id" = String, Path, description = "RustSec ID 或别名，如 RUSTSEC-2020-0071、CVE-2020-26235"),
+        ("max_depth" = Option<usize>, Query, description = "最大反向遍历深度，默认 20，最大 50"),
+        ("max_nodes" = Option<usize>, Query, description = "最多返回的受影响版本数，默认 5000，最大 50000")
+    ),
+    responses(
+        (status = 200, description = "成功获取漏洞影响面", body = BlastRadius),
+        (status = 400, description = "参数不合法", body = ApiErrorBody),
+        (status = 404, description = "漏洞不存在", body = ApiErrorBody),
+        (status = 500, description = "服务器内部错误", body = ApiErrorBody),
+        (status = 503, description = "后端服务不可用", body = ApiErrorBody)
+    ),
+    tag = "security"
+)]
+pub async fn get_blast_radius(
+    pools: &ConnectionPools,
+    id: String,
+    query: BlastRadiusQuery,
+) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok().json(build_blast_radius(pools, id, query).await?))
+}
+/// 收集一个版本的依赖树、依赖许可证以及依赖和自身的漏洞
+async fn tree_snapshot(
+    pools: &ConnectionPools,
+    namespace: &str,
+    name: &str,
+    version: &str,
+) -> Result<TreeSnapshot, ApiError> {
+    let handler = pools.api_handler();
+    let root = format!("{}/{}", name, version);
+    let direct: HashSet<String> = handler
+        .reader
+        .new_get_direct_dependency_nodes(namespace, &root)
+        .await?
+        .into_iter()
+        .map(|nv| nv.name + "/" + &nv.version)
+        .collect();
+    let all = handler
+        .reader
+        .new_get_all_dependencies(namespace.to_string(), root.clone())
+        .await?;
+
+    let dbhandler = pools.pg().await?;
+    let mut names: Vec<String> = all
+        .nodes
+        .iter()
+        .filter_map(|id| id.split_once('/').map(|(name, _)| name.to_string()))
+        .collect();
+    names.sort();
+    names.dedup();
+    let licenses = dbhandler.get_licenses_by_names(&names).await?;
+    let advisories = dbhandler
+        .get_rustsec_by_versions(all.nodes.iter().chain(std::iter::once(&root)))
+        .await?;
+    Ok(TreeSnapshot {
+        version: version.to_string(),
+        direct,
+        all: all.nodes,
+        licenses,
+        advisories,
+        truncated: all.truncated,
+    })
+}
+/// 对比同一 crate 两个版本的依赖树，列出依赖、漏洞和许可证的变化
+#[utoipa::path(
+    get,
+    path = "/api/crates/{nsfront}/{nsbehind}/{cratename}/diff",
+    params(
+        ("nsfront" = String, Path, description = "命名空间前半部分"),
+        ("nsbehind" = String, Path, description = "命名空间后半部分"),
+        ("cratename" = String, Path, description = "crate 名称"),
+        ("from" = String, Query, description = "旧版本号"),
+        ("to" = String, Query, description = "新版本号")
+    ),
+    responses(
+        (status = 200, description = "成功获取依赖变化", body = DependencyDiff),
+        (status = 400, description = "参数不合法", body = ApiErrorBody),
+        (status = 404, description = "版本不存在", body = ApiErrorBody),
+        (status = 500, description = "服务器内部错误", body = ApiErrorBody),
+        (status = 503, description = "后端服务不可用", body = ApiErrorBody)
+    ),
+    tag = "dependencies"
+)]
+pub async fn get_dependency_diff(
+    pools: &ConnectionPools,
+    nsfront: String,
+    nsbehind: String,
+    nname: String,
+    query: DiffQuery,
+) -> Result<HttpResponse, ApiError> {
+    let params = DiffParams::try_from(query)?;
+    let namespace = nsfront + "/" + &nsbehind;
+    let versions = pools
+        .api_handler()
+        .reader
+        .new_get_lib_version(namespace.clone(), nname.clone())
+        .await?;
+    for version in [&params.from, &params.to] {
+        if !versions.contains(version) {
+            return Err(ApiError::NotFound(format!(
+                "{} {} in {}",
+                nname, version, namespace
+            )));
+        }
+    }
+    let from = tree_snapshot(pools, &namespace, &nname, &params.from).await?;
+    let to = tree_snapshot(pools, &namespace, &nname, &params.to).await?;
+    Ok(HttpResponse::Ok().json(diff_trees(&nname, &from, &to)))
+}
+/// 为一个 crate 推荐替代方案，综合向量相似度、依赖图上的同时依赖和替换关系以及评估分数
+#[utoipa::path(
+    get,
+    path = "/api/crates/{nsfront}/{nsbehind}/{cratename}/similar",
+    params(
+        ("nsfront" = String, Path, description = "命名空间前半部分"),
+        ("nsbehind" = String, Path, description = "命名空间后半部分"),
+        ("cratename" = String, Path, description = "crate 名称"),
+        ("limit" = Option<usize>, Query, description = "返回的候选数，默认 10，最大 50")
+    ),
+    responses(
+        (status = 200, description = "成功获取替代候选", body = SimilarCrates),
+        (status = 400, description = "参数不合法", body = ApiErrorBody),
+        (status = 404, description = "crate 不存在", body = ApiErrorBody),
+        (status = 500, description = "服务器内部错误", body = ApiErrorBody),
+        (status = 503, description = "后端服务不可用", body = ApiErrorBody)
+    ),
+    tag = "crates"
+)]
+pub async fn get_similar_crates(
+    pools: &ConnectionPools,
+    nsfront: String,
+    nsbehind: String,
+    nname: String,
+    query: SimilarQuery,
+) -> Result<HttpResponse, ApiError> {
+    let params = SimilarParams::try_from(query)?;
+    let namespace = nsfront + "/" + &nsbehind;
+    let handler = pools.api_handler();
+    let mut versions = handler
+        .reader
+        .new_get_lib_version(namespace.clone(), nname.clone())
+        .await?;
+    let max_version = latest_version(versions.iter().map(String::as_str))
+        .map(str::to_string)
+        .ok_or_else(|| ApiError::NotFound(format!("{} in {}", nname, namespace)))?;
+    let dbhandler = pools.pg().await?;
+
+    // 依赖方及其最后一个依赖目标 crate 的版本，依赖边过多时只读取依赖较新版本的部分
+    sort_newest_first(&mut versions);
+    let targets = versions
+        .iter()
+        .map(|v| format!("{}/{}", nname, v))
+        .collect();
+    let mut dependent_versions: HashMap<String, Vec<String>> = HashMap::new();
+    for edge in shipped(
         handler
             .reader
-            .build_graph(&mut res, &mut visited)
-            .await
-            .unwrap();
-        let graph = serde_json::to_string(&res).unwrap();
-        redisconn
-            .insert_dependency_graph_into_redis(
-                namespace.clone(),
-                nname.clone(),
-                nversion.clone(),
-                graph.clone(),
-            )
-            .await
-            .unwrap();
-        HttpResponse::Ok().json(res)
+            .sample_direct_dependent_edges(targets, MAX_DEPENDENT_EDGES)
+            .await?,
+    ) {
+        if let Some((name, version)) = edge.from.split_once('/') {
+            if name != nname {
+                dependent_versions
+                    .entry(name.to_string())
+                    .or_default()
+                    .push(version.to_string());
+            }
+        }
+    }
+    let dependent_names: Vec<String> = dependent_versions.keys().cloned().collect();
+    let downloads = dbhandler.get_downloads_by_names(&dependent_names).await?;
+    let sampled = sample_dependents(dependent_versions, &downloads);
+
+    // 下载量最高的一部分依赖方，最新版本不是上面的版本时检查是否已移除目标 crate
+    let checks: Vec<String> = sampled
+        .iter()
+        .take(MAX_REPLACEMENT_CHECKS)
+        .map(|(name, _)| name.clone())
+        .collect();
+    let mut versions_of = handler.reader.get_versions_of_crates(&checks).await?;
+    let mut latest_ids = HashMap::new();
+    for (name, version) in sampled.iter().take(MAX_REPLACEMENT_CHECKS) {
+        let versions = versions_of.remove(name).unwrap_or_default();
+        if let Some(latest) = latest_version(versions.iter().map(String::as_str)) {
+            if latest != version {
+                latest_ids.insert(format!("{}/{}", name, latest), name.clone());
+            }
+        }
+    }
+    let sampled_ids: HashMap<String, String> = sampled
+        .iter()
+        .map(|(name, version)| (format!("{}/{}", name, version), name.clone()))
+        .collect();
+    let dependencies_of = |edges: Vec<GraphEdge>, ids: &HashMap<String, String>| {
+        let mut deps: HashMap<String, HashSet<String>> = HashMap::new();
+        for edge in shipped(edges) {
+            if let (Some(from), Some((to, _))) = (ids.get(&edge.from), edge.to.split_once('/')) {
+                deps.entry(from.clone()).or_default().insert(to.to_string());
+            }
+        }
+        deps
+    };
+    let mut depended = dependencies_of(
+        handler
+            .reader
+            .get_direct_dependency_edges(sampled_ids.keys().cloned().collect())
+            .await?,
+        &sampled_ids,
+    );
+    let mut latest = dependencies_of(
+        handler
+            .reader
+            .get_direct_dependency_edges(latest_ids.keys().cloned().collect())
+            .await?,
+        &latest_ids,
+    );
+    let checked: HashSet<&String> = latest_ids.values().collect();
+    let samples: Vec<DependentSample> = sampled
+        .iter()
+        .map(|(name, _)| DependentSample {
+            name: name.clone(),
+            depended: depended.remove(name).unwrap_or_default(),
+            latest: checked
+                .contains(name)
+                .then(|| latest.remove(name).unwrap_or_default()),
+        })
+        .collect();
+    let co = co_dependency(&nname, &samples);
+
+    // 没有 pgvector 或尚未生成 embedding 时只使用依赖图信号
+    let neighbours = dbhandler
+        .get_embedding_neighbours(&nname, &namespace, EMBEDDING_NEIGHBOURS)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::warn!("embedding neighbours of {} unavailable: {}", nname, e);
+            vec![]
+        });
+
+    let mut names = candidate_names(&neighbours, &co);
+    let programs = dbhandler.get_latest_programs_by_names(&names).await?;
+    names.push(nname.clone());
+    let scores = dbhandler.get_program_scores(&names).await?;
+    let mut packages: Vec<(String, Option<String>)> = programs
+        .iter()
+        .map(|(name, (_, max_version))| (name.clone(), Some(max_version.clone())))
+        .collect();
+    packages.push((nname.clone(), Some(max_version.clone())));
+    let advisories: Vec<usize> = dbhandler
+        .get_package_advisories(&packages)
+        .await?
+        .into_iter()
+        .map(|found| found.len())
+        .collect();
+    let candidates: HashMap<String, CandidateInfo> = packages
+        .iter()
+        .zip(&advisories)
+        .filter_map(|((name, _), advisories)| {
+            let (namespace, max_version) = programs.get(name)?.clone();
+            Some((
+                name.clone(),
+                CandidateInfo {
+                    namespace,
+                    max_version,
+                    evaluated_score: scores.get(name).copied().flatten(),
+                    advisories: *advisories,
+                },
+            ))
+        })
+        .collect();
+    let evaluated_score = scores.get(&nname).copied().flatten();
+
+    Ok(HttpResponse::Ok().json(SimilarCrates {
+        alternatives: rank_alternatives(
+            evaluated_score,
+            &neighbours,
+            &co,
+            &candidates,
+            params.limit,
+        ),
+        name: nname,
+        namespace,
+        max_version,
+        evaluated_score,
+        advisories: advisories.last().copied().unwrap_or(0),
+        sampled_dependents: co.sampled,
+    }))
+}
+/// 按 OSV `querybatch` 格式批量查询 crate 版本的漏洞
+#[utoipa::path(
+    post,
+    path = "/api/v1/querybatch",
+    request_body = OsvQueryBatch,
+    responses(
+        (status = 200, description = "每条查询对应一个结果，顺序与请求一致", body = OsvBatchResponse),
+        (status = 400, description = "查询不合法或超过 1000 条", body = ApiErrorBody),
+        (status = 500, description = "服务器内部错误", body = ApiErrorBody),
+        (status = 503, description = "后端服务不可用", body = ApiErrorBody)
+    ),
+    tag = "security"
+)]
+pub async fn query_batch(
+    pools: &ConnectionPools,
+    batch: OsvQueryBatch,
+) -> Result<HttpResponse, ApiError> {
+    let queries: Vec<Option<PackageQuery>> = batch.try_into()?;
+    let packages: Vec<(String, Option<String>)> = queries
+        .iter()
+        .flatten()
+        .map(|q| (q.name.clone(), q.version.clone()))
+        .collect();
+    let dbhandler = pools.pg().await?;
+    let mut matches = dbhandler
+        .get_package_advisories(&packages)
+        .await?
+        .into_iter();
+
+    // 同一漏洞可能出现在多条查询中，只转换一次
+    let mut vulns: std::collections::HashMap<String, Option<OsvVulnerability>> =
+        std::collections::HashMap::new();
+    let mut results = vec![];
+    for query in &queries {
+        let targets = match query {
+            Some(_) => matches.next().unwrap_or_default(),
+            None => vec![],
+        };
+        let mut result = OsvBatchResult::default();
+        for target in targets {
+            let id = target.info.id;
+            if !vulns.contains_key(&id) {
+                let records: Vec<_> = dbhandler
+                    .find_advisory(&id)
+                    .await?
+                    .into_iter()
+                    .filter(|t| t.info.id == id)
+                    .collect();
+                vulns.insert(id.clone(), to_osv(&records));
+            }
+            if let Some(vuln) = &vulns[&id] {
+                if result.vulns.iter().all(|v| v.id != vuln.id) {
+                    result.vulns.push(vuln.clone());
+                }
+            }
+        }
+        results.push(result);
+    }
+    Ok(HttpResponse::Ok().json(OsvBatchResponse { results }))
+}
+/// 读取 multipart 字段，超过 `limit` 字节时返回 400
+async fn read_field_limited(field: &mut Field, limit: usize) -> Result<Vec<u8>, ApiError> {
+    let mut data = Vec::new();
+    while let Some(chunk) = field.next().await {
+        let chunk = chunk.map_err(|e| ApiError::BadRequest(e.to_string()))?;
+        if data.len() + chunk.len() > limit {
+            return Err(ApiError::BadRequest(format!(
+                "upload is larger than {} bytes",
+                limit
+            )));
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(data)
+}
+/// 审计上传的 Cargo.lock 或包含 Cargo.lock 的 zip 项目
+#[utoipa::path(
+    post,
+    path = "/api/audit/lockfile",
+    request_body(content = AuditUpload, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "每个锁定的包的漏洞、yank 状态、许可证和评估分数", body = AuditReport),
+        (status = 400, description = "缺少文件、文件过大或不是合法的 Cargo.lock / zip", body = ApiErrorBody),
+        (status = 401, description = "缺少或无效的 API Key", body = ApiErrorBody),
+        (status = 403, description = "API Key 没有 read 权限", body = ApiErrorBody),
+        (status = 500, description = "服务器内部错误", body = ApiErrorBody),
+        (status = 503, description = "后端服务不可用", body = ApiErrorBody)
+    ),
+    security(("api_key" = [])),
+    tag = "security"
+)]
+pub async fn audit_lockfile(
+    pools: &ConnectionPools,
+    mut payload: Multipart,
+) -> Result<HttpResponse, ApiError> {
+    let mut lockfile = None;
+    while let Some(field) = payload.next().await {
+        let mut field = field.map_err(|e| ApiError::BadRequest(e.to_string()))?;
+        let Some(content_disposition) = field.content_disposition().cloned() else {
+            continue;
+        };
+        if content_disposition.get_name() != Some("file") {
+            continue;
+        }
+        let is_zip = content_disposition
+            .get_filename()
+            .is_some_and(|name| sanitize(name).ends_with(".zip"));
+        let limit = if is_zip {
+            MAX_ARCHIVE_BYTES
+        } else {
+            MAX_LOCKFILE_BYTES
+        };
+        let data = read_field_limited(&mut field, limit).await?;
+        lockfile = Some(if is_zip || data.starts_with(b"PK\x03\x04") {
+            lockfile_from_zip(&data)?
+        } else {
+            String::from_utf8(data)
+                .map_err(|_| ApiError::BadRequest("Cargo.lock is not valid UTF-8".to_string()))?
+        });
+    }
+    let Some(lockfile) = lockfile else {
+        return Err(ApiError::BadRequest("missing file field".to_string()));
+    };
+    let packages = parse_lockfile(&lockfile)?;
+
+    let lookups: Vec<(String, String)> = crates_io_packages(&packages)
+        .into_iter()
+        .map(|p| (p.name.clone(), p.version.clone()))
+        .collect();
+    let names: Vec<String> = lookups
+        .iter()
+        .map(|(name, _)| name.clone())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let dbhandler = pools.pg().await?;
+    let advisories = dbhandler
+        .get_package_advisories(
+            &lookups
+                .iter()
+                .map(|(name, version)| (name.clone(), Some(version.clone())))
+                .collect::<Vec<_>>(),
+        )
+        .await?;
+    let facts = AuditFacts {
+        programs: dbhandler.get_program_scores(&names).await?,
+        licenses: dbhandler.get_licenses_by_names(&names).await?,
+        yanked: pools
+            .pg_cratesio()
+            .await?
+            .get_yanked_versions(&lookups)
+            .await?,
+        advisories: lookups.iter().cloned().zip(advisories).collect(),
+    };
+    Ok(HttpResponse::Ok().json(build_report(&packages, &facts)))
+}
+/// 按 ID 或别名获取 OSV 格式的漏洞记录
+#[utoipa::path(
+    get,
+    path = "/api/v1/vulns/{id}",
+    params(
+        ("id" = String, Path, description = "RustSec ID 或别名，如 RUSTSEC-2020-0071、CVE-2020-26235")
+    ),
+    responses(
+        (status = 200, description = "成功获取漏洞", body = OsvVulnerability),
+        (status = 404, description = "漏洞不存在", body = ApiErrorBody),
+        (status = 500, description = "服务器内部错误", body = ApiErrorBody),
+        (status = 503, description = "后端服务不可用", body = ApiErrorBody)
+    ),
+    tag = "security"
+)]
+pub async fn get_osv_vulnerability(
+    pools: &ConnectionPools,
+    id: String,
+) -> Result<HttpResponse, ApiError> {
+    let targets = pools.pg().await?.find_advisory(&id).await?;
+    // 按别名查询时可能命中多条漏洞，只返回第一条
+    let records: Vec<_> = match targets.first() {
+        Some(first) => {
+            let wanted = first.info.id.clone();
+            targets
+                .into_iter()
+                .filter(|t| t.info.id == wanted)
+                .collect()
+        }
+        None => vec![],
+    };
+    match to_osv(&records) {
+        Some(vuln) => Ok(HttpResponse::Ok().json(vuln)),
+        None => Err(ApiError::NotFound(format!("advisory {}", id))),
+    }
+}
+/// 签发 API Key，明文只在响应中出现一次
+#[utoipa::path(
+    post,
+    path = "/api/keys",
+    request_body = NewApiKey,
+    responses(
+        (status = 201, description = "成功签发", body = IssuedApiKey),
+        (status = 400, description = "请求参数不合法", body = ApiErrorBody),
+        (status = 401, description = "缺少或无效的 API Key", body = ApiErrorBody),
+        (status = 403, description = "API Key 没有 admin 权限", body = ApiErrorBody)
+    ),
+    security(("api_key" = [])),
+    tag = "auth"
+)]
+pub async fn issue_api_key(
+    pools: &ConnectionPools,
+    new: NewApiKey,
+) -> Result<HttpResponse, ApiError> {
+    let dbhandler = pools.pg().await?;
+    let issued = auth::issue_key(&dbhandler, &new).await?;
+    Ok(HttpResponse::Created().json(issued))
+}
+/// 列出 API Key（不含明文）
+#[utoipa::path(
+    get,
+    path = "/api/keys",
+    params(
+        ("email" = Option<String>, Query, description = "只列出该用户的 Key")
+    ),
+    responses(
+        (status = 200, description = "成功获取", body = [ApiKeyInfo]),
+        (status = 401, description = "缺少或无效的 API Key", body = ApiErrorBody),
+        (status = 403, description = "API Key 没有 admin 权限", body = ApiErrorBody)
+    ),
+    security(("api_key" = [])),
+    tag = "auth"
+)]
+pub async fn list_api_keys(
+    pools: &ConnectionPools,
+    query: ApiKeyQuery,
+) -> Result<HttpResponse, ApiError> {
+    let keys = pools
+        .pg()
+        .await?
+        .list_api_keys(query.email.as_deref())
+        .await?;
+    Ok(HttpResponse::Ok().json(keys))
+}
+/// 吊销 API Key
+#[utoipa::path(
+    delete,
+    path = "/api/keys/{id}",
+    params(
+        ("id" = String, Path, description = "Key 的 id，即 `cpk_<id>_...` 中的 id")
+    ),
+    responses(
+        (status = 204, description = "已吊销"),
+        (status = 401, description = "缺少或无效的 API Key", body = ApiErrorBody),
+        (status = 403, description = "API Key 没有 admin 权限", body = ApiErrorBody),
+        (status = 404, description = "Key 不存在或已吊销", body = ApiErrorBody)
+    ),
+    security(("api_key" = [])),
+    tag = "auth"
+)]
+pub async fn revoke_api_key(pools: &ConnectionPools, id: String) -> Result<HttpResponse, ApiError> {
+    if pools.pg().await?.revoke_api_key(&id).await? {
+        Ok(HttpResponse::NoContent().finish())
     } else {
-        tracing::info!("second time");
-        let res_tree: Deptree = serde_json::from_str(&qres).unwrap();
-        HttpResponse::Ok().json(res_tree)
+        Err(ApiError::NotFound(format!("API key {}", id)))
+    }
+}
+/// 查询上传任务的状态、状态变化记录和导入的版本，只有上传者和 admin 可以查看
+#[utoipa::path(
+    get,
+    path = "/api/jobs/{id}",
+    params(
+        ("id" = String, Path, description = "上传接口返回的任务 ID")
+    ),
+    responses(
+        (status = 200, description = "成功获取任务", body = UploadJob),
+        (status = 401, description = "缺少或无效的 API Key", body = ApiErrorBody),
+        (status = 403, description = "API Key 没有 upload 权限", body = ApiErrorBody),
+        (status = 404, description = "任务不存在", body = ApiErrorBody),
+        (status = 500, description = "服务器内部错误", body = ApiErrorBody),
+        (status = 503, description = "后端服务不可用", body = ApiErrorBody)
+    ),
+    security(("api_key" = [])),
+    tag = "upload"
+)]
+pub async fn get_upload_job(
+    pools: &ConnectionPools,
+    principal: Principal,
+    id: String,
+) -> Result<HttpResponse, ApiError> {
+    let dbhandler = pools.pg().await?;
+    // 他人的任务和不存在的任务一样返回 404，不泄露任务是否存在
+    match jobs::load_job(&dbhandler, &id).await? {
+        Some(job) if job.email == principal.email || principal.allows(auth::Scope::Admin) => {
+            Ok(HttpResponse::Ok().json(job))
+        }
+        _ => Err(ApiError::NotFound(format!("upload job {}", id))),
     }
 }
+/// 执行 GraphQL 查询，可在一次请求中获取程序、版本、依赖、漏洞、许可证和评估分数
+#[utoipa::path(
+    post,
+    path = "/api/graphql",
+    request_body(content = Object, description = "GraphQL 请求，包含 query、variables 和 operationName"),
+    responses(
+        (status = 200, description = "GraphQL 响应，字段错误在 errors 中返回", body = Object)
+    ),
+    tag = "graphql"
+)]
+pub async fn graphql_query(
+    pools: &ConnectionPools,
+    schema: &CrateSchema,
+    request: async_graphql::Request,
+) -> Result<HttpResponse, ApiError> {
+    let response = schema.execute(request.data(graphql::loader(pools))).await;
+    Ok(HttpResponse::Ok().json(response))
+}
+/// 获取 GraphQL schema 的 SDL 描述
+#[utoipa::path(
+    get,
+    path = "/api/graphql/schema",
+    responses(
+        (status = 200, description = "GraphQL SDL", body = String, content_type = "text/plain")
+    ),
+    tag = "graphql"
+)]
+pub async fn graphql_schema(schema: &CrateSchema) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; charset=utf-8")
+        .body(schema.sdl()))
+}
+/// 获取被依赖列表，支持分页、排序和过滤
+#[utoipa::path(
+    get,
+    path = "/api/crates/{nsfront}/{nsbehind}/{cratename}/{version}/dependents",
+    params(
+        ("nsfront" = String, Path, description = "命名空间前半部分"),
+        ("nsbehind" = String, Path, description = "命名空间后半部分"),
+        ("cratename" = String, Path, description = "crate 名称"),
+        ("version" = String, Path, description = "版本号"),
+        ("page" = Option<usize>, Query, description = "页码，从 1 开始，给出 cursor 时忽略"),
+        ("per_page" = Option<usize>, Query, description = "每页条数，默认 20，最大 100"),
+        ("sort" = Option<DepSort>, Query, description = "name（默认）、downloads 或 dependents"),
+        ("relation" = Option<Relation>, Query, description = "只返回 direct 或 indirect 被依赖项"),
+        ("has_advisory" = Option<bool>, Query, description = "是否有 RustSec 漏洞"),
+        ("license" = Option<String>, Query, description = "许可证标识符，如 MIT"),
+        ("cursor" = Option<String>, Query, description = "上一页返回的 next_cursor")
+    ),
+    responses(
+        (status = 200, description = "成功获取被依赖列表", body = DependentInfo),
+        (status = 400, description = "参数不合法", body = ApiErrorBody),
+        (status = 500, description = "服务器内部错误", body = ApiErrorBody),
+        (status = 503, description = "后端服务不可用", body = ApiErrorBody)
+    ),
+    tag = "dependencies"
+)]
 pub async fn dependent_redis_cache(
+    pools: &ConnectionPools,
     name: String,
     version: String,
     nsfront: String,
     nsbehind: String,
-) -> impl Responder {
-    let handler = get_tugraph_api_handler().await;
-    let conn = get_redis_connection().await.unwrap();
-    let mut redisconn = RedisHandler { connection: conn };
+    query: DepListQuery,
+) -> Result<HttpResponse, ApiError> {
+    let params = DepListParams::try_from(query)?;
+    let handler = pools.api_handler();
+    let mut redisconn = pools.redis().await?;
     let namespace = nsfront.clone() + "/" + &nsbehind.clone();
-    let qid = format!("dependent:{}:{}:{}", namespace, name, version);
-    let qres = redisconn.query_from_redis(qid.clone()).await.unwrap();
-    if qres.is_empty() {
+    // 缓存未分页的完整列表
+    let key = CacheKey::version(&namespace, &name, &version);
+    let cached = redisconn.get_cached::<DependentInfo>(&key).await?;
+    let all_deps = if let Some(all_deps) = cached {
+        all_deps
+    } else {
         let res_deps = handler
             .reader
             .get_dependent_from_tg(
@@ -1011,50 +2028,42 @@ pub async fn dependent_redis_cache(
                 nsfront.clone(),
                 nsbehind.clone(),
             )
-            .await
-            .unwrap();
-        let val = serde_json::to_string(&res_deps).unwrap();
-        redisconn
-            .insert_dependent_into_redis(
-                namespace.clone(),
-                name.clone(),
-                version.clone(),
-                val.clone(),
-            )
-            .await
-            .unwrap();
-        HttpResponse::Ok().json(res_deps)
-    } else {
-        let res: DependentInfo = serde_json::from_str(&qres).unwrap();
-        HttpResponse::Ok().json(res.clone())
-    }
+            .await?;
+        redisconn.set_cached(&key, &res_deps).await;
+        res_deps
+    };
+    let page = params.apply(all_deps.data)?;
+    Ok(HttpResponse::Ok().json(DependentInfo {
+        direct_count: all_deps.direct_count,
+        indirect_count: all_deps.indirect_count,
+        truncated: all_deps.truncated,
+        total: page.total,
+        page: page.page,
+        per_page: page.per_page,
+        next_cursor: page.next_cursor,
+        data: page.data,
+    }))
 }
 pub async fn new_get_version_page(
+    pools: &ConnectionPools,
     nsfront: String,
     nsbehind: String,
     nname: String,
     _nversion: String,
-) -> impl Responder {
-    let handler = get_tugraph_api_handler().await;
-    let conn = get_redis_connection().await.unwrap();
-    let mut redisconn = RedisHandler { connection: conn };
+) -> Result<HttpResponse, ApiError> {
+    let handler = pools.api_handler();
+    let mut redisconn = pools.redis().await?;
     let namespace = nsfront.clone() + "/" + &nsbehind.clone();
-    let qid = format!("versionpage:{}:{}", namespace, nname);
-    let res = redisconn.query_from_redis(qid.clone()).await.unwrap();
-    if res.is_empty() {
+    let key = CacheKey::program(&namespace, &nname);
+    let cached = redisconn.get_cached::<Vec<Versionpage>>(&key).await?;
+    if let Some(every_version) = cached {
+        Ok(HttpResponse::Ok().json(every_version))
+    } else {
         let every_version = handler
             .reader
             .get_version_page_from_tg(nsfront.clone(), nsbehind.clone(), nname.clone())
-            .await
-            .unwrap();
-        let val = serde_json::to_string(&every_version).unwrap();
-        redisconn
-            .insert_versionpage_into_redis(namespace, nname.clone(), val.clone())
-            .await
-            .unwrap();
-        HttpResponse::Ok().json(every_version)
-    } else {
-        let every_version: Vec<Versionpage> = serde_json::from_str(&res).unwrap();
-        HttpResponse::Ok().json(every_version)
+            .await?;
+        redisconn.set_cached(&key, &every_version).await;
+        Ok(HttpResponse::Ok().json(every_version))
     }
 }
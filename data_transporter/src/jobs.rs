@@ -0,0 +1,392 @@
+//! 用户上传任务
+//!
+//! 每次通过 `upload_crate` 上传项目都会创建一个任务，状态依次为
+//! queued → extracting → importing → analysing → done，任一步出错进入 failed，
+//! 每次状态变化都记入 `upload_job_events`。
+//!
+//! API 服务负责解压（见 [`crate::archive`]）并确认项目中有 `Cargo.toml`，然后通过 `KAFKA_USER_IMPORT_TOPIC` 把项目交给导入程序
+//! （消息的 `extra_field` 为任务 ID）；导入程序解析出版本后由 crates_pro 记录
+//! 版本并进入 analysing。分析结果（sensleak、mirchecker）全部生成，或进入
+//! analysing 超过 `UPLOAD_JOB_ANALYSIS_TIMEOUT_SECS` 后，查询任务时进入 done。
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::archive::{self, ArchiveLimits};
+use crate::db::DBHandler;
+use crate::error::ApiError;
+use crate::pool::ConnectionPools;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// 上传的归档的保存目录
+const ARCHIVE_DIR: &str = "target/zip/upload";
+
+/// 默认的解压目录，可通过 `UPLOAD_DIR` 覆盖，导入程序需要能访问同一路径
+const DEFAULT_UPLOAD_DIR: &str = "target/www/uploads";
+
+/// 默认的分析等待时间（秒）
+const DEFAULT_ANALYSIS_TIMEOUT_SECS: u64 = 24 * 60 * 60;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Queued,
+    Extracting,
+    Importing,
+    Analysing,
+    Done,
+    Failed,
+}
+
+impl JobState {
+    const ORDER: [JobState; 5] = [
+        JobState::Queued,
+        JobState::Extracting,
+        JobState::Importing,
+        JobState::Analysing,
+        JobState::Done,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JobState::Queued => "queued",
+            JobState::Extracting => "extracting",
+            JobState::Importing => "importing",
+            JobState::Analysing => "analysing",
+            JobState::Done => "done",
+            JobState::Failed => "failed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        JobState::ORDER
+            .into_iter()
+            .chain([JobState::Failed])
+            .find(|state| state.as_str() == value)
+    }
+
+    pub fn is_terminal(self) -> bool {
+        matches!(self, JobState::Done | JobState::Failed)
+    }
+
+    /// 可以转移到 `self` 的状态：前一个状态，或者进入 failed 时任一未结束的状态
+    pub fn predecessors(self) -> Vec<JobState> {
+        match self {
+            JobState::Failed => JobState::ORDER
+                .into_iter()
+                .filter(|state| !state.is_terminal())
+                .collect(),
+            _ => JobState::ORDER
+                .windows(2)
+                .filter(|pair| pair[1] == self)
+                .map(|pair| pair[0])
+                .collect(),
+        }
+    }
+}
+
+/// 任务详情
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct UploadJob {
+    pub id: String,
+    pub email: String,
+    /// 上传的文件名
+    pub source: String,
+    pub upload_time: String,
+    pub state: JobState,
+    pub error: Option<String>,
+    #[schema(value_type = String, format = DateTime)]
+    pub created_at: DateTime<Utc>,
+    #[schema(value_type = String, format = DateTime)]
+    pub updated_at: DateTime<Utc>,
+    /// 按时间排序的状态变化记录
+    pub events: Vec<JobEvent>,
+    /// 导入产生的 crate 版本
+    pub versions: Vec<JobVersion>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct JobEvent {
+    pub state: JobState,
+    pub message: Option<String>,
+    #[schema(value_type = String, format = DateTime)]
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct JobVersion {
+    pub namespace: String,
+    pub name: String,
+    pub version: String,
+    /// 版本详情接口
+    pub crate_url: String,
+    pub senseleak: ReportState,
+    pub senseleak_url: String,
+    pub mirchecker: ReportState,
+    pub mirchecker_url: String,
+}
+
+impl JobVersion {
+    pub fn new(
+        namespace: &str,
+        name: &str,
+        version: &str,
+        senseleak: ReportState,
+        mirchecker: ReportState,
+    ) -> Self {
+        let crate_url = format!("/api/crates/{}/{}/{}", namespace, name, version);
+        JobVersion {
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+            version: version.to_string(),
+            senseleak_url: format!("{}/senseleak", crate_url),
+            mirchecker_url: format!("{}/mirchecker", crate_url),
+            crate_url,
+            senseleak,
+            mirchecker,
+        }
+    }
+
+    fn analysed(&self) -> bool {
+        self.senseleak != ReportState::Pending && self.mirchecker != ReportState::Pending
+    }
+}
+
+/// 分析报告的状态
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportState {
+    Pending,
+    Ready,
+    Failed,
+}
+
+/// `upload_crate` 的响应
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct UploadAccepted {
+    /// 只提交 GitHub 链接时不创建任务
+    pub job_id: Option<String>,
+}
+
+pub fn upload_dir() -> PathBuf {
+    PathBuf::from(env::var("UPLOAD_DIR").unwrap_or_else(|_| DEFAULT_UPLOAD_DIR.to_string()))
+}
+
+pub fn archive_path(job_id: &str) -> PathBuf {
+    Path::new(ARCHIVE_DIR).join(job_id)
+}
+
+fn analysis_timeout() -> Duration {
+    Duration::from_secs(
+        env::var("UPLOAD_JOB_ANALYSIS_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(DEFAULT_ANALYSIS_TIMEOUT_SECS),
+    )
+}
+
+/// 处于 analysing 的任务是否可以结束，返回记入事件的说明
+pub fn analysis_outcome(job: &UploadJob, now: DateTime<Utc>, timeout: Duration) -> Option<String> {
+    if job.state != JobState::Analysing {
+        return None;
+    }
+    let pending: Vec<String> = job
+        .versions
+        .iter()
+        .filter(|v| !v.analysed())
+        .map(|v| format!("{}/{}", v.name, v.version))
+        .collect();
+    if pending.is_empty() {
+        return Some("all analysis reports are available".to_string());
+    }
+    let waited = (now - job.updated_at).to_std().unwrap_or_default();
+    (waited >= timeout).then(|| {
+        format!(
+            "analysis timed out, reports missing for {}",
+            pending.join(", ")
+        )
+    })
+}
+
+/// 查询任务，处于 analysing 且分析已完成时先转入 done
+pub async fn load_job(db: &DBHandler, id: &str) -> Result<Option<UploadJob>, ApiError> {
+    let Some(job) = db.get_upload_job(id).await? else {
+        return Ok(None);
+    };
+    match analysis_outcome(&job, Utc::now(), analysis_timeout()) {
+        Some(message) => {
+            db.advance_upload_job(id, JobState::Done, Some(&message))
+                .await?;
+            Ok(db.get_upload_job(id).await?)
+        }
+        None => Ok(Some(job)),
+    }
+}
+
+/// 记录导入结果：成功时保存版本并进入 analysing，否则进入 failed
+pub async fn record_import(
+    db: &DBHandler,
+    id: &str,
+    versions: &[(String, String)],
+    error: Option<&str>,
+) -> Result<(), ApiError> {
+    if let Some(error) = error {
+        db.advance_upload_job(id, JobState::Failed, Some(error))
+            .await?;
+        return Ok(());
+    }
+    let namespace = repo_import::upload_namespace(id);
+    db.add_upload_job_versions(id, &namespace, versions).await?;
+    let message = format!("imported {} crate versions", versions.len());
+    db.advance_upload_job(id, JobState::Analysing, Some(&message))
+        .await?;
+    Ok(())
+}
+
+/// 在后台解压上传的项目并交给导入程序，出错时任务进入 failed
+pub fn spawn_upload_job(pools: ConnectionPools, id: String, crate_name: String) {
+    tokio::spawn(async move {
+        let db = match pools.pg().await {
+            Ok(db) => db,
+            Err(e) => {
+                tracing::error!("upload job {} cannot reach postgres: {}", id, e);
+                return;
+            }
+        };
+        if let Err(e) = run_upload_job(&db, &id, &crate_name).await {
+            tracing::warn!("upload job {} failed: {}", id, e);
+            if let Err(e) = db
+                .advance_upload_job(&id, JobState::Failed, Some(&failure_message(&e)))
+                .await
+            {
+                tracing::error!("failed to record failure of upload job {}: {}", id, e);
+            }
+        }
+    });
+}
+
+/// 记入任务的失败原因，`ApiError` 的 Display 不含 IO 等错误的细节
+fn failure_message(e: &ApiError) -> String {
+    match e {
+        ApiError::Io(e) => format!("io error: {}", e),
+        ApiError::Unavailable { service, detail } => format!("{} unavailable: {}", service, detail),
+        ApiError::Backend(detail) => format!("backend error: {}", detail),
+        _ => e.to_string(),
+    }
+}
+
+async fn run_upload_job(db: &DBHandler, id: &str, crate_name: &str) -> Result<(), ApiError> {
+    db.advance_upload_job(id, JobState::Extracting, None)
+        .await?;
+    let archive = archive_path(id);
+    let dest = std::path::absolute(upload_dir().join(id))?;
+    let root = tokio::task::spawn_blocking(move || {
+        archive::extract(&archive, &dest, &ArchiveLimits::from_env())
+    })
+    .await
+    .map_err(|e| ApiError::Backend(e.to_string()))??;
+
+    // 先进入 importing 再投递消息，导入程序可能在投递返回前就记录导入结果
+    db.advance_upload_job(id, JobState::Importing, None).await?;
+    repo_import::submit_user_upload(id, crate_name, &root)
+        .await
+        .map_err(|e| ApiError::unavailable("kafka", e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(state: JobState, versions: Vec<JobVersion>) -> UploadJob {
+        let at = DateTime::parse_from_rfc3339("2025-10-18T08:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        UploadJob {
+            id: "3f2a".to_string(),
+            email: "dev@example.com".to_string(),
+            source: "demo.zip".to_string(),
+            upload_time: "2025-10-18 16:00".to_string(),
+            state,
+            error: None,
+            created_at: at,
+            updated_at: at,
+            events: vec![],
+            versions,
+        }
+    }
+
+    #[test]
+    fn test_job_states() {
+        assert_eq!(JobState::Queued.predecessors(), []);
+        assert_eq!(JobState::Importing.predecessors(), [JobState::Extracting]);
+        assert_eq!(JobState::Done.predecessors(), [JobState::Analysing]);
+        assert_eq!(
+            JobState::Failed.predecessors(),
+            [
+                JobState::Queued,
+                JobState::Extracting,
+                JobState::Importing,
+                JobState::Analysing,
+            ]
+        );
+        for state in JobState::ORDER.into_iter().chain([JobState::Failed]) {
+            assert_eq!(JobState::parse(state.as_str()), Some(state));
+            assert_eq!(
+                serde_json::to_value(state).unwrap(),
+                serde_json::json!(state.as_str())
+            );
+        }
+        assert_eq!(JobState::parse("running"), None);
+
+        let v = JobVersion::new(
+            "uploads/3f2a",
+            "demo",
+            "0.1.0",
+            ReportState::Ready,
+            ReportState::Pending,
+        );
+        assert_eq!(v.crate_url, "/api/crates/uploads/3f2a/demo/0.1.0");
+        assert_eq!(
+            v.mirchecker_url,
+            "/api/crates/uploads/3f2a/demo/0.1.0/mirchecker"
+        );
+    }
+
+    #[test]
+    fn test_analysis_outcome() {
+        let timeout = Duration::from_secs(3600);
+        let pending = JobVersion::new(
+            "uploads/3f2a",
+            "demo",
+            "0.1.0",
+            ReportState::Ready,
+            ReportState::Pending,
+        );
+        let finished = JobVersion::new(
+            "uploads/3f2a",
+            "demo",
+            "0.2.0",
+            ReportState::Ready,
+            ReportState::Failed,
+        );
+        let analysing = job(JobState::Analysing, vec![pending, finished.clone()]);
+        let soon = analysing.updated_at + chrono::Duration::minutes(10);
+        let late = analysing.updated_at + chrono::Duration::hours(2);
+        assert_eq!(analysis_outcome(&analysing, soon, timeout), None);
+        assert_eq!(
+            analysis_outcome(&analysing, late, timeout).unwrap(),
+            "analysis timed out, reports missing for demo/0.1.0"
+        );
+        assert!(
+            analysis_outcome(&job(JobState::Analysing, vec![finished]), soon, timeout).is_some()
+        );
+        assert_eq!(
+            analysis_outcome(&job(JobState::Importing, vec![]), late, timeout),
+            None
+        );
+    }
+}
@@ -1,22 +1,44 @@
+pub use model::advisory;
+pub mod advisory_index;
+mod archive;
+mod audit;
+pub mod auth;
+pub mod blast_radius;
+pub mod cache;
 mod data_packer;
 mod data_reader;
 pub mod db;
+mod dep_diff;
+mod dep_filter;
+mod dep_graph;
+mod dep_list;
+pub mod error;
+pub mod graphql;
 mod handler;
-mod transporter;
+pub mod jobs;
+mod osv;
+pub mod pool;
 mod redis_store;
+pub mod sbom;
+mod similar;
+mod transporter;
 
+pub use handler::build_blast_radius;
+pub use handler::build_sbom;
 use model::tugraph_model::UVersion;
+use search::crates_search::{SearchConfig, SearchSortCriteria};
+use search::facets::SearchFilters;
 use search::search_prepare;
 use serde::{Deserialize, Serialize};
-use tokio_postgres::NoTls;
 pub use transporter::Transporter;
 
-use crate::data_reader::DataReader; // 确保导入你的 DataReader
+use crate::auth::ApiKeySecurity;
 use crate::db::db_connection_config_from_env;
-use crate::handler::ApiHandler;
+use crate::error::ApiError;
+use crate::pool::{ConnectionPools, PoolConfig};
 
 use actix_multipart::Multipart;
-use actix_web::{web, App, HttpResponse, HttpServer};
+use actix_web::{middleware, web, App, HttpServer};
 use utoipa::{OpenApi, ToSchema};
 use utoipa_swagger_ui::SwaggerUi;
 
@@ -24,27 +46,38 @@ use utoipa_swagger_ui::SwaggerUi;
 pub struct Query {
     query: String,
     pagination: Pagination,
+    /// 过滤条件，结果中同时返回各维度的分面计数
+    #[serde(default)]
+    filters: SearchFilters,
+    /// 排序方式，默认按相关度
+    #[serde(default)]
+    sort: SearchSortCriteria,
 }
 #[derive(Deserialize, Debug, ToSchema)]
 pub struct Pagination {
     page: usize,
     per_page: usize,
 }
-#[derive(Deserialize, Debug, ToSchema,Serialize,Clone)]
+#[derive(Deserialize, Debug, ToSchema, Serialize, Clone)]
 pub struct Loginfo {
     email: String,
-    image:String,
+    image: String,
     name: String,
 }
-#[derive(Deserialize, Debug, ToSchema,Serialize,Clone)]
+#[derive(Deserialize, Debug, ToSchema, Serialize, Clone)]
 pub struct Userinfo {
-    user:Loginfo,
-    expires:String,
+    user: Loginfo,
+    expires: String,
 }
-#[derive(Deserialize, Debug, ToSchema,Serialize,Clone)]
+#[derive(Deserialize, Debug, ToSchema, Serialize, Clone, Default)]
 pub struct UploadedCrate {
-    name:String,
-    time:String,
+    name: String,
+    time: String,
+    /// 上传 zip 项目时创建的任务
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    job_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    state: Option<jobs::JobState>,
 }
 #[allow(non_snake_case)]
 #[derive(Debug, Deserialize)]
@@ -56,35 +89,32 @@ struct Root {
 struct RequestBody {
     session: Userinfo,
 }
-#[allow(non_snake_case)]
-#[derive(Debug, Deserialize)]
-struct RequestBody2 {
-    requestBody: String,
-}
-async fn get_tugraph_api_handler() -> ApiHandler {
-    let tugraph_bolt_url = &std::env::var("TUGRAPH_BOLT_URL").unwrap();
-    let tugraph_user_name = &std::env::var("TUGRAPH_USER_NAME").unwrap();
-    let tugraph_user_password = &std::env::var("TUGRAPH_USER_PASSWORD").unwrap();
-    let tugraph_cratespro_db = &std::env::var("TUGRAPH_CRATESPRO_DB").unwrap();
-    let reader = DataReader::new(
-        tugraph_bolt_url,
-        tugraph_user_name,
-        tugraph_user_password,
-        tugraph_cratespro_db,
-    )
-    .await
-    .unwrap();
-    ApiHandler::new(reader).await
-}
-
 #[derive(OpenApi)]
 #[openapi(
     paths(
+        handler::health_check,
         handler::get_cves,
         handler::get_all_crates,
         //handler::get_graph,
         handler::get_crate_details,
         handler::query_crates,
+        handler::suggest_crates,
+        handler::get_dependency_graph,
+        handler::get_sbom,
+        handler::get_blast_radius,
+        handler::get_dependency_diff,
+        handler::get_similar_crates,
+        handler::query_batch,
+        handler::get_osv_vulnerability,
+        handler::audit_lockfile,
+        handler::get_upload_job,
+        handler::graphql_query,
+        handler::graphql_schema,
+        handler::issue_api_key,
+        handler::list_api_keys,
+        handler::revoke_api_key,
+        handler::dependency_redis_cache,
+        handler::dependent_redis_cache,
         //handler::get_graph,
         //route::get_version_page,
         // route::get_graph,
@@ -101,6 +131,55 @@ async fn get_tugraph_api_handler() -> ApiHandler {
     components(
         schemas(
             model::tugraph_model::Program,
+            error::ApiErrorBody,
+            pool::HealthStatus,
+            pool::BackendHealth,
+            dep_graph::DependencyGraph,
+            dep_graph::GraphNode,
+            dep_graph::GraphEdge,
+            dep_graph::Resolution,
+            sbom::SbomFormat,
+            blast_radius::BlastRadius,
+            blast_radius::NamespaceGroup,
+            blast_radius::AffectedVersion,
+            blast_radius::ProgramKind,
+            dep_diff::DependencyDiff,
+            dep_diff::DependencyChange,
+            dep_diff::AdvisoryChange,
+            dep_diff::LicenseChange,
+            similar::SimilarCrates,
+            similar::SimilarCrate,
+            similar::SimilarReason,
+            osv::OsvQueryBatch,
+            osv::OsvQuery,
+            osv::OsvPackage,
+            osv::OsvBatchResponse,
+            osv::OsvBatchResult,
+            osv::OsvVulnerability,
+            osv::OsvAffected,
+            osv::OsvAffectedPackage,
+            osv::OsvRange,
+            osv::OsvEvent,
+            osv::OsvReference,
+            osv::OsvDatabaseSpecific,
+            audit::AuditUpload,
+            audit::AuditReport,
+            audit::AuditSummary,
+            audit::AuditPackage,
+            audit::AuditAdvisory,
+            audit::PackageSource,
+            jobs::UploadJob,
+            jobs::JobEvent,
+            jobs::JobVersion,
+            jobs::JobState,
+            jobs::ReportState,
+            jobs::UploadAccepted,
+            auth::Scope,
+            auth::NewApiKey,
+            auth::IssuedApiKey,
+            auth::ApiKeyInfo,
+            dep_list::DepSort,
+            dep_list::Relation,
             db::Allcve,
             handler::Versionpage,
             //handler::Deptree,
@@ -123,7 +202,6 @@ async fn get_tugraph_api_handler() -> ApiHandler {
             // route::DependentInfo,
             // route::DependentData,
             // route::Crateinfo,
-            
             // route::Versionpage,
             // route::NewRustsec,
             // NameVersion,
@@ -136,107 +214,164 @@ async fn get_tugraph_api_handler() -> ApiHandler {
         (name = "search", description = "Search API"),
         (name = "security", description = "Security API"),
         (name = "versions", description = "Version API"),
-        //(name = "upload", description = "Upload API"),
-    )
+        (name = "health", description = "Health API"),
+        (name = "upload", description = "Upload API"),
+        (name = "auth", description = "API key API"),
+        (name = "graphql", description = "GraphQL API"),
+    ),
+    modifiers(&ApiKeySecurity)
 )]
 struct ApiDoc;
 
 pub async fn run_api_server() -> std::io::Result<()> {
     tracing::info!("Start run_api_server");
     let db_connection_config = db_connection_config_from_env();
-    let (client, connection) = tokio_postgres::connect(&db_connection_config, NoTls)
+    let pool_config = PoolConfig::from_env();
+    tracing::info!("connection pool config: {:?}", pool_config);
+    let pools = ConnectionPools::new(&pool_config)
         .await
-        .unwrap();
-    tokio::spawn(async move {
-        if let Err(e) = connection.await {
-            eprintln!("connection error: {}", e);
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+    let health = pools.health().await;
+    if !health.ok {
+        tracing::warn!("backend health check failed at startup: {:?}", health);
+    }
+    {
+        let dbhandler = pools
+            .pg()
+            .await
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
+        let pre_search = search_prepare::SearchPrepare::new(&dbhandler.client).await;
+        pre_search.prepare_tsv().await.unwrap();
+        if let Err(e) = pre_search.prepare_fuzzy().await {
+            tracing::warn!("failed to prepare fuzzy search indexes: {}", e);
         }
-    });
-    let pre_search = search_prepare::SearchPrepare::new(&client).await;
-    pre_search.prepare_tsv().await.unwrap();
+    }
+    advisory_index::spawn_advisory_index_refresher(db_connection_config.clone());
+    cache::spawn_cache_invalidator(pools.clone());
+    let pools = web::Data::new(pools);
+    let rate_limits = web::Data::new(auth::RateLimits::from_env());
+    let search_config = web::Data::new(SearchConfig::from_env());
+    let graphql_schema = web::Data::new(graphql::build_schema(graphql::GraphqlLimits::from_env()));
     HttpServer::new(move || {
         tracing::info!("start route");
         App::new()
+            .app_data(pools.clone())
+            .app_data(rate_limits.clone())
+            .app_data(search_config.clone())
+            .app_data(graphql_schema.clone())
+            .wrap(middleware::from_fn(auth::authenticate))
             .service(
                 SwaggerUi::new("/swagger-ui/{_:.*}")
                     .url("/api-docs/openapi.json", ApiDoc::openapi())
             )
+            .route(
+                "/api/health",
+                web::get().to(|pools: web::Data<ConnectionPools>| async move {
+                    handler::health_check(&pools).await
+                }),
+            )
             .route(
                 "/api/cvelist",
                 web::get().to(
-                    || async move {
-                        handler::get_cves().await
+                    |pools: web::Data<ConnectionPools>| async move {
+                        handler::get_cves(&pools).await
                     },
                 ),
             )
             .route(
                 "/api/crates",
-                web::get().to(|| async move {
-                    handler::get_all_crates().await
+                web::get().to(|pools: web::Data<ConnectionPools>| async move {
+                    handler::get_all_crates(&pools).await
                 }),
             )
             .route(
                 "/api/crates/{cratename}",
                 web::get().to(
-                    |name: web::Path<String>| async move {
-                        handler::get_crate_details(name.into_inner().into()).await
+                    |pools: web::Data<ConnectionPools>, name: web::Path<String>| async move {
+                        handler::get_crate_details(&pools, name.into_inner().into()).await
                     },
                 ),
             )
             .route("/api/crates/{nsfront}/{nsbehind}/{cratename}/{version}/versions", 
-            web::get().to(|path: web::Path<(String, String,String,String)>|async move{
+            web::get().to(|pools: web::Data<ConnectionPools>, path: web::Path<(String, String,String,String)>|async move{
                 let (nsfront,nsbehind,cratename, version) = path.into_inner();
-                handler::new_get_version_page(nsfront,nsbehind,cratename,version).await
+                handler::new_get_version_page(&pools, nsfront,nsbehind,cratename,version).await
             }))
             .route("/api/crates/{nsfront}/{nsbehind}/{cratename}/{version}/dependencies/graphpage", 
-            web::get().to(|path: web::Path<(String, String,String,String)>|async move{
+            web::get().to(|pools: web::Data<ConnectionPools>, path: web::Path<(String, String,String,String)>|async move{
                 let (nsfront,nsbehind,cratename, version) = path.into_inner();
-                handler::new_get_graph(nsfront,nsbehind,cratename,version).await
+                handler::new_get_graph(&pools, nsfront,nsbehind,cratename,version).await
             }))
 
             .route(
                 "/api/submit",
                 web::post().to(
-                    | payload: Multipart| async move {
-                        handler::upload_crate(payload).await
+                    |pools: web::Data<ConnectionPools>, principal: auth::Principal, payload: Multipart| async move {
+                        handler::upload_crate(&pools, principal, payload).await
                     },
                 ),
             )
             .route(
                 "/api/submitCrate",
                 web::post().to(
-                    | payload: Multipart| async move {
+                    |pools: web::Data<ConnectionPools>, principal: auth::Principal, payload: Multipart| async move {
                         tracing::info!("enter submitcrate");
-                        handler::upload_crate(payload).await
+                        handler::upload_crate(&pools, principal, payload).await
                     },
                 ),
             )
             .route("/api/submitUserinfo", web::post().to(
-                |payload: String| async move{
+                |pools: web::Data<ConnectionPools>, principal: auth::Principal, payload: String| async move{
                     //web::Json<Userinfo>
                     tracing::info!("enter submitUserinfo");
                     tracing::info!("payload:{}",payload.clone());
-                    let query:Root = serde_json::from_str(&payload).unwrap();
+                    let query:Root = serde_json::from_str(&payload)
+                        .map_err(|e| ApiError::BadRequest(e.to_string()))?;
                     tracing::info!("userinfo {:?}",query);
-                    handler::submituserinfo(query.requestBody.session).await
+                    handler::submituserinfo(&pools, principal, query.requestBody.session).await
             },),)
             .route("/api/profile", web::post().to(
-                |payload: String| async move{
+                |pools: web::Data<ConnectionPools>, principal: auth::Principal| async move{
                     tracing::info!("enter profile");
-                    tracing::info!("payload:{}",payload.clone());
-                    let query:RequestBody2 = serde_json::from_str(&payload).unwrap();
-                    tracing::info!("profile email:{}",query.requestBody.clone());
-                    handler::query_upload_crate(query.requestBody).await
+                    handler::query_upload_crate(&pools, principal.email).await
+            },),)
+            .route("/api/keys", web::post().to(
+                |pools: web::Data<ConnectionPools>, payload: web::Json<auth::NewApiKey>| async move{
+                    handler::issue_api_key(&pools, payload.into_inner()).await
+            },),)
+            .route("/api/keys", web::get().to(
+                |pools: web::Data<ConnectionPools>, query: web::Query<auth::ApiKeyQuery>| async move{
+                    handler::list_api_keys(&pools, query.into_inner()).await
+            },),)
+            .route("/api/keys/{id}", web::delete().to(
+                |pools: web::Data<ConnectionPools>, path: web::Path<String>| async move{
+                    handler::revoke_api_key(&pools, path.into_inner()).await
+            },),)
+            .route("/api/jobs/{id}", web::get().to(
+                |pools: web::Data<ConnectionPools>, principal: auth::Principal, path: web::Path<String>| async move{
+                    handler::get_upload_job(&pools, principal, path.into_inner()).await
+            },),)
+            .route("/api/graphql", web::post().to(
+                |pools: web::Data<ConnectionPools>, schema: web::Data<graphql::CrateSchema>, request: web::Json<async_graphql::Request>| async move{
+                    handler::graphql_query(&pools, &schema, request.into_inner()).await
+            },),)
+            .route("/api/graphql/schema", web::get().to(
+                |schema: web::Data<graphql::CrateSchema>| async move{
+                    handler::graphql_schema(&schema).await
+            },),)
+            .route("/api/search/suggest", web::get().to(
+                |pools: web::Data<ConnectionPools>, search: web::Data<SearchConfig>, query: web::Query<handler::SuggestQuery>| async move{
+                    handler::suggest_crates(&pools, &search, query.into_inner()).await
             },),)
             .route("/api/search", web::post().to(
-                |payload: web::Json<Query>| async move{
+                |pools: web::Data<ConnectionPools>, search: web::Data<SearchConfig>, payload: web::Json<Query>| async move{
                     let query = payload.into_inner();
-                    handler::query_crates(query).await
+                    handler::query_crates(&pools, &search, query).await
             },),)
             .route("/api/crates/{nsfront}/{nsbehind}/{cratename}/{version}/dependencies", 
-            web::get().to(|path: web::Path<(String, String,String,String)>|async move{
+            web::get().to(|pools: web::Data<ConnectionPools>, path: web::Path<(String, String,String,String)>, query: web::Query<dep_list::DepListQuery>|async move{
                 let (nsfront,nsbehind,cratename, version) = path.into_inner();
-                handler::dependency_redis_cache(cratename,version,nsfront,nsbehind).await
+                handler::dependency_redis_cache(&pools, cratename,version,nsfront,nsbehind, query.into_inner()).await
             }))
             /* .route("/api/crates/{nsfront}/{nsbehind}/{cratename}/{version}/dependencycache", 
             web::get().to(|path: web::Path<(String, String,String,String)>|async move{
@@ -244,38 +379,71 @@ pub async fn run_api_server() -> std::io::Result<()> {
                 handler::new_get_dependency(cratename,version,nsfront,nsbehind).await
             }))*/
             .route("/api/crates/{nsfront}/{nsbehind}/{cratename}/{version}/dependencies/graph", 
-            web::get().to(|_path: web::Path<(String, String,String,String)>|async move{
-                HttpResponse::Ok().json(())
+            web::get().to(|pools: web::Data<ConnectionPools>, path: web::Path<(String, String,String,String)>, query: web::Query<dep_graph::GraphQuery>, filter: web::Query<dep_filter::DependencyFilterQuery>|async move{
+                let (_nsfront,_nsbehind,cratename, version) = path.into_inner();
+                handler::get_dependency_graph(&pools, cratename, version, query.into_inner(), filter.into_inner()).await
+            }))
+            .route("/api/crates/{nsfront}/{nsbehind}/{cratename}/{version}/sbom", 
+            web::get().to(|pools: web::Data<ConnectionPools>, path: web::Path<(String, String,String,String)>, query: web::Query<sbom::SbomQuery>|async move{
+                let (nsfront,nsbehind,cratename, version) = path.into_inner();
+                handler::get_sbom(&pools, nsfront, nsbehind, cratename, version, query.into_inner()).await
+            }))
+            .route("/api/advisories/{id}/blast-radius", 
+            web::get().to(|pools: web::Data<ConnectionPools>, path: web::Path<String>, query: web::Query<blast_radius::BlastRadiusQuery>|async move{
+                handler::get_blast_radius(&pools, path.into_inner(), query.into_inner()).await
+            }))
+            .route("/api/audit/lockfile",
+            web::post().to(|pools: web::Data<ConnectionPools>, payload: Multipart|async move{
+                handler::audit_lockfile(&pools, payload).await
+            }))
+            .route("/api/v1/querybatch", 
+            web::post().to(|pools: web::Data<ConnectionPools>, payload: web::Json<osv::OsvQueryBatch>|async move{
+                handler::query_batch(&pools, payload.into_inner()).await
+            }))
+            .route("/api/v1/vulns/{id}", 
+            web::get().to(|pools: web::Data<ConnectionPools>, path: web::Path<String>|async move{
+                handler::get_osv_vulnerability(&pools, path.into_inner()).await
             }))
             .route("/api/crates/{nsfront}/{nsbehind}/{cratename}/{version}/dependents", 
-            web::get().to(|path: web::Path<(String, String,String,String)>|async move{
+            web::get().to(|pools: web::Data<ConnectionPools>, path: web::Path<(String, String,String,String)>, query: web::Query<dep_list::DepListQuery>|async move{
                 let (nsfront,nsbehind,cratename, version) = path.into_inner();
-                handler::dependent_redis_cache(cratename,version,nsfront,nsbehind).await
+                handler::dependent_redis_cache(&pools, cratename,version,nsfront,nsbehind, query.into_inner()).await
             }))
             /* .route("/api/crates/{nsfront}/{nsbehind}/{cratename}/{version}/dependentcache", 
             web::get().to(|path: web::Path<(String, String,String,String)>|async move{
                 let (nsfront,nsbehind,cratename, version) = path.into_inner();
                 handler::new_get_dependent(cratename,version,nsfront,nsbehind).await
             }))*/
+            // 需在 `{version}` 路由之前注册，否则 `diff`、`similar` 会被当作版本号
+            .route("/api/crates/{nsfront}/{nsbehind}/{cratename}/similar", 
+            web::get().to(|pools: web::Data<ConnectionPools>, path: web::Path<(String, String,String)>, query: web::Query<similar::SimilarQuery>|async move{
+                let (nsfront,nsbehind,cratename) = path.into_inner();
+                handler::get_similar_crates(&pools, nsfront, nsbehind, cratename, query.into_inner()).await
+            }))
+            .route("/api/crates/{nsfront}/{nsbehind}/{cratename}/diff", 
+            web::get().to(|pools: web::Data<ConnectionPools>, path: web::Path<(String, String,String)>, query: web::Query<dep_diff::DiffQuery>|async move{
+                let (nsfront,nsbehind,cratename) = path.into_inner();
+                handler::get_dependency_diff(&pools, nsfront, nsbehind, cratename, query.into_inner()).await
+            }))
             .route("/api/crates/{nsfront}/{nsbehind}/{cratename}/{version}", 
-            web::get().to(|path: web::Path<(String, String,String,String)>|async move{
+            web::get().to(|pools: web::Data<ConnectionPools>, path: web::Path<(String, String,String,String)>|async move{
                 let (nsfront,nsbehind,cratename, version) = path.into_inner();
-                handler::new_get_crates_front_info_from_redis(cratename,version,nsfront,nsbehind).await
+                handler::new_get_crates_front_info_from_redis(&pools, cratename,version,nsfront,nsbehind).await
             }))
             .route("/api/crates/{nsfront}/{nsbehind}/{cratename}/{version}/senseleak", 
-            web::get().to(|path: web::Path<(String, String,String,String)>|async move{
+            web::get().to(|pools: web::Data<ConnectionPools>, path: web::Path<(String, String,String,String)>|async move{
                 let (nsfront,nsbehind,_cratename,_versionn) = path.into_inner();
-                handler::get_senseleak(nsfront, nsbehind).await
+                handler::get_senseleak(&pools, nsfront, nsbehind).await
             }))
             .route("/api/graph/{cratename}/{version}/direct", 
-            web::get().to(|path: web::Path<(String,String)>|async move{
+            web::get().to(|pools: web::Data<ConnectionPools>, path: web::Path<(String,String)>, filter: web::Query<dep_filter::DependencyFilterQuery>|async move{
                 let (cratename, version) = path.into_inner();
-                handler::get_direct_dep_for_graph(cratename,version).await
+                handler::get_direct_dep_for_graph(&pools, cratename,version, filter.into_inner()).await
             }))
             .route("/api/crates/{nsfront}/{nsbehind}/{cratename}/{version}/mirchecker", 
-            web::get().to(|path: web::Path<(String, String,String,String)>|async move{
+            web::get().to(|pools: web::Data<ConnectionPools>, path: web::Path<(String, String,String,String)>|async move{
                 let (nsfront,nsbehind,cratename,version) = path.into_inner();
-                handler::get_mirchecker(nsfront, nsbehind,cratename,version).await
+                handler::get_mirchecker(&pools, nsfront, nsbehind,cratename,version).await
             }))
     })
     .bind("0.0.0.0:6888")?
@@ -0,0 +1,435 @@
+//! 与 OSV 兼容的漏洞查询
+//!
+//! `POST /api/v1/querybatch` 接受 OSV `/v1/querybatch` 的请求格式，只支持
+//! `crates.io` 生态，包可以用 `name` + `ecosystem` 或 `pkg:cargo/...` 形式的
+//! purl 指定。响应中每条查询对应一个结果，结果直接包含完整的 OSV 漏洞记录，
+//! 同时提供 `GET /api/v1/vulns/{id}` 供只读取 ID 的 OSV 工具补全详情。
+//!
+//! 只按提交哈希（`commit`）查询的条目无法对应到 crate 版本，返回空结果。
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::advisory::AffectedRange;
+use crate::advisory_index::AdvisoryTarget;
+use crate::error::ApiError;
+
+/// 单次请求最多包含的查询数，与 OSV 的限制一致
+pub const MAX_BATCH_QUERIES: usize = 1000;
+
+pub const OSV_SCHEMA_VERSION: &str = "1.6.0";
+
+pub const ECOSYSTEM: &str = "crates.io";
+
+#[derive(Deserialize, Debug, Default, ToSchema)]
+pub struct OsvQueryBatch {
+    #[serde(default)]
+    pub queries: Vec<OsvQuery>,
+}
+
+#[derive(Deserialize, Debug, Default, ToSchema)]
+pub struct OsvQuery {
+    pub package: Option<OsvPackage>,
+    pub version: Option<String>,
+    pub commit: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default, ToSchema)]
+pub struct OsvPackage {
+    pub name: Option<String>,
+    pub ecosystem: Option<String>,
+    pub purl: Option<String>,
+}
+
+/// 解析后的查询，`version` 为空时查询该 crate 的全部漏洞
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageQuery {
+    pub name: String,
+    pub version: Option<String>,
+}
+
+impl OsvQuery {
+    /// 只有 `commit` 的查询返回 `None`
+    fn parse(self) -> Result<Option<PackageQuery>, String> {
+        let version = self.version.filter(|v| !v.trim().is_empty());
+        let Some(package) = self.package else {
+            return match self.commit {
+                Some(_) => Ok(None),
+                None => Err("either package or commit is required".to_string()),
+            };
+        };
+        if let Some(purl) = package.purl {
+            if package.name.is_some() || package.ecosystem.is_some() {
+                return Err("purl cannot be combined with name or ecosystem".to_string());
+            }
+            let (name, purl_version) = parse_purl(&purl)?;
+            if purl_version.is_some() && version.is_some() {
+                return Err("version is given both in purl and in the query".to_string());
+            }
+            return Ok(Some(PackageQuery {
+                name,
+                version: purl_version.or(version),
+            }));
+        }
+        match package.ecosystem.as_deref() {
+            Some(ECOSYSTEM) => {}
+            Some(other) => return Err(format!("unsupported ecosystem '{}'", other)),
+            None => return Err("package.ecosystem is required".to_string()),
+        }
+        match package.name.filter(|n| !n.trim().is_empty()) {
+            Some(name) => Ok(Some(PackageQuery {
+                name: name.trim().to_string(),
+                version,
+            })),
+            None => Err("package.name is required".to_string()),
+        }
+    }
+}
+
+/// 解析 `pkg:cargo/name@version`，忽略限定符和子路径
+fn parse_purl(purl: &str) -> Result<(String, Option<String>), String> {
+    let rest = purl
+        .trim()
+        .strip_prefix("pkg:cargo/")
+        .ok_or_else(|| format!("unsupported purl '{}'", purl))?;
+    let rest = rest.split(['?', '#']).next().unwrap_or_default();
+    let (name, version) = match rest.split_once('@') {
+        Some((name, version)) => (name, Some(version.to_string())),
+        None => (rest, None),
+    };
+    if name.is_empty() {
+        return Err(format!("purl '{}' has no package name", purl));
+    }
+    Ok((name.to_string(), version.filter(|v| !v.is_empty())))
+}
+
+impl TryFrom<OsvQueryBatch> for Vec<Option<PackageQuery>> {
+    type Error = ApiError;
+
+    fn try_from(batch: OsvQueryBatch) -> Result<Self, ApiError> {
+        if batch.queries.len() > MAX_BATCH_QUERIES {
+            return Err(ApiError::BadRequest(format!(
+                "at most {} queries per batch, got {}",
+                MAX_BATCH_QUERIES,
+                batch.queries.len()
+            )));
+        }
+        batch
+            .queries
+            .into_iter()
+            .enumerate()
+            .map(|(i, query)| {
+                query
+                    .parse()
+                    .map_err(|e| ApiError::BadRequest(format!("queries[{}]: {}", i, e)))
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, ToSchema)]
+pub struct OsvBatchResponse {
+    pub results: Vec<OsvBatchResult>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, ToSchema)]
+pub struct OsvBatchResult {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vulns: Vec<OsvVulnerability>,
+}
+
+/// OSV 格式的漏洞记录
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct OsvVulnerability {
+    pub schema_version: String,
+    pub id: String,
+    pub modified: String,
+    pub published: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    pub summary: String,
+    pub details: String,
+    pub affected: Vec<OsvAffected>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub references: Vec<OsvReference>,
+    pub database_specific: OsvDatabaseSpecific,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct OsvAffected {
+    pub package: OsvAffectedPackage,
+    pub ranges: Vec<OsvRange>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct OsvAffectedPackage {
+    pub ecosystem: String,
+    pub name: String,
+    pub purl: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct OsvRange {
+    #[serde(rename = "type")]
+    pub range_type: String,
+    pub events: Vec<OsvEvent>,
+}
+
+/// 序列化为 `{"introduced": "1.0.0"}` 这样的单键对象
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OsvEvent {
+    Introduced(String),
+    Fixed(String),
+    LastAffected(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct OsvReference {
+    #[serde(rename = "type")]
+    pub reference_type: String,
+    pub url: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct OsvDatabaseSpecific {
+    pub url: String,
+    /// 非漏洞类通告的种类，如 `unmaintained`、`unsound`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub informational: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keywords: Vec<String>,
+}
+
+/// 把同一漏洞 ID 的全部记录转换为一条 OSV 漏洞，每个受影响的 crate 一项 `affected`
+pub fn to_osv(targets: &[AdvisoryTarget]) -> Option<OsvVulnerability> {
+    let info = &targets.first()?.info;
+    let mut affected: Vec<OsvAffected> = vec![];
+    for target in targets {
+        if affected.iter().any(|a| a.package.name == target.crate_name) {
+            continue;
+        }
+        affected.push(OsvAffected {
+            package: OsvAffectedPackage {
+                ecosystem: ECOSYSTEM.to_string(),
+                name: target.crate_name.clone(),
+                purl: format!("pkg:cargo/{}", target.crate_name),
+            },
+            ranges: osv_ranges(&target.matcher.affected_ranges()),
+        });
+    }
+
+    let mut references = vec![OsvReference {
+        reference_type: "ADVISORY".to_string(),
+        url: info.url.clone(),
+    }];
+    for a in &affected {
+        references.push(OsvReference {
+            reference_type: "PACKAGE".to_string(),
+            url: format!("https://crates.io/crates/{}", a.package.name),
+        });
+    }
+    for url in split_list(&info.reference) {
+        if references.iter().all(|r| r.url != url) {
+            references.push(OsvReference {
+                reference_type: "WEB".to_string(),
+                url,
+            });
+        }
+    }
+
+    Some(OsvVulnerability {
+        schema_version: OSV_SCHEMA_VERSION.to_string(),
+        id: info.id.clone(),
+        modified: timestamp(if info.issued.is_empty() {
+            &info.reported
+        } else {
+            &info.issued
+        }),
+        published: timestamp(&info.reported),
+        aliases: split_list(&info.aliases),
+        summary: info.subtitle.clone(),
+        details: info.description.clone(),
+        affected,
+        references,
+        database_specific: OsvDatabaseSpecific {
+            url: info.url.clone(),
+            informational: Some(info.ttype.clone())
+                .filter(|t| !t.is_empty() && t != "vulnerability"),
+            keywords: split_list(&info.keywords),
+        },
+    })
+}
+
+/// 以 `fixed` 结束的区间合并为一个 SEMVER 范围；OSV 不允许 `fixed` 与
+/// `last_affected` 出现在同一范围中，以 `last_affected` 结束的区间各占一个范围。
+fn osv_ranges(ranges: &[AffectedRange]) -> Vec<OsvRange> {
+    let introduced = |r: &AffectedRange| {
+        OsvEvent::Introduced(
+            r.introduced
+                .as_ref()
+                .map_or("0".to_string(), |v| v.to_string()),
+        )
+    };
+    let mut fixed = vec![];
+    let mut result = vec![];
+    for r in ranges {
+        match &r.last_affected {
+            Some(last) => result.push(vec![
+                introduced(r),
+                OsvEvent::LastAffected(last.to_string()),
+            ]),
+            None => {
+                fixed.push(introduced(r));
+                fixed.extend(r.fixed.as_ref().map(|v| OsvEvent::Fixed(v.to_string())));
+            }
+        }
+    }
+    if !fixed.is_empty() {
+        result.insert(0, fixed);
+    }
+    result
+        .into_iter()
+        .map(|events| OsvRange {
+            range_type: "SEMVER".to_string(),
+            events,
+        })
+        .collect()
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(';')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// RustSec 只记录日期，转换为 OSV 要求的 RFC 3339 时间
+fn timestamp(date: &str) -> String {
+    match NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d") {
+        Ok(date) => format!("{}T00:00:00Z", date),
+        Err(_) => date.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advisory::AdvisoryMatcher;
+    use crate::handler::NewRustsec;
+
+    fn query(json: &str) -> Result<Vec<Option<PackageQuery>>, ApiError> {
+        serde_json::from_str::<OsvQueryBatch>(json)
+            .unwrap()
+            .try_into()
+    }
+
+    #[test]
+    fn test_parse_queries() {
+        let parsed = query(
+            r#"{"queries": [
+                {"package": {"name": "time", "ecosystem": "crates.io"}, "version": "0.1.44"},
+                {"package": {"purl": "pkg:cargo/smallvec@1.6.0"}},
+                {"package": {"purl": "pkg:cargo/tokio"}, "version": "1.0.0"},
+                {"package": {"name": "chrono", "ecosystem": "crates.io"}},
+                {"commit": "6879efc2c1596d11a6a6ad296f80063b558d5e0f"}
+            ]}"#,
+        )
+        .unwrap();
+        let package = |name: &str, version: Option<&str>| {
+            Some(PackageQuery {
+                name: name.to_string(),
+                version: version.map(str::to_string),
+            })
+        };
+        assert_eq!(
+            parsed,
+            [
+                package("time", Some("0.1.44")),
+                package("smallvec", Some("1.6.0")),
+                package("tokio", Some("1.0.0")),
+                package("chrono", None),
+                None,
+            ]
+        );
+
+        for bad in [
+            r#"{"queries": [{"package": {"name": "lodash", "ecosystem": "npm"}}]}"#,
+            r#"{"queries": [{"package": {"name": "time"}}]}"#,
+            r#"{"queries": [{"package": {"purl": "pkg:npm/lodash"}}]}"#,
+            r#"{"queries": [{"package": {"purl": "pkg:cargo/time@0.1.0"}, "version": "0.1.1"}]}"#,
+            r#"{"queries": [{"version": "1.0.0"}]}"#,
+        ] {
+            assert!(
+                matches!(query(bad), Err(ApiError::BadRequest(_))),
+                "{}",
+                bad
+            );
+        }
+        let too_many = OsvQueryBatch {
+            queries: (0..=MAX_BATCH_QUERIES)
+                .map(|_| OsvQuery::default())
+                .collect(),
+        };
+        assert!(Vec::<Option<PackageQuery>>::try_from(too_many).is_err());
+    }
+
+    #[test]
+    fn test_to_osv() {
+        let info = NewRustsec {
+            id: "RUSTSEC-2020-0071".to_string(),
+            subtitle: "Potential segfault in the time crate".to_string(),
+            reported: "2020-11-18".to_string(),
+            issued: "2020-11-18".to_string(),
+            package: "time".to_string(),
+            ttype: "vulnerability".to_string(),
+            keywords: "segfault".to_string(),
+            aliases: "CVE-2020-26235;GHSA-wcg3-cvx6-7396".to_string(),
+            reference: "https://github.com/time-rs/time/issues/293".to_string(),
+            patched: ">=0.2.23".to_string(),
+            unaffected: "<0.2.0".to_string(),
+            description: "Unix-like operating systems may segfault".to_string(),
+            url: "https://rustsec.org/advisories/RUSTSEC-2020-0071.html".to_string(),
+        };
+        let target = AdvisoryTarget {
+            crate_name: "time".to_string(),
+            matcher: AdvisoryMatcher::new(&info.patched, &info.unaffected),
+            info,
+        };
+        let vuln = to_osv(&[target]).unwrap();
+        assert_eq!(vuln.published, "2020-11-18T00:00:00Z");
+        assert_eq!(vuln.aliases, ["CVE-2020-26235", "GHSA-wcg3-cvx6-7396"]);
+        assert_eq!(vuln.database_specific.informational, None);
+        assert_eq!(vuln.references.len(), 3);
+
+        let json = serde_json::to_value(&vuln.affected).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([{
+                "package": {"ecosystem": "crates.io", "name": "time", "purl": "pkg:cargo/time"},
+                "ranges": [{
+                    "type": "SEMVER",
+                    "events": [
+                        {"introduced": "0.2.0"},
+                        {"fixed": "0.2.23"}
+                    ]
+                }]
+            }])
+        );
+
+        assert_eq!(
+            osv_ranges(&AdvisoryMatcher::new("> 1.2.3", "").affected_ranges()),
+            [OsvRange {
+                range_type: "SEMVER".to_string(),
+                events: vec![
+                    OsvEvent::Introduced("0".to_string()),
+                    OsvEvent::LastAffected("1.2.3".to_string()),
+                ],
+            }]
+        );
+        assert!(to_osv(&[]).is_none());
+    }
+}
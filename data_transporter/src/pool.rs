@@ -0,0 +1,285 @@
+//! API 服务共享的连接池
+//!
+//! Postgres 和 Redis 连接由 deadpool 管理，连接在被取出复用前会做一次健康检查；
+//! TuGraph 的 `Graph` 内部自带连接池，整个服务只创建一个 `TuGraphClient`。
+//! 池大小和等待超时通过环境变量配置。
+
+use std::{env, error::Error, time::Duration};
+
+use async_trait::async_trait;
+use deadpool::{
+    managed::{self, Object, RecycleError, RecycleResult},
+    Runtime,
+};
+use redis::RedisError;
+use serde::Serialize;
+use tokio_postgres::NoTls;
+use tudriver::tugraph_client::TuGraphClient;
+use utoipa::ToSchema;
+
+use crate::{
+    data_reader::DataReader,
+    db::{db_connection_config_from_env, db_cratesio_connection_config_from_env, DBHandler},
+    error::ApiError,
+    handler::ApiHandler,
+    redis_store::{redis_url_from_env, RedisHandler},
+};
+
+pub type PgPool = managed::Pool<PgManager>;
+pub type RedisPool = managed::Pool<RedisManager>;
+
+/// 连接池配置
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    pub postgres_size: usize,
+    pub redis_size: usize,
+    pub tugraph_size: usize,
+    pub wait_timeout: Duration,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            postgres_size: 16,
+            redis_size: 16,
+            tugraph_size: 1000,
+            wait_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl PoolConfig {
+    /// 从 `POSTGRES_POOL_SIZE`、`REDIS_POOL_SIZE`、`TUGRAPH_POOL_SIZE`、
+    /// `POOL_WAIT_TIMEOUT_SECS` 读取配置，未设置的项使用默认值
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(key: &str) -> Option<T> {
+            env::var(key).ok().and_then(|v| v.parse().ok())
+        }
+        let default = PoolConfig::default();
+        PoolConfig {
+            postgres_size: var("POSTGRES_POOL_SIZE").unwrap_or(default.postgres_size),
+            redis_size: var("REDIS_POOL_SIZE").unwrap_or(default.redis_size),
+            tugraph_size: var("TUGRAPH_POOL_SIZE").unwrap_or(default.tugraph_size),
+            wait_timeout: var("POOL_WAIT_TIMEOUT_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(default.wait_timeout),
+        }
+    }
+}
+
+pub struct PgManager {
+    db_connection_config: String,
+}
+
+#[async_trait]
+impl managed::Manager for PgManager {
+    type Type = DBHandler;
+    type Error = tokio_postgres::Error;
+
+    async fn create(&self) -> Result<DBHandler, tokio_postgres::Error> {
+        let (client, connection) =
+            tokio_postgres::connect(&self.db_connection_config, NoTls).await?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                tracing::error!("postgres connection error: {}", e);
+            }
+        });
+        Ok(DBHandler { client })
+    }
+
+    async fn recycle(&self, obj: &mut DBHandler) -> RecycleResult<tokio_postgres::Error> {
+        if obj.client.is_closed() {
+            return Err(RecycleError::StaticMessage("connection closed"));
+        }
+        obj.client.simple_query("SELECT 1").await?;
+        Ok(())
+    }
+}
+
+pub struct RedisManager {
+    url: String,
+}
+
+#[async_trait]
+impl managed::Manager for RedisManager {
+    type Type = RedisHandler;
+    type Error = RedisError;
+
+    async fn create(&self) -> Result<RedisHandler, RedisError> {
+        let connection = redis::Client::open(self.url.as_str())?
+            .get_multiplexed_async_connection()
+            .await?;
+        Ok(RedisHandler { connection })
+    }
+
+    async fn recycle(&self, obj: &mut RedisHandler) -> RecycleResult<RedisError> {
+        redis::cmd("PING")
+            .query_async::<_, String>(obj.get_connection_mut())
+            .await?;
+        Ok(())
+    }
+}
+
+/// 按配置构建一个 Postgres 连接池，连接在首次使用时才建立
+pub fn pg_pool(db_connection_config: String, config: &PoolConfig) -> PgPool {
+    managed::Pool::builder(PgManager {
+        db_connection_config,
+    })
+    .max_size(config.postgres_size)
+    .wait_timeout(Some(config.wait_timeout))
+    .runtime(Runtime::Tokio1)
+    .build()
+    .expect("runtime is set, building the pool cannot fail")
+}
+
+fn redis_pool(url: String, config: &PoolConfig) -> RedisPool {
+    managed::Pool::builder(RedisManager { url })
+        .max_size(config.redis_size)
+        .wait_timeout(Some(config.wait_timeout))
+        .runtime(Runtime::Tokio1)
+        .build()
+        .expect("runtime is set, building the pool cannot fail")
+}
+
+/// 服务启动时创建一次，通过 `web::Data` 共享给所有 handler
+#[derive(Clone)]
+pub struct ConnectionPools {
+    pub pg: PgPool,
+    pub pg_cratesio: PgPool,
+    pub redis: RedisPool,
+    pub tugraph: TuGraphClient,
+}
+
+impl ConnectionPools {
+    pub async fn new(config: &PoolConfig) -> Result<Self, Box<dyn Error>> {
+        let tugraph = TuGraphClient::with_max_connections(
+            &env::var("TUGRAPH_BOLT_URL")?,
+            &env::var("TUGRAPH_USER_NAME")?,
+            &env::var("TUGRAPH_USER_PASSWORD")?,
+            &env::var("TUGRAPH_CRATESPRO_DB")?,
+            config.tugraph_size,
+        )
+        .await?;
+        Ok(ConnectionPools {
+            pg: pg_pool(db_connection_config_from_env(), config),
+            pg_cratesio: pg_pool(db_cratesio_connection_config_from_env(), config),
+            redis: redis_pool(redis_url_from_env(), config),
+            tugraph,
+        })
+    }
+
+    /// 从 cratespro 库的连接池取出一个连接
+    pub async fn pg(&self) -> Result<Object<PgManager>, ApiError> {
+        self.pg
+            .get()
+            .await
+            .map_err(|e| ApiError::unavailable("postgres", e))
+    }
+
+    /// 从 crates.io 数据库的连接池取出一个连接
+    pub async fn pg_cratesio(&self) -> Result<Object<PgManager>, ApiError> {
+        self.pg_cratesio
+            .get()
+            .await
+            .map_err(|e| ApiError::unavailable("postgres", e))
+    }
+
+    /// 从 Redis 连接池取出一个连接
+    pub async fn redis(&self) -> Result<Object<RedisManager>, ApiError> {
+        self.redis
+            .get()
+            .await
+            .map_err(|e| ApiError::unavailable("redis", e))
+    }
+
+    /// 基于共享连接构建图数据读取器
+    pub(crate) fn data_reader(&self) -> DataReader {
+        DataReader::with_pools(
+            self.tugraph.clone(),
+            self.pg.clone(),
+            self.pg_cratesio.clone(),
+        )
+    }
+
+    /// 基于共享连接构建 TuGraph 查询入口
+    pub fn api_handler(&self) -> ApiHandler {
+        ApiHandler::new(self.data_reader())
+    }
+
+    /// 检查各个后端是否可用
+    pub async fn health(&self) -> HealthStatus {
+        let postgres = match self.pg().await {
+            Ok(_) => BackendHealth::up(self.pg.status()),
+            Err(e) => BackendHealth::down(self.pg.status(), e),
+        };
+        let redis = match self.redis().await {
+            Ok(_) => BackendHealth::up(self.redis.status()),
+            Err(e) => BackendHealth::down(self.redis.status(), e),
+        };
+        let tugraph = match self.tugraph.exec_query("RETURN 1").await {
+            Ok(_) => BackendHealth {
+                up: true,
+                error: None,
+                size: None,
+                max_size: None,
+            },
+            Err(e) => BackendHealth {
+                up: false,
+                error: Some(e.to_string()),
+                size: None,
+                max_size: None,
+            },
+        };
+        HealthStatus {
+            ok: postgres.up && redis.up && tugraph.up,
+            postgres,
+            redis,
+            tugraph,
+            semantic_fallbacks: search::crates_search::semantic_fallbacks(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct HealthStatus {
+    pub ok: bool,
+    pub postgres: BackendHealth,
+    pub redis: BackendHealth,
+    pub tugraph: BackendHealth,
+    /// 启动以来搜索的向量检索出错、退回只按关键词排序的次数
+    pub semantic_fallbacks: u64,
+}
+
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct BackendHealth {
+    pub up: bool,
+    pub error: Option<String>,
+    /// 连接池当前连接数
+    pub size: Option<usize>,
+    /// 连接池最大连接数
+    pub max_size: Option<usize>,
+}
+
+impl BackendHealth {
+    fn up(status: deadpool::Status) -> Self {
+        BackendHealth {
+            up: true,
+            error: None,
+            size: Some(status.size),
+            max_size: Some(status.max_size),
+        }
+    }
+
+    fn down(status: deadpool::Status, e: ApiError) -> Self {
+        let error = match e {
+            ApiError::Unavailable { detail, .. } => detail,
+            e => e.to_string(),
+        };
+        BackendHealth {
+            up: false,
+            error: Some(error),
+            size: Some(status.size),
+            max_size: Some(status.max_size),
+        }
+    }
+}
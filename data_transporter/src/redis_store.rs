@@ -1,32 +1,24 @@
+use std::collections::BTreeSet;
 use std::env;
 
-use redis::{Commands, Connection};
+use futures_util::StreamExt;
+use redis::{aio::MultiplexedConnection, AsyncCommands};
+
+use crate::cache::{index_key, CacheKey, CacheKind, Cached, LEGACY_PREFIXES};
 
 pub struct RedisHandler {
-    pub connection: Connection,
+    pub connection: MultiplexedConnection,
 }
-///建立一个 Redis 客户端连接
-pub async fn get_redis_connection() -> Result<Connection, Box<dyn std::error::Error>> {
+/// 从环境变量构建 Redis 连接地址
+pub fn redis_url_from_env() -> String {
     let host = env::var("REDIS_HOST").unwrap_or_else(|_| "172.17.0.1".to_string());
     let password = env::var("REDIS_PASSWORD").unwrap_or_else(|_| "".to_string());
-    println!("host:{}", host);
-    // 构建连接字符串
-    let conn_string = format!("redis://:{}@{}:6379/", password, host);
-    println!("conn_string:{}", conn_string);
-    println!("尝试连接 Redis: {}", conn_string);
-
-    // 创建客户端
-    let client = redis::Client::open(&*conn_string).unwrap();
-    println!("finish get client");
-    // 获取连接
-    let conn = client.get_connection().unwrap();
-    Ok(conn)
+    format!("redis://:{}@{}:6379/", password, host)
 }
 
 impl RedisHandler {
-
     ///获取 Redis 连接的可变引用
-    pub fn get_connection_mut(&mut self) -> &mut Connection {
+    pub fn get_connection_mut(&mut self) -> &mut MultiplexedConnection {
         &mut self.connection
     }
     /// 查询指定 key（qid）在 Redis 中的值
@@ -34,7 +26,7 @@ impl RedisHandler {
         &mut self,
         qid: String,
     ) -> Result<String, Box<dyn std::error::Error>> {
-        match self.get_connection_mut().get::<_, String>(&qid) {
+        match self.get_connection_mut().get::<_, String>(&qid).await {
             Ok(value) => Ok(value),
             Err(e) => {
                 if e.kind() == redis::ErrorKind::TypeError {
@@ -45,122 +37,135 @@ impl RedisHandler {
             }
         }
     }
-    
-    /// 向 Redis 插入 crates（包）信息缓存
-    pub async fn insert_crates_info_into_redis(
+
+    /// 读取缓存，不存在或无法解析时返回 `None`
+    pub async fn get_cached<T: Cached>(
         &mut self,
-        namespace: String,
-        name: String,
-        version: String,
-        value: String,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        println!("start insert crates_info");
-        let key = format!("crates_info:{}:{}:{}", namespace, name, version);
+        key: &CacheKey,
+    ) -> Result<Option<T>, Box<dyn std::error::Error>> {
+        let redis_key = key.redis_key(T::KIND);
+        let value = self.query_from_redis(redis_key.clone()).await?;
+        if value.is_empty() {
+            return Ok(None);
+        }
+        match serde_json::from_str(&value) {
+            Ok(cached) => Ok(Some(cached)),
+            Err(e) => {
+                tracing::warn!("ignoring stale cache entry {}: {}", redis_key, e);
+                Ok(None)
+            }
+        }
+    }
 
-        // 使用管道设置值和过期时间
-        let _: () = redis::pipe()
-            .cmd("SET")
-            .arg(&key)
-            .arg(&value)
-            .cmd("EXPIRE")
-            .arg(&key)
-            .arg(7 * 24 * 60 * 60) // 一周的秒数
-            .query(&mut self.connection)?;
-        println!("finish insert crates_info");
-        Ok(())
+    /// 写入缓存并按种类设置过期时间，同时把 key 记入该 crate 的索引集合。
+    /// 写入失败只记录日志，不影响本次响应
+    pub async fn set_cached<T: Cached>(&mut self, key: &CacheKey, value: &T) {
+        if let Err(e) = self.write_cached(key, value).await {
+            tracing::warn!("failed to cache {}: {}", key.redis_key(T::KIND), e);
+        }
     }
 
-    // 向 Redis 插入依赖信息
-    pub async fn insert_dependency_into_redis(
+    async fn write_cached<T: Cached>(
         &mut self,
-        namespace: String,
-        name: String,
-        version: String,
-        value: String,
+        key: &CacheKey,
+        value: &T,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        println!("start insert dependency");
-        let key = format!("dependency:{}:{}:{}", namespace, name, version);
-
-        // 使用管道设置值和过期时间
+        let redis_key = key.redis_key(T::KIND);
+        let index = index_key(&key.name);
+        let ttl = T::KIND.ttl().as_secs();
+        // 索引不短于其中任何一条缓存
+        let index_ttl = CacheKind::ALL
+            .iter()
+            .map(|kind| kind.ttl().as_secs())
+            .max()
+            .unwrap_or(ttl);
         let _: () = redis::pipe()
             .cmd("SET")
-            .arg(&key)
-            .arg(&value)
+            .arg(&redis_key)
+            .arg(serde_json::to_string(value)?)
+            .arg("EX")
+            .arg(ttl)
+            .cmd("SADD")
+            .arg(&index)
+            .arg(&redis_key)
             .cmd("EXPIRE")
-            .arg(&key)
-            .arg(7 * 24 * 60 * 60) // 一周的秒数
-            .query(&mut self.connection)?;
-        println!("finish insert dependency");
+            .arg(&index)
+            .arg(index_ttl)
+            .query_async(&mut self.connection)
+            .await?;
         Ok(())
     }
 
-    /// 向 Redis 插入依赖图信息（表示依赖关系的图结构数据）
-    pub async fn insert_dependency_graph_into_redis(
+    /// 删除这些 crate 的全部缓存，返回删除的条数
+    pub async fn invalidate_crates(
         &mut self,
-        namespace: String,
-        name: String,
-        version: String,
-        value: String,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        println!("start insert dependencygraph");
-        let key = format!("dependencygraph:{}:{}:{}", namespace, name, version);
-
-        // 使用管道设置值和过期时间
-        let _: () = redis::pipe()
-            .cmd("SET")
-            .arg(&key)
-            .arg(&value)
-            .cmd("EXPIRE")
-            .arg(&key)
-            .arg(7 * 24 * 60 * 60) // 一周的秒数
-            .query(&mut self.connection)?;
-        println!("finish insert dependencygraph");
-        Ok(())
+        names: &BTreeSet<String>,
+    ) -> Result<usize, redis::RedisError> {
+        let mut removed = 0;
+        for name in names {
+            let index = index_key(name);
+            let keys: Vec<String> = self.connection.smembers(&index).await?;
+            if !keys.is_empty() {
+                removed += self.connection.del::<_, usize>(&keys).await?;
+            }
+            self.connection.del::<_, ()>(&index).await?;
+        }
+        Ok(removed)
     }
-    /// Redis 插入“被依赖者”的信息（即谁依赖了当前包）
-    pub async fn insert_dependent_into_redis(
+
+    /// 限流计数：`bucket` 在第 `window` 个时间窗口内的请求数加一并返回，
+    /// 计数在 `ttl_secs` 秒后过期
+    pub async fn count_request(
         &mut self,
-        namespace: String,
-        name: String,
-        version: String,
-        value: String,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        println!("start insert dependent");
-        let key = format!("dependent:{}:{}:{}", namespace, name, version);
+        bucket: &str,
+        window: u64,
+        ttl_secs: u64,
+    ) -> Result<u64, redis::RedisError> {
+        let key = format!("ratelimit:{}:{}", bucket, window);
+        let (count,): (u64,) = redis::pipe()
+            .incr(&key, 1)
+            .expire(&key, ttl_secs as usize)
+            .ignore()
+            .query_async(&mut self.connection)
+            .await?;
+        Ok(count)
+    }
 
-        // 使用管道设置值和过期时间
-        let _: () = redis::pipe()
-            .cmd("SET")
-            .arg(&key)
-            .arg(&value)
-            .cmd("EXPIRE")
-            .arg(&key)
-            .arg(7 * 24 * 60 * 60) // 一周的秒数
-            .query(&mut self.connection)?;
-        println!("finish insert dependent");
-        Ok(())
+    /// 删除全部响应缓存和索引，包括旧版本留下的缓存
+    pub async fn invalidate_all(&mut self) -> Result<usize, redis::RedisError> {
+        let patterns = CacheKind::ALL
+            .iter()
+            .map(|kind| kind.prefix())
+            .chain(LEGACY_PREFIXES)
+            .map(|prefix| format!("{}:*", prefix))
+            .chain(std::iter::once(index_key("*")))
+            .collect();
+        self.delete_matching(patterns).await
     }
 
-    /// 向 Redis 插入一个包的版本页信息（例如包含所有版本的列表）
-    pub async fn insert_versionpage_into_redis(
-        &mut self,
-        namespace: String,
-        name: String,
-        value: String,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        println!("start insert versionpage");
-        let key = format!("versionpage:{}:{}", namespace, name);
+    /// 删除旧版本写入的没有过期时间的缓存
+    pub async fn invalidate_legacy(&mut self) -> Result<usize, redis::RedisError> {
+        self.delete_matching(
+            LEGACY_PREFIXES
+                .map(|prefix| format!("{}:*", prefix))
+                .to_vec(),
+        )
+        .await
+    }
 
-        // 使用管道设置值和过期时间
-        let _: () = redis::pipe()
-            .cmd("SET")
-            .arg(&key)
-            .arg(&value)
-            .cmd("EXPIRE")
-            .arg(&key)
-            .arg(7 * 24 * 60 * 60) // 一周的秒数
-            .query(&mut self.connection)?;
-        println!("finish insert versionpage");
-        Ok(())
+    async fn delete_matching(&mut self, patterns: Vec<String>) -> Result<usize, redis::RedisError> {
+        let mut removed = 0;
+        for pattern in patterns {
+            let keys: Vec<String> = self
+                .connection
+                .scan_match::<_, String>(&pattern)
+                .await?
+                .collect()
+                .await;
+            for chunk in keys.chunks(500) {
+                removed += self.connection.del::<_, usize>(chunk).await?;
+            }
+        }
+        Ok(removed)
     }
 }
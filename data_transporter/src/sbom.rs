@@ -0,0 +1,498 @@
+//! 软件物料清单（SBOM）
+//!
+//! 把一个版本的传递依赖、许可证和 RustSec 漏洞整理为 [`Sbom`]，再输出为
+//! CycloneDX 1.5 或 SPDX 2.3 的 JSON 文档。这里只负责格式转换，数据的收集在
+//! `handler::build_sbom` 中完成。
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::handler::NewRustsec;
+
+const TOOL_NAME: &str = "cratespro";
+const SPDX_NAMESPACE_BASE: &str = "https://cratespro.dev/spdx";
+
+/// SBOM 的输出格式
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SbomFormat {
+    /// CycloneDX 1.5 JSON
+    #[default]
+    CycloneDx,
+    /// SPDX 2.3 JSON
+    Spdx,
+}
+
+impl FromStr for SbomFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "cyclonedx" => Ok(SbomFormat::CycloneDx),
+            "spdx" => Ok(SbomFormat::Spdx),
+            other => Err(format!(
+                "unknown SBOM format `{}`, expected `cyclonedx` or `spdx`",
+                other
+            )),
+        }
+    }
+}
+
+/// SBOM 接口的查询参数
+#[derive(Deserialize, Debug, Default, Clone, ToSchema)]
+pub struct SbomQuery {
+    /// `cyclonedx`（默认）或 `spdx`
+    pub format: Option<SbomFormat>,
+}
+
+/// SBOM 中的一个 crate 版本
+#[derive(Debug, Clone)]
+pub struct SbomComponent {
+    pub name: String,
+    pub version: String,
+    /// `license` 表中记录的许可证，未知时为 `None`
+    pub license: Option<String>,
+    /// 影响该版本的 RustSec 漏洞
+    pub advisories: Vec<NewRustsec>,
+}
+
+impl SbomComponent {
+    /// `name/version`
+    pub fn id(&self) -> String {
+        format!("{}/{}", self.name, self.version)
+    }
+
+    /// Package URL，版本中的 `+` 需要转义
+    pub fn purl(&self) -> String {
+        format!(
+            "pkg:cargo/{}@{}",
+            self.name,
+            self.version.replace('+', "%2B")
+        )
+    }
+
+    fn spdx_id(&self) -> String {
+        format!("SPDXRef-Package-{}", spdx_id_part(&self.id()))
+    }
+}
+
+/// 一个版本及其全部传递依赖
+#[derive(Debug, Clone)]
+pub struct Sbom {
+    pub root: SbomComponent,
+    /// 传递依赖，不含根节点
+    pub components: Vec<SbomComponent>,
+    /// `(from, to)` 形式的直接依赖边，两端均为 `name/version`
+    pub dependencies: Vec<(String, String)>,
+    /// 传递依赖过多、遍历被截断，清单不完整
+    pub truncated: bool,
+    pub created: DateTime<Utc>,
+}
+
+impl Sbom {
+    /// 按指定格式输出，文档的序列号随机生成
+    pub fn render(&self, format: SbomFormat) -> Value {
+        match format {
+            SbomFormat::CycloneDx => self.to_cyclonedx(Uuid::new_v4()),
+            SbomFormat::Spdx => self.to_spdx(Uuid::new_v4()),
+        }
+    }
+
+    fn all_components(&self) -> impl Iterator<Item = &SbomComponent> {
+        std::iter::once(&self.root).chain(&self.components)
+    }
+
+    fn timestamp(&self) -> String {
+        self.created.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+    }
+
+    /// 每个节点的直接依赖，只保留两端都在清单中的边
+    fn dependency_map(&self) -> BTreeMap<String, BTreeSet<String>> {
+        let mut map: BTreeMap<String, BTreeSet<String>> = self
+            .all_components()
+            .map(|c| (c.id(), BTreeSet::new()))
+            .collect();
+        for (from, to) in &self.dependencies {
+            if from != to && map.contains_key(to) {
+                if let Some(deps) = map.get_mut(from) {
+                    deps.insert(to.clone());
+                }
+            }
+        }
+        map
+    }
+
+    /// CycloneDX 1.5 JSON，`bom-ref` 使用 purl，清单被截断时用 `compositions` 标记为不完整
+    pub fn to_cyclonedx(&self, serial: Uuid) -> Value {
+        let purls: BTreeMap<String, String> =
+            self.all_components().map(|c| (c.id(), c.purl())).collect();
+
+        let dependencies: Vec<Value> = self
+            .dependency_map()
+            .into_iter()
+            .filter_map(|(from, to)| {
+                Some(json!({
+                    "ref": purls.get(&from)?,
+                    "dependsOn": to.iter().filter_map(|id| purls.get(id)).collect::<Vec<_>>(),
+                }))
+            })
+            .collect();
+
+        // 同一漏洞可能影响多个版本，合并为一条
+        let mut vulnerabilities: BTreeMap<&str, (&NewRustsec, BTreeSet<String>)> = BTreeMap::new();
+        for c in self.all_components() {
+            for advisory in &c.advisories {
+                vulnerabilities
+                    .entry(advisory.id.as_str())
+                    .or_insert_with(|| (advisory, BTreeSet::new()))
+                    .1
+                    .insert(c.purl());
+            }
+        }
+        let vulnerabilities: Vec<Value> = vulnerabilities
+            .into_values()
+            .map(|(advisory, affects)| {
+                json!({
+                    "id": advisory.id,
+                    "source": { "name": "RustSec", "url": advisory.url },
+                    "description": advisory.subtitle,
+                    "detail": advisory.description,
+                    "advisories": [{ "url": advisory.url }],
+                    "affects": affects
+                        .into_iter()
+                        .map(|r| json!({ "ref": r }))
+                        .collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+
+        let mut bom = json!({
+            "bomFormat": "CycloneDX",
+            "specVersion": "1.5",
+            "serialNumber": format!("urn:uuid:{}", serial),
+            "version": 1,
+            "metadata": {
+                "timestamp": self.timestamp(),
+                "tools": {
+                    "components": [{ "type": "application", "name": TOOL_NAME }],
+                },
+                "component": cyclonedx_component(&self.root),
+            },
+            "components": self.components.iter().map(cyclonedx_component).collect::<Vec<_>>(),
+            "dependencies": dependencies,
+            "vulnerabilities": vulnerabilities,
+        });
+        if self.truncated {
+            bom["compositions"] = json!([{
+                "aggregate": "incomplete",
+                "assemblies": [self.root.purl()],
+            }]);
+        }
+        bom
+    }
+
+    /// SPDX 2.3 JSON，漏洞记录为 `SECURITY` 类的 external reference，清单被截断时在文档注释中说明
+    pub fn to_spdx(&self, namespace: Uuid) -> Value {
+        let ids: BTreeMap<String, String> = self
+            .all_components()
+            .map(|c| (c.id(), c.spdx_id()))
+            .collect();
+
+        let packages: Vec<Value> = self
+            .all_components()
+            .map(|c| {
+                let mut external_refs = vec![json!({
+                    "referenceCategory": "PACKAGE-MANAGER",
+                    "referenceType": "purl",
+                    "referenceLocator": c.purl(),
+                })];
+                external_refs.extend(c.advisories.iter().map(|advisory| {
+                    json!({
+                        "referenceCategory": "SECURITY",
+                        "referenceType": "advisory",
+                        "referenceLocator": advisory.url,
+                        "comment": advisory.id,
+                    })
+                }));
+                json!({
+                    "name": c.name,
+                    "SPDXID": c.spdx_id(),
+                    "versionInfo": c.version,
+                    "downloadLocation": "NOASSERTION",
+                    "filesAnalyzed": false,
+                    "licenseConcluded": "NOASSERTION",
+                    "licenseDeclared": c
+                        .license
+                        .as_deref()
+                        .and_then(license_expression)
+                        .unwrap_or_else(|| "NOASSERTION".to_string()),
+                    "copyrightText": "NOASSERTION",
+                    "externalRefs": external_refs,
+                })
+            })
+            .collect();
+
+        let mut relationships = vec![json!({
+            "spdxElementId": "SPDXRef-DOCUMENT",
+            "relationshipType": "DESCRIBES",
+            "relatedSpdxElement": self.root.spdx_id(),
+        })];
+        for (from, to) in self.dependency_map() {
+            let Some(from) = ids.get(&from) else {
+                continue;
+            };
+            relationships.extend(to.iter().filter_map(|to| ids.get(to)).map(|to| {
+                json!({
+                    "spdxElementId": from,
+                    "relationshipType": "DEPENDS_ON",
+                    "relatedSpdxElement": to,
+                })
+            }));
+        }
+
+        let mut doc = json!({
+            "spdxVersion": "SPDX-2.3",
+            "dataLicense": "CC0-1.0",
+            "SPDXID": "SPDXRef-DOCUMENT",
+            "name": self.root.id(),
+            "documentNamespace": format!(
+                "{}/{}-{}-{}",
+                SPDX_NAMESPACE_BASE,
+                self.root.name,
+                self.root.version,
+                namespace
+            ),
+            "creationInfo": {
+                "created": self.timestamp(),
+                "creators": [format!("Tool: {}", TOOL_NAME)],
+            },
+            "packages": packages,
+            "relationships": relationships,
+        });
+        if self.truncated {
+            doc["comment"] = json!(
+                "The dependency tree was too large and has been truncated, the package list is incomplete."
+            );
+        }
+        doc
+    }
+}
+
+fn cyclonedx_component(c: &SbomComponent) -> Value {
+    let mut component = json!({
+        "type": "library",
+        "bom-ref": c.purl(),
+        "name": c.name,
+        "version": c.version,
+        "purl": c.purl(),
+    });
+    if let Some(license) = &c.license {
+        component["licenses"] = match license_expression(license) {
+            Some(expression) => json!([{ "expression": expression }]),
+            None => json!([{ "license": { "name": license } }]),
+        };
+    }
+    component
+}
+
+/// 把 crates.io 上的许可证字段转换为 SPDX 表达式。旧版本常用 `MIT/Apache-2.0`
+/// 表示 OR；不是合法的 SPDX 表达式（如 `Apache 2.0`）时返回 `None`。
+fn license_expression(license: &str) -> Option<String> {
+    let expression = license
+        .split('/')
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join(" OR ");
+    spdx::Expression::parse(&expression).ok()?;
+    Some(expression)
+}
+
+/// SPDXID 只允许字母、数字、`.` 和 `-`。`-` 作为转义符写为 `--`，其他字符按 UTF-8
+/// 字节写为 `-XX`，保证不同的输入得到不同的 ID（如 `foo_bar` 和 `foo-bar`）
+fn spdx_id_part(s: &str) -> String {
+    let mut id = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c.is_ascii_alphanumeric() || c == '.' {
+            id.push(c);
+        } else if c == '-' {
+            id.push_str("--");
+        } else {
+            let mut buf = [0; 4];
+            for byte in c.encode_utf8(&mut buf).bytes() {
+                id.push_str(&format!("-{:02X}", byte));
+            }
+        }
+    }
+    id
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn component(name: &str, version: &str, license: Option<&str>) -> SbomComponent {
+        SbomComponent {
+            name: name.to_string(),
+            version: version.to_string(),
+            license: license.map(str::to_string),
+            advisories: vec![],
+        }
+    }
+
+    fn fixture() -> Sbom {
+        let mut time = component("time", "0.1.43", Some("MIT/Apache-2.0"));
+        time.advisories.push(NewRustsec {
+            id: "RUSTSEC-2020-0071".to_string(),
+            subtitle: "Potential segfault in the time crate".to_string(),
+            url: "https://rustsec.org/advisories/RUSTSEC-2020-0071.html".to_string(),
+            ..Default::default()
+        });
+        Sbom {
+            root: component("my_app", "1.0.0+build", Some("MIT")),
+            components: vec![time, component("libc", "0.2.150", None)],
+            dependencies: vec![
+                ("my_app/1.0.0+build".to_string(), "time/0.1.43".to_string()),
+                ("time/0.1.43".to_string(), "libc/0.2.150".to_string()),
+                // 不在清单中的依赖被忽略
+                ("libc/0.2.150".to_string(), "unknown/1.0.0".to_string()),
+            ],
+            truncated: false,
+            created: DateTime::parse_from_rfc3339("2024-05-01T08:00:00Z")
+                .unwrap()
+                .with_timezone(&Utc),
+        }
+    }
+
+    #[test]
+    fn test_sbom_documents() {
+        let sbom = fixture();
+        let serial = Uuid::nil();
+
+        let bom = sbom.to_cyclonedx(serial);
+        assert_eq!(bom["bomFormat"], "CycloneDX");
+        assert_eq!(bom["specVersion"], "1.5");
+        assert_eq!(
+            bom["serialNumber"],
+            "urn:uuid:00000000-0000-0000-0000-000000000000"
+        );
+        assert_eq!(bom["metadata"]["timestamp"], "2024-05-01T08:00:00Z");
+        assert_eq!(
+            bom["metadata"]["component"]["purl"],
+            "pkg:cargo/my_app@1.0.0%2Bbuild"
+        );
+        assert_eq!(bom["components"].as_array().unwrap().len(), 2);
+        assert_eq!(
+            bom["components"][0]["licenses"][0]["expression"],
+            "MIT OR Apache-2.0"
+        );
+        assert!(bom["components"][1].get("licenses").is_none());
+        let dependencies = bom["dependencies"].as_array().unwrap();
+        assert_eq!(dependencies.len(), 3);
+        assert!(dependencies.contains(&json!({
+            "ref": "pkg:cargo/time@0.1.43",
+            "dependsOn": ["pkg:cargo/libc@0.2.150"],
+        })));
+        assert!(dependencies.contains(&json!({
+            "ref": "pkg:cargo/libc@0.2.150",
+            "dependsOn": [],
+        })));
+        assert!(bom.get("compositions").is_none());
+        assert_eq!(bom["vulnerabilities"][0]["id"], "RUSTSEC-2020-0071");
+        assert_eq!(
+            bom["vulnerabilities"][0]["affects"],
+            json!([{ "ref": "pkg:cargo/time@0.1.43" }])
+        );
+
+        let doc = sbom.to_spdx(serial);
+        assert_eq!(doc["spdxVersion"], "SPDX-2.3");
+        assert_eq!(doc["dataLicense"], "CC0-1.0");
+        assert_eq!(doc["SPDXID"], "SPDXRef-DOCUMENT");
+        assert_eq!(
+            doc["documentNamespace"],
+            "https://cratespro.dev/spdx/my_app-1.0.0+build-00000000-0000-0000-0000-000000000000"
+        );
+        assert_eq!(doc["creationInfo"]["creators"][0], "Tool: cratespro");
+        let packages = doc["packages"].as_array().unwrap();
+        assert_eq!(packages.len(), 3);
+        assert_eq!(
+            packages[0]["SPDXID"],
+            "SPDXRef-Package-my-5Fapp-2F1.0.0-2Bbuild"
+        );
+        assert_eq!(packages[1]["licenseDeclared"], "MIT OR Apache-2.0");
+        assert_eq!(packages[2]["licenseDeclared"], "NOASSERTION");
+        assert_eq!(
+            packages[1]["externalRefs"][1]["referenceCategory"],
+            "SECURITY"
+        );
+        let relationships = doc["relationships"].as_array().unwrap();
+        assert_eq!(relationships.len(), 3);
+        assert_eq!(
+            relationships[0],
+            json!({
+                "spdxElementId": "SPDXRef-DOCUMENT",
+                "relationshipType": "DESCRIBES",
+                "relatedSpdxElement": "SPDXRef-Package-my-5Fapp-2F1.0.0-2Bbuild",
+            })
+        );
+        assert!(relationships.contains(&json!({
+            "spdxElementId": "SPDXRef-Package-time-2F0.1.43",
+            "relationshipType": "DEPENDS_ON",
+            "relatedSpdxElement": "SPDXRef-Package-libc-2F0.2.150",
+        })));
+        assert!(doc.get("comment").is_none());
+
+        assert!("xml".parse::<SbomFormat>().is_err());
+        assert_eq!(
+            license_expression("GPL-3.0 WITH Classpath-exception-2.0"),
+            Some("GPL-3.0 WITH Classpath-exception-2.0".to_string())
+        );
+        assert_eq!(license_expression("MIT, see LICENSE"), None);
+    }
+
+    #[test]
+    fn test_sbom_invalid_license() {
+        assert_eq!(license_expression("Apache 2.0"), None);
+        assert_eq!(license_expression("MIT OR Foo-1.0"), None);
+        assert_eq!(
+            license_expression("Apache-2.0 / MIT"),
+            Some("Apache-2.0 OR MIT".to_string())
+        );
+
+        let mut sbom = fixture();
+        sbom.components[0].license = Some("Apache 2.0".to_string());
+        let bom = sbom.to_cyclonedx(Uuid::nil());
+        assert_eq!(
+            bom["components"][0]["licenses"],
+            json!([{ "license": { "name": "Apache 2.0" } }])
+        );
+        let doc = sbom.to_spdx(Uuid::nil());
+        assert_eq!(doc["packages"][1]["licenseDeclared"], "NOASSERTION");
+    }
+
+    #[test]
+    fn test_sbom_spdx_ids_and_truncation() {
+        let ids: BTreeSet<String> = ["foo_bar", "foo-bar", "foo-5Fbar", "foo--bar", "foo.bar"]
+            .iter()
+            .map(|name| component(name, "1.0.0", None).spdx_id())
+            .collect();
+        assert_eq!(ids.len(), 5);
+        assert_eq!(spdx_id_part("serde-json/1.0"), "serde--json-2F1.0");
+        assert_eq!(spdx_id_part("é"), "-C3-A9");
+
+        let mut sbom = fixture();
+        sbom.truncated = true;
+        let bom = sbom.to_cyclonedx(Uuid::nil());
+        assert_eq!(bom["compositions"][0]["aggregate"], "incomplete");
+        assert_eq!(
+            bom["compositions"][0]["assemblies"],
+            json!(["pkg:cargo/my_app@1.0.0%2Bbuild"])
+        );
+        assert!(sbom.to_spdx(Uuid::nil())["comment"].is_string());
+    }
+}
@@ -0,0 +1,443 @@
+//! 相似 crate 与替代方案推荐
+//!
+//! 候选来自三类信号：`programs.embedding` 的余弦近邻；依赖图中与目标 crate 一起被
+//! 依赖方引用的 crate（同时依赖）；以及依赖方最新版本不再依赖目标 crate 时，相对
+//! 最后一个依赖它的版本新增的依赖（被替换）。三者加权求和后再加上归一化的评估分数，
+//! 最新版本有未修复漏洞的候选会被下调。每个结果附带命中的理由。
+
+use std::collections::{HashMap, HashSet};
+
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::error::ApiError;
+
+/// 默认返回的候选数
+const DEFAULT_LIMIT: usize = 10;
+/// 最多返回的候选数
+const MAX_LIMIT: usize = 50;
+
+/// 语义相似度的权重
+const WEIGHT_EMBEDDING: f64 = 1.0;
+/// 同时依赖比例的权重，常用基础库（如 serde）几乎总是同时出现，因此权重较低
+const WEIGHT_ALONGSIDE: f64 = 0.3;
+/// 被替换比例的权重
+const WEIGHT_REPLACED: f64 = 1.0;
+/// 归一化评估分数的权重
+const WEIGHT_SCORE: f64 = 0.3;
+/// 最新版本有未修复漏洞时扣除的分数
+const ADVISORY_PENALTY: f64 = 0.5;
+/// 只有同时依赖信号的候选，至少要有这么多个依赖方
+const MIN_ALONGSIDE: usize = 2;
+/// 参与同时依赖统计的依赖方数上限，按下载量取前若干个
+pub const MAX_SAMPLED_DEPENDENTS: usize = 200;
+/// 检查最新版本是否移除了目标 crate 的依赖方数上限
+pub const MAX_REPLACEMENT_CHECKS: usize = 50;
+/// 读取的依赖边数上限。常用 crate 的依赖边可达数十万条，按版本从新到旧读取，
+/// 超出时只统计依赖较新版本的依赖方
+pub const MAX_DEPENDENT_EDGES: usize = 20_000;
+/// 向量近邻的候选数
+pub const EMBEDDING_NEIGHBOURS: i64 = 50;
+
+/// 相似 crate 的查询参数
+#[derive(Deserialize, Debug, Default, Clone, ToSchema)]
+pub struct SimilarQuery {
+    /// 返回的候选数，默认 10，最大 50
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimilarParams {
+    pub limit: usize,
+}
+
+impl TryFrom<SimilarQuery> for SimilarParams {
+    type Error = ApiError;
+
+    fn try_from(q: SimilarQuery) -> Result<Self, ApiError> {
+        let limit = q.limit.unwrap_or(DEFAULT_LIMIT);
+        if limit == 0 || limit > MAX_LIMIT {
+            return Err(ApiError::BadRequest(format!(
+                "`limit` must be between 1 and {}",
+                MAX_LIMIT
+            )));
+        }
+        Ok(SimilarParams { limit })
+    }
+}
+
+/// 按 semver 取最高版本，无法解析的版本视为最低
+pub fn latest_version<'a>(versions: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    versions.into_iter().max_by_key(|v| Version::parse(v).ok())
+}
+
+/// 按 semver 从新到旧排序，无法解析的版本排在最后
+pub fn sort_newest_first(versions: &mut [String]) {
+    versions.sort_by_cached_key(|v| std::cmp::Reverse(Version::parse(v).ok()));
+}
+
+/// 按下载量从高到低选出参与统计的依赖方，返回每个依赖方最后一个依赖目标 crate 的版本
+pub fn sample_dependents(
+    versions: HashMap<String, Vec<String>>,
+    downloads: &HashMap<String, i64>,
+) -> Vec<(String, String)> {
+    let mut sampled: Vec<(String, String)> = versions
+        .into_iter()
+        .filter_map(|(name, versions)| {
+            let version = latest_version(versions.iter().map(String::as_str))?.to_string();
+            Some((name, version))
+        })
+        .collect();
+    sampled.sort_by(|a, b| {
+        let downloads = |name: &String| downloads.get(name).copied().unwrap_or(0);
+        downloads(&b.0)
+            .cmp(&downloads(&a.0))
+            .then_with(|| a.0.cmp(&b.0))
+    });
+    sampled.truncate(MAX_SAMPLED_DEPENDENTS);
+    sampled
+}
+
+/// 抽样的一个依赖方，依赖均为 crate 名称
+#[derive(Debug, Default, Clone)]
+pub struct DependentSample {
+    pub name: String,
+    /// 最后一个依赖目标 crate 的版本的直接依赖
+    pub depended: HashSet<String>,
+    /// 最新版本的直接依赖，最新版本即上述版本时为空
+    pub latest: Option<HashSet<String>>,
+}
+
+/// 依赖图上的共现统计
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CoDependency {
+    /// 抽样的依赖方数
+    pub sampled: usize,
+    /// 最新版本不再依赖目标 crate 的依赖方数
+    pub dropped: usize,
+    /// 与目标 crate 同时被依赖的次数，`name -> dependents`
+    pub alongside: HashMap<String, usize>,
+    /// 在依赖方移除目标 crate 后新增的次数，`name -> dependents`
+    pub replaced: HashMap<String, usize>,
+}
+
+/// 统计依赖方中与 `target` 同时出现或替换了 `target` 的 crate
+pub fn co_dependency(target: &str, samples: &[DependentSample]) -> CoDependency {
+    let mut stats = CoDependency {
+        sampled: samples.len(),
+        ..Default::default()
+    };
+    for sample in samples {
+        let skip = |name: &String| name == target || *name == sample.name;
+        for name in sample.depended.iter().filter(|n| !skip(n)) {
+            *stats.alongside.entry(name.clone()).or_default() += 1;
+        }
+        let Some(latest) = &sample.latest else {
+            continue;
+        };
+        if latest.contains(target) {
+            continue;
+        }
+        stats.dropped += 1;
+        for name in latest.difference(&sample.depended).filter(|n| !skip(n)) {
+            *stats.replaced.entry(name.clone()).or_default() += 1;
+        }
+    }
+    stats
+}
+
+/// 有推荐信号的候选名称
+pub fn candidate_names(neighbours: &[(String, f32)], co: &CoDependency) -> Vec<String> {
+    let mut names: Vec<String> = neighbours
+        .iter()
+        .map(|(name, _)| name.clone())
+        .chain(co.replaced.keys().cloned())
+        .chain(
+            co.alongside
+                .iter()
+                .filter(|(_, n)| **n >= MIN_ALONGSIDE)
+                .map(|(name, _)| name.clone()),
+        )
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// 候选 crate 的收录信息
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CandidateInfo {
+    pub namespace: String,
+    pub max_version: String,
+    pub evaluated_score: Option<f64>,
+    /// 最新版本上未修复的漏洞数
+    pub advisories: usize,
+}
+
+/// 推荐某个候选的理由
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SimilarReason {
+    /// 名称和描述的向量余弦相似度
+    Embedding { similarity: f32 },
+    /// 抽样的依赖方中有 `dependents` 个同时依赖两者
+    DependedAlongside { dependents: usize, sampled: usize },
+    /// 有 `dependents` 个依赖方移除目标 crate 后改为依赖该 crate
+    ReplacedBy { dependents: usize, dropped: usize },
+    /// 评估分数高于目标 crate
+    HigherScore { score: f64 },
+}
+
+/// 一个替代候选
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct SimilarCrate {
+    pub name: String,
+    pub namespace: String,
+    pub max_version: String,
+    /// 综合得分，越大越推荐
+    pub score: f64,
+    pub evaluated_score: Option<f64>,
+    /// 最新版本上未修复的漏洞数
+    pub advisories: usize,
+    pub reasons: Vec<SimilarReason>,
+}
+
+/// 目标 crate 及其替代候选
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct SimilarCrates {
+    pub name: String,
+    pub namespace: String,
+    pub max_version: String,
+    pub evaluated_score: Option<f64>,
+    /// 最新版本上未修复的漏洞数
+    pub advisories: usize,
+    /// 参与统计的依赖方数
+    pub sampled_dependents: usize,
+    pub alternatives: Vec<SimilarCrate>,
+}
+
+/// 合并各项信号并排序，没有收录信息的候选会被忽略
+pub fn rank_alternatives(
+    target_score: Option<f64>,
+    neighbours: &[(String, f32)],
+    co: &CoDependency,
+    candidates: &HashMap<String, CandidateInfo>,
+    limit: usize,
+) -> Vec<SimilarCrate> {
+    let similarity: HashMap<&str, f32> = neighbours
+        .iter()
+        .map(|(name, similarity)| (name.as_str(), *similarity))
+        .collect();
+    let names = candidate_names(neighbours, co);
+    let max_score = names
+        .iter()
+        .filter_map(|name| candidates.get(name)?.evaluated_score)
+        .chain(target_score)
+        .fold(0.0_f64, f64::max);
+    let share = |count: usize, total: usize| {
+        if total == 0 {
+            0.0
+        } else {
+            count as f64 / total as f64
+        }
+    };
+
+    let mut ranked: Vec<SimilarCrate> = names
+        .into_iter()
+        .filter_map(|name| {
+            let info = candidates.get(&name)?;
+            let mut score = 0.0;
+            let mut reasons = vec![];
+            if let Some(&similarity) = similarity.get(name.as_str()) {
+                score += WEIGHT_EMBEDDING * similarity.max(0.0) as f64;
+                reasons.push(SimilarReason::Embedding { similarity });
+            }
+            if let Some(&dependents) = co.replaced.get(&name) {
+                score += WEIGHT_REPLACED * share(dependents, co.dropped);
+                reasons.push(SimilarReason::ReplacedBy {
+                    dependents,
+                    dropped: co.dropped,
+                });
+            }
+            match co.alongside.get(&name) {
+                Some(&dependents) if dependents >= MIN_ALONGSIDE => {
+                    score += WEIGHT_ALONGSIDE * share(dependents, co.sampled);
+                    reasons.push(SimilarReason::DependedAlongside {
+                        dependents,
+                        sampled: co.sampled,
+                    });
+                }
+                _ => {}
+            }
+            if let Some(evaluated) = info.evaluated_score {
+                if max_score > 0.0 {
+                    score += WEIGHT_SCORE * evaluated.max(0.0) / max_score;
+                }
+                if evaluated > target_score.unwrap_or(0.0) {
+                    reasons.push(SimilarReason::HigherScore { score: evaluated });
+                }
+            }
+            if info.advisories > 0 {
+                score -= ADVISORY_PENALTY;
+            }
+            Some(SimilarCrate {
+                name,
+                namespace: info.namespace.clone(),
+                max_version: info.max_version.clone(),
+                score,
+                evaluated_score: info.evaluated_score,
+                advisories: info.advisories,
+                reasons,
+            })
+        })
+        .collect();
+    ranked.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    ranked.truncate(limit);
+    ranked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(names: &[&str]) -> HashSet<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    fn info(evaluated_score: Option<f64>, advisories: usize) -> CandidateInfo {
+        CandidateInfo {
+            namespace: "ns/repo".to_string(),
+            max_version: "1.0.0".to_string(),
+            evaluated_score,
+            advisories,
+        }
+    }
+
+    #[test]
+    fn test_similar_params() {
+        let params = SimilarParams::try_from(SimilarQuery::default()).unwrap();
+        assert_eq!(params.limit, DEFAULT_LIMIT);
+        for limit in [0, MAX_LIMIT + 1] {
+            let query = SimilarQuery { limit: Some(limit) };
+            assert!(matches!(
+                SimilarParams::try_from(query),
+                Err(ApiError::BadRequest(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_sample_dependents() {
+        let versions: HashMap<String, Vec<String>> = [
+            (
+                "a".to_string(),
+                vec!["0.9.0".to_string(), "0.10.0".to_string()],
+            ),
+            (
+                "b".to_string(),
+                vec!["1.0.0".to_string(), "bad".to_string()],
+            ),
+            ("c".to_string(), vec!["2.0.0".to_string()]),
+        ]
+        .into();
+        let downloads = [("b".to_string(), 100), ("a".to_string(), 100)].into();
+        assert_eq!(
+            sample_dependents(versions, &downloads),
+            [
+                ("a".to_string(), "0.10.0".to_string()),
+                ("b".to_string(), "1.0.0".to_string()),
+                ("c".to_string(), "2.0.0".to_string()),
+            ]
+        );
+
+        let mut versions: Vec<String> = ["0.9.0", "bad", "0.10.0", "1.0.0-rc.1"]
+            .map(str::to_string)
+            .into();
+        sort_newest_first(&mut versions);
+        assert_eq!(versions, ["1.0.0-rc.1", "0.10.0", "0.9.0", "bad"]);
+    }
+
+    #[test]
+    fn test_co_dependency() {
+        let samples = vec![
+            DependentSample {
+                name: "app".to_string(),
+                depended: set(&["openssl", "serde", "app"]),
+                latest: Some(set(&["rustls", "serde"])),
+            },
+            DependentSample {
+                name: "cli".to_string(),
+                depended: set(&["openssl", "serde"]),
+                latest: Some(set(&["openssl", "serde", "clap"])),
+            },
+            DependentSample {
+                name: "web".to_string(),
+                depended: set(&["openssl", "serde", "tokio"]),
+                latest: None,
+            },
+        ];
+        let co = co_dependency("openssl", &samples);
+        assert_eq!(co.sampled, 3);
+        // 只有 app 的最新版本移除了 openssl，cli 新增的 clap 不算替换
+        assert_eq!(co.dropped, 1);
+        assert_eq!(co.replaced, [("rustls".to_string(), 1)].into());
+        assert_eq!(co.alongside["serde"], 3);
+        assert_eq!(co.alongside["tokio"], 1);
+        assert!(!co.alongside.contains_key("openssl"));
+        assert!(!co.alongside.contains_key("app"));
+    }
+
+    #[test]
+    fn test_rank_alternatives() {
+        let co = CoDependency {
+            sampled: 10,
+            dropped: 2,
+            alongside: [("serde".to_string(), 9), ("rare".to_string(), 1)].into(),
+            replaced: [("rustls".to_string(), 2)].into(),
+        };
+        let neighbours = vec![
+            ("native-tls".to_string(), 0.9),
+            ("rustls".to_string(), 0.8),
+            ("unknown".to_string(), 0.95),
+        ];
+        let candidates: HashMap<String, CandidateInfo> = [
+            ("native-tls".to_string(), info(Some(40.0), 1)),
+            ("rustls".to_string(), info(Some(80.0), 0)),
+            ("serde".to_string(), info(None, 0)),
+            ("rare".to_string(), info(None, 0)),
+        ]
+        .into();
+        let ranked = rank_alternatives(Some(50.0), &neighbours, &co, &candidates, 10);
+        let names: Vec<&str> = ranked.iter().map(|c| c.name.as_str()).collect();
+        // 未收录的 unknown 和同时依赖次数过少的 rare 被忽略，native-tls 因漏洞被下调
+        assert_eq!(names, ["rustls", "native-tls", "serde"]);
+        assert!((ranked[0].score - (0.8_f32 as f64 + 1.0 + 0.3)).abs() < 1e-6);
+        assert_eq!(
+            ranked[0].reasons,
+            [
+                SimilarReason::Embedding { similarity: 0.8 },
+                SimilarReason::ReplacedBy {
+                    dependents: 2,
+                    dropped: 2
+                },
+                SimilarReason::HigherScore { score: 80.0 },
+            ]
+        );
+        assert_eq!(
+            ranked[2].reasons,
+            [SimilarReason::DependedAlongside {
+                dependents: 9,
+                sampled: 10
+            }]
+        );
+
+        let ranked = rank_alternatives(None, &neighbours, &co, &candidates, 1);
+        assert_eq!(ranked.len(), 1);
+    }
+}
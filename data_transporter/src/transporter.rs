@@ -18,13 +18,17 @@ impl Transporter {
 
     pub async fn transport_data(&mut self) -> Result<(), ()> {
         tracing::info!("Start to pack the data");
-        let ids = self.reader.get_all_programs_id().await;
+        let ids = self.reader.get_all_programs_id().await.unwrap();
         for id in ids {
             tracing::info!("id:{}", id);
-            let program: model::tugraph_model::Program =
-                self.reader.get_program(&id).await.unwrap();
-            let (uprogram, islib): (model::tugraph_model::UProgram, bool) =
-                self.reader.get_type(&id).await.unwrap();
+            let Some(program) = self.reader.get_program(&id).await.unwrap() else {
+                tracing::warn!("program {} disappeared, skip", id);
+                continue;
+            };
+            let Some((uprogram, islib)) = self.reader.get_type(&id).await.unwrap() else {
+                tracing::warn!("program {} has no type, skip", id);
+                continue;
+            };
             let versions: Vec<crate::VersionInfo> =
                 self.reader.get_versions(&id, islib).await.unwrap();
 
@@ -15,6 +15,7 @@ pub mod github_sync_status;
 pub mod github_user;
 pub mod graph_info;
 pub mod license;
+pub mod metadata;
 pub mod program_dependencies;
 pub mod program_versions;
 pub mod programs;
@@ -26,4 +27,3 @@ pub mod sea_orm_active_enums;
 pub mod senseleak_res;
 pub mod userloginfo;
 pub mod version_info;
-pub mod metadata;
@@ -13,6 +13,7 @@ pub use super::github_sync_status::Entity as GithubSyncStatus;
 pub use super::github_user::Entity as GithubUser;
 pub use super::graph_info::Entity as GraphInfo;
 pub use super::license::Entity as License;
+pub use super::metadata::Entity as Metadata;
 pub use super::program_dependencies::Entity as ProgramDependencies;
 pub use super::program_versions::Entity as ProgramVersions;
 pub use super::programs::Entity as Programs;
@@ -23,4 +24,3 @@ pub use super::rustsecs::Entity as Rustsecs;
 pub use super::senseleak_res::Entity as SenseleakRes;
 pub use super::userloginfo::Entity as Userloginfo;
 pub use super::version_info::Entity as VersionInfo;
-pub use super::metadata::Entity as Metadata;
\ No newline at end of file
@@ -30,6 +30,8 @@ pub struct Model {
     pub unaffected: String,
     #[sea_orm(column_type = "Text")]
     pub description: String,
+    #[sea_orm(column_type = "Text")]
+    pub affected_functions: String,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
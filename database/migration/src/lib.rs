@@ -8,6 +8,9 @@ mod m20250409_090128_update_repo_id_type;
 mod m20250418_081905_add_new_tables;
 mod m20250424_092358_alter_programs;
 mod m20250604_133200_init_repository_metadata;
+mod m20251017_000000_add_rustsec_affected_functions;
+mod m20251018_000000_add_upload_jobs;
+mod m20251019_000000_add_api_keys;
 
 pub struct Migrator;
 
@@ -23,6 +26,9 @@ impl MigratorTrait for Migrator {
             Box::new(m20250418_081905_add_new_tables::Migration),
             Box::new(m20250424_092358_alter_programs::Migration),
             Box::new(m20250604_133200_init_repository_metadata::Migration),
+            Box::new(m20251017_000000_add_rustsec_affected_functions::Migration),
+            Box::new(m20251018_000000_add_upload_jobs::Migration),
+            Box::new(m20251019_000000_add_api_keys::Migration),
         ]
     }
 }
@@ -33,4 +33,4 @@ impl MigrationTrait for Migration {
     async fn down(&self, _: &SchemaManager) -> Result<(), DbErr> {
         Ok(())
     }
-}
\ No newline at end of file
+}
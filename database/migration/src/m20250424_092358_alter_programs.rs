@@ -75,4 +75,4 @@ enum Crates {
 #[derive(DeriveIden)]
 enum GithubUser {
     Table,
-}
\ No newline at end of file
+}
@@ -37,7 +37,11 @@ impl MigrationTrait for Migration {
                     .col(double(Metadata::EvaluatedScore).not_null().default(0.0))
                     .col(double(Metadata::PopularityScore).not_null().default(0.0))
                     .col(double(Metadata::ActivityScore).not_null().default(0.0))
-                    .col(double(Metadata::MaintainabilityScore).not_null().default(0.0))
+                    .col(
+                        double(Metadata::MaintainabilityScore)
+                            .not_null()
+                            .default(0.0),
+                    )
                     .col(double(Metadata::MaturityScore).not_null().default(0.0))
                     .col(double(Metadata::OpennessScore).not_null().default(0.0))
                     .col(double(Metadata::GrowthScore).not_null().default(0.0))
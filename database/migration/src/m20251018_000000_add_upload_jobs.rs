@@ -0,0 +1,152 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(UploadJobs::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(UploadJobs::Id)
+                            .text()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(UploadJobs::Email).text().not_null())
+                    .col(ColumnDef::new(UploadJobs::Source).text().not_null())
+                    .col(ColumnDef::new(UploadJobs::UploadTime).text().not_null())
+                    .col(ColumnDef::new(UploadJobs::State).text().not_null())
+                    .col(ColumnDef::new(UploadJobs::Error).text())
+                    .col(
+                        ColumnDef::new(UploadJobs::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(
+                        ColumnDef::new(UploadJobs::UpdatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_upload_jobs_email")
+                    .table(UploadJobs::Table)
+                    .col(UploadJobs::Email)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(UploadJobEvents::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(UploadJobEvents::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(UploadJobEvents::JobId).text().not_null())
+                    .col(ColumnDef::new(UploadJobEvents::State).text().not_null())
+                    .col(ColumnDef::new(UploadJobEvents::Message).text())
+                    .col(
+                        ColumnDef::new(UploadJobEvents::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .if_not_exists()
+                    .name("idx_upload_job_events_job_id")
+                    .table(UploadJobEvents::Table)
+                    .col(UploadJobEvents::JobId)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(UploadJobVersions::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(UploadJobVersions::JobId).text().not_null())
+                    .col(
+                        ColumnDef::new(UploadJobVersions::Namespace)
+                            .text()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(UploadJobVersions::Name).text().not_null())
+                    .col(ColumnDef::new(UploadJobVersions::Version).text().not_null())
+                    .primary_key(
+                        Index::create()
+                            .col(UploadJobVersions::JobId)
+                            .col(UploadJobVersions::Namespace)
+                            .col(UploadJobVersions::Name)
+                            .col(UploadJobVersions::Version),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(UploadJobVersions::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(UploadJobEvents::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(UploadJobs::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UploadJobs {
+    Table,
+    Id,
+    Email,
+    Source,
+    UploadTime,
+    State,
+    Error,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(DeriveIden)]
+enum UploadJobEvents {
+    Table,
+    Id,
+    JobId,
+    State,
+    Message,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum UploadJobVersions {
+    Table,
+    JobId,
+    Namespace,
+    Name,
+    Version,
+}
@@ -1,8 +1,7 @@
-
 use entity::metadata;
 use futures::Stream;
 use sea_orm::DatabaseConnection;
-use sea_orm::{DbErr, EntityTrait, QueryOrder,};
+use sea_orm::{DbErr, EntityTrait, QueryOrder};
 use std::sync::Arc;
 
 #[derive(Clone)]
@@ -1,11 +1,13 @@
 use evaluate_database::EvaluateDatabase;
 use github_handler_storage::GithubHanlderStorage;
 use init::database_connection;
+use rustsec_storage::RustsecStorage;
 use std::{path::PathBuf, sync::Arc};
 
 pub mod evaluate_database;
 pub mod github_handler_storage;
 pub mod init;
+pub mod rustsec_storage;
 
 #[derive(Clone)]
 pub struct Context {
@@ -28,11 +30,16 @@ impl Context {
     pub fn evaluate_database_stg(&self) -> EvaluateDatabase {
         self.services.evaluate_database.clone()
     }
+
+    pub fn rustsec_stg(&self) -> RustsecStorage {
+        self.services.rustsec_storage.clone()
+    }
 }
 #[derive(Clone)]
 pub struct Service {
     github_handler_storage: GithubHanlderStorage,
     evaluate_database: EvaluateDatabase,
+    rustsec_storage: RustsecStorage,
 }
 
 impl Service {
@@ -41,6 +48,7 @@ impl Service {
         Self {
             github_handler_storage: GithubHanlderStorage::new(connection.clone()).await,
             evaluate_database: EvaluateDatabase::new(connection.clone()).await,
+            rustsec_storage: RustsecStorage::new(connection.clone()).await,
         }
     }
 
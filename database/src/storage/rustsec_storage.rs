@@ -0,0 +1,115 @@
+use std::{collections::HashMap, sync::Arc};
+
+use entity::{rustsec_info, rustsecs};
+use sea_orm::{
+    sea_query::OnConflict, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait,
+    QueryFilter, Statement, TransactionTrait,
+};
+
+/// 漏洞数据变更后发送 `NOTIFY` 的通道名，data_transporter 的漏洞索引监听该通道
+pub const RUSTSEC_CHANGED_CHANNEL: &str = "rustsec_changed";
+
+#[derive(Clone)]
+pub struct RustsecStorage {
+    pub connection: Arc<DatabaseConnection>,
+}
+
+impl RustsecStorage {
+    /// 获取底层连接
+    pub fn get_connection(&self) -> &DatabaseConnection {
+        &self.connection
+    }
+
+    pub async fn new(connection: Arc<DatabaseConnection>) -> Self {
+        RustsecStorage { connection }
+    }
+
+    /// 读取库中已有的全部漏洞，按 id 索引
+    pub async fn get_all_advisories(
+        &self,
+    ) -> Result<HashMap<String, (rustsecs::Model, rustsec_info::Model)>, DbErr> {
+        let mut infos: HashMap<String, rustsec_info::Model> = rustsec_info::Entity::find()
+            .all(self.get_connection())
+            .await?
+            .into_iter()
+            .map(|m| (m.id.clone(), m))
+            .collect();
+        let advisories = rustsecs::Entity::find()
+            .all(self.get_connection())
+            .await?
+            .into_iter()
+            .filter_map(|m| infos.remove(&m.id).map(|info| (m.id.clone(), (m, info))))
+            .collect();
+        Ok(advisories)
+    }
+
+    /// 在同一个事务中写入新增/变更的漏洞并删除撤回的漏洞
+    pub async fn apply_advisory_changes(
+        &self,
+        upserts: Vec<(rustsecs::Model, rustsec_info::Model)>,
+        withdrawn: Vec<String>,
+    ) -> Result<(), DbErr> {
+        let txn = self.get_connection().begin().await?;
+        for (advisory, info) in upserts {
+            rustsecs::Entity::insert(rustsecs::ActiveModel::from(advisory))
+                .on_conflict(
+                    OnConflict::column(rustsecs::Column::Id)
+                        .update_columns([
+                            rustsecs::Column::PublishTime,
+                            rustsecs::Column::Cratename,
+                            rustsecs::Column::Patched,
+                            rustsecs::Column::Aliases,
+                            rustsecs::Column::SmallDesc,
+                            rustsecs::Column::Description,
+                        ])
+                        .to_owned(),
+                )
+                .exec(&txn)
+                .await?;
+            rustsec_info::Entity::insert(rustsec_info::ActiveModel::from(info))
+                .on_conflict(
+                    OnConflict::column(rustsec_info::Column::Id)
+                        .update_columns([
+                            rustsec_info::Column::Subtitle,
+                            rustsec_info::Column::Reported,
+                            rustsec_info::Column::Issued,
+                            rustsec_info::Column::Package,
+                            rustsec_info::Column::Type,
+                            rustsec_info::Column::Keywords,
+                            rustsec_info::Column::Aliases,
+                            rustsec_info::Column::Reference,
+                            rustsec_info::Column::Patched,
+                            rustsec_info::Column::Unaffected,
+                            rustsec_info::Column::Description,
+                            rustsec_info::Column::AffectedFunctions,
+                        ])
+                        .to_owned(),
+                )
+                .exec(&txn)
+                .await?;
+        }
+        if !withdrawn.is_empty() {
+            rustsecs::Entity::delete_many()
+                .filter(rustsecs::Column::Id.is_in(withdrawn.clone()))
+                .exec(&txn)
+                .await?;
+            rustsec_info::Entity::delete_many()
+                .filter(rustsec_info::Column::Id.is_in(withdrawn))
+                .exec(&txn)
+                .await?;
+        }
+        txn.commit().await
+    }
+
+    /// 通知监听方漏洞数据已变更
+    pub async fn notify_rustsec_changed(&self, payload: &str) -> Result<(), DbErr> {
+        let conn = self.get_connection();
+        conn.execute(Statement::from_sql_and_values(
+            conn.get_database_backend(),
+            "SELECT pg_notify($1, $2)",
+            [RUSTSEC_CHANGED_CHANNEL.into(), payload.into()],
+        ))
+        .await?;
+        Ok(())
+    }
+}
@@ -70,8 +70,6 @@ impl AnyEvaluationPass for Maturity {
     }
 }
 
-
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -101,6 +99,4 @@ mod tests {
         println!("result: {}", result);
         assert_eq!(result, 0.0);
     }
-
-    
-}
\ No newline at end of file
+}
@@ -14,7 +14,7 @@ impl From<&MetadataModel> for OpennessData {
             has_license: model
                 .license_name
                 .as_ref()
-                .map_or(false, |name| !name.is_empty()),
+                .is_some_and(|name| !name.is_empty()),
         }
     }
 }
@@ -2,7 +2,7 @@ use std::path::PathBuf;
 
 use clap::{ArgAction, Parser, Subcommand};
 use database::storage::Context;
-use services::sync_repo;
+use services::{advisory_db, sync_repo};
 use tracing::info;
 
 use crate::services::github_api::GitHubApiClient;
@@ -73,12 +73,16 @@ enum Commands {
     /// 给programs设置 in_cratesio 字段
     UpdateProgram,
     /// 拉取评价算法所需的数据
-    SyncEvaluateData{
+    SyncEvaluateData {
         /// 更新全部库
         #[arg(long, action = ArgAction::SetTrue)]
         update_all: bool,
     },
-    
+    /// 从本地 rustsec/advisory-db 仓库导入漏洞数据
+    SyncRustsec {
+        /// advisory-db 仓库路径
+        path: PathBuf,
+    },
 }
 
 // 定义错误类型
@@ -128,7 +132,10 @@ async fn main() -> Result<(), BoxError> {
             github_client.start_graphql_sync(&context).await?;
         }
 
-        Some(Commands::AnalyzeAll { cratesio, not_analyzed }) => {
+        Some(Commands::AnalyzeAll {
+            cratesio,
+            not_analyzed,
+        }) => {
             tracing::info!("cratesio:{}, not_analyzed:{}", cratesio, not_analyzed);
             contributor_analysis::analyze_all(context, cratesio, not_analyzed).await?;
         }
@@ -145,9 +152,15 @@ async fn main() -> Result<(), BoxError> {
             sync_repo::update_programs(context).await?;
         }
 
-        Some(Commands::SyncEvaluateData { update_all })=>{
+        Some(Commands::SyncEvaluateData { update_all }) => {
             let github_client = GitHubApiClient::new();
-            github_client.start_metadata_sync(context,update_all).await?;
+            github_client
+                .start_metadata_sync(context, update_all)
+                .await?;
+        }
+
+        Some(Commands::SyncRustsec { path }) => {
+            advisory_db::sync_rustsec(context, &path).await?;
         }
 
         None => {
@@ -0,0 +1,373 @@
+//! 从本地 rustsec/advisory-db 仓库导入漏洞数据
+//!
+//! 每个漏洞是一个 `RUSTSEC-*.md` 文件，开头为 ```` ```toml ```` 包裹的元数据，
+//! 之后是 `# 标题` 和 Markdown 描述。导入时与库中已有数据比对，
+//! 只写入新增/变更的漏洞，并删除已被撤回（`withdrawn`）的漏洞。
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::Path,
+};
+
+use anyhow::{anyhow, Context as _};
+use database::storage::Context;
+use entity::{rustsec_info, rustsecs};
+use serde::Deserialize;
+use tracing::{info, warn};
+use walkdir::WalkDir;
+
+#[derive(Debug, Deserialize)]
+struct FrontMatter {
+    advisory: AdvisoryMeta,
+    #[serde(default)]
+    affected: AffectedMeta,
+    #[serde(default)]
+    versions: VersionsMeta,
+}
+
+#[derive(Debug, Deserialize)]
+struct AdvisoryMeta {
+    id: String,
+    package: String,
+    date: String,
+    url: Option<String>,
+    #[serde(default)]
+    references: Vec<String>,
+    #[serde(default)]
+    keywords: Vec<String>,
+    #[serde(default)]
+    aliases: Vec<String>,
+    informational: Option<String>,
+    withdrawn: Option<String>,
+    // 旧格式把标题和描述写在 toml 中
+    title: Option<String>,
+    description: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct AffectedMeta {
+    #[serde(default)]
+    functions: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct VersionsMeta {
+    #[serde(default)]
+    patched: Vec<String>,
+    #[serde(default)]
+    unaffected: Vec<String>,
+}
+
+/// 解析后的一条漏洞
+#[derive(Debug, Clone, PartialEq)]
+pub struct Advisory {
+    pub id: String,
+    pub package: String,
+    pub date: String,
+    pub title: String,
+    pub description: String,
+    pub kind: String,
+    pub aliases: Vec<String>,
+    pub keywords: Vec<String>,
+    pub references: Vec<String>,
+    pub patched: Vec<String>,
+    pub unaffected: Vec<String>,
+    pub functions: BTreeMap<String, Vec<String>>,
+    pub withdrawn: Option<String>,
+}
+
+impl Advisory {
+    /// 解析单个漏洞文件的内容
+    pub fn parse(content: &str) -> anyhow::Result<Self> {
+        let rest = content
+            .trim_start()
+            .strip_prefix("```toml")
+            .ok_or_else(|| anyhow!("missing ```toml front matter"))?;
+        let (front, body) = rest
+            .split_once("\n```")
+            .ok_or_else(|| anyhow!("unterminated front matter"))?;
+        let meta: FrontMatter = toml::from_str(front)?;
+
+        let body = body.trim();
+        let (heading, markdown) = match body.strip_prefix("# ") {
+            Some(b) => b.split_once('\n').unwrap_or((b, "")),
+            None => ("", body),
+        };
+        let title = meta
+            .advisory
+            .title
+            .unwrap_or_else(|| heading.trim().to_string());
+        let description = meta
+            .advisory
+            .description
+            .unwrap_or_else(|| markdown.trim().to_string());
+
+        let mut references: Vec<String> = meta.advisory.url.into_iter().collect();
+        references.extend(meta.advisory.references);
+
+        Ok(Advisory {
+            id: meta.advisory.id,
+            package: meta.advisory.package,
+            date: meta.advisory.date,
+            title,
+            description,
+            kind: meta
+                .advisory
+                .informational
+                .unwrap_or_else(|| "vulnerability".to_string()),
+            aliases: meta.advisory.aliases,
+            keywords: meta.advisory.keywords,
+            references,
+            patched: meta.versions.patched,
+            unaffected: meta.versions.unaffected,
+            functions: meta.affected.functions,
+            withdrawn: meta.advisory.withdrawn,
+        })
+    }
+
+    /// 转换为 `rustsecs` / `rustsec_info` 两张表的行
+    ///
+    /// 别名、关键字、引用以 `;` 分隔，版本范围以 `|` 分隔，受影响函数存为 JSON。
+    pub fn to_models(&self) -> (rustsecs::Model, rustsec_info::Model) {
+        let aliases = self.aliases.join(";");
+        let patched = self.patched.join("|");
+        let advisory = rustsecs::Model {
+            id: self.id.clone(),
+            publish_time: self.date.clone(),
+            cratename: self.package.clone(),
+            patched: patched.clone(),
+            aliases: aliases.clone(),
+            small_desc: self.title.clone(),
+            description: self.description.clone(),
+        };
+        let info = rustsec_info::Model {
+            id: self.id.clone(),
+            subtitle: self.title.clone(),
+            reported: self.date.clone(),
+            issued: self.date.clone(),
+            package: self.package.clone(),
+            r#type: self.kind.clone(),
+            keywords: self.keywords.join(";"),
+            aliases,
+            reference: self.references.join(";"),
+            patched,
+            unaffected: self.unaffected.join("|"),
+            description: self.description.clone(),
+            affected_functions: if self.functions.is_empty() {
+                String::new()
+            } else {
+                serde_json::to_string(&self.functions).unwrap()
+            },
+        };
+        (advisory, info)
+    }
+}
+
+/// 读取 advisory-db 目录下的所有漏洞文件，解析失败的文件记录日志后跳过
+pub fn load_advisories(root: &Path) -> anyhow::Result<Vec<Advisory>> {
+    if !root.is_dir() {
+        return Err(anyhow!("{} is not a directory", root.display()));
+    }
+    let mut advisories = Vec::new();
+    for entry in WalkDir::new(root)
+        .into_iter()
+        .filter_entry(|e| e.file_name() != ".git")
+    {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy();
+        if !entry.file_type().is_file() || !name.starts_with("RUSTSEC-") || !name.ends_with(".md") {
+            continue;
+        }
+        let content = fs::read_to_string(entry.path())
+            .with_context(|| format!("failed to read {}", entry.path().display()))?;
+        match Advisory::parse(&content) {
+            Ok(advisory) => advisories.push(advisory),
+            Err(e) => warn!("skip {}: {}", entry.path().display(), e),
+        }
+    }
+    advisories.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(advisories)
+}
+
+/// 本次导入与上次导入结果的差异
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportReport {
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+    pub withdrawn: Vec<String>,
+    pub unchanged: usize,
+}
+
+impl ImportReport {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.withdrawn.is_empty()
+    }
+}
+
+/// 对比解析结果和库中已有数据
+///
+/// 已撤回且库中不存在的漏洞直接忽略；advisory-db 中被删除的文件不做处理。
+pub fn diff_advisories(
+    advisories: &[Advisory],
+    existing: &HashMap<String, (rustsecs::Model, rustsec_info::Model)>,
+) -> ImportReport {
+    let mut report = ImportReport::default();
+    for advisory in advisories {
+        match (existing.get(&advisory.id), &advisory.withdrawn) {
+            (Some(_), Some(_)) => report.withdrawn.push(advisory.id.clone()),
+            (None, Some(_)) => {}
+            (None, None) => report.added.push(advisory.id.clone()),
+            (Some(stored), None) => {
+                if *stored == advisory.to_models() {
+                    report.unchanged += 1;
+                } else {
+                    report.changed.push(advisory.id.clone());
+                }
+            }
+        }
+    }
+    report
+}
+
+/// 导入 advisory-db 并通知漏洞索引刷新
+pub(crate) async fn sync_rustsec(context: Context, root: &Path) -> anyhow::Result<ImportReport> {
+    let advisories = load_advisories(root)?;
+    info!(
+        "parsed {} advisories from {}",
+        advisories.len(),
+        root.display()
+    );
+
+    let stg = context.rustsec_stg();
+    let existing = stg.get_all_advisories().await?;
+    let report = diff_advisories(&advisories, &existing);
+
+    let upserts = advisories
+        .iter()
+        .filter(|a| report.added.contains(&a.id) || report.changed.contains(&a.id))
+        .map(Advisory::to_models)
+        .collect();
+    stg.apply_advisory_changes(upserts, report.withdrawn.clone())
+        .await?;
+
+    for id in &report.added {
+        info!("added: {}", id);
+    }
+    for id in &report.changed {
+        info!("changed: {}", id);
+    }
+    for id in &report.withdrawn {
+        info!("withdrawn: {}", id);
+    }
+    info!(
+        "rustsec sync finished: {} added, {} changed, {} withdrawn, {} unchanged",
+        report.added.len(),
+        report.changed.len(),
+        report.withdrawn.len(),
+        report.unchanged
+    );
+
+    if !report.is_empty() {
+        stg.notify_rustsec_changed(&format!(
+            "added={},changed={},withdrawn={}",
+            report.added.len(),
+            report.changed.len(),
+            report.withdrawn.len()
+        ))
+        .await?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, path::PathBuf};
+
+    use super::{diff_advisories, load_advisories, Advisory};
+
+    fn fixture_dir() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/advisory-db")
+    }
+
+    fn find<'a>(advisories: &'a [Advisory], id: &str) -> &'a Advisory {
+        advisories.iter().find(|a| a.id == id).unwrap()
+    }
+
+    #[test]
+    fn test_load_fixture_advisories() {
+        let advisories = load_advisories(&fixture_dir()).unwrap();
+        let ids: Vec<&str> = advisories.iter().map(|a| a.id.as_str()).collect();
+        // broken.md 不是漏洞文件名，RUSTSEC-0000-0000.md 解析失败被跳过
+        assert_eq!(
+            ids,
+            [
+                "RUSTSEC-2020-0071",
+                "RUSTSEC-2021-0124",
+                "RUSTSEC-2023-0018"
+            ]
+        );
+
+        let time = find(&advisories, "RUSTSEC-2020-0071");
+        assert_eq!(time.package, "time");
+        assert_eq!(time.title, "Potential segfault in the time crate");
+        assert!(time.description.starts_with("### Impact"));
+        assert_eq!(time.aliases, ["CVE-2020-26235", "GHSA-wcg3-cvx6-7396"]);
+        assert_eq!(time.patched, [">=0.2.23"]);
+        assert_eq!(time.unaffected, ["=0.2.0", "=0.2.1", "=0.2.2"]);
+        assert_eq!(
+            time.functions["time::OffsetDateTime::now_local"],
+            ["<0.2.23"]
+        );
+        assert_eq!(time.kind, "vulnerability");
+        assert_eq!(
+            time.references[0],
+            "https://github.com/time-rs/time/issues/293"
+        );
+
+        let withdrawn = find(&advisories, "RUSTSEC-2023-0018");
+        assert_eq!(withdrawn.withdrawn.as_deref(), Some("2023-03-22"));
+        assert_eq!(withdrawn.kind, "unsound");
+    }
+
+    #[test]
+    fn test_to_models_join_fields() {
+        let advisories = load_advisories(&fixture_dir()).unwrap();
+        let (advisory, info) = find(&advisories, "RUSTSEC-2021-0124").to_models();
+        assert_eq!(advisory.cratename, "tokio");
+        assert_eq!(advisory.patched, ">=1.8.4, <1.9.0|>=1.13.1");
+        assert_eq!(info.unaffected, "<0.2.5");
+        assert_eq!(info.aliases, "CVE-2021-45710;GHSA-fg7r-2g4j-5cgr");
+        assert_eq!(info.keywords, "race condition;use after free");
+        assert_eq!(info.affected_functions, "");
+        assert_eq!(info.subtitle, advisory.small_desc);
+    }
+
+    #[test]
+    fn test_diff_advisories() {
+        let advisories = load_advisories(&fixture_dir()).unwrap();
+
+        // 首次导入：未撤回的全部新增，撤回的忽略
+        let report = diff_advisories(&advisories, &HashMap::new());
+        assert_eq!(report.added, ["RUSTSEC-2020-0071", "RUSTSEC-2021-0124"]);
+        assert!(report.changed.is_empty());
+        assert!(report.withdrawn.is_empty());
+
+        // 再次导入：time 的修复版本有变化，撤回的漏洞仍在库中
+        let mut existing: HashMap<_, _> = advisories
+            .iter()
+            .map(|a| (a.id.clone(), a.to_models()))
+            .collect();
+        existing.get_mut("RUSTSEC-2020-0071").unwrap().0.patched = ">=0.2.22".to_string();
+        let report = diff_advisories(&advisories, &existing);
+        assert!(report.added.is_empty());
+        assert_eq!(report.changed, ["RUSTSEC-2020-0071"]);
+        assert_eq!(report.withdrawn, ["RUSTSEC-2023-0018"]);
+        assert_eq!(report.unchanged, 1);
+    }
+
+    #[test]
+    fn test_parse_rejects_missing_front_matter() {
+        assert!(Advisory::parse("# Title\n\nno front matter").is_err());
+    }
+}
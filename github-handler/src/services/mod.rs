@@ -1,2 +1,3 @@
+pub mod advisory_db;
 pub mod github_api;
 pub mod sync_repo;
@@ -0,0 +1,563 @@
+//! RustSec 漏洞版本范围匹配
+//!
+//! `rustsecs` / `rustsec_info` 中的 `patched`、`unaffected` 字段保存的是
+//! 以 `|` 拼接的 semver 要求列表（例如 `">= 1.8.4, < 1.9.0|>= 1.13.1"`）。
+//! 一个版本只要落在任意一个 patched 或 unaffected 范围内就不受影响，
+//! 否则视为受影响，这与 RustSec 官方的判定规则一致。
+
+use std::cmp::Ordering;
+
+use semver::{BuildMetadata, Comparator, Op, Prerelease, Version, VersionReq};
+
+/// 数据库中多个版本要求之间的分隔符
+const RANGE_SEPARATOR: char = '|';
+
+/// 一组版本要求，任意一个满足即视为命中
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VersionRanges {
+    reqs: Vec<VersionReq>,
+}
+
+impl VersionRanges {
+    /// 解析数据库中的 `patched` / `unaffected` 字段。
+    /// 空串、`Null` 以及无法解析的片段会被跳过。
+    pub fn parse(field: &str) -> Self {
+        let mut reqs = vec![];
+        for part in field.split(RANGE_SEPARATOR) {
+            let part = part.trim();
+            if part.is_empty() || part.eq_ignore_ascii_case("null") {
+                continue;
+            }
+            match VersionReq::parse(part) {
+                Ok(req) => reqs.push(req),
+                Err(e) => tracing::warn!("skip unparsable advisory range '{}': {}", part, e),
+            }
+        }
+        Self { reqs }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.reqs.is_empty()
+    }
+
+    pub fn reqs(&self) -> &[VersionReq] {
+        &self.reqs
+    }
+
+    /// 判断版本是否落在任意一个范围内。
+    ///
+    /// 与 `VersionReq::matches` 不同，这里按版本的全序比较，
+    /// 因此 `1.3.0-beta.1` 会被 `>= 1.2.0` 命中。
+    pub fn contains(&self, version: &Version) -> bool {
+        self.reqs.iter().any(|req| req_contains(req, version))
+    }
+}
+
+/// 某个 crate 的一条漏洞记录对应的版本匹配器
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AdvisoryMatcher {
+    pub patched: VersionRanges,
+    pub unaffected: VersionRanges,
+}
+
+impl AdvisoryMatcher {
+    pub fn new(patched: &str, unaffected: &str) -> Self {
+        Self {
+            patched: VersionRanges::parse(patched),
+            unaffected: VersionRanges::parse(unaffected),
+        }
+    }
+
+    /// 判断 `version` 是否受该漏洞影响。
+    /// 无法解析的版本号保守地视为受影响。
+    pub fn is_affected(&self, version: &str) -> bool {
+        match Version::parse(version.trim()) {
+            Ok(v) => self.is_affected_version(&v),
+            Err(_) => {
+                tracing::warn!("unparsable version '{}', treat as affected", version);
+                true
+            }
+        }
+    }
+
+    pub fn is_affected_version(&self, version: &Version) -> bool {
+        !self.patched.contains(version) && !self.unaffected.contains(version)
+    }
+
+    /// 把受影响的版本表示为按版本排序、互不相交的区间，即 patched 与
+    /// unaffected 全部范围的补集，用于导出 OSV 的 `affected.ranges`。
+    pub fn affected_ranges(&self) -> Vec<AffectedRange> {
+        let mut safe: Vec<Interval> = self
+            .patched
+            .reqs()
+            .iter()
+            .chain(self.unaffected.reqs())
+            .filter_map(req_interval)
+            .collect();
+        safe.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut merged: Vec<Interval> = vec![];
+        for (lo, hi) in safe {
+            if let Some(last) = merged.last_mut() {
+                // 与上一段重叠或相接时合并
+                let touches = match (&last.1, &lo) {
+                    (Some(end), Some(lo)) => lo <= end,
+                    _ => true,
+                };
+                if touches {
+                    last.1 = match (last.1.take(), hi) {
+                        (Some(end), Some(hi)) => Some(end.max(hi)),
+                        _ => None,
+                    };
+                    continue;
+                }
+            }
+            merged.push((lo, hi));
+        }
+
+        let mut ranges = vec![];
+        let mut start: Option<Pos> = None;
+        for (lo, hi) in merged {
+            if let Some(lo) = lo {
+                if start.as_ref().is_none_or(|start| *start < lo) {
+                    ranges.push(AffectedRange::between(start, Some(lo)));
+                }
+            }
+            match hi {
+                Some(hi) => start = Some(hi),
+                None => return ranges,
+            }
+        }
+        ranges.push(AffectedRange::between(start, None));
+        ranges
+    }
+}
+
+/// 一段受影响的版本区间
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AffectedRange {
+    /// 包含该版本，为空表示从最早的版本开始
+    pub introduced: Option<Version>,
+    /// 不包含该版本，与 `last_affected` 至多一个非空，都为空表示没有上界
+    pub fixed: Option<Version>,
+    /// 包含该版本
+    pub last_affected: Option<Version>,
+}
+
+impl AffectedRange {
+    fn between(start: Option<Pos>, end: Option<Pos>) -> Self {
+        let introduced = start.map(|(v, after)| if after { successor(&v) } else { v });
+        let (fixed, last_affected) = match end {
+            Some((v, false)) => (Some(v), None),
+            Some((v, true)) => (None, Some(v)),
+            None => (None, None),
+        };
+        AffectedRange {
+            introduced,
+            fixed,
+            last_affected,
+        }
+    }
+
+    pub fn contains(&self, version: &Version) -> bool {
+        self.introduced.as_ref().is_none_or(|v| version >= v)
+            && self.fixed.as_ref().is_none_or(|v| version < v)
+            && self.last_affected.as_ref().is_none_or(|v| version <= v)
+    }
+}
+
+/// 版本序列上的一个位置：`(v, false)` 紧挨在 `v` 之前，`(v, true)` 紧挨在 `v` 之后
+type Pos = (Version, bool);
+
+/// 一段区间的起止位置，`None` 表示无界
+type Interval = (Option<Pos>, Option<Pos>);
+
+/// 版本要求对应的区间，不可能满足时返回 `None`
+fn req_interval(req: &VersionReq) -> Option<Interval> {
+    let mut lo: Option<Pos> = None;
+    let mut hi: Option<Pos> = None;
+    for cmp in &req.comparators {
+        let (lower, upper) = comparator_bounds(cmp);
+        if let Some(b) = lower {
+            let pos = (b.version, !b.inclusive);
+            lo = Some(lo.map_or(pos.clone(), |lo| lo.max(pos)));
+        }
+        if let Some(b) = upper {
+            let pos = (b.version, b.inclusive);
+            hi = Some(hi.map_or(pos.clone(), |hi| hi.min(pos)));
+        }
+    }
+    match (&lo, &hi) {
+        (Some(lo), Some(hi)) if lo >= hi => None,
+        _ => Some((lo, hi)),
+    }
+}
+
+/// 紧跟在 `v` 之后的最小版本
+fn successor(v: &Version) -> Version {
+    let mut next = v.clone();
+    next.build = BuildMetadata::EMPTY;
+    if v.pre.is_empty() {
+        next.patch += 1;
+        next.pre = Prerelease::new("0").unwrap();
+    } else {
+        next.pre = Prerelease::new(&format!("{}.0", v.pre)).unwrap();
+    }
+    next
+}
+
+/// 范围的一个端点，`inclusive` 表示是否包含该点
+#[derive(Debug, Clone)]
+struct Bound {
+    version: Version,
+    inclusive: bool,
+}
+
+fn req_contains(req: &VersionReq, version: &Version) -> bool {
+    req.comparators
+        .iter()
+        .all(|cmp| comparator_contains(cmp, version))
+}
+
+fn comparator_contains(cmp: &Comparator, version: &Version) -> bool {
+    let (lower, upper) = comparator_bounds(cmp);
+    let above_lower = lower.is_none_or(|b| match version.cmp(&b.version) {
+        Ordering::Greater => true,
+        Ordering::Equal => b.inclusive,
+        Ordering::Less => false,
+    });
+    let below_upper = upper.is_none_or(|b| match version.cmp(&b.version) {
+        Ordering::Less => true,
+        Ordering::Equal => b.inclusive,
+        Ordering::Greater => false,
+    });
+    above_lower && below_upper
+}
+
+fn version(major: u64, minor: u64, patch: u64) -> Version {
+    Version::new(major, minor, patch)
+}
+
+fn bound(major: u64, minor: u64, patch: u64, pre: &Prerelease, inclusive: bool) -> Option<Bound> {
+    Some(Bound {
+        version: Version {
+            major,
+            minor,
+            patch,
+            pre: pre.clone(),
+            build: BuildMetadata::EMPTY,
+        },
+        inclusive,
+    })
+}
+
+/// 隐含上界（`^`、`~`、`*` 以及省略的版本位）用 `x.y.z-0` 表示，
+/// 这样上界版本自身的预发布版本也会被排除在外。
+fn implied_upper(next: Version) -> Option<Bound> {
+    bound(
+        next.major,
+        next.minor,
+        next.patch,
+        &Prerelease::new("0").unwrap(),
+        false,
+    )
+}
+
+/// 把一个比较器展开成 [下界, 上界] 区间
+fn comparator_bounds(cmp: &Comparator) -> (Option<Bound>, Option<Bound>) {
+    let major = cmp.major;
+    let minor = cmp.minor.unwrap_or(0);
+    let patch = cmp.patch.unwrap_or(0);
+    let pre = &cmp.pre;
+    // 省略版本位时，按省略的最高位计算下一个区间的起点
+    let next_partial = || match (cmp.minor, cmp.patch) {
+        (None, _) => version(major + 1, 0, 0),
+        (Some(minor), None) => version(major, minor + 1, 0),
+        (Some(minor), Some(patch)) => version(major, minor, patch + 1),
+    };
+
+    match cmp.op {
+        Op::Exact | Op::Wildcard => match (cmp.minor, cmp.patch) {
+            (Some(_), Some(_)) => (
+                bound(major, minor, patch, pre, true),
+                bound(major, minor, patch, pre, true),
+            ),
+            _ => (
+                bound(major, minor, 0, pre, true),
+                implied_upper(next_partial()),
+            ),
+        },
+        Op::Greater => match (cmp.minor, cmp.patch) {
+            (Some(_), Some(_)) => (bound(major, minor, patch, pre, false), None),
+            _ => {
+                let next = next_partial();
+                (
+                    bound(next.major, next.minor, next.patch, &Prerelease::EMPTY, true),
+                    None,
+                )
+            }
+        },
+        Op::GreaterEq => (bound(major, minor, patch, pre, true), None),
+        Op::Less => (None, bound(major, minor, patch, pre, false)),
+        Op::LessEq => match (cmp.minor, cmp.patch) {
+            (Some(_), Some(_)) => (None, bound(major, minor, patch, pre, true)),
+            _ => (None, implied_upper(next_partial())),
+        },
+        Op::Tilde => {
+            let next = match cmp.minor {
+                None => version(major + 1, 0, 0),
+                Some(minor) => version(major, minor + 1, 0),
+            };
+            (bound(major, minor, patch, pre, true), implied_upper(next))
+        }
+        Op::Caret => {
+            let next = match (cmp.minor, cmp.patch) {
+                _ if major > 0 => version(major + 1, 0, 0),
+                (None, _) => version(1, 0, 0),
+                (Some(minor), _) if minor > 0 => version(0, minor + 1, 0),
+                (Some(_), None) => version(0, 1, 0),
+                (Some(_), Some(patch)) => version(0, 0, patch + 1),
+            };
+            (bound(major, minor, patch, pre, true), implied_upper(next))
+        }
+        _ => {
+            tracing::warn!("unsupported comparator '{}'", cmp);
+            (None, None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// (advisory, patched, unaffected, [(version, affected)])
+    ///
+    /// 前半部分的 patched / unaffected 取自 rustsec/advisory-db，
+    /// 后半部分为覆盖 `~`、省略版本位和异常输入的补充用例。
+    type Case = (
+        &'static str,
+        &'static str,
+        &'static str,
+        &'static [(&'static str, bool)],
+    );
+
+    const CASES: &[Case] = &[
+        // tokio: data race in ReadHalf::unsplit
+        (
+            "RUSTSEC-2021-0124",
+            ">= 1.8.4, < 1.9.0|>= 1.13.1",
+            "< 0.1.14",
+            &[
+                ("0.1.13", false),
+                ("0.1.14", true),
+                ("1.8.3", true),
+                ("1.8.4", false),
+                ("1.9.0", true),
+                ("1.13.0", true),
+                ("1.13.1", false),
+                ("1.40.0", false),
+            ],
+        ),
+        // tokio: named pipe server option misuse
+        (
+            "RUSTSEC-2023-0001",
+            ">= 1.18.4, < 1.19.0|>= 1.20.3, < 1.21.0|>= 1.23.1",
+            "< 1.7.0",
+            &[
+                ("1.6.9", false),
+                ("1.18.3", true),
+                ("1.18.5", false),
+                ("1.22.0", true),
+                ("1.23.1", false),
+            ],
+        ),
+        // nix: out-of-bounds write in getgrouplist, patched with caret ranges
+        (
+            "RUSTSEC-2021-0119",
+            "^0.20.2|^0.21.2|^0.22.2|>= 0.23.0",
+            "< 0.16.0",
+            &[
+                ("0.15.0", false),
+                ("0.19.1", true),
+                ("0.20.1", true),
+                ("0.20.2", false),
+                ("0.21.0", true),
+                ("0.22.3", false),
+                ("0.26.4", false),
+            ],
+        ),
+        // time: segfault in localtime_r, unaffected listed as exact versions
+        (
+            "RUSTSEC-2020-0071",
+            ">= 0.2.23",
+            "= 0.2.0|= 0.2.1|= 0.2.2|= 0.2.3|= 0.2.4|= 0.2.5|= 0.2.6",
+            &[
+                ("0.1.43", true),
+                ("0.2.3", false),
+                ("0.2.7", true),
+                ("0.2.23", false),
+                ("0.3.36", false),
+            ],
+        ),
+        // crossbeam-deque: data race in steal
+        (
+            "RUSTSEC-2021-0093",
+            ">= 0.7.4, < 0.8.0|>= 0.8.1",
+            "",
+            &[
+                ("0.7.3", true),
+                ("0.7.4", false),
+                ("0.8.0", true),
+                ("0.8.1", false),
+            ],
+        ),
+        // smallvec: buffer overflow in insert_many
+        (
+            "RUSTSEC-2021-0003",
+            ">= 0.6.14, < 1.0.0|>= 1.6.1",
+            "",
+            &[
+                ("0.6.13", true),
+                ("1.0.0", true),
+                ("1.6.1", false),
+                // 预发布版本按全序比较，2.0.0-alpha.1 已晚于修复版本
+                ("2.0.0-alpha.1", false),
+            ],
+        ),
+        // regex: denial of service in regex parsing
+        (
+            "RUSTSEC-2022-0013",
+            ">= 1.5.5",
+            "",
+            &[("1.5.4", true), ("1.5.5", false), ("1.5.5-rc.1", true)],
+        ),
+        // failure: unmaintained, no patched release
+        ("RUSTSEC-2020-0036", "", "", &[("0.1.8", true)]),
+        ("RUSTSEC-2020-0036", "Null", "Null", &[("0.1.8", true)]),
+        (
+            "tilde",
+            "~1.2.3",
+            "",
+            &[
+                ("1.2.3", false),
+                ("1.2.9", false),
+                ("1.3.0", true),
+                ("1.3.0-alpha", true),
+            ],
+        ),
+        ("tilde", "~1", "", &[("1.9.0", false), ("2.0.0", true)]),
+        // caret 上界不包含下一个大版本的预发布版本
+        (
+            "caret",
+            "^1.2.3",
+            "",
+            &[("1.9.9", false), ("2.0.0-alpha", true)],
+        ),
+        ("caret", "^0.0.3", "", &[("0.0.3", false), ("0.0.4", true)]),
+        ("partial", "> 1.2", "", &[("1.2.9", true), ("1.3.0", false)]),
+        (
+            "partial",
+            "<= 1.2",
+            "",
+            &[("1.2.9", false), ("1.3.0", true)],
+        ),
+        ("partial", "= 1.2", "", &[("1.2.5", false), ("1.3.0", true)]),
+        ("partial", "1.2.*", "", &[("1.2.5", false), ("1.3.0", true)]),
+        // 无法解析的范围被忽略，无法解析的版本视为受影响
+        (
+            "garbage",
+            "not a range|>= 2.0.0",
+            "",
+            &[
+                ("2.1.0", false),
+                ("1.0.0", true),
+                ("latest", true),
+                ("", true),
+            ],
+        ),
+    ];
+
+    #[test]
+    fn test_advisory_table() {
+        for (id, patched, unaffected, versions) in CASES {
+            let matcher = AdvisoryMatcher::new(patched, unaffected);
+            for (version, affected) in versions.iter() {
+                assert_eq!(
+                    matcher.is_affected(version),
+                    *affected,
+                    "{} patched={:?} unaffected={:?} version={}",
+                    id,
+                    patched,
+                    unaffected,
+                    version
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_affected_ranges() {
+        let ranges =
+            AdvisoryMatcher::new(">= 1.8.4, < 1.9.0|>= 1.13.1", "< 0.1.14").affected_ranges();
+        let v = |s: &str| Some(Version::parse(s).unwrap());
+        assert_eq!(
+            ranges,
+            [
+                AffectedRange {
+                    introduced: v("0.1.14"),
+                    fixed: v("1.8.4"),
+                    last_affected: None,
+                },
+                AffectedRange {
+                    introduced: v("1.9.0"),
+                    fixed: v("1.13.1"),
+                    last_affected: None,
+                },
+            ]
+        );
+        // 没有修复版本时整个版本序列都受影响
+        assert_eq!(
+            AdvisoryMatcher::new("", "").affected_ranges(),
+            [AffectedRange {
+                introduced: None,
+                fixed: None,
+                last_affected: None,
+            }]
+        );
+        assert_eq!(
+            AdvisoryMatcher::new("> 1.2.3", "<= 0.9.0").affected_ranges(),
+            [AffectedRange {
+                introduced: v("0.9.1-0"),
+                fixed: None,
+                last_affected: v("1.2.3"),
+            }]
+        );
+
+        // 区间表示与逐个版本判断一致
+        for (id, patched, unaffected, versions) in CASES {
+            let matcher = AdvisoryMatcher::new(patched, unaffected);
+            let ranges = matcher.affected_ranges();
+            for (version, affected) in versions.iter() {
+                let Ok(version) = Version::parse(version) else {
+                    continue;
+                };
+                assert_eq!(
+                    ranges.iter().any(|r| r.contains(&version)),
+                    *affected,
+                    "{} {:?} version={}",
+                    id,
+                    ranges,
+                    version
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_skips_empty_and_invalid() {
+        assert!(VersionRanges::parse("").is_empty());
+        assert!(VersionRanges::parse("Null").is_empty());
+        assert_eq!(VersionRanges::parse(" >= 1.0.0 | | ??? ").reqs().len(), 1);
+    }
+}
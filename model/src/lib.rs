@@ -1,3 +1,4 @@
+pub mod advisory;
 pub mod general_model;
 pub mod github;
 pub mod repo_sync_model;
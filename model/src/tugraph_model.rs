@@ -218,11 +218,142 @@ pub struct HasDepVersion {
     pub DST_ID: String,
 }
 
+/// The section of `Cargo.toml` a dependency is declared in.
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencyKind {
+    /// `[dependencies]`
+    #[default]
+    Normal,
+    /// `[dev-dependencies]`
+    Dev,
+    /// `[build-dependencies]`
+    Build,
+}
+
+impl DependencyKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DependencyKind::Normal => "normal",
+            DependencyKind::Dev => "dev",
+            DependencyKind::Build => "build",
+        }
+    }
+}
+
+impl std::str::FromStr for DependencyKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "normal" => Ok(DependencyKind::Normal),
+            "dev" => Ok(DependencyKind::Dev),
+            "build" => Ok(DependencyKind::Build),
+            _ => Err(format!("unknown dependency kind: {}", s)),
+        }
+    }
+}
+
+/// Where a dependency comes from.
+#[derive(
+    Serialize,
+    Deserialize,
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum DependencySource {
+    /// crates.io or another registry
+    #[default]
+    Registry,
+    /// `git = "..."`
+    Git,
+    /// `path = "..."`
+    Path,
+}
+
+impl DependencySource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DependencySource::Registry => "registry",
+            DependencySource::Git => "git",
+            DependencySource::Path => "path",
+        }
+    }
+}
+
+impl std::str::FromStr for DependencySource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "registry" => Ok(DependencySource::Registry),
+            "git" => Ok(DependencySource::Git),
+            "path" => Ok(DependencySource::Path),
+            _ => Err(format!("unknown dependency source: {}", s)),
+        }
+    }
+}
+
 #[allow(non_snake_case)]
 #[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct DependsOn {
     pub SRC_ID: String,
     pub DST_ID: String,
+    /// normal, dev or build.
+    pub dependency_type: DependencyKind,
+    /// Whether this dependency is optional.
+    pub optional: bool,
+    /// Whether the default features of the dependency are used.
+    pub default_features: bool,
+    /// Activated features of the dependency, joined by `,`.
+    pub features: String,
+    /// The `cfg(..)` expression or target triple from `[target.'..'.dependencies]`,
+    /// empty if the dependency applies to all targets.
+    pub target: String,
+    /// Whether the dependency is enabled by the default features of the dependent,
+    /// always true for non-optional dependencies.
+    pub enabled_by_default: bool,
+    /// registry, git or path.
+    pub source: DependencySource,
+    /// The version requirement as declared, e.g. `^1.2`.
+    pub version_req: String,
+}
+
+/// An edge of the resolved graph in the `Cargo.lock` of an application version.
+///
+/// Different lockfiles may resolve the same crate differently, so every edge is
+/// tagged with the application version whose lockfile it comes from.
+#[allow(non_snake_case)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct LockedDependsOn {
+    pub SRC_ID: String,
+    pub DST_ID: String,
+    /// The `name/version` of the application version owning the lockfile.
+    pub root: String,
+    /// registry, git or path.
+    pub source: DependencySource,
 }
 
 pub trait CrateType2Idx {
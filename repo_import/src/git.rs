@@ -1,6 +1,6 @@
 use crate::{utils::extract_namespace, utils::insert_namespace_by_repo_path, ImportContext};
 use git2::{ObjectType, Oid, Repository};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use url::Url;
 
 impl ImportContext {
@@ -43,6 +43,28 @@ impl ImportContext {
     }
 }
 
+/// 为没有 `.git` 的本地目录（如用户上传的项目）建立只有一次提交的仓库
+pub(crate) fn init_local_repo(path: &Path) -> Result<(), git2::Error> {
+    if path.join(".git").is_dir() {
+        return Ok(());
+    }
+    let repo = Repository::init(path)?;
+    let mut index = repo.index()?;
+    index.add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)?;
+    index.write()?;
+    let tree = repo.find_tree(index.write_tree()?)?;
+    let signature = git2::Signature::now("cratespro", "cratespro@localhost")?;
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        "import user upload",
+        &tree,
+        &[],
+    )?;
+    Ok(())
+}
+
 async fn clone(path: &PathBuf, url: &str) -> Result<(), git2::Error> {
     if !path.is_dir() {
         //tracing::info!("Start cloning repo into {:?} from URL {}", path, url);
@@ -1,6 +1,7 @@
 mod crate_info;
 mod git;
 mod kafka_handler;
+mod lockfile;
 mod utils;
 mod version_info;
 
@@ -35,15 +36,71 @@ use url::Url;
 use version_info::VersionUpdater;
 
 const CLONE_CRATES_DIR: &str = "/mnt/crates/local_crates_file/";
+
+/// checkpoint 文件头，后跟小端序的格式版本号和 bincode 编码的 [`ImportContext`]
+const CHECKPOINT_MAGIC: &[u8; 8] = b"CRPROCKP";
+/// [`ImportContext`] 或其中任何类型的字段变化时加一，版本不一致的 checkpoint 拒绝加载
+const CHECKPOINT_VERSION: u32 = 1;
+
+/// `depends_on` 边的 CSV 文件名。列变化时修改文件名中的版本号并同步修改 import.config.tmp，
+/// 旧格式的文件和旧的导入配置都不会被误用
+const DEPENDS_ON_CSV: &str = "depends_on.v2.csv";
+/// 记录 `version_req` 之前的文件名，写入新文件时删除
+const LEGACY_DEPENDS_ON_CSV: &str = "depends_on.csv";
 // const TUGRAPH_IMPORT_FILES_PG: &str = "./tugraph_import_files_mq/";
 
 pub use kafka_handler::reset_kafka_offset;
 
+/// 用户上传的项目导入后所在的命名空间
+pub fn upload_namespace(job_id: &str) -> String {
+    format!("uploads/{}", job_id)
+}
+
+/// 把解压好的用户上传项目交给导入程序，`project_dir` 需为绝对路径
+pub async fn submit_user_upload(
+    job_id: &str,
+    crate_name: &str,
+    project_dir: &Path,
+) -> Result<(), String> {
+    let kafka_broker = env::var("KAFKA_BROKER").map_err(|e| format!("KAFKA_BROKER: {}", e))?;
+    let topic = env::var("KAFKA_USER_IMPORT_TOPIC")
+        .map_err(|e| format!("KAFKA_USER_IMPORT_TOPIC: {}", e))?;
+    let producer = KafkaHandler::new_producer(&kafka_broker).map_err(|e| e.to_string())?;
+    let message = repo_sync_model::MessageModel {
+        db_model: repo_sync_model::Model {
+            id: 0,
+            crate_name: crate_name.to_string(),
+            github_url: None,
+            mega_url: format!("file://{}", project_dir.display()),
+            crate_type: repo_sync_model::CrateType::Lib,
+            status: repo_sync_model::RepoSyncStatus::Syncing,
+            err_message: None,
+        },
+        message_kind: repo_sync_model::MessageKind::User,
+        source_of_data: repo_sync_model::SourceOfData::Github,
+        timestamp: chrono::Utc::now(),
+        extra_field: job_id.to_string(),
+    };
+    let payload = serde_json::to_string(&message).map_err(|e| e.to_string())?;
+    producer.send_message(&topic, job_id, &payload).await;
+    Ok(())
+}
+
 pub enum MessageKind {
     Mega,
     UserUpload,
 }
 
+/// 处理一条导入消息的结果
+#[derive(Debug, Default)]
+pub struct ImportedMessage {
+    pub versions: Vec<model::general_model::VersionWithTag>,
+    /// 用户上传对应的任务 ID，来自消息的 `extra_field`
+    pub upload_job: Option<String>,
+    /// 用户上传导入失败的原因
+    pub error: Option<String>,
+}
+
 pub struct ImportMessage<'a> {
     kind: MessageKind,
     message: BorrowedMessage<'a>,
@@ -80,7 +137,7 @@ impl ImportDriver {
             let checkpoint_path = format!("{}/latest.json", checkpoint_dir);
 
             match ImportContext::load_from_file(&checkpoint_path).await {
-                Ok(mut ctx) => {
+                Ok(Some(mut ctx)) => {
                     // 如果有保存的 offset 且不需要重置到0，则恢复到该位置
                     if let Some(offset) = ctx.kafka_offset {
                         tracing::info!("Restoring Kafka consumer to offset: {}", offset);
@@ -92,13 +149,18 @@ impl ImportDriver {
                     tracing::info!("Restored context from checkpoint");
                     ctx
                 }
-                Err(e) => {
-                    tracing::warn!("Failed to load checkpoint: {}", e);
+                Ok(None) => {
+                    tracing::info!("No checkpoint at {}, starting from scratch", checkpoint_path);
                     ImportContext {
                         dont_clone,
                         ..Default::default()
                     }
                 }
+                // 静默地从空上下文开始会丢掉全部导入进度，必须由人决定
+                Err(e) => panic!(
+                    "Failed to load checkpoint {}: {}. Move it away or set SHOULD_RESET_KAFKA_OFFSET=1 to re-import from scratch",
+                    checkpoint_path, e
+                ),
             }
         } else {
             // 如果需要重置offset，则创建一个新的context
@@ -140,7 +202,8 @@ impl ImportDriver {
     }
     #[allow(clippy::let_unit_value)]
     #[allow(unused_variables)]
-    pub async fn import_from_mq_for_a_message(&mut self) -> Result<(), ()> {
+    /// 导入一条消息对应的仓库，返回新导入的版本
+    pub async fn import_from_mq_for_a_message(&mut self) -> Result<ImportedMessage, ()> {
         tracing::info!("Try to import from a message!");
         // //tracing::debug
         // println!("Context size: {}", self.context.calculate_memory_usage());
@@ -176,6 +239,14 @@ impl ImportDriver {
             message.timestamp()
         );
 
+        // 用户上传来自单独的 topic，不记录它的 offset
+        if matches!(kind, MessageKind::UserUpload) {
+            return match model {
+                Some(model) => Ok(self.import_user_upload(model, &kafka_analysis_topic).await),
+                None => Err(()),
+            };
+        }
+
         // 早一个offset，防止当前消息没解析完就结束了
         let offset = message.offset();
         self.context.kafka_offset = Some(offset);
@@ -184,28 +255,6 @@ impl ImportDriver {
             self.context.print_status().await;
         }
 
-        /*if matches!(kind, MessageKind::UserUpload) {
-            //from user upload
-            tracing::info!("user upload path:{}", model.clone().unwrap().mega_url);
-            let usr_upload_path = model.unwrap().mega_url;
-            let namespace = extract_namespace(&usr_upload_path).expect("Failed to parse URL");
-            let path = PathBuf::from(&usr_upload_path);
-            insert_namespace_by_repo_path(path.to_str().unwrap().to_string(), namespace.clone());
-            let new_versions = self
-                .context
-                .parse_a_local_repo_and_return_new_versions(path, "".to_string())
-                .await
-                .unwrap();
-            for ver in new_versions {
-                self.sender_handler
-                    .send_message(
-                        &kafka_analysis_topic,
-                        "",
-                        &serde_json::to_string(&ver).unwrap(),
-                    )
-                    .await;
-            }
-        } else {*/
         //from mega
         let mega_url_suffix = model.unwrap().db_model.mega_url;
 
@@ -224,6 +273,7 @@ impl ImportDriver {
         let namespace = extract_namespace(git_url.as_ref()).expect("Failed to parse URL");
         let path = PathBuf::from(&clone_crates_dir).join(namespace.clone());
 
+        let mut imported = vec![];
         //changes
         if !path.is_dir() {
             //if user_upload, no clone
@@ -263,7 +313,7 @@ impl ImportDriver {
                 .unwrap();
 
             //if matches!(kind, MessageKind::UserUpload) {
-            for ver in new_versions {
+            for ver in &new_versions {
                 self.sender_handler
                     .send_message(
                         &kafka_analysis_topic,
@@ -278,6 +328,7 @@ impl ImportDriver {
                     ver.git_url
                 );
             }
+            imported.extend(new_versions);
             //}
         } else {
             tracing::info!("dir {} already exist", path.to_str().unwrap().to_string());
@@ -308,7 +359,7 @@ impl ImportDriver {
                 .await
                 .unwrap();
             //if matches!(kind, MessageKind::UserUpload) {
-            for ver in new_versions {
+            for ver in &new_versions {
                 self.sender_handler
                     .send_message(
                         &kafka_analysis_topic,
@@ -323,11 +374,66 @@ impl ImportDriver {
                     ver.git_url
                 );
             }
+            imported.extend(new_versions);
             //}
         } //changes
           //self.context.write_tugraph_import_files();
         tracing::info!("Finish to import from a message!");
-        Ok(())
+        Ok(ImportedMessage {
+            versions: imported,
+            ..Default::default()
+        })
+    }
+
+    /// 导入用户上传并解压好的项目，`mega_url` 为项目目录的 `file://` 地址，
+    /// `extra_field` 为上传任务 ID
+    async fn import_user_upload(
+        &mut self,
+        model: repo_sync_model::MessageModel,
+        kafka_analysis_topic: &str,
+    ) -> ImportedMessage {
+        let job_id = model.extra_field;
+        let git_url = model.db_model.mega_url;
+        tracing::info!("user upload {}: {}", job_id, git_url);
+        let result = match git_url.strip_prefix("file://").map(PathBuf::from) {
+            None => Err(format!("unsupported upload location {}", git_url)),
+            Some(path) => {
+                insert_namespace_by_repo_path(
+                    path.to_str().unwrap().to_string(),
+                    upload_namespace(&job_id),
+                );
+                match git::init_local_repo(&path) {
+                    Err(e) => Err(format!("failed to prepare {}: {}", path.display(), e)),
+                    Ok(()) => {
+                        self.context
+                            .parse_a_local_repo_and_return_new_versions(path, git_url.clone())
+                            .await
+                    }
+                }
+            }
+        };
+        let (versions, error) = match result {
+            Ok(versions) if versions.is_empty() => (
+                versions,
+                Some("no new crate version found in the upload".to_string()),
+            ),
+            Ok(versions) => (versions, None),
+            Err(e) => (vec![], Some(e)),
+        };
+        for ver in &versions {
+            self.sender_handler
+                .send_message(
+                    kafka_analysis_topic,
+                    "",
+                    &serde_json::to_string(&ver).unwrap(),
+                )
+                .await;
+        }
+        ImportedMessage {
+            versions,
+            upload_job: Some(job_id),
+            error,
+        }
     }
 
     pub async fn save_checkpoint(&mut self) -> Result<(), Box<dyn Error>> {
@@ -514,6 +620,8 @@ pub struct ImportContext {
     app_has_dep_version: Vec<HasDepVersion>,
 
     pub depends_on: Vec<DependsOn>,
+    /// resolved edges from the `Cargo.lock` of application versions
+    pub locked_depends_on: Vec<LockedDependsOn>,
 
     /// help is judge whether it is a new program
     program_memory: HashSet<model::general_model::Program>,
@@ -793,6 +901,16 @@ impl ImportContext {
                             self.application_versions.push(version.clone());
                             self.app_has_version.push(has_version);
                             self.app_has_dep_version.push(has_dep_version);
+
+                            let root = name_join_version(&name, &version.version);
+                            for edge in &dependencies.locked_dependencies {
+                                self.locked_depends_on.push(LockedDependsOn {
+                                    SRC_ID: name_join_version(&edge.from_name, &edge.from_version),
+                                    DST_ID: name_join_version(&edge.to_name, &edge.to_version),
+                                    root: root.clone(),
+                                    source: edge.source,
+                                });
+                            }
                         }
                         self.versions.push(dep_version);
 
@@ -992,9 +1110,25 @@ impl ImportContext {
             self.app_has_dep_version.clone(),
         );
         let _ = write_into_csv(
-            tugraph_import_files.join("depends_on.csv"),
+            tugraph_import_files.join(DEPENDS_ON_CSV),
             self.depends_on.clone(),
         );
+        let _ = fs::remove_file(tugraph_import_files.join(LEGACY_DEPENDS_ON_CSV));
+        // only keep the locked edges whose versions have been imported,
+        // the others are written once those versions show up
+        let version_parser = &self.version_updater.version_parser;
+        let known = |id: &str| {
+            id.split_once('/')
+                .is_some_and(|(name, version)| version_parser.exists(name, version))
+        };
+        let _ = write_into_csv(
+            tugraph_import_files.join("locked_depends_on.csv"),
+            self.locked_depends_on
+                .iter()
+                .filter(|e| known(&e.SRC_ID) && known(&e.DST_ID))
+                .cloned()
+                .collect(),
+        );
         tracing::info!("Finish to write");
         let write_need_time = write_time.elapsed();
         tracing::trace!("write need time: {:?}", write_need_time);
@@ -1002,8 +1136,7 @@ impl ImportContext {
 
     pub async fn save_to_file(&mut self, path: &str) -> Result<(), String> {
         self.normalize().await;
-        let serialized =
-            bincode::serialize(self).map_err(|e| format!("Serialization error: {}", e))?;
+        let serialized = self.encode_checkpoint()?;
 
         let mut file = File::create(path)
             .await
@@ -1016,20 +1149,47 @@ impl ImportContext {
         Ok(())
     }
 
-    pub async fn load_from_file(path: &str) -> Result<Self, String> {
+    /// 读取 checkpoint，文件不存在时返回 `None`，格式版本不一致或损坏时返回错误
+    pub async fn load_from_file(path: &str) -> Result<Option<Self>, String> {
         tracing::info!("Start to load from file: {}", path);
 
-        let content = tokio::fs::read(path)
-            .await
-            .map_err(|e| format!("Failed to read file: {}", e))?;
+        let content = match tokio::fs::read(path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("Failed to read file: {}", e)),
+        };
 
-        let context: ImportContext =
-            bincode::deserialize(&content).map_err(|e| format!("Deserialization error: {}", e))?;
+        let context = ImportContext::decode_checkpoint(&content)?;
         tracing::info!(
             "Context loaded successfully, there are {} programs",
             context.programs.len()
         );
-        Ok(context)
+        Ok(Some(context))
+    }
+
+    fn encode_checkpoint(&self) -> Result<Vec<u8>, String> {
+        let mut bytes = CHECKPOINT_MAGIC.to_vec();
+        bytes.extend_from_slice(&CHECKPOINT_VERSION.to_le_bytes());
+        bincode::serialize_into(&mut bytes, self)
+            .map_err(|e| format!("Serialization error: {}", e))?;
+        Ok(bytes)
+    }
+
+    fn decode_checkpoint(bytes: &[u8]) -> Result<Self, String> {
+        let Some(body) = bytes.strip_prefix(CHECKPOINT_MAGIC.as_slice()) else {
+            return Err("checkpoint predates format versioning".to_string());
+        };
+        let (version, body) = body
+            .split_first_chunk::<4>()
+            .ok_or("checkpoint is truncated")?;
+        let version = u32::from_le_bytes(*version);
+        if version != CHECKPOINT_VERSION {
+            return Err(format!(
+                "checkpoint format version {} does not match the supported version {}",
+                version, CHECKPOINT_VERSION
+            ));
+        }
+        bincode::deserialize(body).map_err(|e| format!("Deserialization error: {}", e))
     }
 
     fn format_status(&self) -> String {
@@ -1058,7 +1218,8 @@ impl ImportContext {
              - App Has Version: {}\n\
              - Lib Has Dep Version: {}\n\
              - App Has Dep Version: {}\n\
-             - Depends On: {}\n",
+             - Depends On: {}\n\
+             - Locked Depends On: {}\n",
             SystemTime::now()
                 .duration_since(UNIX_EPOCH)
                 .unwrap()
@@ -1080,6 +1241,7 @@ impl ImportContext {
             self.lib_has_dep_version.len(),
             self.app_has_dep_version.len(),
             self.depends_on.len(),
+            self.locked_depends_on.len(),
         )
     }
 
@@ -1163,6 +1325,11 @@ impl ImportContext {
                 self.depends_on.capacity(),
                 mem::size_of::<DependsOn>(),
             ),
+            (
+                "LockedDependsOn",
+                self.locked_depends_on.capacity(),
+                mem::size_of::<LockedDependsOn>(),
+            ),
             (
                 "ProgramMemory",
                 self.program_memory.capacity(),
@@ -1184,3 +1351,49 @@ impl ImportContext {
         output
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_version() {
+        let context = ImportContext {
+            kafka_offset: Some(42),
+            ..Default::default()
+        };
+        let bytes = context.encode_checkpoint().unwrap();
+        let decoded = ImportContext::decode_checkpoint(&bytes).unwrap();
+        assert_eq!(decoded.kafka_offset, Some(42));
+
+        // 没有文件头的旧 checkpoint
+        let legacy = bincode::serialize(&context).unwrap();
+        assert!(ImportContext::decode_checkpoint(&legacy).is_err());
+
+        let mut newer = bytes.clone();
+        newer[CHECKPOINT_MAGIC.len()..CHECKPOINT_MAGIC.len() + 4]
+            .copy_from_slice(&(CHECKPOINT_VERSION + 1).to_le_bytes());
+        let err = ImportContext::decode_checkpoint(&newer).err().unwrap();
+        assert!(err.contains("version"));
+    }
+
+    #[test]
+    fn test_depends_on_csv_matches_import_config() {
+        let config: serde_json::Value =
+            serde_json::from_str(include_str!("../../import.config.tmp")).unwrap();
+        let file = config["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|f| f["label"] == "depends_on")
+            .unwrap();
+        assert!(file["path"].as_str().unwrap().ends_with(DEPENDS_ON_CSV));
+
+        // write_into_csv 按字段名写表头
+        let header = match serde_json::to_value(DependsOn::default()).unwrap() {
+            serde_json::Value::Object(map) => map.keys().cloned().collect::<Vec<_>>(),
+            _ => unreachable!(),
+        };
+        assert_eq!(file["columns"], serde_json::json!(header));
+    }
+}
@@ -0,0 +1,184 @@
+//! Reading the exact resolution of a package from `Cargo.lock`.
+//!
+//! The declared graph (`depends_on`) guesses which version a requirement resolves
+//! to. When a tag ships a `Cargo.lock`, the versions it pins are what actually
+//! got built, so they are recorded separately as `locked_depends_on` edges.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use model::tugraph_model::DependencySource;
+use serde::{Deserialize, Serialize};
+use toml::Value;
+
+/// An edge of the resolved graph in a lockfile
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct LockedEdge {
+    pub from_name: String,
+    pub from_version: String,
+    pub to_name: String,
+    pub to_version: String,
+    pub source: DependencySource,
+}
+
+#[derive(Debug)]
+struct LockedPackage<'a> {
+    name: &'a str,
+    version: &'a str,
+    source: Option<&'a str>,
+    dependencies: Vec<&'a str>,
+}
+
+fn source_kind(source: Option<&str>) -> DependencySource {
+    match source {
+        None => DependencySource::Path,
+        Some(s) if s.starts_with("git+") => DependencySource::Git,
+        Some(_) => DependencySource::Registry,
+    }
+}
+
+/// All edges reachable from `name`/`version` in the lockfile, i.e. the full
+/// resolved dependency graph of that package as it was built.
+///
+/// Dependency entries are `"name"`, `"name version"` or
+/// `"name version (source)"`; a bare name is only written when the name is
+/// unique in the lockfile.
+pub(crate) fn locked_dependencies(lock: &Value, name: &str, version: &str) -> Vec<LockedEdge> {
+    let Some(packages) = lock.get("package").and_then(|p| p.as_array()) else {
+        return vec![];
+    };
+    let packages: Vec<LockedPackage> = packages
+        .iter()
+        .filter_map(|p| {
+            Some(LockedPackage {
+                name: p.get("name")?.as_str()?,
+                version: p.get("version")?.as_str()?,
+                source: p.get("source").and_then(|s| s.as_str()),
+                dependencies: p
+                    .get("dependencies")
+                    .and_then(|d| d.as_array())
+                    .map(|d| d.iter().filter_map(|d| d.as_str()).collect())
+                    .unwrap_or_default(),
+            })
+        })
+        .collect();
+
+    let mut by_name: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, p) in packages.iter().enumerate() {
+        by_name.entry(p.name).or_default().push(i);
+    }
+    let resolve = |dep: &str| -> Option<usize> {
+        let mut parts = dep.splitn(3, ' ');
+        let dep_name = parts.next()?;
+        let dep_version = parts.next();
+        let dep_source = parts
+            .next()
+            .map(|s| s.trim_start_matches('(').trim_end_matches(')'));
+        let candidates = by_name.get(dep_name)?;
+        candidates.iter().copied().find(|&i| {
+            let p = &packages[i];
+            dep_version.is_none_or(|v| p.version == v)
+                && dep_source.is_none_or(|s| p.source == Some(s))
+        })
+    };
+
+    let Some(root) = packages
+        .iter()
+        .position(|p| p.name == name && p.version == version)
+    else {
+        return vec![];
+    };
+
+    let mut edges = vec![];
+    let mut visited = HashSet::from([root]);
+    let mut queue = VecDeque::from([root]);
+    while let Some(i) = queue.pop_front() {
+        let from = &packages[i];
+        for dep in &from.dependencies {
+            let Some(j) = resolve(dep) else {
+                tracing::warn!("unresolved lockfile dependency {:?} of {}", dep, from.name);
+                continue;
+            };
+            let to = &packages[j];
+            edges.push(LockedEdge {
+                from_name: from.name.to_string(),
+                from_version: from.version.to_string(),
+                to_name: to.name.to_string(),
+                to_version: to.version.to_string(),
+                source: source_kind(to.source),
+            });
+            if visited.insert(j) {
+                queue.push_back(j);
+            }
+        }
+    }
+    edges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locked_dependencies() {
+        let lock: Value = r#"
+            version = 3
+
+            [[package]]
+            name = "app"
+            version = "0.1.0"
+            dependencies = ["serde", "rand 0.8.5", "rand 0.7.3", "helper"]
+
+            [[package]]
+            name = "helper"
+            version = "0.2.0"
+            dependencies = ["serde"]
+
+            [[package]]
+            name = "serde"
+            version = "1.0.200"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+
+            [[package]]
+            name = "rand"
+            version = "0.8.5"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+
+            [[package]]
+            name = "rand"
+            version = "0.7.3"
+            source = "git+https://github.com/rust-random/rand?branch=0.7#abc"
+
+            [[package]]
+            name = "unrelated"
+            version = "1.0.0"
+            dependencies = ["serde"]
+        "#
+        .parse()
+        .unwrap();
+
+        let edges = locked_dependencies(&lock, "app", "0.1.0");
+        let pairs: Vec<(&str, &str, &str, DependencySource)> = edges
+            .iter()
+            .map(|e| {
+                (
+                    e.from_name.as_str(),
+                    e.to_name.as_str(),
+                    e.to_version.as_str(),
+                    e.source,
+                )
+            })
+            .collect();
+        assert_eq!(
+            pairs,
+            [
+                ("app", "serde", "1.0.200", DependencySource::Registry),
+                ("app", "rand", "0.8.5", DependencySource::Registry),
+                ("app", "rand", "0.7.3", DependencySource::Git),
+                ("app", "helper", "0.2.0", DependencySource::Path),
+                ("helper", "serde", "1.0.200", DependencySource::Registry),
+            ]
+        );
+
+        assert!(locked_dependencies(&lock, "app", "9.9.9").is_empty());
+    }
+}
@@ -1,11 +1,12 @@
 use crate::git::get_all_git_tags_with_time_sorted;
+use crate::lockfile::{locked_dependencies, LockedEdge};
 use crate::utils::name_join_version;
 use crate::ImportContext;
 use git2::{Oid, Repository};
 use git2::{TreeWalkMode, TreeWalkResult};
-use model::tugraph_model::DependsOn;
+use model::tugraph_model::{DependencyKind, DependencySource, DependsOn};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::mem;
 use std::path::PathBuf;
 use toml::Value;
@@ -17,12 +18,318 @@ use toml::Value;
 pub struct Dependencies {
     pub(crate) crate_name: String,
     pub(crate) version: String,
-    pub(crate) dependencies: Vec<(String, String)>,
+    pub(crate) dependencies: Vec<DependencySpec>,
+    /// resolved graph from the `Cargo.lock` of the package or its workspace, if any
+    pub(crate) locked_dependencies: Vec<LockedEdge>,
 
     pub(crate) git_url: String,
     pub(crate) tag_name: String,
 }
 
+/// A dependency declared in `Cargo.toml`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DependencySpec {
+    pub name: String,
+    pub attrs: DependencyAttrs,
+}
+
+/// Attributes recorded on the `depends_on` edge
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DependencyAttrs {
+    /// the version requirement as declared, e.g. `^1.2`
+    pub version_req: String,
+    pub kind: DependencyKind,
+    pub optional: bool,
+    pub default_features: bool,
+    pub features: Vec<String>,
+    /// `cfg(..)` expression or target triple, `None` for all targets
+    pub target: Option<String>,
+    /// whether the dependency is enabled by the `default` feature of the dependent
+    pub enabled_by_default: bool,
+    pub source: DependencySource,
+}
+
+const DEPENDENCY_TABLES: [(&str, &str, DependencyKind); 3] = [
+    ("dependencies", "dependencies", DependencyKind::Normal),
+    ("dev-dependencies", "dev_dependencies", DependencyKind::Dev),
+    (
+        "build-dependencies",
+        "build_dependencies",
+        DependencyKind::Build,
+    ),
+];
+
+/// All the `Cargo.toml` of a repo at a given commit, keyed by their directory
+/// relative to the repo root (`""` for the root).
+///
+/// A package is resolved against its workspace root so that `workspace = true`
+/// inheritance and `path` dependencies can be looked up. Directories are kept
+/// sorted so that packages are always visited in the same order.
+#[derive(Debug, Default)]
+pub(crate) struct Manifests {
+    by_dir: BTreeMap<String, Value>,
+    locks: HashMap<String, Value>,
+}
+
+impl Manifests {
+    pub(crate) fn insert(&mut self, dir: &str, content: &str) {
+        match content.parse::<Value>() {
+            Ok(toml) => {
+                self.by_dir
+                    .insert(dir.trim_end_matches('/').to_string(), toml);
+            }
+            Err(_) => tracing::error!("Failed to parse Cargo.toml for {:?}", content),
+        }
+    }
+
+    pub(crate) fn insert_lock(&mut self, dir: &str, content: &str) {
+        match content.parse::<Value>() {
+            Ok(lock) => {
+                self.locks
+                    .insert(dir.trim_end_matches('/').to_string(), lock);
+            }
+            Err(_) => tracing::error!("Failed to parse Cargo.lock in {:?}", dir),
+        }
+    }
+
+    /// Directories containing a `Cargo.toml`, in lexicographic order.
+    pub(crate) fn dirs(&self) -> impl Iterator<Item = &String> {
+        self.by_dir.keys()
+    }
+
+    /// The directory and manifest of the workspace `dir` belongs to: the one
+    /// pointed to by `package.workspace`, or the nearest ancestor with `[workspace]`.
+    fn workspace_of(&self, dir: &str) -> Option<(String, &Value)> {
+        let toml = self.by_dir.get(dir)?;
+        if let Some(ws_path) = toml
+            .get("package")
+            .and_then(|p| p.get("workspace"))
+            .and_then(|w| w.as_str())
+        {
+            let ws_dir = join_relative(dir, ws_path);
+            return self.by_dir.get(&ws_dir).map(|ws| (ws_dir, ws));
+        }
+        let mut cur = Some(dir);
+        while let Some(d) = cur {
+            if let Some(ws) = self.by_dir.get(d).filter(|t| t.get("workspace").is_some()) {
+                return Some((d.to_string(), ws));
+            }
+            cur = (!d.is_empty()).then(|| d.rsplit_once('/').map_or("", |(parent, _)| parent));
+        }
+        None
+    }
+
+    /// `(name, version)` of the package in `dir`, with `version.workspace = true` resolved.
+    pub(crate) fn package(&self, dir: &str) -> Option<(String, String)> {
+        let package = self.by_dir.get(dir)?.get("package")?;
+        let name = package.get("name")?.as_str()?.to_string();
+        let version = match package.get("version")? {
+            Value::String(v) => v.clone(),
+            v if is_workspace_inherited(v) => {
+                let (_, ws) = self.workspace_of(dir)?;
+                ws.get("workspace")?
+                    .get("package")?
+                    .get("version")?
+                    .as_str()?
+                    .to_string()
+            }
+            _ => return None,
+        };
+        Some((name, version))
+    }
+
+    /// The resolved graph of the package in `dir`, read from its own `Cargo.lock`
+    /// or the one of its workspace.
+    pub(crate) fn locked_dependencies(
+        &self,
+        dir: &str,
+        name: &str,
+        version: &str,
+    ) -> Vec<LockedEdge> {
+        let lock = self.locks.get(dir).or_else(|| {
+            self.workspace_of(dir)
+                .and_then(|(ws_dir, _)| self.locks.get(&ws_dir))
+        });
+        lock.map(|lock| locked_dependencies(lock, name, version))
+            .unwrap_or_default()
+    }
+
+    /// Collect all dependencies of the package in `dir`, including dev/build
+    /// dependencies and the ones under `[target.'cfg(..)'.*]`.
+    pub(crate) fn dependency_specs(&self, dir: &str) -> Vec<DependencySpec> {
+        let Some(toml) = self.by_dir.get(dir) else {
+            return vec![];
+        };
+        let mut sections = vec![(toml, None)];
+        if let Some(targets) = toml.get("target").and_then(|t| t.as_table()) {
+            for (target, table) in targets {
+                sections.push((table, Some(target.clone())));
+            }
+        }
+
+        let default_enabled = optional_deps_enabled_by_default(toml);
+        let mut specs = vec![];
+        for (section, target) in sections {
+            for (key, alt_key, kind) in DEPENDENCY_TABLES {
+                let Some(deps_table) = section
+                    .get(key)
+                    .or_else(|| section.get(alt_key))
+                    .and_then(|t| t.as_table())
+                else {
+                    continue;
+                };
+                for (dep_key, val) in deps_table {
+                    if let Some(mut spec) = self.resolve_dependency(dir, dep_key, val) {
+                        spec.attrs.kind = kind;
+                        spec.attrs.target.clone_from(&target);
+                        // features refer to the dependency by its key, not the real crate name
+                        spec.attrs.enabled_by_default =
+                            !spec.attrs.optional || default_enabled.contains(dep_key);
+                        specs.push(spec);
+                    }
+                }
+            }
+        }
+        specs
+    }
+
+    /// Resolve one dependency entry: apply workspace inheritance, map a
+    /// `package = ".."` rename to the real crate, and find the version of a
+    /// `path` dependency from its own manifest.
+    fn resolve_dependency(&self, dir: &str, dep_key: &str, val: &Value) -> Option<DependencySpec> {
+        let mut attrs = DependencyAttrs {
+            default_features: true,
+            ..Default::default()
+        };
+        // the declaration that carries version/source, and the dir its `path` is relative to
+        let inherited = is_workspace_inherited(val);
+        let (decl, decl_dir) = if inherited {
+            let (ws_dir, ws) = self.workspace_of(dir)?;
+            let decl = ws.get("workspace")?.get("dependencies")?.get(dep_key)?;
+            (decl, ws_dir)
+        } else {
+            (val, dir.to_string())
+        };
+
+        let empty = toml::map::Map::new();
+        let (decl_tab, decl_version) = match decl {
+            Value::String(version) => (&empty, Some(version.as_str())),
+            Value::Table(tab) => (tab, tab.get("version").and_then(|v| v.as_str())),
+            _ => return None,
+        };
+        let member_tab = val.as_table();
+
+        attrs.optional = member_tab
+            .and_then(|t| t.get("optional"))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        attrs.default_features = decl_tab
+            .get("default-features")
+            .or_else(|| decl_tab.get("default_features"))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true);
+        // features are additive: the member may enable more on top of the workspace
+        let feature_tables = if inherited {
+            vec![Some(decl_tab), member_tab]
+        } else {
+            vec![Some(decl_tab)]
+        };
+        for table in feature_tables.into_iter().flatten() {
+            for feature in table
+                .get("features")
+                .and_then(|v| v.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|f| f.as_str())
+            {
+                if !attrs.features.iter().any(|f| f == feature) {
+                    attrs.features.push(feature.to_string());
+                }
+            }
+        }
+
+        let name = decl_tab
+            .get("package")
+            .and_then(|p| p.as_str())
+            .unwrap_or(dep_key)
+            .to_string();
+        let path = decl_tab.get("path").and_then(|p| p.as_str());
+        attrs.source = if path.is_some() {
+            DependencySource::Path
+        } else if decl_tab.contains_key("git") {
+            DependencySource::Git
+        } else {
+            DependencySource::Registry
+        };
+
+        attrs.version_req = match (decl_version, path) {
+            (Some(version), _) => version.to_owned(),
+            (None, Some(path)) => {
+                let (_, version) = self.package(&join_relative(&decl_dir, path))?;
+                format!("={}", version)
+            }
+            // a git dependency without version takes whatever is at the revision
+            (None, None) if attrs.source == DependencySource::Git => "*".to_string(),
+            (None, None) => return None,
+        };
+
+        Some(DependencySpec { name, attrs })
+    }
+}
+
+/// `{ workspace = true }` or `version.workspace = true`
+fn is_workspace_inherited(val: &Value) -> bool {
+    val.get("workspace").and_then(|w| w.as_bool()) == Some(true)
+}
+
+/// Join a relative path onto a repo-relative directory, resolving `.` and `..`.
+fn join_relative(dir: &str, rel: &str) -> String {
+    let mut parts: Vec<&str> = dir.split('/').filter(|p| !p.is_empty()).collect();
+    for part in rel.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            p => parts.push(p),
+        }
+    }
+    parts.join("/")
+}
+
+/// Names of optional dependencies turned on by the `default` feature, following
+/// `[features]` transitively. `dep:x` and `x/feat` enable `x`, `x?/feat` does not.
+fn optional_deps_enabled_by_default(toml: &Value) -> HashSet<String> {
+    let mut enabled = HashSet::new();
+    let Some(features) = toml.get("features").and_then(|f| f.as_table()) else {
+        return enabled;
+    };
+    let mut visited = HashSet::new();
+    let mut stack = vec!["default".to_string()];
+    while let Some(feature) = stack.pop() {
+        if !visited.insert(feature.clone()) {
+            continue;
+        }
+        // a feature not defined in `[features]` is the implicit feature of an optional dependency
+        let Some(items) = features.get(&feature).and_then(|f| f.as_array()) else {
+            enabled.insert(feature);
+            continue;
+        };
+        for item in items.iter().filter_map(|i| i.as_str()) {
+            if let Some(dep) = item.strip_prefix("dep:") {
+                enabled.insert(dep.to_string());
+            } else if let Some((dep, _)) = item.split_once('/') {
+                if !dep.ends_with('?') {
+                    enabled.insert(dep.to_string());
+                }
+            } else {
+                stack.push(item.to_string());
+            }
+        }
+    }
+    enabled
+}
+
 impl ImportContext {
     /// a git repo contains different crates
     #[allow(clippy::type_complexity)]
@@ -31,7 +338,7 @@ impl ImportContext {
         repo_path: &PathBuf,
         git_url: &str,
     ) -> Vec<Dependencies> {
-        let mut crate_version_map: HashMap<(String, String), Dependencies> = HashMap::default();
+        let mut crate_version_map: BTreeMap<(String, String), Dependencies> = BTreeMap::default();
 
         let versions = get_all_git_tags_with_time_sorted(repo_path).await; //tag id time
 
@@ -62,14 +369,15 @@ impl ImportContext {
         tag_name: &str,
         tree: Oid,
     ) -> Vec<Dependencies> {
-        let mut res = Vec::new();
+        let mut manifests = Manifests::default();
 
         // Lock the repository and tree for reading
         let repo = Repository::open(repo_path).unwrap();
         let tree = repo.find_tree(tree).expect("Failed to find tree");
 
-        // Walk the tree to find Cargo.toml
-        tree.walk(TreeWalkMode::PostOrder, |_, entry| {
+        // Walk the tree to collect all Cargo.toml and Cargo.lock first, packages
+        // are resolved against their workspace root afterwards
+        tree.walk(TreeWalkMode::PostOrder, |root, entry| {
             if entry.name() == Some("Cargo.toml") {
                 // for each Cargo.toml in repo of given commit
                 let obj = entry
@@ -79,10 +387,16 @@ impl ImportContext {
                 let content = std::str::from_utf8(blob.content())
                     .expect("Cargo.toml content is not valid UTF-8");
 
-                if let Some(dependencies) =
-                    self.parse_a_package_of_a_version(content, git_url, tag_name)
+                manifests.insert(root, content);
+            } else if entry.name() == Some("Cargo.lock") {
+                if let Some(content) = entry
+                    .to_object(&repo)
+                    .ok()
+                    .as_ref()
+                    .and_then(|obj| obj.as_blob())
+                    .and_then(|blob| std::str::from_utf8(blob.content()).ok())
                 {
-                    res.push(dependencies);
+                    manifests.insert_lock(root, content);
                 }
             }
 
@@ -90,82 +404,63 @@ impl ImportContext {
         })
         .unwrap();
 
-        res
+        // several manifests may declare the same package (e.g. vendored copies),
+        // keep the first one in directory order so that imports are reproducible
+        let mut seen = HashSet::new();
+        manifests
+            .dirs()
+            .filter_map(|dir| self.parse_a_package_of_a_version(&manifests, dir, git_url, tag_name))
+            .filter(|deps| seen.insert((deps.crate_name.clone(), deps.version.clone())))
+            .collect()
     }
 
     fn parse_a_package_of_a_version(
         &self,
-        cargo_toml_content: &str,
+        manifests: &Manifests,
+        dir: &str,
         git_url: &str,
         tag_name: &str,
     ) -> Option<Dependencies> {
-        match cargo_toml_content.parse::<Value>() {
-            Ok(toml) => {
-                if let Some(package) = toml.get("package") {
-                    if let Some(crate_name) = package.get("name") {
-                        let crate_name = crate_name.as_str()?.to_string();
-                        let version = package.get("version")?.as_str()?.to_string();
-
-                        // e.g. 0.1.53a2 is invalid version number.
-                        if semver::Version::parse(&version).is_err() {
-                            return None;
-                        }
-
-                        // dedup
-                        if self
-                            .version_updater
-                            .version_parser
-                            .exists(&crate_name, &version)
-                        {
-                            return None;
-                        }
-
-                        let mut dependencies = vec![];
-
-                        if let Some(dep_table) = toml.get("dependencies") {
-                            if let Some(deps_table) = dep_table.as_table() {
-                                for (name, val) in deps_table {
-                                    if let Some(version) = val.as_str() {
-                                        dependencies.push((name.clone(), version.to_owned()));
-                                    } else if let Some(ver_tab) = val.as_table() {
-                                        if let Some(val) = ver_tab.get("version") {
-                                            if let Some(version) = val.as_str() {
-                                                dependencies
-                                                    .push((name.clone(), version.to_owned()));
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
+        let (crate_name, version) = manifests.package(dir)?;
 
-                        let dependencies = Dependencies {
-                            crate_name,
-                            version,
-                            dependencies,
-                            git_url: git_url.to_string(),
-                            tag_name: tag_name.to_string(),
-                        };
+        // e.g. 0.1.53a2 is invalid version number.
+        if semver::Version::parse(&version).is_err() {
+            return None;
+        }
 
-                        return Some(dependencies);
-                    }
-                }
-            }
-            Err(_) => tracing::error!("Failed to parse Cargo.toml for {:?}", cargo_toml_content),
+        // dedup
+        if self
+            .version_updater
+            .version_parser
+            .exists(&crate_name, &version)
+        {
+            return None;
         }
-        None
+
+        Some(Dependencies {
+            locked_dependencies: manifests.locked_dependencies(dir, &crate_name, &version),
+            crate_name,
+            version,
+            dependencies: manifests.dependency_specs(dir),
+            git_url: git_url.to_string(),
+            tag_name: tag_name.to_string(),
+        })
     }
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct VersionUpdater {
     /// a reverse record: who depends on the key?
-    pub reverse_depends_on_map: HashMap<String, Vec<(String, model::general_model::Version)>>,
+    #[allow(clippy::type_complexity)]
+    pub reverse_depends_on_map:
+        HashMap<String, Vec<(model::general_model::Version, DependencyAttrs)>>,
 
     /// a actual map: a crate **actually** depends on which?
     /// it is used to build `depends_on` edges.
-    pub actually_depends_on_map:
-        HashMap<model::general_model::Version, Vec<model::general_model::Version>>,
+    pub actually_depends_on_map: HashMap<
+        model::general_model::Version,
+        Vec<(model::general_model::Version, DependencyAttrs)>,
+    >,
 
     pub version_parser: VersionParser,
 }
@@ -174,13 +469,24 @@ impl VersionUpdater {
     pub async fn to_depends_on_edges(&self) -> Vec<DependsOn> {
         let mut edges = vec![];
         for (src, dsts) in &self.actually_depends_on_map {
-            for dst in dsts {
+            for (dst, attrs) in dsts {
                 #[allow(non_snake_case)]
                 let SRC_ID = name_join_version(&src.name, &src.version);
 
                 #[allow(non_snake_case)]
                 let DST_ID = name_join_version(&dst.name, &dst.version);
-                let depends_on = DependsOn { SRC_ID, DST_ID };
+                let depends_on = DependsOn {
+                    SRC_ID,
+                    DST_ID,
+                    dependency_type: attrs.kind,
+                    optional: attrs.optional,
+                    default_features: attrs.default_features,
+                    features: attrs.features.join(","),
+                    target: attrs.target.clone().unwrap_or_default(),
+                    enabled_by_default: attrs.enabled_by_default,
+                    source: attrs.source,
+                    version_req: attrs.version_req.clone(),
+                };
                 edges.push(depends_on);
             }
         }
@@ -202,10 +508,15 @@ impl VersionUpdater {
         cur_release: &model::general_model::Version,
         info: &Dependencies,
     ) {
-        for (name, version) in &info.dependencies {
+        for dep in &info.dependencies {
             //let dep = model::general_model::Version::new(&name, &version);
-            self.insert_reverse_dep(name, version, &cur_release.name, &cur_release.version)
-                .await;
+            self.insert_reverse_dep(
+                &dep.name,
+                &cur_release.name,
+                &cur_release.version,
+                dep.attrs.clone(),
+            )
+            .await;
         }
 
         // a new version should not exist before.
@@ -215,18 +526,21 @@ impl VersionUpdater {
             .insert(cur_release.clone(), cur_dependencies);
     }
 
-    async fn search_dependencies(&self, info: &Dependencies) -> Vec<model::general_model::Version> {
-        let mut res: Vec<model::general_model::Version> = vec![];
-        for (dependency_name, dependency_version) in &info.dependencies {
+    async fn search_dependencies(
+        &self,
+        info: &Dependencies,
+    ) -> Vec<(model::general_model::Version, DependencyAttrs)> {
+        let mut res = vec![];
+        for dep in &info.dependencies {
             let version_option = self
                 .version_parser
-                .find_latest_matching_version(dependency_name, dependency_version)
+                .find_latest_matching_version(&dep.name, &dep.attrs.version_req)
                 .await;
 
             if let Some(dependency_actual_version) = &version_option {
                 let dependency =
-                    model::general_model::Version::new(dependency_name, dependency_actual_version);
-                res.push(dependency);
+                    model::general_model::Version::new(&dep.name, dependency_actual_version);
+                res.push((dependency, dep.attrs.clone()));
             }
         }
         res
@@ -237,8 +551,8 @@ impl VersionUpdater {
             .unwrap_or_else(|_| panic!("failed to parse version {:?}", &cur_release));
         let wrapped_reverse_map = self.reverse_depends_on_map.get(&cur_release.name);
         if let Some(reverse_map) = wrapped_reverse_map {
-            for (required_version, reverse_dep) in reverse_map {
-                let requirement = match semver::VersionReq::parse(required_version) {
+            for (reverse_dep, attrs) in reverse_map {
+                let requirement = match semver::VersionReq::parse(&attrs.version_req) {
                     Ok(req) => req,
                     Err(_) => {
                         tracing::error!("failed to transform to VersionReq");
@@ -250,8 +564,8 @@ impl VersionUpdater {
                     if let Some(v) = self.actually_depends_on_map.get_mut(reverse_dep) {
                         let mut found = false;
                         let mut exist = false;
-                        for x in &mut *v {
-                            if x.name == cur_release.name {
+                        for (x, x_attrs) in &mut *v {
+                            if x.name == cur_release.name && x_attrs == attrs {
                                 found = true;
                                 let prev_sem_ver = semver::Version::parse(&x.version).unwrap();
                                 if sem_ver == prev_sem_ver {
@@ -265,23 +579,32 @@ impl VersionUpdater {
                         }
                         #[allow(clippy::if_same_then_else)]
                         if !found {
-                            v.push(model::general_model::Version::new(
-                                &cur_release.name,
-                                &cur_release.version,
+                            v.push((
+                                model::general_model::Version::new(
+                                    &cur_release.name,
+                                    &cur_release.version,
+                                ),
+                                attrs.clone(),
                             ));
                         } else if !exist {
-                            v.push(model::general_model::Version::new(
-                                &cur_release.name,
-                                &cur_release.version,
+                            v.push((
+                                model::general_model::Version::new(
+                                    &cur_release.name,
+                                    &cur_release.version,
+                                ),
+                                attrs.clone(),
                             ));
                         }
                     } else {
                         // No vec
                         self.actually_depends_on_map.insert(
                             reverse_dep.clone(),
-                            vec![model::general_model::Version::new(
-                                &cur_release.name,
-                                &cur_release.version,
+                            vec![(
+                                model::general_model::Version::new(
+                                    &cur_release.name,
+                                    &cur_release.version,
+                                ),
+                                attrs.clone(),
                             )],
                         );
                     }
@@ -295,16 +618,16 @@ impl VersionUpdater {
     pub async fn insert_reverse_dep(
         &mut self,
         dependency_name: &str,
-        dependency_version: &str,
         dependent_name: &str,
         dependent_version: &str,
+        attrs: DependencyAttrs,
     ) {
         //let dependency = model::general_model::Version::new(dependency_name, dependency_version);
         let dependent = model::general_model::Version::new(dependent_name, dependent_version);
         self.reverse_depends_on_map
             .entry(dependency_name.to_string())
             .or_default()
-            .push((dependency_version.to_string(), dependent));
+            .push((dependent, attrs));
     }
 }
 
@@ -338,14 +661,8 @@ impl VersionParser {
         target_version: &str,
     ) -> Option<String> {
         if let Some(lib_map) = self.version_map.get(target_lib) {
-            // if the lib exists
-            let req_str = if target_version.contains('.') {
-                format!("^{}", target_version)
-            } else {
-                format!("{}.*", target_version)
-            };
-
-            let requirement = match semver::VersionReq::parse(&req_str) {
+            // if the lib exists, a bare version such as "1.2" means "^1.2" as in cargo
+            let requirement = match semver::VersionReq::parse(target_version) {
                 Ok(req) => req,
                 Err(_) => return None, // 如果无法解析为有效的版本请求，则返回 None
             };
@@ -374,17 +691,19 @@ impl VersionUpdater {
         // Calculate heap size for reverse_depends_on_map
         for (key, value) in &self.reverse_depends_on_map {
             heap_size += key.capacity() * mem::size_of::<char>(); // String capacity
-            heap_size +=
-                value.capacity() * mem::size_of::<(String, model::general_model::Version)>();
-            for (s, _) in value {
-                heap_size += s.capacity() * mem::size_of::<char>(); // String capacity
+            heap_size += value.capacity()
+                * mem::size_of::<(model::general_model::Version, DependencyAttrs)>();
+            for (_, attrs) in value {
+                heap_size += attrs.version_req.capacity() * mem::size_of::<char>();
+                // String capacity
             }
         }
 
         // Calculate heap size for actually_depends_on_map
         for value in self.actually_depends_on_map.values() {
             heap_size += mem::size_of::<model::general_model::Version>(); // Key size
-            heap_size += value.capacity() * mem::size_of::<model::general_model::Version>();
+            heap_size += value.capacity()
+                * mem::size_of::<(model::general_model::Version, DependencyAttrs)>();
         }
 
         format!(" [Version Updater: {}] ", stack_size + heap_size)
@@ -413,7 +732,12 @@ impl VersionParser {
 
 #[cfg(test)]
 mod tests {
-    use super::VersionParser;
+    use super::{DependencySpec, Manifests, VersionParser};
+    use model::tugraph_model::{DependencyKind, DependencySource};
+
+    fn find<'a>(specs: &'a [DependencySpec], name: &str) -> &'a DependencySpec {
+        specs.iter().find(|s| s.name == name).unwrap()
+    }
 
     #[tokio::test]
     async fn test_insert_and_find_version() {
@@ -458,4 +782,192 @@ mod tests {
             None
         );
     }
+
+    #[test]
+    fn test_parse_dependency_specs() {
+        let mut manifests = Manifests::default();
+        manifests.insert(
+            "",
+            r#"
+            [package]
+            name = "demo"
+            version = "0.1.0"
+
+            [features]
+            default = ["std"]
+            std = ["dep:serde", "log?/std"]
+            extra = ["rand/small_rng"]
+
+            [dependencies]
+            anyhow = "1"
+            serde = { version = "1.0", optional = true, default-features = false, features = ["derive"] }
+            log = { version = "0.4", optional = true }
+            rand = { version = "0.8", optional = true }
+            local = { path = "../local" }
+
+            [dev-dependencies]
+            tokio = { version = "1", features = ["macros", "rt"] }
+
+            [build-dependencies]
+            cc = "1.0"
+
+            [target.'cfg(unix)'.dependencies]
+            libc = "0.2"
+
+            [target.'cfg(windows)'.dev-dependencies]
+            winapi = "0.3"
+        "#,
+        );
+
+        let specs = manifests.dependency_specs("");
+        let find = |name: &str| find(&specs, name);
+
+        // path dependency whose manifest is not in the repo is skipped
+        assert_eq!(specs.len(), 8);
+        assert!(specs.iter().all(|s| s.name != "local"));
+
+        let anyhow = find("anyhow");
+        assert_eq!(anyhow.attrs.version_req, "1");
+        assert_eq!(anyhow.attrs.kind, DependencyKind::Normal);
+        assert!(anyhow.attrs.default_features);
+        assert!(anyhow.attrs.enabled_by_default);
+
+        let serde = find("serde");
+        assert!(serde.attrs.optional);
+        assert!(!serde.attrs.default_features);
+        assert_eq!(serde.attrs.features, ["derive"]);
+        assert!(serde.attrs.enabled_by_default);
+
+        // `log?/std` does not enable log, `rand` is only enabled by a non-default feature
+        assert!(!find("log").attrs.enabled_by_default);
+        assert!(!find("rand").attrs.enabled_by_default);
+
+        let tokio = find("tokio");
+        assert_eq!(tokio.attrs.kind, DependencyKind::Dev);
+        assert_eq!(tokio.attrs.features, ["macros", "rt"]);
+        assert_eq!(find("cc").attrs.kind, DependencyKind::Build);
+
+        let libc = find("libc");
+        assert_eq!(libc.attrs.kind, DependencyKind::Normal);
+        assert_eq!(libc.attrs.target.as_deref(), Some("cfg(unix)"));
+        let winapi = find("winapi");
+        assert_eq!(winapi.attrs.kind, DependencyKind::Dev);
+        assert_eq!(winapi.attrs.target.as_deref(), Some("cfg(windows)"));
+    }
+
+    #[test]
+    fn test_workspace_inheritance_rename_and_sources() {
+        let mut manifests = Manifests::default();
+        manifests.insert(
+            "",
+            r#"
+            [workspace]
+            members = ["tokio", "tokio-util", "local"]
+
+            [workspace.package]
+            version = "1.38.0"
+
+            [workspace.dependencies]
+            serde = { version = "1.0", default-features = false, features = ["derive"] }
+            futures = "0.3"
+            tokio-util = { path = "tokio-util" }
+        "#,
+        );
+        manifests.insert(
+            "tokio/",
+            r#"
+            [package]
+            name = "tokio"
+            version.workspace = true
+
+            [dependencies]
+            serde = { workspace = true, features = ["rc"], optional = true }
+            futures.workspace = true
+            tokio-util = { workspace = true }
+            rand06 = { package = "rand", version = "0.6" }
+            local = { path = "../local" }
+            mygit = { git = "https://github.com/example/mygit" }
+            missing = { workspace = true }
+        "#,
+        );
+        manifests.insert(
+            "tokio-util",
+            r#"
+            [package]
+            name = "tokio-util"
+            version = "0.7.11"
+        "#,
+        );
+        manifests.insert(
+            "local",
+            r#"
+            [package]
+            name = "local"
+            version = "0.3.0"
+        "#,
+        );
+
+        assert_eq!(
+            manifests.package("tokio"),
+            Some(("tokio".to_string(), "1.38.0".to_string()))
+        );
+        // the virtual workspace root is not a package
+        assert_eq!(manifests.package(""), None);
+        assert_eq!(
+            manifests.dirs().collect::<Vec<_>>(),
+            ["", "local", "tokio", "tokio-util"]
+        );
+
+        let specs = manifests.dependency_specs("tokio");
+        // `missing` is not declared in `[workspace.dependencies]`
+        assert_eq!(specs.len(), 6);
+
+        let serde = find(&specs, "serde");
+        assert_eq!(serde.attrs.version_req, "1.0");
+        assert!(serde.attrs.optional);
+        assert!(!serde.attrs.default_features);
+        assert_eq!(serde.attrs.features, ["derive", "rc"]);
+        assert_eq!(serde.attrs.source, DependencySource::Registry);
+
+        assert_eq!(find(&specs, "futures").attrs.version_req, "0.3");
+
+        // path of an inherited dependency is relative to the workspace root
+        let tokio_util = find(&specs, "tokio-util");
+        assert_eq!(tokio_util.attrs.version_req, "=0.7.11");
+        assert_eq!(tokio_util.attrs.source, DependencySource::Path);
+
+        // renamed dependency is recorded under the real crate name
+        assert_eq!(find(&specs, "rand").attrs.version_req, "0.6");
+        assert!(specs.iter().all(|s| s.name != "rand06"));
+
+        assert_eq!(find(&specs, "local").attrs.version_req, "=0.3.0");
+        assert_eq!(find(&specs, "local").attrs.source, DependencySource::Path);
+
+        let mygit = find(&specs, "mygit");
+        assert_eq!(mygit.attrs.version_req, "*");
+        assert_eq!(mygit.attrs.source, DependencySource::Git);
+    }
+
+    #[tokio::test]
+    async fn test_find_version_with_operators() {
+        let mut parser = VersionParser::default();
+        parser.insert_version("tokio-util", "0.7.10").await;
+        parser.insert_version("tokio-util", "0.7.11").await;
+        assert_eq!(
+            parser
+                .find_latest_matching_version("tokio-util", "=0.7.10")
+                .await,
+            Some("0.7.10".to_string())
+        );
+        assert_eq!(
+            parser
+                .find_latest_matching_version("tokio-util", ">=0.7, <0.8")
+                .await,
+            Some("0.7.11".to_string())
+        );
+        assert_eq!(
+            parser.find_latest_matching_version("tokio-util", "*").await,
+            Some("0.7.11".to_string())
+        );
+    }
 }
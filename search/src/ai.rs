@@ -131,7 +131,7 @@ async fn get_crate_info_with_embedding(
 ) -> Result<String, Box<dyn std::error::Error>> {
     let question_embedding = crate::embedding::get_one_text_embedding(question).await?;
     let top_n = 5;
-    let results: Vec<(i32, String, String)> =
+    let results: Vec<(String, String, String)> =
         crate::embedding::search_crates_by_embedding(client, &question_embedding, top_n).await?;
 
     let mut results_text = String::new();
@@ -1,18 +1,120 @@
 use semver::Version;
+use serde::{Deserialize, Serialize};
 use std::env;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use tokio_postgres::Client as PgClient;
+use utoipa::ToSchema;
+
+use crate::embedding::{self, EmbeddingProvider};
+use crate::facets::{self, FilterSql, SearchFacets, SearchFilters};
+use crate::ranking::{self, RankingWeights};
 
 pub struct SearchModule<'a> {
     pg_client: &'a PgClient,
     table_name: String,
+    weights: RankingWeights,
+    embedder: Option<Arc<dyn EmbeddingProvider>>,
 }
 
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
 pub enum SearchSortCriteria {
     Comprehensive,
+    #[default]
+    #[serde(rename = "relevance")]
     Relavance,
     Downloads,
 }
 
+/// 带过滤条件的搜索结果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchResults {
+    pub crates: Vec<RecommendCrate>,
+    pub facets: SearchFacets,
+    /// 全文检索没有结果，`crates` 来自按名称的模糊匹配
+    pub fuzzy: bool,
+}
+
+/// 自动补全的候选名称
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct Suggestion {
+    pub name: String,
+    pub downloads: i64,
+}
+
+/// 关键词的匹配方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MatchMode {
+    /// `tsv` 全文检索，`$1` 为 tsquery
+    FullText,
+    /// 按名称的 `pg_trgm` 三元组相似度匹配，`$1` 为小写的关键词
+    Fuzzy,
+    /// `embedding` 列的余弦近邻，`$1` 为 pgvector 文本形式的查询向量
+    Semantic,
+}
+
+/// 向量检索最多取的近邻数
+const SEMANTIC_CANDIDATES: usize = 100;
+
+/// 向量检索出错、退回只按关键词排序的次数
+static SEMANTIC_FALLBACKS: AtomicU64 = AtomicU64::new(0);
+
+/// 进程启动以来向量检索出错、退回只按关键词排序的次数
+pub fn semantic_fallbacks() -> u64 {
+    SEMANTIC_FALLBACKS.load(Ordering::Relaxed)
+}
+
+/// 向量检索的候选条件：`$param` 为查询向量，先用向量索引取近邻，再与过滤条件组合
+pub(crate) fn semantic_condition(table_name: &str, param: usize) -> String {
+    format!(
+        "p.id IN (SELECT id FROM {} WHERE embedding IS NOT NULL
+            ORDER BY embedding <=> ${}::text::vector LIMIT {})",
+        table_name, param, SEMANTIC_CANDIDATES
+    )
+}
+
+/// `find` 的结果
+struct Found {
+    mode: MatchMode,
+    /// `$1` 参数
+    query: String,
+    crates: Vec<RecommendCrate>,
+    /// 参与排序的向量检索所用的查询向量，分面计数同样包含其近邻
+    semantic: Option<String>,
+}
+
+impl MatchMode {
+    /// 关键词转换为 `$1` 参数，没有可用的词时返回 `None`
+    pub(crate) fn keyword(self, keyword: &str) -> Option<String> {
+        match self {
+            MatchMode::FullText => tsquery(keyword),
+            MatchMode::Fuzzy => {
+                let keyword = keyword.trim().to_lowercase();
+                (!keyword.is_empty()).then_some(keyword)
+            }
+            // 查询向量需要先请求嵌入服务，见 `SearchModule::semantic_candidates`
+            MatchMode::Semantic => None,
+        }
+    }
+
+    pub(crate) fn rank(self) -> &'static str {
+        match self {
+            MatchMode::FullText => "ts_rank(p.tsv, to_tsquery($1))",
+            MatchMode::Fuzzy => "similarity(p.name, $1)",
+            MatchMode::Semantic => "(1 - (p.embedding <=> $1::text::vector))::real",
+        }
+    }
+
+    pub(crate) fn condition(self, table_name: &str) -> String {
+        match self {
+            MatchMode::FullText => "p.tsv @@ to_tsquery($1)".to_string(),
+            MatchMode::Fuzzy => "p.name % $1".to_string(),
+            MatchMode::Semantic => semantic_condition(table_name, 1),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct RecommendCrate {
     pub id: String,
@@ -22,27 +124,262 @@ pub struct RecommendCrate {
     pub namespace: String,
     pub max_version: String,
     pub rank: f32,
+    pub evaluated_score: Option<f64>,
+}
+
+/// 搜索的排序权重和嵌入提供者。嵌入提供者持有 HTTP 客户端，应在启动时创建一次并在请求间共享
+#[derive(Clone)]
+pub struct SearchConfig {
+    pub weights: RankingWeights,
+    pub embedder: Option<Arc<dyn EmbeddingProvider>>,
+}
+
+impl SearchConfig {
+    /// 权重取自 `SEARCH_WEIGHT_*`，嵌入提供者由 `EMBEDDING_PROVIDER` 指定，配置无效时不使用向量检索
+    pub fn from_env() -> Self {
+        let embedder = match embedding::provider_from_env() {
+            Ok(embedder) => Some(embedder),
+            Err(e) => {
+                tracing::warn!("semantic search disabled: {}", e);
+                None
+            }
+        };
+        SearchConfig {
+            weights: RankingWeights::from_env(),
+            embedder,
+        }
+    }
 }
 
 impl<'a> SearchModule<'a> {
-    pub async fn new(pg_client: &'a PgClient) -> Self {
+    pub async fn new(pg_client: &'a PgClient, config: &SearchConfig) -> Self {
         let table_name = env::var("TABLE_NAME").unwrap_or_else(|_| "crates".to_string());
         SearchModule {
             pg_client,
             table_name,
+            weights: config.weights,
+            embedder: config.embedder.clone(),
         }
     }
 
+    /// 替换 `Comprehensive` 排序使用的权重，默认从环境变量读取
+    pub fn with_weights(mut self, weights: RankingWeights) -> Self {
+        self.weights = weights;
+        self
+    }
+
+    /// 替换计算查询向量的嵌入提供者
+    pub fn with_embedder(mut self, embedder: Arc<dyn EmbeddingProvider>) -> Self {
+        self.embedder = Some(embedder);
+        self
+    }
+
     pub async fn search_crate(
         &self,
         keyword: &str,
         sort_by: SearchSortCriteria,
     ) -> Result<Vec<RecommendCrate>, Box<dyn std::error::Error>> {
-        let mut crates = search_crate_without_ai(self.pg_client, &self.table_name, keyword).await?;
-        sort_crates(&mut crates, sort_by);
-        rearrange_crates(&mut crates, keyword);
-        Ok(crates)
+        // 没有过滤条件也不计算分面，用不到漏洞信息
+        let filter_sql = FilterSql::new(&SearchFilters::default(), vec![]);
+        Ok(self
+            .find(keyword, &filter_sql, sort_by)
+            .await?
+            .map(|found| found.crates)
+            .unwrap_or_default())
+    }
+
+    /// 按关键词搜索并应用过滤条件，同时返回分面计数。调用前应先用 [`SearchFilters::validate`] 检查参数
+    pub async fn search(
+        &self,
+        keyword: &str,
+        filters: &SearchFilters,
+        sort_by: SearchSortCriteria,
+    ) -> Result<SearchResults, Box<dyn std::error::Error>> {
+        let affected = facets::affected_program_ids(self.pg_client, &self.table_name).await?;
+        let filter_sql = FilterSql::new(filters, affected);
+        let Some(found) = self.find(keyword, &filter_sql, sort_by).await? else {
+            return Ok(SearchResults::default());
+        };
+        // 融合进结果的向量检索候选也计入分面
+        let mut params = filter_sql.params(&found.query);
+        let semantic = found.semantic.as_ref().map(|vector| {
+            params.push(vector);
+            params.len()
+        });
+        let statement = facets::facet_query(&self.table_name, found.mode, &filter_sql, semantic);
+        let rows = self.pg_client.query(statement.as_str(), &params).await?;
+        let facets = SearchFacets::from_rows(rows.iter().map(|row| {
+            let value: Option<String> = row.get("value");
+            (
+                row.get("facet"),
+                value.unwrap_or_default(),
+                row.get("count"),
+            )
+        }));
+        Ok(SearchResults {
+            crates: found.crates,
+            facets,
+            fuzzy: found.mode == MatchMode::Fuzzy,
+        })
+    }
+
+    /// 先全文检索，没有结果时按名称模糊匹配，`Comprehensive` 排序时再融合向量检索的结果
+    async fn find(
+        &self,
+        keyword: &str,
+        filter_sql: &FilterSql,
+        sort_by: SearchSortCriteria,
+    ) -> Result<Option<Found>, Box<dyn std::error::Error>> {
+        let mut found = None;
+        for mode in [MatchMode::FullText, MatchMode::Fuzzy] {
+            let Some(query) = mode.keyword(keyword) else {
+                continue;
+            };
+            let crates =
+                search_crate_without_ai(self.pg_client, &self.table_name, &query, mode, filter_sql)
+                    .await?;
+            let empty = crates.is_empty();
+            found = Some(Found {
+                mode,
+                query,
+                crates,
+                semantic: None,
+            });
+            if !empty {
+                break;
+            }
+        }
+        if let Some(found) = found.as_mut() {
+            sort_crates(&mut found.crates, sort_by);
+            if sort_by == SearchSortCriteria::Comprehensive {
+                let semantic = match self.semantic_candidates(keyword, filter_sql).await {
+                    Ok(Some((vector, candidates))) => {
+                        found.semantic = Some(vector);
+                        candidates
+                    }
+                    Ok(None) => vec![],
+                    Err(e) => {
+                        SEMANTIC_FALLBACKS.fetch_add(1, Ordering::Relaxed);
+                        tracing::warn!("semantic search failed, ranking by keyword only: {}", e);
+                        vec![]
+                    }
+                };
+                found.crates =
+                    ranking::fuse(std::mem::take(&mut found.crates), semantic, &self.weights);
+            }
+            rearrange_crates(&mut found.crates, keyword);
+        }
+        Ok(found)
+    }
+
+    /// 查询向量和与关键词语义最接近的程序，程序按相似度从高到低排列。未配置嵌入服务或
+    /// 语义权重为 0 时返回 `None`；出错时（如嵌入服务不可用或没有 `embedding` 列）调用方只按关键词排序
+    async fn semantic_candidates(
+        &self,
+        keyword: &str,
+        filter_sql: &FilterSql,
+    ) -> Result<Option<(String, Vec<RecommendCrate>)>, Box<dyn std::error::Error>> {
+        let keyword = keyword.trim();
+        let Some(embedder) = &self.embedder else {
+            return Ok(None);
+        };
+        if self.weights.semantic == 0.0 || keyword.is_empty() {
+            return Ok(None);
+        }
+        let embedding = embedding::embed_one(embedder.as_ref(), keyword).await?;
+        // 全零向量（如本地哈希提供者遇到只有标点的关键词）与所有程序的距离相同，近邻没有意义
+        if is_zero_vector(&embedding) {
+            return Ok(None);
+        }
+        let vector = vector_literal(&embedding);
+        let candidates = search_crate_without_ai(
+            self.pg_client,
+            &self.table_name,
+            &vector,
+            MatchMode::Semantic,
+            filter_sql,
+        )
+        .await?;
+        Ok(Some((vector, candidates)))
+    }
+
+    /// 名称自动补全：先返回以 `prefix` 开头的名称，按下载量排序，不足 `limit` 个时补充相似的名称
+    pub async fn suggest(
+        &self,
+        prefix: &str,
+        limit: i64,
+    ) -> Result<Vec<Suggestion>, Box<dyn std::error::Error>> {
+        let Some(keyword) = MatchMode::Fuzzy.keyword(prefix) else {
+            return Ok(vec![]);
+        };
+        let statement = format!(
+            "SELECT name, downloads FROM (
+                SELECT name, coalesce(max(downloads), 0) AS downloads, 0 AS tier, 1::real AS sim
+                FROM {0}
+                WHERE lower(name) LIKE $1
+                GROUP BY name
+                UNION ALL
+                SELECT name, coalesce(max(downloads), 0), 1, max(similarity(name, $2))
+                FROM {0}
+                WHERE name % $2 AND lower(name) NOT LIKE $1
+                GROUP BY name
+            ) s
+            ORDER BY tier, sim DESC, downloads DESC, name
+            LIMIT $3",
+            self.table_name
+        );
+        let rows = self
+            .pg_client
+            .query(
+                statement.as_str(),
+                &[&like_prefix(&keyword), &keyword, &limit],
+            )
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| Suggestion {
+                name: row.get("name"),
+                downloads: row.get("downloads"),
+            })
+            .collect())
+    }
+}
+
+/// 把用户输入转换为安全的 tsquery：只保留字母和数字组成的词，用 `&` 连接，最后一个词按前缀匹配。
+/// 没有可用的词时返回 `None`
+fn tsquery(keyword: &str) -> Option<String> {
+    let words: Vec<String> = keyword
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let last = words.last()?;
+    let mut terms = words[..words.len() - 1].to_vec();
+    terms.push(format!("{}:*", last));
+    Some(terms.join(" & "))
+}
+
+fn is_zero_vector(embedding: &[f32]) -> bool {
+    embedding.iter().all(|v| *v == 0.0)
+}
+
+/// pgvector 的文本形式，如 `[0.1,0.2]`
+fn vector_literal(embedding: &[f32]) -> String {
+    let values: Vec<String> = embedding.iter().map(f32::to_string).collect();
+    format!("[{}]", values.join(","))
+}
+
+/// 转义 LIKE 的通配符，生成前缀匹配模式
+fn like_prefix(prefix: &str) -> String {
+    let mut pattern = String::with_capacity(prefix.len() + 1);
+    for c in prefix.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
     }
+    pattern.push('%');
+    pattern
 }
 
 fn version_cmp(a: &RecommendCrate, b: &RecommendCrate) -> std::cmp::Ordering {
@@ -91,6 +428,7 @@ fn sort_crates_by_downloads(crate_vec: &mut [RecommendCrate]) {
 
 fn sort_crates(crate_vec: &mut [RecommendCrate], sort_by: SearchSortCriteria) {
     match sort_by {
+        // Comprehensive 先得到关键词检索的排名，再在 `find` 中与向量检索融合
         SearchSortCriteria::Comprehensive | SearchSortCriteria::Relavance => {
             sort_crates_by_relevance(crate_vec);
         }
@@ -117,19 +455,22 @@ fn rearrange_crates(crates: &mut Vec<RecommendCrate>, keyword: &str) {
 async fn search_crate_without_ai(
     client: &PgClient,
     table_name: &str,
-    keyword: &str,
+    query: &String,
+    mode: MatchMode,
+    filter_sql: &FilterSql,
 ) -> Result<Vec<RecommendCrate>, Box<dyn std::error::Error>> {
-    let tsquery_keyword = keyword.replace(" ", " & ");
-    let query = format!("{}:*", tsquery_keyword);
-
     let statement = format!(
-        "SELECT {0}.id::text, {0}.name, {0}.description, ts_rank({0}.tsv, to_tsquery($1)) AS rank,{0}.downloads,{0}.namespace,{0}.max_version
-        FROM {0}
-        WHERE {0}.tsv @@ to_tsquery($1)
+        "{}
+        SELECT id, name, description, rank, downloads, namespace, max_version, evaluated_score
+        FROM matched
+        WHERE {}
         ORDER BY rank DESC",
-        table_name
+        facets::matched_cte(table_name, mode, None),
+        filter_sql.all()
     );
-    let rows = client.query(statement.as_str(), &[&query]).await?;
+    let rows = client
+        .query(statement.as_str(), &filter_sql.params(query))
+        .await?;
     let mut recommend_crates = Vec::<RecommendCrate>::new();
 
     for row in rows.iter() {
@@ -140,6 +481,7 @@ async fn search_crate_without_ai(
         let namespace: Option<String> = row.get("namespace");
         let max_version: Option<String> = row.get("max_version");
         let rank: Option<f32> = row.get("rank");
+        let evaluated_score: Option<f64> = row.get("evaluated_score");
 
         recommend_crates.push(RecommendCrate {
             id: id.unwrap_or_default(),
@@ -149,8 +491,53 @@ async fn search_crate_without_ai(
             namespace: namespace.unwrap_or_default(),
             max_version: max_version.unwrap_or_default(),
             rank: rank.unwrap_or(0.0),
+            evaluated_score,
         });
     }
 
     Ok(recommend_crates)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tsquery() {
+        assert_eq!(tsquery("tokio").as_deref(), Some("tokio:*"));
+        assert_eq!(
+            tsquery("  Async  Runtime ").as_deref(),
+            Some("async & runtime:*")
+        );
+        // 运算符和引号不会进入 tsquery
+        assert_eq!(
+            tsquery("serde-json & 'x' | !y").as_deref(),
+            Some("serde & json & x & y:*")
+        );
+        assert_eq!(tsquery("serd_json").as_deref(), Some("serd & json:*"));
+        assert_eq!(tsquery(" -&' :* "), None);
+    }
+
+    #[test]
+    fn test_like_prefix() {
+        assert_eq!(like_prefix("tok"), "tok%");
+        assert_eq!(like_prefix("serde_j"), "serde\\_j%");
+        assert_eq!(like_prefix("100%\\"), "100\\%\\\\%");
+    }
+
+    #[test]
+    fn test_zero_vector() {
+        assert!(is_zero_vector(&embedding::HashEmbedding::embed_one(
+            " -&' "
+        )));
+        assert!(!is_zero_vector(&embedding::HashEmbedding::embed_one(
+            "tokio"
+        )));
+    }
+
+    #[test]
+    fn test_fuzzy_keyword() {
+        assert_eq!(MatchMode::Fuzzy.keyword(" Toko ").as_deref(), Some("toko"));
+        assert_eq!(MatchMode::Fuzzy.keyword("   "), None);
+    }
+}
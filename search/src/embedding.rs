@@ -1,10 +1,66 @@
+use async_trait::async_trait;
 use pgvector::Vector;
 use reqwest::Client;
 use serde::Deserialize;
 use serde_json::json;
 use std::env;
+use std::str::FromStr;
+use std::sync::Arc;
 use tokio_postgres::Client as PgClient;
 
+/// `embedding` 列的维度，所有提供者都生成这个长度的向量
+pub const EMBEDDING_DIMENSIONS: usize = 1536;
+
+/// 每次请求嵌入的文本数
+const EMBEDDING_BATCH_SIZE: usize = 32;
+
+/// 文本嵌入的提供者
+#[async_trait]
+pub trait EmbeddingProvider: Send + Sync {
+    /// 提供者和模型的标识，与向量一起记录在数据库中。标识变化后全部程序都会重新计算
+    fn model(&self) -> String;
+
+    /// 按顺序返回每个文本的向量，长度为 [`EMBEDDING_DIMENSIONS`]
+    async fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>, Box<dyn std::error::Error>>;
+}
+
+/// 嵌入提供者的类型，由 `EMBEDDING_PROVIDER` 选择
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EmbeddingBackend {
+    /// OpenAI 兼容的 `/v1/embeddings` 接口
+    #[default]
+    OpenAi,
+    /// 本地的哈希 n-gram 向量，不需要网络
+    Local,
+}
+
+impl FromStr for EmbeddingBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "openai" => Ok(EmbeddingBackend::OpenAi),
+            "local" => Ok(EmbeddingBackend::Local),
+            other => Err(format!(
+                "unknown EMBEDDING_PROVIDER {:?}, expected openai or local",
+                other
+            )),
+        }
+    }
+}
+
+/// 按 `EMBEDDING_PROVIDER`（`openai` 或 `local`，默认 `openai`）创建提供者
+pub fn provider_from_env() -> Result<Arc<dyn EmbeddingProvider>, String> {
+    let backend = match env::var("EMBEDDING_PROVIDER") {
+        Ok(value) if !value.trim().is_empty() => value.parse()?,
+        _ => EmbeddingBackend::default(),
+    };
+    Ok(match backend {
+        EmbeddingBackend::OpenAi => Arc::new(OpenAiEmbedding::from_env()),
+        EmbeddingBackend::Local => Arc::new(HashEmbedding),
+    })
+}
+
 #[derive(Deserialize)]
 struct EmbeddingData {
     embedding: Vec<f32>,
@@ -15,121 +71,266 @@ struct EmbeddingResponse {
     data: Vec<EmbeddingData>,
 }
 
+/// OpenAI 兼容的嵌入接口
+pub struct OpenAiEmbedding {
+    client: Client,
+    url: Option<String>,
+    api_key: Option<String>,
+    model: String,
+}
+
+impl OpenAiEmbedding {
+    /// 读取 `OPEN_AI_EMBEDDING_URL`、`OPENAI_API_KEY` 和 `OPEN_AI_EMBEDDING_MODEL`（默认
+    /// `text-embedding-3-small`）。缺少的配置在请求时才报错
+    pub fn from_env() -> Self {
+        let non_empty = |name: &str| env::var(name).ok().filter(|v| !v.is_empty());
+        OpenAiEmbedding {
+            client: Client::new(),
+            url: non_empty("OPEN_AI_EMBEDDING_URL"),
+            api_key: non_empty("OPENAI_API_KEY"),
+            model: non_empty("OPEN_AI_EMBEDDING_MODEL")
+                .unwrap_or_else(|| "text-embedding-3-small".to_string()),
+        }
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for OpenAiEmbedding {
+    fn model(&self) -> String {
+        format!("openai:{}", self.model)
+    }
+
+    async fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>, Box<dyn std::error::Error>> {
+        // 未配置时返回错误而不是 panic，搜索会退回到只按关键词排序
+        let api_key = self.api_key.as_ref().ok_or("OPENAI_API_KEY not set")?;
+        let url = self.url.as_ref().ok_or("OPEN_AI_EMBEDDING_URL not set")?;
+        let request_body = json!({
+            "input": texts,
+            "model": self.model,
+        });
+        let response = self
+            .client
+            .post(url)
+            .header("Content-Type", "application/json")
+            .header("AUTHORIZATION", format!("Bearer {}", api_key))
+            .json(&request_body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<EmbeddingResponse>()
+            .await?;
+
+        let embeddings: Vec<Vec<f32>> = response.data.into_iter().map(|d| d.embedding).collect();
+        if embeddings.len() != texts.len() {
+            return Err(format!(
+                "expected {} embeddings, got {}",
+                texts.len(),
+                embeddings.len()
+            )
+            .into());
+        }
+        Ok(embeddings)
+    }
+}
+
+/// 确定性的本地嵌入：单词和字符三元组经特征哈希映射到固定维度，再做 L2 归一化。
+/// 只能捕捉字面上的相似，用于测试和无法访问外部服务的部署
+pub struct HashEmbedding;
+
+impl HashEmbedding {
+    pub fn embed_one(text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0f32; EMBEDDING_DIMENSIONS];
+        let mut add = |feature: &str, weight: f32| {
+            let hash = fnv1a(feature.as_bytes());
+            let index = (hash % EMBEDDING_DIMENSIONS as u64) as usize;
+            // 用哈希的最高位决定符号，减小冲突带来的偏差
+            let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+            vector[index] += sign * weight;
+        };
+        for word in text
+            .to_lowercase()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+        {
+            add(word, 2.0);
+            let chars: Vec<char> = format!("^{}$", word).chars().collect();
+            for gram in chars.windows(3) {
+                add(&gram.iter().collect::<String>(), 1.0);
+            }
+        }
+        let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|v| *v /= norm);
+        }
+        vector
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for HashEmbedding {
+    fn model(&self) -> String {
+        format!("local:hash-ngram-{}", EMBEDDING_DIMENSIONS)
+    }
+
+    async fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>, Box<dyn std::error::Error>> {
+        Ok(texts.iter().map(|text| Self::embed_one(text)).collect())
+    }
+}
+
+/// 64 位 FNV-1a，结果不随 Rust 版本和平台变化
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
 pub async fn get_one_text_embedding(text: &str) -> Result<Vec<f32>, Box<dyn std::error::Error>> {
-    get_texts_embedding(&[text])
-        .await
-        .map(|embeddings| embeddings[0].clone())
-}
-
-//TODO 1: 优化get_texts_embedding函数，使其使用batch API
-async fn get_texts_embedding(texts: &[&str]) -> Result<Vec<Vec<f32>>, Box<dyn std::error::Error>> {
-    let api_key = env::var("OPENAI_API_KEY").expect("OPENAI_API_KEY not set");
-    let client = Client::new();
-    let open_ai_embedding_url =
-        env::var("OPEN_AI_EMBEDDING_URL").expect("OPEN_AI_EMBEDDING_URL not set");
-
-    // let url = "https://api.xty.app/v1/embeddings";
-    let request_body = json!({
-        "input": texts,
-        "model": "text-embedding-3-small"
-    });
-    let response = client
-        .post(open_ai_embedding_url)
-        .header("Content-Type", "application/json")
-        .header("AUTHORIZATION", format!("Bearer {}", api_key))
-        .json(&request_body)
-        .send()
+    let provider = provider_from_env()?;
+    embed_one(provider.as_ref(), text).await
+}
+
+pub(crate) async fn embed_one(
+    provider: &dyn EmbeddingProvider,
+    text: &str,
+) -> Result<Vec<f32>, Box<dyn std::error::Error>> {
+    provider
+        .embed(&[text])
         .await?
-        .json::<EmbeddingResponse>()
-        .await?;
+        .into_iter()
+        .next()
+        .ok_or_else(|| "empty embedding response".into())
+}
+
+/// 参与嵌入的文本，和 [`SOURCE_HASH`] 使用相同的字段
+fn crate_text(name: &str, description: &str) -> String {
+    format!("crate name:{}, crate description:{}", name, description)
+}
 
-    let embeddings: Vec<Vec<f32>> = response.data.into_iter().map(|d| d.embedding).collect();
+/// 计算嵌入时名称和描述的摘要，与 `embedding_hash` 列比较以发现变化
+const SOURCE_HASH: &str = "md5(coalesce(name, '') || E'\\n' || coalesce(description, ''))";
 
-    Ok(embeddings)
+/// 一次刷新的统计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RefreshStats {
+    /// 名称或描述变化、模型变化或还没有向量的程序数
+    pub stale: usize,
+    pub updated: usize,
 }
 
-pub async fn update_crate_embeddings(
-    client: &PgClient,
-    crate_id: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let table_name = env::var("TABLE_NAME").expect("TABLE_NAME not set");
-    let query = format!("SELECT name, description FROM {} WHERE id = $1", table_name);
-    let row = client
-        .query_one(&query, &[&crate_id])
-        .await
-        .expect("Failed to get crate");
-    let name: String = row.get("name");
-    let description: String = row.get("description");
-    let text = format!("crate name:{}, crate description:{}", name, description);
-    let embedding = get_one_text_embedding(&text).await.unwrap();
-    let embedding = Vector::from(embedding);
-    let update_query = format!("UPDATE {} SET embedding = $1 WHERE id = $2", table_name);
-    client
-        .execute(&update_query, &[&embedding, &crate_id])
-        .await
-        .expect("Failed to update crate embedding");
-    Ok(())
-}
-
-pub async fn update_all_crate_embeddings(
+/// 增量刷新：只为还没有向量、名称或描述变化过、或由其他模型生成向量的程序重新计算。
+/// 每个程序的摘要和模型记录在 `embedding_hash`、`embedding_model` 列中
+pub async fn refresh_crate_embeddings(
     client: &PgClient,
-) -> Result<(), Box<dyn std::error::Error>> {
+    provider: &dyn EmbeddingProvider,
+) -> Result<RefreshStats, Box<dyn std::error::Error>> {
     let table_name = env::var("TABLE_NAME").expect("TABLE_NAME not set");
-    let query = format!("SELECT id, name, description FROM {}", table_name);
-    let rows = client.query(&query, &[]).await?;
-    let texts: Vec<String> = rows
-        .iter()
-        .map(|row| {
-            let name: Option<&str> = row.get("name");
-            let description: Option<&str> = row.get("description");
-            format!(
-                "crate name:{}, crate description:{}",
-                name.unwrap_or(""),
-                description.unwrap_or("")
-            )
-        })
-        .collect();
-    let text_refs: Vec<&str> = texts.iter().map(|s| s.as_str()).collect();
-    let mut all_embeddings = Vec::new();
-    for chunk in text_refs.chunks(32) {
-        let embeddings = get_texts_embedding(chunk).await?;
-        println!("Got embeddings for {} texts", embeddings.len());
-        all_embeddings.extend(embeddings);
-    }
-
-    // println!("{:?}", all_embeddings);
-    for (i, row) in rows.iter().enumerate() {
-        let id: &str = row.get("id");
-        let embedding = Vector::from(all_embeddings[i].clone());
-        let update_query = format!("UPDATE {} SET embedding = $1 WHERE id = $2", table_name);
-        client.execute(&update_query, &[&embedding, &id]).await?;
-        println!("Updated embedding for crate {}", id);
+    let model = provider.model();
+    let query = format!(
+        "SELECT id::text AS id, name, description, {1} AS hash FROM {0}
+        WHERE embedding IS NULL
+            OR embedding_model IS DISTINCT FROM $1
+            OR embedding_hash IS DISTINCT FROM {1}",
+        table_name, SOURCE_HASH
+    );
+    let rows = client.query(&query, &[&model]).await?;
+    let update_query = format!(
+        "UPDATE {} SET embedding = $1, embedding_model = $2, embedding_hash = $3 WHERE id::text = $4",
+        table_name
+    );
+    let mut stats = RefreshStats {
+        stale: rows.len(),
+        updated: 0,
+    };
+    for chunk in rows.chunks(EMBEDDING_BATCH_SIZE) {
+        let texts: Vec<String> = chunk
+            .iter()
+            .map(|row| {
+                let name: Option<&str> = row.get("name");
+                let description: Option<&str> = row.get("description");
+                crate_text(name.unwrap_or(""), description.unwrap_or(""))
+            })
+            .collect();
+        let text_refs: Vec<&str> = texts.iter().map(|s| s.as_str()).collect();
+        let embeddings = provider.embed(&text_refs).await?;
+        for (row, embedding) in chunk.iter().zip(embeddings) {
+            let id: &str = row.get("id");
+            let hash: &str = row.get("hash");
+            let embedding = Vector::from(embedding);
+            client
+                .execute(&update_query, &[&embedding, &model, &hash, &id])
+                .await?;
+        }
+        stats.updated += chunk.len();
+        println!(
+            "Updated embeddings for {}/{} crates",
+            stats.updated, stats.stale
+        );
     }
-    Ok(())
+    Ok(stats)
 }
 
-// 新增函数
 pub(crate) async fn search_crates_by_embedding(
     client: &PgClient,
     embedding: &[f32],
     n: i64,
-) -> Result<Vec<(i32, String, String)>, Box<dyn std::error::Error>> {
+) -> Result<Vec<(String, String, String)>, Box<dyn std::error::Error>> {
     let table_name = env::var("TABLE_NAME").expect("TABLE_NAME not set");
     let query = format!(
-        "SELECT id, name, description FROM {} ORDER BY embedding <=> $1 LIMIT $2",
+        "SELECT id::text AS id, name, description FROM {}
+        WHERE embedding IS NOT NULL
+        ORDER BY embedding <=> $1 LIMIT $2",
         table_name
     );
+    let embedding = Vector::from(embedding.to_vec());
     let rows = client.query(&query, &[&embedding, &n]).await?;
 
     let results = rows
         .iter()
         .map(|row| {
-            let id: i32 = row.get("id");
-            let name: String = row.get("name");
-            let description: String = row.get("description");
-            (id, name, description)
+            let id: String = row.get("id");
+            let name: Option<String> = row.get("name");
+            let description: Option<String> = row.get("description");
+            (
+                id,
+                name.unwrap_or_default(),
+                description.unwrap_or_default(),
+            )
         })
         .collect();
 
     Ok(results)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cosine(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(x, y)| x * y).sum()
+    }
+
+    #[test]
+    fn test_hash_embedding() {
+        let tokio = HashEmbedding::embed_one("crate name:tokio, crate description:async runtime");
+        assert_eq!(tokio.len(), EMBEDDING_DIMENSIONS);
+        assert!((cosine(&tokio, &tokio) - 1.0).abs() < 1e-5);
+        // 相同输入总是得到相同的向量
+        assert_eq!(
+            tokio,
+            HashEmbedding::embed_one("crate name:tokio, crate description:async runtime")
+        );
+        let runtime = HashEmbedding::embed_one("async runtime");
+        let json = HashEmbedding::embed_one("json serialization");
+        assert!(cosine(&tokio, &runtime) > cosine(&tokio, &json));
+        assert!(HashEmbedding::embed_one(" - ").iter().all(|v| *v == 0.0));
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+    }
+
+    #[test]
+    fn test_embedding_backend() {
+        assert_eq!("OpenAI".parse(), Ok(EmbeddingBackend::OpenAi));
+        assert_eq!(" local ".parse(), Ok(EmbeddingBackend::Local));
+        assert!("bert".parse::<EmbeddingBackend>().is_err());
+        assert_eq!(HashEmbedding.model(), "local:hash-ngram-1536");
+    }
+}
@@ -0,0 +1,190 @@
+//! 搜索排序质量的离线评估
+//!
+//! 标注集为 JSON Lines，每行一个查询及其相关程序的分级标注（数值越大越相关），例如：
+//!
+//! ```text
+//! {"query": "async runtime", "relevant": {"tokio": 3, "async-std": 2, "smol": 2}}
+//! ```
+//!
+//! 对每种排序方式运行全部查询，计算 nDCG@k、MRR 和 Recall@k 的平均值。
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::crates_search::{SearchModule, SearchSortCriteria};
+use crate::facets::SearchFilters;
+
+/// 一条标注的查询
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LabelledQuery {
+    pub query: String,
+    #[serde(default)]
+    pub filters: SearchFilters,
+    /// 程序名称到相关度等级，未列出的视为不相关
+    pub relevant: HashMap<String, u32>,
+}
+
+/// 读取标注集，忽略空行和 `#` 开头的注释行
+pub fn load_queries(
+    path: impl AsRef<Path>,
+) -> Result<Vec<LabelledQuery>, Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(path)?;
+    let mut queries = vec![];
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let query: LabelledQuery =
+            serde_json::from_str(line).map_err(|e| format!("line {}: {}", i + 1, e))?;
+        queries.push(query);
+    }
+    Ok(queries)
+}
+
+/// 单个查询的指标
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct QueryMetrics {
+    pub ndcg: f64,
+    /// 第一个相关结果排名的倒数
+    pub reciprocal_rank: f64,
+    pub recall: f64,
+}
+
+/// 按名称排列的结果相对标注的指标，只看前 `k` 个结果（MRR 不受 `k` 限制）
+pub fn score_ranking(ranked: &[String], relevant: &HashMap<String, u32>, k: usize) -> QueryMetrics {
+    let gain = |name: &String| relevant.get(name).copied().unwrap_or(0);
+    let discount = |i: usize| 1.0 / ((i + 2) as f64).log2();
+    let dcg: f64 = ranked
+        .iter()
+        .take(k)
+        .enumerate()
+        .map(|(i, name)| (2f64.powi(gain(name) as i32) - 1.0) * discount(i))
+        .sum();
+    let mut ideal: Vec<u32> = relevant.values().copied().filter(|g| *g > 0).collect();
+    ideal.sort_unstable_by(|a, b| b.cmp(a));
+    let idcg: f64 = ideal
+        .iter()
+        .take(k)
+        .enumerate()
+        .map(|(i, g)| (2f64.powi(*g as i32) - 1.0) * discount(i))
+        .sum();
+    let reciprocal_rank = ranked
+        .iter()
+        .position(|name| gain(name) > 0)
+        .map_or(0.0, |i| 1.0 / (i + 1) as f64);
+    let found = ranked.iter().take(k).filter(|name| gain(name) > 0).count();
+    QueryMetrics {
+        ndcg: if idcg > 0.0 { dcg / idcg } else { 0.0 },
+        reciprocal_rank,
+        recall: if ideal.is_empty() {
+            0.0
+        } else {
+            found as f64 / ideal.len() as f64
+        },
+    }
+}
+
+/// 一种排序方式在整个标注集上的评估结果
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct EvaluationReport {
+    pub sort_by: SearchSortCriteria,
+    pub k: usize,
+    /// 全部查询的平均值
+    pub mean: QueryMetrics,
+    pub queries: Vec<(String, QueryMetrics)>,
+}
+
+/// 用 `sort_by` 运行标注集中的全部查询并计算指标
+pub async fn evaluate(
+    module: &SearchModule<'_>,
+    queries: &[LabelledQuery],
+    sort_by: SearchSortCriteria,
+    k: usize,
+) -> Result<EvaluationReport, Box<dyn std::error::Error>> {
+    let mut results = vec![];
+    for query in queries {
+        let crates = module
+            .search(&query.query, &query.filters, sort_by)
+            .await?
+            .crates;
+        // 同名的多个程序只保留排名最高的一个，与标注的粒度一致
+        let mut seen = HashSet::new();
+        let ranked: Vec<String> = crates
+            .into_iter()
+            .map(|c| c.name)
+            .filter(|name| seen.insert(name.clone()))
+            .collect();
+        results.push((
+            query.query.clone(),
+            score_ranking(&ranked, &query.relevant, k),
+        ));
+    }
+    Ok(EvaluationReport {
+        sort_by,
+        k,
+        mean: mean(results.iter().map(|(_, m)| m)),
+        queries: results,
+    })
+}
+
+fn mean<'a>(metrics: impl ExactSizeIterator<Item = &'a QueryMetrics>) -> QueryMetrics {
+    let n = metrics.len();
+    if n == 0 {
+        return QueryMetrics::default();
+    }
+    let sum = metrics.fold(QueryMetrics::default(), |acc, m| QueryMetrics {
+        ndcg: acc.ndcg + m.ndcg,
+        reciprocal_rank: acc.reciprocal_rank + m.reciprocal_rank,
+        recall: acc.recall + m.recall,
+    });
+    QueryMetrics {
+        ndcg: sum.ndcg / n as f64,
+        reciprocal_rank: sum.reciprocal_rank / n as f64,
+        recall: sum.recall / n as f64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranked(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn test_score_ranking() {
+        let relevant: HashMap<String, u32> = [("tokio".to_string(), 3), ("smol".to_string(), 1)]
+            .into_iter()
+            .collect();
+        let perfect = score_ranking(&ranked(&["tokio", "smol", "x"]), &relevant, 10);
+        assert!((perfect.ndcg - 1.0).abs() < 1e-9);
+        assert_eq!(perfect.reciprocal_rank, 1.0);
+        assert_eq!(perfect.recall, 1.0);
+
+        let swapped = score_ranking(&ranked(&["x", "smol", "tokio"]), &relevant, 10);
+        assert!(swapped.ndcg < perfect.ndcg);
+        assert_eq!(swapped.reciprocal_rank, 0.5);
+
+        // tokio 在前 k 个之外
+        let cut = score_ranking(&ranked(&["smol", "x", "tokio"]), &relevant, 2);
+        assert_eq!(cut.recall, 0.5);
+        assert_eq!(cut.reciprocal_rank, 1.0);
+
+        let none = score_ranking(&[], &relevant, 10);
+        assert_eq!(none, QueryMetrics::default());
+    }
+
+    #[test]
+    fn test_labelled_queries() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/eval/queries.jsonl");
+        let queries = load_queries(path).unwrap();
+        assert!(!queries.is_empty());
+        assert!(queries.iter().all(|q| q.relevant.values().any(|g| *g > 0)));
+        assert_eq!(queries[0].filters, SearchFilters::default());
+    }
+}
@@ -0,0 +1,586 @@
+//! 搜索过滤条件和分面统计
+//!
+//! 过滤条件和分面统计都在 Postgres 中与 `tsv` 全文检索一起计算。每个维度的分面计数基于
+//! 关键词和其他维度的过滤条件，不受该维度自身条件的影响，这样选中某个值后仍能看到其他可选值的数量。
+//! 与搜索结果一样，同一 (name, namespace) 的多行只计一次。
+
+use std::collections::BTreeSet;
+
+use chrono::NaiveDate;
+use model::advisory::AdvisoryMatcher;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use tokio_postgres::types::ToSql;
+use tokio_postgres::Client as PgClient;
+use utoipa::ToSchema;
+
+use crate::crates_search::{semantic_condition, MatchMode};
+
+/// 程序类型
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ProgramType {
+    Library,
+    Application,
+}
+
+impl ProgramType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProgramType::Library => "library",
+            ProgramType::Application => "application",
+        }
+    }
+}
+
+/// 搜索过滤条件，未设置的条件不生效
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, ToSchema)]
+pub struct SearchFilters {
+    /// 只返回库或应用
+    pub program_type: Option<ProgramType>,
+    /// SPDX 许可证表达式，如 `MIT OR Apache-2.0`。用 `AND` 连接时要求包含全部标识符，
+    /// 否则包含任一标识符即匹配
+    pub license: Option<String>,
+    /// 只返回最新版本不受已知 RustSec 漏洞影响的程序
+    #[serde(default)]
+    pub no_advisories: bool,
+    /// `metadata.evaluated_score` 的下限
+    pub min_score: Option<f64>,
+    /// 下载量下限（含）
+    pub min_downloads: Option<i64>,
+    /// 下载量上限（含）
+    pub max_downloads: Option<i64>,
+    /// `YYYY-MM-DD`，只返回此后更新过的程序
+    pub updated_since: Option<String>,
+}
+
+impl SearchFilters {
+    /// 检查参数是否合法，返回给调用方的错误描述
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(license) = &self.license {
+            if LicenseMatch::parse(license).ids.is_empty() {
+                return Err(format!("invalid license expression: {:?}", license));
+            }
+        }
+        if self.min_score.is_some_and(|s| !s.is_finite()) {
+            return Err("min_score must be a finite number".to_string());
+        }
+        if let (Some(min), Some(max)) = (self.min_downloads, self.max_downloads) {
+            if min > max {
+                return Err("min_downloads must not exceed max_downloads".to_string());
+            }
+        }
+        if let Some(date) = &self.updated_since {
+            NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .map_err(|_| format!("updated_since must be YYYY-MM-DD, got {:?}", date))?;
+        }
+        Ok(())
+    }
+}
+
+/// 从许可证表达式中解析出的标识符
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LicenseMatch {
+    /// 大写的 SPDX 标识符
+    ids: Vec<String>,
+    /// 是否要求全部包含
+    all: bool,
+}
+
+impl LicenseMatch {
+    fn parse(expr: &str) -> Self {
+        let mut ids = vec![];
+        let (mut and, mut or) = (false, false);
+        for token in split_license(expr) {
+            match token.as_str() {
+                "AND" => and = true,
+                "OR" => or = true,
+                // `WITH` 之后是例外条款，不是许可证
+                "WITH" => {}
+                _ => {
+                    if !ids.contains(&token) {
+                        ids.push(token)
+                    }
+                }
+            }
+        }
+        LicenseMatch {
+            ids,
+            all: and && !or,
+        }
+    }
+}
+
+/// 按 SPDX 表达式的分隔符拆分并转为大写，与 SQL 中的 `regexp_split_to_array` 保持一致
+fn split_license(expr: &str) -> Vec<String> {
+    expr.to_uppercase()
+        .split(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '+' | '-')))
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// SQL 中把程序的全部许可证拆分为大写标识符数组
+const LICENSE_TOKENS: &str =
+    "regexp_split_to_array(upper(array_to_string(licenses, ' ')), '[^A-Z0-9.+-]+')";
+
+/// 过滤维度，也是分面的维度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dimension {
+    ProgramType,
+    License,
+    Advisories,
+    Score,
+    Downloads,
+    Updated,
+}
+
+/// 由过滤条件生成的 SQL 条件和参数，`$1` 留给全文检索的关键词，
+/// `$2` 为最新版本受漏洞影响的程序 id，见 [`affected_program_ids`]
+pub(crate) struct FilterSql {
+    conditions: Vec<(Dimension, String)>,
+    params: Vec<Box<dyn ToSql + Sync + Send>>,
+}
+
+impl FilterSql {
+    pub(crate) fn new(filters: &SearchFilters, affected: Vec<String>) -> Self {
+        let mut sql = FilterSql {
+            conditions: vec![],
+            params: vec![],
+        };
+        sql.push(affected);
+        if let Some(program_type) = filters.program_type {
+            let n = sql.push(program_type.as_str().to_string());
+            sql.add(Dimension::ProgramType, format!("program_type = ${}", n));
+        }
+        if let Some(license) = &filters.license {
+            let license = LicenseMatch::parse(license);
+            let op = if license.all { "@>" } else { "&&" };
+            let n = sql.push(license.ids);
+            sql.add(
+                Dimension::License,
+                format!("{} {} ${}::text[]", LICENSE_TOKENS, op, n),
+            );
+        }
+        if filters.no_advisories {
+            sql.add(Dimension::Advisories, "NOT has_advisories".to_string());
+        }
+        if let Some(min_score) = filters.min_score {
+            let n = sql.push(min_score);
+            sql.add(Dimension::Score, format!("evaluated_score >= ${}", n));
+        }
+        if let Some(min) = filters.min_downloads {
+            let n = sql.push(min);
+            sql.add(Dimension::Downloads, format!("downloads >= ${}", n));
+        }
+        if let Some(max) = filters.max_downloads {
+            let n = sql.push(max);
+            sql.add(Dimension::Downloads, format!("downloads <= ${}", n));
+        }
+        if let Some(date) = &filters.updated_since {
+            let n = sql.push(date.clone());
+            sql.add(
+                Dimension::Updated,
+                format!("updated_at >= ${}::text::date", n),
+            );
+        }
+        sql
+    }
+
+    /// 添加一个参数，返回其编号
+    fn push(&mut self, value: impl ToSql + Sync + Send + 'static) -> usize {
+        self.params.push(Box::new(value));
+        self.params.len() + 1
+    }
+
+    fn add(&mut self, dimension: Dimension, condition: String) {
+        self.conditions.push((dimension, condition));
+    }
+
+    /// 除 `except` 维度外全部条件的 `AND` 组合
+    fn condition(&self, except: Option<Dimension>) -> String {
+        let mut parts = vec!["TRUE"];
+        parts.extend(
+            self.conditions
+                .iter()
+                .filter(|(d, _)| Some(*d) != except)
+                .map(|(_, c)| c.as_str()),
+        );
+        parts.join(" AND ")
+    }
+
+    /// 全部条件
+    pub(crate) fn all(&self) -> String {
+        self.condition(None)
+    }
+
+    /// 查询参数，`keyword` 为 `$1`
+    pub(crate) fn params<'a>(&'a self, keyword: &'a String) -> Vec<&'a (dyn ToSql + Sync)> {
+        let mut params: Vec<&(dyn ToSql + Sync)> = vec![keyword];
+        params.extend(
+            self.params
+                .iter()
+                .map(|p| p.as_ref() as &(dyn ToSql + Sync)),
+        );
+        params
+    }
+}
+
+/// 关键词匹配到的程序及用于过滤和分面的字段，供后续查询作为 `matched` 使用。
+/// `semantic` 为查询向量的参数编号，设置时同时包含向量检索的近邻。
+/// 一个程序可能有多条许可证记录，聚合为 `licenses` 数组，避免程序被重复计数
+pub(crate) fn matched_cte(table_name: &str, mode: MatchMode, semantic: Option<usize>) -> String {
+    let mut condition = mode.condition(table_name);
+    if let Some(param) = semantic {
+        condition = format!(
+            "({}) OR {}",
+            condition,
+            semantic_condition(table_name, param)
+        );
+    }
+    format!(
+        "WITH matched AS (
+            SELECT p.id::text AS id, p.name, p.description, p.downloads, p.namespace, p.max_version,
+                {1} AS rank,
+                lower(p.program_type) AS program_type,
+                ARRAY(SELECT DISTINCT lc.license FROM license lc
+                    WHERE lc.program_id = p.id::text AND lc.license IS NOT NULL) AS licenses,
+                m.evaluated_score,
+                p.updated_at,
+                p.id::text = ANY($2::text[]) AS has_advisories
+            FROM {0} p
+            LEFT JOIN metadata m ON m.id = p.id
+            WHERE {2}
+        )",
+        table_name,
+        mode.rank(),
+        condition
+    )
+}
+
+/// 许可证分面最多返回的取值数
+const LICENSE_FACET_LIMIT: usize = 20;
+
+const SCORE_BUCKETS: [&str; 6] = ["0-20", "20-40", "40-60", "60-80", "80+", "unscored"];
+const DOWNLOAD_BUCKETS: [&str; 5] = ["<1k", "1k-10k", "10k-100k", "100k-1M", "1M+"];
+const UPDATED_BUCKETS: [&str; 4] = ["last_30_days", "last_year", "older", "unknown"];
+
+/// 最新版本受漏洞影响的程序 id。patched 和 unaffected 是 semver 范围，无法在 SQL 中判断，
+/// 这里读出有漏洞记录的程序后按 [`AdvisoryMatcher`] 计算
+pub(crate) async fn affected_program_ids(
+    client: &PgClient,
+    table_name: &str,
+) -> Result<Vec<String>, tokio_postgres::Error> {
+    let statement = format!(
+        "SELECT p.id::text AS id, p.max_version, i.patched, i.unaffected
+        FROM {} p
+        JOIN rustsecs r ON r.cratename = p.name
+        JOIN rustsec_info i ON i.id = r.id",
+        table_name
+    );
+    let rows = client.query(statement.as_str(), &[]).await?;
+    Ok(affected_ids(rows.iter().map(|row| {
+        let max_version: Option<String> = row.get("max_version");
+        (
+            row.get("id"),
+            max_version.unwrap_or_default(),
+            row.get("patched"),
+            row.get("unaffected"),
+        )
+    })))
+}
+
+/// 由 (id, max_version, patched, unaffected) 行得到受影响的 id，无法解析的版本保守地视为受影响
+fn affected_ids(rows: impl IntoIterator<Item = (String, String, String, String)>) -> Vec<String> {
+    let mut ids = BTreeSet::new();
+    for (id, max_version, patched, unaffected) in rows {
+        let affected = match Version::parse(max_version.trim()) {
+            Ok(version) => {
+                AdvisoryMatcher::new(&patched, &unaffected).is_affected_version(&version)
+            }
+            Err(_) => true,
+        };
+        if affected {
+            ids.insert(id);
+        }
+    }
+    ids.into_iter().collect()
+}
+
+/// 计算全部分面的 SQL，每行为 (facet, value, count)，count 为不同 (name, namespace) 的个数。
+/// `semantic` 见 [`matched_cte`]
+pub(crate) fn facet_query(
+    table_name: &str,
+    mode: MatchMode,
+    filters: &FilterSql,
+    semantic: Option<usize>,
+) -> String {
+    let branch = |facet: &str, value: &str, dimension: Dimension| {
+        format!(
+            "(SELECT '{}' AS facet, {} AS value, count(DISTINCT (name, namespace)) AS count
+                FROM matched WHERE {} GROUP BY 2)",
+            facet,
+            value,
+            filters.condition(Some(dimension)),
+        )
+    };
+    let license = format!(
+        "(SELECT 'license' AS facet, license AS value, count(DISTINCT (name, namespace)) AS count
+            FROM matched, unnest(licenses) AS license
+            WHERE {} GROUP BY 2 ORDER BY 3 DESC, 2 LIMIT {})",
+        filters.condition(Some(Dimension::License)),
+        LICENSE_FACET_LIMIT
+    );
+    let branches = [
+        branch(
+            "program_type",
+            "coalesce(nullif(program_type, ''), 'unknown')",
+            Dimension::ProgramType,
+        ),
+        license,
+        branch(
+            "advisories",
+            "CASE WHEN has_advisories THEN 'has_advisories' ELSE 'none' END",
+            Dimension::Advisories,
+        ),
+        branch(
+            "score",
+            "CASE WHEN evaluated_score IS NULL THEN 'unscored'
+                WHEN evaluated_score < 20 THEN '0-20'
+                WHEN evaluated_score < 40 THEN '20-40'
+                WHEN evaluated_score < 60 THEN '40-60'
+                WHEN evaluated_score < 80 THEN '60-80'
+                ELSE '80+' END",
+            Dimension::Score,
+        ),
+        branch(
+            "downloads",
+            "CASE WHEN downloads < 1000 THEN '<1k'
+                WHEN downloads < 10000 THEN '1k-10k'
+                WHEN downloads < 100000 THEN '10k-100k'
+                WHEN downloads < 1000000 THEN '100k-1M'
+                ELSE '1M+' END",
+            Dimension::Downloads,
+        ),
+        branch(
+            "updated",
+            "CASE WHEN updated_at IS NULL THEN 'unknown'
+                WHEN updated_at >= now() - interval '30 days' THEN 'last_30_days'
+                WHEN updated_at >= now() - interval '1 year' THEN 'last_year'
+                ELSE 'older' END",
+            Dimension::Updated,
+        ),
+    ];
+    format!(
+        "{}\n{}",
+        matched_cte(table_name, mode, semantic),
+        branches.join("\nUNION ALL\n")
+    )
+}
+
+/// 分面中的一个取值及匹配数量
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct FacetCount {
+    pub value: String,
+    pub count: i64,
+}
+
+/// 各维度的分面计数
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, ToSchema)]
+pub struct SearchFacets {
+    /// `library`、`application` 或 `unknown`
+    pub program_type: Vec<FacetCount>,
+    /// 出现最多的许可证，最多 20 个
+    pub license: Vec<FacetCount>,
+    /// `none` 或 `has_advisories`
+    pub advisories: Vec<FacetCount>,
+    /// 评估分数区间：`0-20` … `80+`，没有评估的为 `unscored`
+    pub score: Vec<FacetCount>,
+    /// 下载量区间：`<1k`、`1k-10k`、`10k-100k`、`100k-1M`、`1M+`
+    pub downloads: Vec<FacetCount>,
+    /// 最近更新时间：`last_30_days`、`last_year`、`older`、`unknown`
+    pub updated: Vec<FacetCount>,
+}
+
+impl SearchFacets {
+    /// 由 (facet, value, count) 行构建，区间按固定顺序，其余按数量从多到少排列
+    pub(crate) fn from_rows(rows: impl IntoIterator<Item = (String, String, i64)>) -> Self {
+        let mut facets = SearchFacets::default();
+        for (facet, value, count) in rows {
+            let list = match facet.as_str() {
+                "program_type" => &mut facets.program_type,
+                "license" => &mut facets.license,
+                "advisories" => &mut facets.advisories,
+                "score" => &mut facets.score,
+                "downloads" => &mut facets.downloads,
+                "updated" => &mut facets.updated,
+                _ => continue,
+            };
+            list.push(FacetCount { value, count });
+        }
+        for list in [
+            &mut facets.program_type,
+            &mut facets.license,
+            &mut facets.advisories,
+        ] {
+            list.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+        }
+        let by_bucket = |list: &mut Vec<FacetCount>, buckets: &[&str]| {
+            list.sort_by_key(|f| buckets.iter().position(|b| *b == f.value));
+        };
+        by_bucket(&mut facets.score, &SCORE_BUCKETS);
+        by_bucket(&mut facets.downloads, &DOWNLOAD_BUCKETS);
+        by_bucket(&mut facets.updated, &UPDATED_BUCKETS);
+        facets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_license_match() {
+        assert_eq!(
+            LicenseMatch::parse("MIT OR Apache-2.0"),
+            LicenseMatch {
+                ids: vec!["MIT".to_string(), "APACHE-2.0".to_string()],
+                all: false,
+            }
+        );
+        assert_eq!(
+            LicenseMatch::parse("(mit AND Unicode-DFS-2016)"),
+            LicenseMatch {
+                ids: vec!["MIT".to_string(), "UNICODE-DFS-2016".to_string()],
+                all: true,
+            }
+        );
+        let with = LicenseMatch::parse("Apache-2.0 WITH LLVM-exception / MIT");
+        assert_eq!(with.ids, ["APACHE-2.0", "LLVM-EXCEPTION", "MIT"]);
+        assert!(LicenseMatch::parse(" ( ) ").ids.is_empty());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(SearchFilters::default().validate().is_ok());
+        let filters = SearchFilters {
+            license: Some("MIT".to_string()),
+            min_downloads: Some(10),
+            max_downloads: Some(10),
+            updated_since: Some("2025-01-31".to_string()),
+            ..Default::default()
+        };
+        assert!(filters.validate().is_ok());
+        for invalid in [
+            SearchFilters {
+                license: Some("()".to_string()),
+                ..Default::default()
+            },
+            SearchFilters {
+                min_score: Some(f64::NAN),
+                ..Default::default()
+            },
+            SearchFilters {
+                min_downloads: Some(100),
+                max_downloads: Some(10),
+                ..Default::default()
+            },
+            SearchFilters {
+                updated_since: Some("31/01/2025".to_string()),
+                ..Default::default()
+            },
+        ] {
+            assert!(invalid.validate().is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_filter_sql() {
+        let filters = SearchFilters {
+            program_type: Some(ProgramType::Library),
+            license: Some("MIT AND Apache-2.0".to_string()),
+            no_advisories: true,
+            min_downloads: Some(1000),
+            max_downloads: Some(5000),
+            ..Default::default()
+        };
+        let sql = FilterSql::new(&filters, vec!["7".to_string()]);
+        assert_eq!(
+            sql.all(),
+            format!(
+                "TRUE AND program_type = $3 AND {} @> $4::text[] AND NOT has_advisories \
+                 AND downloads >= $5 AND downloads <= $6",
+                LICENSE_TOKENS
+            )
+        );
+        // 分面计数不受本维度条件影响
+        assert_eq!(
+            sql.condition(Some(Dimension::Downloads)),
+            format!(
+                "TRUE AND program_type = $3 AND {} @> $4::text[] AND NOT has_advisories",
+                LICENSE_TOKENS
+            )
+        );
+        let keyword = "tokio:*".to_string();
+        assert_eq!(sql.params(&keyword).len(), 6);
+        assert_eq!(
+            FilterSql::new(&SearchFilters::default(), vec![]).all(),
+            "TRUE"
+        );
+    }
+
+    #[test]
+    fn test_facet_query_semantic() {
+        let filters = FilterSql::new(&SearchFilters::default(), vec![]);
+        let keyword_only = facet_query("crates", MatchMode::FullText, &filters, None);
+        assert!(!keyword_only.contains("vector"));
+        // 融合进结果的向量检索近邻也计入分面
+        let fused = facet_query("crates", MatchMode::FullText, &filters, Some(3));
+        assert!(fused.contains(&format!(
+            "(p.tsv @@ to_tsquery($1)) OR {}",
+            semantic_condition("crates", 3)
+        )));
+    }
+
+    #[test]
+    fn test_affected_ids() {
+        let row = |id: &str, version: &str, patched: &str, unaffected: &str| {
+            (
+                id.to_string(),
+                version.to_string(),
+                patched.to_string(),
+                unaffected.to_string(),
+            )
+        };
+        let ids = affected_ids([
+            // 最新版本已修复
+            row("1", "1.2.0", ">= 1.1.0", ""),
+            // 一个漏洞已修复，另一个仍影响最新版本
+            row("2", "0.3.0", ">= 0.2.0", ""),
+            row("2", "0.3.0", ">= 0.4.0", ""),
+            row("3", "0.1.0", "", "< 0.2.0"),
+            row("4", "null", ">= 1.0.0", ""),
+        ]);
+        assert_eq!(ids, ["2", "4"]);
+    }
+
+    #[test]
+    fn test_facets_from_rows() {
+        let row =
+            |facet: &str, value: &str, count: i64| (facet.to_string(), value.to_string(), count);
+        let facets = SearchFacets::from_rows([
+            row("downloads", "1M+", 2),
+            row("license", "MIT", 3),
+            row("downloads", "<1k", 7),
+            row("license", "Apache-2.0", 5),
+            row("score", "unscored", 4),
+            row("score", "40-60", 1),
+            row("unknown", "x", 1),
+        ]);
+        let values = |list: &[FacetCount]| list.iter().map(|f| f.value.clone()).collect::<Vec<_>>();
+        assert_eq!(values(&facets.license), ["Apache-2.0", "MIT"]);
+        assert_eq!(values(&facets.downloads), ["<1k", "1M+"]);
+        assert_eq!(values(&facets.score), ["40-60", "unscored"]);
+        assert!(facets.program_type.is_empty());
+    }
+}
@@ -1,4 +1,7 @@
 pub mod ai;
 pub mod crates_search;
 pub mod embedding;
+pub mod evaluation;
+pub mod facets;
+pub mod ranking;
 pub mod search_prepare;
@@ -0,0 +1,227 @@
+//! `SearchSortCriteria::Comprehensive` 的混合排序
+//!
+//! 全文检索（或模糊匹配）和 pgvector 近邻检索各自给出一个排名，用倒数排名融合（RRF）合并，
+//! 再加上归一化的下载量和 `metadata.evaluated_score`。各项权重可通过环境变量调整。
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::str::FromStr;
+
+use crate::crates_search::RecommendCrate;
+
+/// 混合排序各项的权重
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RankingWeights {
+    /// 关键词检索排名的 RRF 权重
+    pub lexical: f32,
+    /// 向量近邻排名的 RRF 权重，为 0 时不计算查询向量
+    pub semantic: f32,
+    /// 对数归一化下载量的权重
+    pub downloads: f32,
+    /// 归一化评估分数的权重
+    pub score: f32,
+    /// RRF 的平滑常数 k，越大排名靠后的结果衰减越慢
+    pub rrf_k: f32,
+}
+
+impl Default for RankingWeights {
+    fn default() -> Self {
+        RankingWeights {
+            lexical: 1.0,
+            semantic: 1.0,
+            downloads: 0.5,
+            score: 0.3,
+            rrf_k: 60.0,
+        }
+    }
+}
+
+impl RankingWeights {
+    /// 从 `SEARCH_WEIGHT_LEXICAL`、`SEARCH_WEIGHT_SEMANTIC`、`SEARCH_WEIGHT_DOWNLOADS`、
+    /// `SEARCH_WEIGHT_SCORE` 和 `SEARCH_RRF_K` 读取，未设置或无效的使用默认值
+    pub fn from_env() -> Self {
+        fn var<T: FromStr>(name: &str, default: T) -> T {
+            env::var(name)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        }
+        let default = RankingWeights::default();
+        let weights = RankingWeights {
+            lexical: var("SEARCH_WEIGHT_LEXICAL", default.lexical),
+            semantic: var("SEARCH_WEIGHT_SEMANTIC", default.semantic),
+            downloads: var("SEARCH_WEIGHT_DOWNLOADS", default.downloads),
+            score: var("SEARCH_WEIGHT_SCORE", default.score),
+            rrf_k: var("SEARCH_RRF_K", default.rrf_k),
+        };
+        weights.sanitized()
+    }
+
+    /// 权重不能为负，k 至少为 1
+    fn sanitized(self) -> Self {
+        let weight = |w: f32| if w.is_finite() && w > 0.0 { w } else { 0.0 };
+        RankingWeights {
+            lexical: weight(self.lexical),
+            semantic: weight(self.semantic),
+            downloads: weight(self.downloads),
+            score: weight(self.score),
+            rrf_k: if self.rrf_k.is_finite() && self.rrf_k >= 1.0 {
+                self.rrf_k
+            } else {
+                RankingWeights::default().rrf_k
+            },
+        }
+    }
+
+    /// 第 `rank` 名（从 0 开始）的 RRF 得分，归一化到第一名为 1
+    fn rrf(&self, rank: usize) -> f32 {
+        (self.rrf_k + 1.0) / (self.rrf_k + 1.0 + rank as f32)
+    }
+}
+
+/// 融合关键词检索和向量检索的结果，两个列表都应已按各自的相关度从高到低排列。
+/// 返回的结果按综合得分排序，`rank` 字段为综合得分
+pub(crate) fn fuse(
+    lexical: Vec<RecommendCrate>,
+    semantic: Vec<RecommendCrate>,
+    weights: &RankingWeights,
+) -> Vec<RecommendCrate> {
+    let mut fused: Vec<RecommendCrate> = vec![];
+    let mut scores: Vec<f32> = vec![];
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for (list, weight) in [(lexical, weights.lexical), (semantic, weights.semantic)] {
+        // 同一个程序在一个列表中只按最靠前的一次计分
+        let mut seen = HashSet::new();
+        for (rank, item) in list.into_iter().enumerate() {
+            if !seen.insert(item.id.clone()) {
+                continue;
+            }
+            let score = weight * weights.rrf(rank);
+            match index.get(&item.id) {
+                Some(&i) => scores[i] += score,
+                None => {
+                    index.insert(item.id.clone(), fused.len());
+                    fused.push(item);
+                    scores.push(score);
+                }
+            }
+        }
+    }
+
+    let max_downloads = fused.iter().map(|c| c.downloads.max(0)).max().unwrap_or(0);
+    let max_score = fused
+        .iter()
+        .filter_map(|c| c.evaluated_score)
+        .fold(0.0_f64, f64::max);
+    for (item, mut score) in fused.iter_mut().zip(scores) {
+        if max_downloads > 0 {
+            score += weights.downloads * (item.downloads.max(0) as f32).ln_1p()
+                / (max_downloads as f32).ln_1p();
+        }
+        if max_score > 0.0 {
+            let evaluated = item.evaluated_score.unwrap_or(0.0).max(0.0);
+            score += weights.score * (evaluated / max_score) as f32;
+        }
+        item.rank = score;
+    }
+    fused.sort_by(|a, b| {
+        b.rank
+            .partial_cmp(&a.rank)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    fused
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: &str, downloads: i64, evaluated_score: Option<f64>) -> RecommendCrate {
+        RecommendCrate {
+            id: id.to_string(),
+            name: id.to_string(),
+            description: String::new(),
+            downloads,
+            namespace: String::new(),
+            max_version: "1.0.0".to_string(),
+            rank: 0.0,
+            evaluated_score,
+        }
+    }
+
+    fn names(crates: &[RecommendCrate]) -> Vec<&str> {
+        crates.iter().map(|c| c.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_fuse_rrf() {
+        let weights = RankingWeights {
+            downloads: 0.0,
+            score: 0.0,
+            ..Default::default()
+        };
+        let lexical = vec![item("a", 0, None), item("b", 0, None), item("c", 0, None)];
+        let semantic = vec![item("c", 0, None), item("d", 0, None), item("c", 0, None)];
+        let fused = fuse(lexical, semantic, &weights);
+        // c 同时出现在两个列表中，重复出现只计一次
+        assert_eq!(names(&fused), ["c", "a", "b", "d"]);
+        assert!((fused[1].rank - 1.0).abs() < 1e-6);
+        assert!((fused[0].rank - (weights.rrf(2) + 1.0)).abs() < 1e-6);
+
+        let lexical_only = RankingWeights {
+            semantic: 0.0,
+            ..weights
+        };
+        let fused = fuse(
+            vec![item("a", 0, None), item("b", 0, None)],
+            vec![item("b", 0, None)],
+            &lexical_only,
+        );
+        assert_eq!(names(&fused), ["a", "b"]);
+    }
+
+    #[test]
+    fn test_fuse_popularity_and_score() {
+        let weights = RankingWeights {
+            lexical: 0.0,
+            semantic: 0.0,
+            ..Default::default()
+        };
+        let fused = fuse(
+            vec![
+                item("unscored", 0, None),
+                item("popular", 1_000_000, Some(10.0)),
+                item("scored", 10, Some(80.0)),
+            ],
+            vec![],
+            &weights,
+        );
+        assert_eq!(names(&fused), ["popular", "scored", "unscored"]);
+        assert!((fused[0].rank - (weights.downloads + weights.score / 8.0)).abs() < 1e-6);
+        assert_eq!(fused[2].rank, 0.0);
+    }
+
+    #[test]
+    fn test_sanitized_weights() {
+        let weights = RankingWeights {
+            lexical: -1.0,
+            semantic: f32::NAN,
+            downloads: 0.2,
+            score: 0.0,
+            rrf_k: 0.0,
+        }
+        .sanitized();
+        assert_eq!(
+            weights,
+            RankingWeights {
+                lexical: 0.0,
+                semantic: 0.0,
+                downloads: 0.2,
+                score: 0.0,
+                rrf_k: 60.0,
+            }
+        );
+        assert_eq!(weights.rrf(0), 1.0);
+    }
+}
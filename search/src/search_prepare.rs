@@ -28,6 +28,17 @@ impl<'a> SearchPrepare<'a> {
         Ok(())
     }
 
+    pub async fn prepare_fuzzy(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let table_exists = self.crates_table_exists().await?;
+        if !table_exists {
+            return Err("crates table not exists".into());
+        }
+        self.add_trgm_extension().await?;
+        self.create_trgm_index().await?;
+        self.create_name_prefix_index().await?;
+        Ok(())
+    }
+
     pub async fn prepare_embedding(&self) -> Result<(), Box<dyn std::error::Error>> {
         let table_exists = self.crates_table_exists().await?;
         if !table_exists {
@@ -84,18 +95,30 @@ impl<'a> SearchPrepare<'a> {
         Ok(())
     }
 
-    // 功能五：添加embedding列
+    // 功能五：添加embedding列，以及记录生成向量时的文本摘要和模型的列，用于增量刷新
     pub async fn add_embedding_column(&self) -> Result<(), Box<dyn std::error::Error>> {
         let query = format!(
-            "ALTER TABLE {} ADD COLUMN IF NOT EXISTS embedding vector(1536)",
-            self.table_name
+            "ALTER TABLE {0} ADD COLUMN IF NOT EXISTS embedding vector({1}),
+                ADD COLUMN IF NOT EXISTS embedding_hash text,
+                ADD COLUMN IF NOT EXISTS embedding_model text",
+            self.table_name,
+            embedding::EMBEDDING_DIMENSIONS
         );
         self.pg_client.execute(&query, &[]).await?;
         Ok(())
     }
 
+    // 用 `EMBEDDING_PROVIDER` 指定的提供者为变化过的crate重新计算向量
     pub async fn set_embedding_column(&self) -> Result<(), Box<dyn std::error::Error>> {
-        embedding::update_all_crate_embeddings(self.pg_client).await
+        let provider = embedding::provider_from_env()?;
+        let stats = embedding::refresh_crate_embeddings(self.pg_client, provider.as_ref()).await?;
+        println!(
+            "Refreshed {} of {} stale embeddings with {}",
+            stats.updated,
+            stats.stale,
+            provider.model()
+        );
+        Ok(())
     }
     // 功能六：为embedding列创建索引
     pub async fn create_embedding_index(&self) -> Result<(), Box<dyn std::error::Error>> {
@@ -198,4 +221,31 @@ impl<'a> SearchPrepare<'a> {
         self.pg_client.execute(query, &[]).await?;
         Ok(())
     }
+
+    // 功能九：添加pg_trgm扩展，用于名称的模糊匹配
+    pub async fn add_trgm_extension(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let query = "CREATE EXTENSION IF NOT EXISTS pg_trgm";
+        self.pg_client.execute(query, &[]).await?;
+        Ok(())
+    }
+
+    // 功能十：为name列创建三元组索引，支持 `%` 相似度查询
+    pub async fn create_trgm_index(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let query = format!(
+            "CREATE INDEX IF NOT EXISTS idx_{}_name_trgm ON {} USING gin (name gin_trgm_ops)",
+            self.table_name, self.table_name
+        );
+        self.pg_client.execute(&query, &[]).await?;
+        Ok(())
+    }
+
+    // 功能十一：为小写的name创建前缀索引，支持自动补全的 `LIKE 'prefix%'` 查询
+    pub async fn create_name_prefix_index(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let query = format!(
+            "CREATE INDEX IF NOT EXISTS idx_{}_name_prefix ON {} (lower(name) text_pattern_ops)",
+            self.table_name, self.table_name
+        );
+        self.pg_client.execute(&query, &[]).await?;
+        Ok(())
+    }
 }
@@ -21,7 +21,7 @@ use tokio::sync::mpsc;
 use url::Url;
 use walkdir::WalkDir;
 
-use entity::{sea_orm_active_enums::SyncStatusEnum, repo_sync_result};
+use entity::{repo_sync_result, sea_orm_active_enums::SyncStatusEnum};
 
 use crate::{
     kafka::{self},
@@ -24,13 +24,26 @@ impl TuGraphClient {
         user: &str,
         password: &str,
         db: &str,
+    ) -> Result<Self, Box<dyn Error>> {
+        Self::with_max_connections(uri, user, password, db, 1000).await
+    }
+
+    /// Initialize TuGraph Client with a bounded connection pool.
+    /// The underlying `Graph` pools bolt connections itself, so one client
+    /// should be created and cloned wherever it is needed.
+    pub async fn with_max_connections(
+        uri: &str,
+        user: &str,
+        password: &str,
+        db: &str,
+        max_connections: usize,
     ) -> Result<Self, Box<dyn Error>> {
         let graph_name = if db.is_empty() { "default" } else { db };
         let config = ConfigBuilder::default()
             .uri(uri)
             .user(user)
             .password(password)
-            .max_connections(1000)
+            .max_connections(max_connections)
             .fetch_size(10000)
             .db(graph_name)
             .build()?;
@@ -38,7 +51,7 @@ impl TuGraphClient {
             "Begin to connect to Tugraph, uri: {uri}, user: {user}, password: {password}, db: {db}"
         );
 
-        let graph = Graph::connect(config).await.unwrap();
+        let graph = Graph::connect(config).await?;
         tracing::info!("Success to connect to Tugraph");
         Ok(TuGraphClient { graph })
     }